@@ -0,0 +1,59 @@
+#!/usr/bin/env node
+
+// Builds the sidecar binaries listed in tauri.conf.json `bundle.externalBin`
+//
+// Tauri expects each sidecar at src-tauri/binaries/<name>-<target triple>. They are
+// binaries of the app's own package, so building them runs tauri-build, which fails
+// while the sidecars don't exist yet. TAURI_CONFIG drops them from the config for this
+// build, and a target directory of its own keeps that from invalidating the app build.
+
+import { execFileSync } from 'node:child_process';
+import { copyFileSync, mkdirSync } from 'node:fs';
+import path from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+const root = path.resolve(path.dirname(fileURLToPath(import.meta.url)), '..');
+const srcTauri = path.join(root, 'src-tauri');
+
+const SIDECARS = ['sandbox_probe'];
+
+function targetTriple() {
+  if (process.env.TAURI_ENV_TARGET_TRIPLE) {
+    return process.env.TAURI_ENV_TARGET_TRIPLE;
+  }
+  const info = execFileSync('rustc', ['-vV'], { encoding: 'utf8' });
+  const host = info.split('\n').find((line) => line.startsWith('host: '));
+  if (!host) {
+    throw new Error('Failed to determine the target triple from `rustc -vV`');
+  }
+  return host.slice('host: '.length).trim();
+}
+
+const triple = targetTriple();
+const extension = triple.includes('windows') ? '.exe' : '';
+const outDir = path.join(srcTauri, 'binaries');
+mkdirSync(outDir, { recursive: true });
+
+const targetDir = path.join(srcTauri, 'target', 'sidecars');
+execFileSync(
+  'cargo',
+  [
+    'build',
+    '--release',
+    '--manifest-path', path.join(srcTauri, 'Cargo.toml'),
+    '--target', triple,
+    '--target-dir', targetDir,
+    ...SIDECARS.flatMap((name) => ['--bin', name]),
+  ],
+  {
+    stdio: 'inherit',
+    env: { ...process.env, TAURI_CONFIG: JSON.stringify({ bundle: { externalBin: null } }) },
+  },
+);
+
+for (const name of SIDECARS) {
+  const built = path.join(targetDir, triple, 'release', `${name}${extension}`);
+  const output = path.join(outDir, `${name}-${triple}${extension}`);
+  copyFileSync(built, output);
+  console.log(`Built sidecar ${path.relative(root, output)}`);
+}
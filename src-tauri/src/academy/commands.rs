@@ -349,7 +349,7 @@ pub async fn get_user_academy_stats(user_id: String) -> Result<UserStats, String
             params![user_id, now],
         ).map_err(|e| e.to_string())?;
         
-        Ok(UserStats {
+        Ok::<_, String>(UserStats {
             user_id,
             total_lessons_completed: 0,
             total_exercises_solved: 0,
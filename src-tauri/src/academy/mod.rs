@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tauri::command;
 
+pub mod commands;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Lesson {
     pub id: String,
@@ -0,0 +1,742 @@
+use crate::agents::versions::{self, AgentSnapshot};
+use crate::commands::agents::{AgentData, AgentExport};
+use log::{info, warn};
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Directory in a project holding its agent definitions, one `AgentExport` per JSON file
+pub const PROJECT_AGENTS_DIR: &str = ".claude/agents";
+
+/// An agent definition file in a project
+#[derive(Debug, Clone)]
+pub struct AgentFile {
+    /// Path relative to the project
+    pub file: String,
+    pub agent: AgentData,
+}
+
+/// An agent definition file that couldn't be read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidAgentFile {
+    pub file: String,
+    pub error: String,
+}
+
+/// A name defined by more than one agent a project can see
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentConflict {
+    pub name: String,
+    /// The global agent with the name, if one has it
+    pub global_agent_id: Option<i64>,
+    /// Definition files in the project with the name
+    pub files: Vec<String>,
+}
+
+/// The sandbox settings of an agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxSettings {
+    pub sandbox_enabled: bool,
+    pub enable_file_read: bool,
+    pub enable_file_write: bool,
+    pub enable_network: bool,
+}
+
+impl SandboxSettings {
+    /// What a new agent gets unless the user chooses otherwise
+    pub const DEFAULT: Self = SandboxSettings {
+        sandbox_enabled: true,
+        enable_file_read: true,
+        enable_file_write: true,
+        enable_network: false,
+    };
+
+    pub fn of(agent: &AgentData) -> Self {
+        SandboxSettings {
+            sandbox_enabled: agent.sandbox_enabled,
+            enable_file_read: agent.enable_file_read,
+            enable_file_write: agent.enable_file_write,
+            enable_network: agent.enable_network,
+        }
+    }
+
+    fn of_snapshot(snapshot: &AgentSnapshot) -> Self {
+        SandboxSettings {
+            sandbox_enabled: snapshot.sandbox_enabled,
+            enable_file_read: snapshot.enable_file_read,
+            enable_file_write: snapshot.enable_file_write,
+            enable_network: snapshot.enable_network,
+        }
+    }
+
+    /// Each setting at whichever of the two allows less
+    pub fn strictest(self, other: Self) -> Self {
+        SandboxSettings {
+            sandbox_enabled: self.sandbox_enabled || other.sandbox_enabled,
+            enable_file_read: self.enable_file_read && other.enable_file_read,
+            enable_file_write: self.enable_file_write && other.enable_file_write,
+            enable_network: self.enable_network && other.enable_network,
+        }
+    }
+
+    /// Parse settings stored as JSON, ignoring ones that don't parse
+    pub fn from_stored(value: Option<String>) -> Option<Self> {
+        let value = value?;
+        match serde_json::from_str(&value) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                warn!("Ignoring stored sandbox settings {}: {}", value, e);
+                None
+            }
+        }
+    }
+
+    fn to_stored(self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
+}
+
+/// Settings a definition file asks for and the ones it gets
+///
+/// A file never loosens the sandbox of its agent by itself. Settings that would are kept at
+/// what the agent had, or the defaults for a new agent, and held until the user approves them.
+fn sandbox_from_file(
+    agent: &AgentData,
+    current: SandboxSettings,
+) -> (SandboxSettings, Option<SandboxSettings>) {
+    let requested = SandboxSettings::of(agent);
+    let applied = requested.strictest(current);
+    (applied, (applied != requested).then_some(requested))
+}
+
+/// What syncing the agents of a project changed, by definition file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectAgentSync {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// Files left as they were last synced, until they can be read again
+    pub invalid: Vec<InvalidAgentFile>,
+    pub conflicts: Vec<AgentConflict>,
+}
+
+/// Agent definitions in `project_path`
+///
+/// Files that aren't valid exports come back separately. A project without the directory
+/// has no agents.
+pub fn read_agent_files(
+    project_path: &Path,
+) -> std::io::Result<(Vec<AgentFile>, Vec<InvalidAgentFile>)> {
+    let entries = match std::fs::read_dir(project_path.join(PROJECT_AGENTS_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+        Err(e) => return Err(e),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut agents = Vec::new();
+    let mut invalid = Vec::new();
+    for path in paths {
+        let file = format!(
+            "{}/{}",
+            PROJECT_AGENTS_DIR,
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str::<AgentExport>(&json).map_err(|e| e.to_string()))
+            .and_then(|export| match export.version {
+                1 => Ok(export.agent),
+                version => Err(format!("Unsupported export version: {}", version)),
+            });
+        match parsed {
+            Ok(agent) => agents.push(AgentFile { file, agent }),
+            Err(error) => invalid.push(InvalidAgentFile { file, error }),
+        }
+    }
+    Ok((agents, invalid))
+}
+
+/// Definition files of a project, read before the database is locked to sync them
+#[derive(Debug, Clone)]
+pub struct ProjectAgentFiles {
+    files: Vec<AgentFile>,
+    invalid: Vec<InvalidAgentFile>,
+}
+
+/// Read the definition files of a project for `sync_project_agents`
+pub fn read_project_agents(project_path: &str) -> Result<ProjectAgentFiles, String> {
+    let (files, invalid) = read_agent_files(Path::new(project_path))
+        .map_err(|e| format!("Failed to read {}: {}", PROJECT_AGENTS_DIR, e))?;
+    Ok(ProjectAgentFiles { files, invalid })
+}
+
+/// Bring the project agents in the database in line with the definition files
+///
+/// Agents are added, updated or removed as their files are, and changes are recorded as
+/// new versions. Settings the files don't hold, like the sandbox policy, are kept. Agents
+/// whose file is gone are only marked removed, keeping their runs and versions, and come
+/// back under the same id if the file does.
+pub fn sync_project_agents(
+    conn: &Connection,
+    project_path: &str,
+    agent_files: ProjectAgentFiles,
+) -> Result<ProjectAgentSync, String> {
+    let ProjectAgentFiles { files, invalid } = agent_files;
+    let present: HashSet<&str> = files
+        .iter()
+        .map(|file| file.file.as_str())
+        .chain(invalid.iter().map(|invalid| invalid.file.as_str()))
+        .collect();
+
+    let synced: HashMap<String, (i64, bool)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT source_file, id, removed_at IS NOT NULL FROM agents WHERE project_path = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let synced = stmt
+            .query_map(params![project_path], |row| {
+                Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<SqliteResult<_>>()
+            .map_err(|e| e.to_string())?;
+        synced
+    };
+
+    let mut sync = ProjectAgentSync::default();
+    for AgentFile { file, agent } in &files {
+        let result = match synced.get(file) {
+            Some(&(id, true)) => restore(conn, id)
+                .and_then(|_| update_from_file(conn, id, agent))
+                .map(|_| sync.added.push(file.clone())),
+            Some(&(id, false)) => update_from_file(conn, id, agent).map(|updated| {
+                if updated {
+                    sync.updated.push(file.clone());
+                }
+            }),
+            None => insert_from_file(conn, project_path, file, agent).map(|_| {
+                sync.added.push(file.clone());
+            }),
+        };
+        result.map_err(|e| format!("Failed to sync {}: {}", file, e))?;
+    }
+
+    for (file, &(id, removed)) in &synced {
+        if !removed && !present.contains(file.as_str()) {
+            conn.execute(
+                "UPDATE agents SET removed_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                params![id],
+            )
+            .map_err(|e| format!("Failed to remove the agent of {}: {}", file, e))?;
+            sync.removed.push(file.clone());
+        }
+    }
+    sync.added.sort();
+    sync.removed.sort();
+
+    for invalid in &invalid {
+        warn!("Skipping agent file {}: {}", invalid.file, invalid.error);
+    }
+    sync.invalid = invalid;
+    sync.conflicts = find_conflicts(conn, project_path).map_err(|e| e.to_string())?;
+    if !sync.added.is_empty() || !sync.updated.is_empty() || !sync.removed.is_empty() {
+        info!(
+            "Synced agents of {}: {} added, {} updated, {} removed",
+            project_path,
+            sync.added.len(),
+            sync.updated.len(),
+            sync.removed.len()
+        );
+    }
+    Ok(sync)
+}
+
+/// Names shared by agents a project can see, its own and the global ones
+pub fn find_conflicts(conn: &Connection, project_path: &str) -> SqliteResult<Vec<AgentConflict>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, id, source_file FROM agents
+         WHERE removed_at IS NULL AND (project_path IS NULL OR project_path = ?1)
+         ORDER BY source_file, id",
+    )?;
+    let rows = stmt
+        .query_map(params![project_path], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut by_name: BTreeMap<String, AgentConflict> = BTreeMap::new();
+    for (name, id, source_file) in rows {
+        let entry = by_name.entry(name.clone()).or_insert(AgentConflict {
+            name,
+            global_agent_id: None,
+            files: Vec::new(),
+        });
+        match source_file {
+            Some(file) => entry.files.push(file),
+            None => {
+                entry.global_agent_id.get_or_insert(id);
+            }
+        }
+    }
+
+    Ok(by_name
+        .into_values()
+        .filter(|conflict| {
+            let definitions =
+                conflict.files.len() + usize::from(conflict.global_agent_id.is_some());
+            !conflict.files.is_empty() && definitions > 1
+        })
+        .collect())
+}
+
+/// File name for a new definition of the agent called `name`
+pub fn file_name_for(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "agent.json".to_string()
+    } else {
+        format!("{}.json", slug)
+    }
+}
+
+/// Write an agent definition file, in the format `export_agent` produces
+pub fn write_agent_file(path: &Path, agent: &AgentData) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let export = AgentExport {
+        version: 1,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        agent: agent.clone(),
+    };
+    let json = serde_json::to_string_pretty(&export)?;
+    std::fs::write(path, json + "\n")
+}
+
+/// Give a project agent the sandbox settings its file asks for, once the user approved them
+///
+/// Only applies `settings` while they are still what the file asks for, returning whether
+/// they were applied.
+pub fn approve_sandbox(
+    conn: &Connection,
+    id: i64,
+    settings: SandboxSettings,
+) -> SqliteResult<bool> {
+    let pending = SandboxSettings::from_stored(conn.query_row(
+        "SELECT pending_sandbox FROM agents WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )?);
+    if pending != Some(settings) {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE agents SET sandbox_enabled = ?1, enable_file_read = ?2, enable_file_write = ?3,
+             enable_network = ?4, pending_sandbox = NULL
+         WHERE id = ?5",
+        params![
+            settings.sandbox_enabled,
+            settings.enable_file_read,
+            settings.enable_file_write,
+            settings.enable_network,
+            id,
+        ],
+    )?;
+    versions::record_version(conn, id)?;
+    Ok(true)
+}
+
+fn insert_from_file(
+    conn: &Connection,
+    project_path: &str,
+    file: &str,
+    agent: &AgentData,
+) -> SqliteResult<i64> {
+    let (sandbox, pending) = sandbox_from_file(agent, SandboxSettings::DEFAULT);
+    conn.execute(
+        "INSERT INTO agents (name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, project_path, source_file, pending_sandbox) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            agent.name,
+            agent.icon,
+            agent.system_prompt,
+            agent.default_task,
+            agent.model,
+            sandbox.sandbox_enabled,
+            sandbox.enable_file_read,
+            sandbox.enable_file_write,
+            sandbox.enable_network,
+            project_path,
+            file,
+            pending.map(SandboxSettings::to_stored),
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    versions::record_version(conn, id)?;
+    Ok(id)
+}
+
+/// Bring back agent `id`, removed while its file was gone
+fn restore(conn: &Connection, id: i64) -> SqliteResult<usize> {
+    conn.execute(
+        "UPDATE agents SET removed_at = NULL WHERE id = ?1",
+        params![id],
+    )
+}
+
+/// Update agent `id` to match its file, returning whether anything changed
+fn update_from_file(conn: &Connection, id: i64, agent: &AgentData) -> SqliteResult<bool> {
+    let current = versions::current_snapshot(conn, id)?;
+    let (sandbox, pending) = sandbox_from_file(agent, SandboxSettings::of_snapshot(&current));
+    let from_file = AgentSnapshot {
+        name: agent.name.clone(),
+        icon: agent.icon.clone(),
+        system_prompt: agent.system_prompt.clone(),
+        default_task: agent.default_task.clone(),
+        model: agent.model.clone(),
+        sandbox_enabled: sandbox.sandbox_enabled,
+        enable_file_read: sandbox.enable_file_read,
+        enable_file_write: sandbox.enable_file_write,
+        enable_network: sandbox.enable_network,
+        sandbox_policy: current.sandbox_policy,
+    };
+    let current_pending = SandboxSettings::from_stored(conn.query_row(
+        "SELECT pending_sandbox FROM agents WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )?);
+    if from_file == current && pending == current_pending {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE agents SET name = ?1, icon = ?2, system_prompt = ?3, default_task = ?4,
+             model = ?5, sandbox_enabled = ?6, enable_file_read = ?7, enable_file_write = ?8,
+             enable_network = ?9, pending_sandbox = ?10
+         WHERE id = ?11",
+        params![
+            agent.name,
+            agent.icon,
+            agent.system_prompt,
+            agent.default_task,
+            agent.model,
+            sandbox.sandbox_enabled,
+            sandbox.enable_file_read,
+            sandbox.enable_file_write,
+            sandbox.enable_network,
+            pending.map(SandboxSettings::to_stored),
+            id,
+        ],
+    )?;
+    versions::record_version(conn, id)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn agents_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agents (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 name TEXT NOT NULL,
+                 icon TEXT NOT NULL,
+                 system_prompt TEXT NOT NULL,
+                 default_task TEXT,
+                 model TEXT NOT NULL DEFAULT 'sonnet',
+                 sandbox_enabled BOOLEAN NOT NULL DEFAULT 1,
+                 enable_file_read BOOLEAN NOT NULL DEFAULT 1,
+                 enable_file_write BOOLEAN NOT NULL DEFAULT 1,
+                 enable_network BOOLEAN NOT NULL DEFAULT 0,
+                 sandbox_policy TEXT,
+                 project_path TEXT,
+                 source_file TEXT,
+                 pending_sandbox TEXT,
+                 removed_at TEXT
+             );
+             CREATE TABLE agent_versions (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 agent_id INTEGER NOT NULL,
+                 version INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 icon TEXT NOT NULL,
+                 system_prompt TEXT NOT NULL,
+                 default_task TEXT,
+                 model TEXT NOT NULL,
+                 sandbox_enabled BOOLEAN NOT NULL,
+                 enable_file_read BOOLEAN NOT NULL,
+                 enable_file_write BOOLEAN NOT NULL,
+                 enable_network BOOLEAN NOT NULL,
+                 sandbox_policy TEXT,
+                 created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+             );",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn sync_files(conn: &Connection, project_path: &str) -> Result<ProjectAgentSync, String> {
+        sync_project_agents(conn, project_path, read_project_agents(project_path)?)
+    }
+
+    fn agent(name: &str, prompt: &str) -> AgentData {
+        AgentData {
+            name: name.to_string(),
+            icon: "bot".to_string(),
+            system_prompt: prompt.to_string(),
+            default_task: None,
+            model: "sonnet".to_string(),
+            sandbox_enabled: true,
+            enable_file_read: true,
+            enable_file_write: false,
+            enable_network: false,
+        }
+    }
+
+    fn project_agents(conn: &Connection, project: &str) -> Vec<(String, String)> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT name, system_prompt FROM agents
+                 WHERE project_path = ?1 AND removed_at IS NULL ORDER BY id",
+            )
+            .unwrap();
+        let agents = stmt
+            .query_map([project], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<SqliteResult<_>>()
+            .unwrap();
+        agents
+    }
+
+    #[test]
+    fn test_sync_follows_the_files() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_str().unwrap();
+        let dir = project.path().join(PROJECT_AGENTS_DIR);
+        let conn = agents_db();
+
+        write_agent_file(&dir.join("reviewer.json"), &agent("Reviewer", "Review.")).unwrap();
+        write_agent_file(&dir.join("tester.json"), &agent("Tester", "Test.")).unwrap();
+        let sync = sync_files(&conn, project_path).unwrap();
+        assert_eq!(
+            sync.added,
+            vec![".claude/agents/reviewer.json", ".claude/agents/tester.json"]
+        );
+        assert_eq!(
+            sync_files(&conn, project_path).unwrap(),
+            ProjectAgentSync::default()
+        );
+
+        write_agent_file(&dir.join("reviewer.json"), &agent("Reviewer", "Review it.")).unwrap();
+        std::fs::remove_file(dir.join("tester.json")).unwrap();
+        let sync = sync_files(&conn, project_path).unwrap();
+        assert_eq!(sync.updated, vec![".claude/agents/reviewer.json"]);
+        assert_eq!(sync.removed, vec![".claude/agents/tester.json"]);
+        assert_eq!(
+            project_agents(&conn, project_path),
+            vec![("Reviewer".to_string(), "Review it.".to_string())]
+        );
+        let version = versions::latest_version(&conn, 1).unwrap().unwrap();
+        assert_eq!(version.version, 2);
+    }
+
+    #[test]
+    fn test_agent_returns_with_its_file() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_str().unwrap();
+        let path = project.path().join(PROJECT_AGENTS_DIR).join("tester.json");
+        let conn = agents_db();
+
+        write_agent_file(&path, &agent("Tester", "Test.")).unwrap();
+        sync_files(&conn, project_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        sync_files(&conn, project_path).unwrap();
+        assert!(project_agents(&conn, project_path).is_empty());
+        // Removing it again reports nothing new
+        assert_eq!(
+            sync_files(&conn, project_path).unwrap(),
+            ProjectAgentSync::default()
+        );
+
+        write_agent_file(&path, &agent("Tester", "Test it.")).unwrap();
+        let sync = sync_files(&conn, project_path).unwrap();
+        assert_eq!(sync.added, vec![".claude/agents/tester.json"]);
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM agents WHERE removed_at IS NULL")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<SqliteResult<_>>()
+            .unwrap();
+        assert_eq!(ids, vec![1]);
+        assert_eq!(
+            project_agents(&conn, project_path),
+            vec![("Tester".to_string(), "Test it.".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_invalid_file_keeps_its_agent() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_str().unwrap();
+        let path = project
+            .path()
+            .join(PROJECT_AGENTS_DIR)
+            .join("reviewer.json");
+        let conn = agents_db();
+
+        write_agent_file(&path, &agent("Reviewer", "Review.")).unwrap();
+        sync_files(&conn, project_path).unwrap();
+
+        std::fs::write(&path, "{ \"version\": 1, ").unwrap();
+        let sync = sync_files(&conn, project_path).unwrap();
+        assert!(sync.removed.is_empty());
+        assert_eq!(sync.invalid.len(), 1);
+        assert_eq!(sync.invalid[0].file, ".claude/agents/reviewer.json");
+        assert_eq!(project_agents(&conn, project_path).len(), 1);
+    }
+
+    #[test]
+    fn test_conflicts_with_global_and_project_agents() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_str().unwrap();
+        let dir = project.path().join(PROJECT_AGENTS_DIR);
+        let conn = agents_db();
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt) VALUES ('Reviewer', 'bot', 'Mine.')",
+            [],
+        )
+        .unwrap();
+
+        write_agent_file(&dir.join("reviewer.json"), &agent("Reviewer", "Ours.")).unwrap();
+        write_agent_file(&dir.join("a.json"), &agent("Tester", "Test.")).unwrap();
+        write_agent_file(&dir.join("b.json"), &agent("Tester", "Test more.")).unwrap();
+        write_agent_file(&dir.join("docs.json"), &agent("Docs", "Write.")).unwrap();
+
+        let sync = sync_files(&conn, project_path).unwrap();
+        assert_eq!(
+            sync.conflicts,
+            vec![
+                AgentConflict {
+                    name: "Reviewer".to_string(),
+                    global_agent_id: Some(1),
+                    files: vec![".claude/agents/reviewer.json".to_string()],
+                },
+                AgentConflict {
+                    name: "Tester".to_string(),
+                    global_agent_id: None,
+                    files: vec![
+                        ".claude/agents/a.json".to_string(),
+                        ".claude/agents/b.json".to_string()
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removed_agents_dont_conflict() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_str().unwrap();
+        let dir = project.path().join(PROJECT_AGENTS_DIR);
+        let conn = agents_db();
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt, removed_at)
+             VALUES ('Reviewer', 'bot', 'Mine.', CURRENT_TIMESTAMP)",
+            [],
+        )
+        .unwrap();
+
+        write_agent_file(&dir.join("reviewer.json"), &agent("Reviewer", "Ours.")).unwrap();
+        let sync = sync_files(&conn, project_path).unwrap();
+        assert!(sync.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_files_never_loosen_the_sandbox_unapproved() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_str().unwrap();
+        let path = project
+            .path()
+            .join(PROJECT_AGENTS_DIR)
+            .join("reviewer.json");
+        let conn = agents_db();
+        let sandbox = |conn: &Connection| {
+            conn.query_row(
+                "SELECT sandbox_enabled, enable_network, pending_sandbox FROM agents WHERE id = 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, bool>(0)?,
+                        row.get::<_, bool>(1)?,
+                        SandboxSettings::from_stored(row.get(2)?),
+                    ))
+                },
+            )
+            .unwrap()
+        };
+
+        let mut open = agent("Reviewer", "Review.");
+        open.sandbox_enabled = false;
+        write_agent_file(&path, &open).unwrap();
+        sync_files(&conn, project_path).unwrap();
+        let requested = SandboxSettings::of(&open);
+        assert_eq!(sandbox(&conn), (true, false, Some(requested)));
+
+        open.enable_network = true;
+        write_agent_file(&path, &open).unwrap();
+        sync_files(&conn, project_path).unwrap();
+        assert!(!approve_sandbox(&conn, 1, requested).unwrap());
+
+        let requested = SandboxSettings::of(&open);
+        assert_eq!(sandbox(&conn), (true, false, Some(requested)));
+        assert!(approve_sandbox(&conn, 1, requested).unwrap());
+        assert_eq!(sandbox(&conn), (false, true, None));
+        assert_eq!(
+            sync_files(&conn, project_path).unwrap(),
+            ProjectAgentSync::default()
+        );
+
+        // Tightening applies right away, and loosening again needs approval again
+        write_agent_file(&path, &agent("Reviewer", "Review.")).unwrap();
+        sync_files(&conn, project_path).unwrap();
+        assert_eq!(sandbox(&conn), (true, false, None));
+        write_agent_file(&path, &open).unwrap();
+        sync_files(&conn, project_path).unwrap();
+        assert_eq!(sandbox(&conn), (true, false, Some(requested)));
+    }
+
+    #[test]
+    fn test_file_name_for() {
+        assert_eq!(file_name_for("Code Reviewer"), "code-reviewer.json");
+        assert_eq!(file_name_for("  Git: commit bot!"), "git-commit-bot.json");
+        assert_eq!(file_name_for("🤖"), "agent.json");
+    }
+}
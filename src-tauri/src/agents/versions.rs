@@ -0,0 +1,498 @@
+use crate::sandbox::policy::SandboxPolicy;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// Columns `AgentSnapshot::from_row` expects, in order, in both `agents` and `agent_versions`
+const SNAPSHOT_COLUMNS: &str = "name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, sandbox_policy";
+
+/// Everything about an agent that shapes how it runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentSnapshot {
+    pub name: String,
+    pub icon: String,
+    pub system_prompt: String,
+    pub default_task: Option<String>,
+    pub model: String,
+    pub sandbox_enabled: bool,
+    pub enable_file_read: bool,
+    pub enable_file_write: bool,
+    pub enable_network: bool,
+    #[serde(default)]
+    pub sandbox_policy: Option<SandboxPolicy>,
+}
+
+impl AgentSnapshot {
+    /// Read a snapshot from `SNAPSHOT_COLUMNS` starting at column `start`
+    fn from_row(row: &rusqlite::Row, start: usize) -> rusqlite::Result<Self> {
+        Ok(AgentSnapshot {
+            name: row.get(start)?,
+            icon: row.get(start + 1)?,
+            system_prompt: row.get(start + 2)?,
+            default_task: row.get(start + 3)?,
+            model: row
+                .get::<_, String>(start + 4)
+                .unwrap_or_else(|_| "sonnet".to_string()),
+            sandbox_enabled: row.get::<_, bool>(start + 5).unwrap_or(true),
+            enable_file_read: row.get::<_, bool>(start + 6).unwrap_or(true),
+            enable_file_write: row.get::<_, bool>(start + 7).unwrap_or(true),
+            enable_network: row.get::<_, bool>(start + 8).unwrap_or(false),
+            sandbox_policy: SandboxPolicy::from_stored(row.get(start + 9)?),
+        })
+    }
+}
+
+/// An agent as it was at one point, never changed once recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentVersion {
+    pub id: i64,
+    pub agent_id: i64,
+    /// Counts up from 1 for each agent
+    pub version: i64,
+    #[serde(flatten)]
+    pub snapshot: AgentSnapshot,
+    pub created_at: String,
+}
+
+impl AgentVersion {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(AgentVersion {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            version: row.get(2)?,
+            created_at: row.get(3)?,
+            snapshot: AgentSnapshot::from_row(row, 4)?,
+        })
+    }
+}
+
+/// A setting that differs between two versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: JsonValue,
+    pub to: JsonValue,
+}
+
+/// How a line of the system prompt changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineChange {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub change: LineChange,
+    pub text: String,
+}
+
+/// What changed from one version of an agent to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionDiff {
+    pub from_version: i64,
+    pub to_version: i64,
+    /// Settings other than the system prompt that differ
+    pub changes: Vec<FieldChange>,
+    /// The system prompt line by line, empty when it is the same in both
+    pub system_prompt: Vec<DiffLine>,
+}
+
+/// The agent as it is now
+pub fn current_snapshot(conn: &Connection, agent_id: i64) -> SqliteResult<AgentSnapshot> {
+    conn.query_row(
+        &format!("SELECT {} FROM agents WHERE id = ?1", SNAPSHOT_COLUMNS),
+        params![agent_id],
+        |row| AgentSnapshot::from_row(row, 0),
+    )
+}
+
+/// Record the agent as it is now, unless its latest version already matches
+///
+/// Returns the ID of the version matching the agent.
+pub fn record_version(conn: &Connection, agent_id: i64) -> SqliteResult<i64> {
+    let current = current_snapshot(conn, agent_id)?;
+
+    let latest = latest_version(conn, agent_id)?;
+    if let Some(latest) = &latest {
+        if latest.snapshot == current {
+            return Ok(latest.id);
+        }
+    }
+
+    conn.execute(
+        &format!(
+            "INSERT INTO agent_versions (agent_id, version, {})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            SNAPSHOT_COLUMNS
+        ),
+        params![
+            agent_id,
+            latest.map_or(1, |latest| latest.version + 1),
+            current.name,
+            current.icon,
+            current.system_prompt,
+            current.default_task,
+            current.model,
+            current.sandbox_enabled,
+            current.enable_file_read,
+            current.enable_file_write,
+            current.enable_network,
+            current.sandbox_policy.map(SandboxPolicy::as_str),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Record a first version for agents created before versions were kept
+pub fn record_missing_versions(conn: &Connection) -> SqliteResult<()> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM agents WHERE id NOT IN (SELECT agent_id FROM agent_versions)")?;
+    let agent_ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    for agent_id in agent_ids {
+        record_version(conn, agent_id)?;
+    }
+    Ok(())
+}
+
+/// The most recent version of an agent, if any was recorded
+pub fn latest_version(conn: &Connection, agent_id: i64) -> SqliteResult<Option<AgentVersion>> {
+    conn.query_row(
+        &format!(
+            "SELECT id, agent_id, version, created_at, {} FROM agent_versions
+             WHERE agent_id = ?1 ORDER BY version DESC LIMIT 1",
+            SNAPSHOT_COLUMNS
+        ),
+        params![agent_id],
+        AgentVersion::from_row,
+    )
+    .optional()
+}
+
+/// Versions of an agent, newest first
+pub fn list_versions(conn: &Connection, agent_id: i64) -> SqliteResult<Vec<AgentVersion>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, agent_id, version, created_at, {} FROM agent_versions
+         WHERE agent_id = ?1 ORDER BY version DESC",
+        SNAPSHOT_COLUMNS
+    ))?;
+    let versions = stmt
+        .query_map(params![agent_id], AgentVersion::from_row)?
+        .collect();
+    versions
+}
+
+pub fn get_version(conn: &Connection, version_id: i64) -> SqliteResult<AgentVersion> {
+    conn.query_row(
+        &format!(
+            "SELECT id, agent_id, version, created_at, {} FROM agent_versions WHERE id = ?1",
+            SNAPSHOT_COLUMNS
+        ),
+        params![version_id],
+        AgentVersion::from_row,
+    )
+}
+
+/// Put an agent back the way it was at `version_id`
+///
+/// The rollback is itself recorded as a new version, so history only ever grows. Returns
+/// the ID of that version, or None when the version belongs to another agent.
+pub fn rollback(conn: &Connection, agent_id: i64, version_id: i64) -> SqliteResult<Option<i64>> {
+    let target = get_version(conn, version_id)?;
+    if target.agent_id != agent_id {
+        return Ok(None);
+    }
+
+    let snapshot = target.snapshot;
+    conn.execute(
+        "UPDATE agents SET name = ?1, icon = ?2, system_prompt = ?3, default_task = ?4,
+             model = ?5, sandbox_enabled = ?6, enable_file_read = ?7, enable_file_write = ?8,
+             enable_network = ?9, sandbox_policy = ?10
+         WHERE id = ?11",
+        params![
+            snapshot.name,
+            snapshot.icon,
+            snapshot.system_prompt,
+            snapshot.default_task,
+            snapshot.model,
+            snapshot.sandbox_enabled,
+            snapshot.enable_file_read,
+            snapshot.enable_file_write,
+            snapshot.enable_network,
+            snapshot.sandbox_policy.map(SandboxPolicy::as_str),
+            agent_id,
+        ],
+    )?;
+    record_version(conn, agent_id).map(Some)
+}
+
+/// Compare two versions, field by field and the system prompt line by line
+pub fn diff_versions(from: &AgentVersion, to: &AgentVersion) -> VersionDiff {
+    let fields = |snapshot: &AgentSnapshot| match serde_json::to_value(snapshot) {
+        Ok(JsonValue::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let from_fields = fields(&from.snapshot);
+    let to_fields = fields(&to.snapshot);
+
+    let changes = from_fields
+        .iter()
+        .filter(|(field, _)| field.as_str() != "system_prompt")
+        .filter_map(|(field, from_value)| {
+            let to_value = to_fields.get(field).cloned().unwrap_or(JsonValue::Null);
+            (*from_value != to_value).then(|| FieldChange {
+                field: field.clone(),
+                from: from_value.clone(),
+                to: to_value,
+            })
+        })
+        .collect();
+
+    let system_prompt = if from.snapshot.system_prompt == to.snapshot.system_prompt {
+        Vec::new()
+    } else {
+        diff_lines(&from.snapshot.system_prompt, &to.snapshot.system_prompt)
+    };
+
+    VersionDiff {
+        from_version: from.version,
+        to_version: to.version,
+        changes,
+        system_prompt,
+    }
+}
+
+/// Most cells the line diff table may have, about 32 MiB, before falling back to a plain
+/// replacement of the lines that differ
+const MAX_DIFF_CELLS: usize = 4 * 1024 * 1024;
+
+/// Line diff from `old` to `new`, keeping the longest run of lines common to both
+///
+/// Lines shared at the start and end are matched directly. When what is left between them
+/// is too large to compare, it is shown as removed and then added as a whole.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let line = |change, text: &str| DiffLine {
+        change,
+        text: text.to_string(),
+    };
+    let mut lines: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|text| line(LineChange::Unchanged, text))
+        .collect();
+
+    let cells = (old_middle.len() + 1).saturating_mul(new_middle.len() + 1);
+    if cells > MAX_DIFF_CELLS {
+        lines.extend(
+            old_middle
+                .iter()
+                .map(|text| line(LineChange::Removed, text)),
+        );
+        lines.extend(new_middle.iter().map(|text| line(LineChange::Added, text)));
+    } else {
+        lines.extend(diff_middle(old_middle, new_middle));
+    }
+
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|text| line(LineChange::Unchanged, text)),
+    );
+    lines
+}
+
+/// Longest common subsequence diff, in time and space that grow with both lengths multiplied
+fn diff_middle(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    // common[i][j] is how many lines old[i..] and new[j..] have in common, in order
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let line = |change, text: &str| DiffLine {
+        change,
+        text: text.to_string(),
+    };
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(line(LineChange::Unchanged, old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(line(LineChange::Removed, old[i]));
+            i += 1;
+        } else {
+            lines.push(line(LineChange::Added, new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|text| line(LineChange::Removed, text)));
+    lines.extend(new[j..].iter().map(|text| line(LineChange::Added, text)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agents (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 name TEXT NOT NULL,
+                 icon TEXT NOT NULL,
+                 system_prompt TEXT NOT NULL,
+                 default_task TEXT,
+                 model TEXT NOT NULL DEFAULT 'sonnet',
+                 sandbox_enabled BOOLEAN NOT NULL DEFAULT 1,
+                 enable_file_read BOOLEAN NOT NULL DEFAULT 1,
+                 enable_file_write BOOLEAN NOT NULL DEFAULT 1,
+                 enable_network BOOLEAN NOT NULL DEFAULT 0,
+                 sandbox_policy TEXT
+             );
+             CREATE TABLE agent_versions (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 agent_id INTEGER NOT NULL,
+                 version INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 icon TEXT NOT NULL,
+                 system_prompt TEXT NOT NULL,
+                 default_task TEXT,
+                 model TEXT NOT NULL,
+                 sandbox_enabled BOOLEAN NOT NULL,
+                 enable_file_read BOOLEAN NOT NULL,
+                 enable_file_write BOOLEAN NOT NULL,
+                 enable_network BOOLEAN NOT NULL,
+                 sandbox_policy TEXT,
+                 created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                 UNIQUE (agent_id, version)
+             );
+             INSERT INTO agents (name, icon, system_prompt) VALUES ('reviewer', 'bot', 'Review.');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_versions_only_record_changes() {
+        let conn = versions_db();
+        record_missing_versions(&conn).unwrap();
+        let first = latest_version(&conn, 1).unwrap().unwrap();
+        assert_eq!(first.version, 1);
+        assert_eq!(record_version(&conn, 1).unwrap(), first.id);
+
+        conn.execute("UPDATE agents SET model = 'opus' WHERE id = 1", [])
+            .unwrap();
+        let second = record_version(&conn, 1).unwrap();
+        assert_ne!(second, first.id);
+        assert_eq!(get_version(&conn, second).unwrap().version, 2);
+        assert_eq!(list_versions(&conn, 1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rollback_records_a_new_version() {
+        let conn = versions_db();
+        let first = record_version(&conn, 1).unwrap();
+        conn.execute(
+            "UPDATE agents SET system_prompt = 'Review harder.', sandbox_policy = 'off'",
+            [],
+        )
+        .unwrap();
+        record_version(&conn, 1).unwrap();
+
+        let rolled_back = rollback(&conn, 1, first).unwrap().unwrap();
+        let version = get_version(&conn, rolled_back).unwrap();
+        assert_eq!(version.version, 3);
+        assert_eq!(
+            version.snapshot,
+            get_version(&conn, first).unwrap().snapshot
+        );
+
+        assert_eq!(rollback(&conn, 2, first).unwrap(), None);
+    }
+
+    #[test]
+    fn test_diff_versions() {
+        let conn = versions_db();
+        let first = record_version(&conn, 1).unwrap();
+        conn.execute(
+            "UPDATE agents SET system_prompt = 'Read the diff.\nReview.', enable_network = 1",
+            [],
+        )
+        .unwrap();
+        let second = record_version(&conn, 1).unwrap();
+
+        let diff = diff_versions(
+            &get_version(&conn, first).unwrap(),
+            &get_version(&conn, second).unwrap(),
+        );
+        assert_eq!((diff.from_version, diff.to_version), (1, 2));
+        assert_eq!(
+            diff.changes,
+            vec![FieldChange {
+                field: "enable_network".to_string(),
+                from: JsonValue::Bool(false),
+                to: JsonValue::Bool(true),
+            }]
+        );
+        let changes: Vec<_> = diff.system_prompt.iter().map(|l| l.change).collect();
+        assert_eq!(changes, vec![LineChange::Added, LineChange::Unchanged]);
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let lines = diff_lines("a\nb\nc", "a\nc\nd");
+        let summary: Vec<_> = lines.iter().map(|l| (l.change, l.text.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (LineChange::Unchanged, "a"),
+                (LineChange::Removed, "b"),
+                (LineChange::Unchanged, "c"),
+                (LineChange::Added, "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_lines_replaces_large_changes_whole() {
+        let old: String = (0..3000).map(|i| format!("old {}\n", i)).collect();
+        let new: String = (0..3000).map(|i| format!("new {}\n", i)).collect();
+        let lines = diff_lines(&format!("same\n{}end", old), &format!("same\n{}end", new));
+
+        assert_eq!(lines.len(), 6002);
+        assert_eq!(lines[0].change, LineChange::Unchanged);
+        assert!(lines[1..3001]
+            .iter()
+            .all(|line| line.change == LineChange::Removed));
+        assert!(lines[3001..6001]
+            .iter()
+            .all(|line| line.change == LineChange::Added));
+        assert_eq!(lines[6001].text, "end");
+    }
+}
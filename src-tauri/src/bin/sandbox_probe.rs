@@ -0,0 +1,94 @@
+//! Probe run under a sandbox profile by `test_sandbox_profile`
+//!
+//! Each argument is an operation to attempt, either `kind` or `kind:target`. One line
+//! is printed per operation, with its index, `allowed`, `denied` or `failed`, and the
+//! error if there was one.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn main() {
+    for (index, operation) in std::env::args().skip(1).enumerate() {
+        match attempt(&operation) {
+            Ok(()) => println!("{} allowed", index),
+            Err(e) if is_denial(&e) => println!("{} denied {}", index, e),
+            Err(e) => println!("{} failed {}", index, e),
+        }
+    }
+}
+
+fn attempt(operation: &str) -> io::Result<()> {
+    let (kind, target) = operation.split_once(':').unwrap_or((operation, ""));
+    match kind {
+        "read" => {
+            let mut buf = [0u8; 1];
+            fs::File::open(target)?.read(&mut buf).map(|_| ())
+        }
+        "write" => OpenOptions::new()
+            .append(true)
+            .open(target)?
+            .write_all(b"probe\n"),
+        "create" => {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)?;
+            // Removal may be denied separately, which is not what this checks
+            let _ = fs::remove_file(target);
+            Ok(())
+        }
+        "connect" => {
+            let addr: SocketAddr = target
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map(|_| ())
+        }
+        "system_info" => system_info(),
+        "spawn" => Command::new(target)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|_| ()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown operation {}", kind),
+        )),
+    }
+}
+
+/// Whether an error comes from the sandbox rather than the operation itself
+fn is_denial(error: &io::Error) -> bool {
+    if error.kind() == io::ErrorKind::PermissionDenied {
+        return true;
+    }
+    // Without a network namespace route, connecting to loopback fails this way
+    #[cfg(unix)]
+    if matches!(
+        error.raw_os_error(),
+        Some(libc::ENETUNREACH) | Some(libc::EPERM)
+    ) {
+        return true;
+    }
+    false
+}
+
+#[cfg(unix)]
+fn system_info() -> io::Result<()> {
+    // uname goes through sysctl on macOS, which Seatbelt profiles control
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn system_info() -> io::Result<()> {
+    std::thread::available_parallelism().map(|_| ())
+}
@@ -310,6 +310,7 @@ impl CommunicationManager {
             MessageType::Completion => "## Task Complete",
             MessageType::BlockerReport => "## Blocker Report",
             MessageType::ContextShare => "## Context Share",
+            MessageType::Assignment => "## Assignment",
         };
 
         let status_emoji = match message.status {
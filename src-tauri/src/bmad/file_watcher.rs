@@ -18,7 +18,6 @@ pub struct FileWatcher {
     _watcher: RecommendedWatcher,
     watched_projects: Arc<Mutex<HashMap<Uuid, PathBuf>>>,
     event_sender: broadcast::Sender<FileChangeEvent>,
-    _event_receiver: Receiver<notify::Result<Event>>,
 }
 
 impl FileWatcher {
@@ -51,7 +50,6 @@ impl FileWatcher {
             _watcher: watcher,
             watched_projects,
             event_sender,
-            _event_receiver: rx,
         })
     }
 
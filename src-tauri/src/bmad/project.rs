@@ -240,9 +240,10 @@ impl ProjectManager {
 
         project.settings = settings;
         project.last_modified = Utc::now();
+        let project = project.clone();
 
         // Save updated metadata
-        self.save_project_metadata(project)?;
+        self.save_project_metadata(&project)?;
 
         info!("Updated settings for project: {}", project_id);
         Ok(())
@@ -60,7 +60,7 @@ pub struct AgentMessage {
     pub status: MessageStatus,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AgentType {
     Analyst,
     Architect,
@@ -84,7 +84,7 @@ pub enum MessageType {
     Assignment,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MessageStatus {
     Pending,
     Read,
@@ -168,7 +168,7 @@ pub struct AgentStatus {
     pub estimated_start: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AgentStatusType {
     Idle,
     Active,
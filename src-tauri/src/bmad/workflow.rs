@@ -4,6 +4,7 @@ use crate::bmad::types::{
 };
 use crate::bmad::{StateManager, CommunicationManager};
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use tracing::{debug, error, info, warn};
@@ -15,7 +16,7 @@ pub struct WorkflowManager {
     communication_manager: CommunicationManager,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentRecommendation {
     pub agent: AgentType,
     pub reason: String,
@@ -274,6 +275,8 @@ impl WorkflowManager {
                     prerequisites: vec!["All quality checks passed".to_string()],
                 });
             }
+
+            (BMadPhase::Planning, _) => {}
         }
 
         // Apply intelligent prioritization based on agent availability and dependencies
@@ -318,6 +321,7 @@ impl WorkflowManager {
                 AgentStatusType::Active => load += 0.8,
                 AgentStatusType::Waiting => load += 0.3,
                 AgentStatusType::Blocked => load += 1.0, // Fully loaded due to blocker
+                AgentStatusType::Offline => load += 1.0, // Unavailable
                 AgentStatusType::Idle => load += 0.0,
             }
             
@@ -449,7 +453,7 @@ impl WorkflowManager {
         let event = WorkflowEvent {
             id: Uuid::new_v4(),
             event_type: WorkflowEventType::StoryStart,
-            agent: assigned_agent,
+            agent: assigned_agent.clone(),
             description: format!("Started story: {}", story_name),
             timestamp: Utc::now(),
             metadata: HashMap::new(),
@@ -484,7 +488,7 @@ impl WorkflowManager {
         let event = WorkflowEvent {
             id: Uuid::new_v4(),
             event_type: WorkflowEventType::StoryComplete,
-            agent: completing_agent,
+            agent: completing_agent.clone(),
             description: format!("Completed story: {}", story_name),
             timestamp: Utc::now(),
             metadata: HashMap::new(),
@@ -496,7 +500,7 @@ impl WorkflowManager {
         // Create handoff message to QA if needed
         if self.story_needs_qa(&story_name)? {
             let message = self.communication_manager.create_message(
-                completing_agent,
+                completing_agent.clone(),
                 Some(AgentType::QualityAssurance),
                 format!("Story '{}' completed and ready for QA review. Please test functionality and verify requirements are met.", story_name),
                 MessageType::Handoff,
@@ -536,7 +540,7 @@ impl WorkflowManager {
 
         // Create blocker report message
         let message = self.communication_manager.create_message(
-            agent,
+            agent.clone(),
             None, // Broadcast to team
             format!("🚫 **Blocker Reported**\n\n{}\n\n**Affected Story:** {}\n\nPlease assist in resolving this blocker.", 
                 description, 
@@ -572,8 +576,8 @@ impl WorkflowManager {
 
         // Create resolution message
         let message = self.communication_manager.create_message(
-            resolver_agent,
-            Some(blocked_agent),
+            resolver_agent.clone(),
+            Some(blocked_agent.clone()),
             format!("✅ **Blocker Resolved**\n\n{}\n\nYou can now continue with your tasks.", resolution),
             MessageType::Update,
             vec![],
@@ -0,0 +1,532 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+use zstd::stream::{encode_all, Decoder};
+
+use super::{storage::CheckpointStorage, Checkpoint, CheckpointPaths, FileSnapshot};
+
+/// Version of the archive format written by `export_archive`
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Largest decompressed archive `read_archive` accepts
+pub const MAX_ARCHIVE_SIZE: u64 = 1 << 30;
+
+/// Portable, self-contained copy of one checkpoint or a timeline branch
+///
+/// Serialized as zstd-compressed JSON. File contents are stored once per hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointArchive {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub source_project_id: String,
+    pub source_session_id: String,
+    /// Checkpoints ordered root first, so parents always precede their children
+    pub checkpoints: Vec<ArchivedCheckpoint>,
+    /// File contents keyed by their SHA-256 hash
+    pub blobs: BTreeMap<String, String>,
+}
+
+/// A checkpoint together with its messages and file references
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub messages: String,
+    /// SHA-256 of `messages`
+    pub messages_hash: String,
+    pub files: Vec<ArchivedFile>,
+}
+
+/// Reference from a checkpoint to a blob in the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedFile {
+    /// Project-relative path
+    pub path: PathBuf,
+    /// SHA-256 of the content; deleted files carry the hash of empty content
+    pub hash: String,
+    pub is_deleted: bool,
+    pub permissions: Option<u32>,
+    pub size: u64,
+}
+
+/// Result of importing an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveImportResult {
+    pub project_id: String,
+    pub session_id: String,
+    pub checkpoints: Vec<Checkpoint>,
+    /// Number of distinct file blobs whose hash was verified
+    pub verified_blobs: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Export a checkpoint to a compressed archive
+///
+/// With `include_ancestors` the whole branch from the timeline root down to the
+/// checkpoint is exported, which is what a faithful reproduction usually needs.
+pub fn export_archive(
+    storage: &CheckpointStorage,
+    project_id: &str,
+    session_id: &str,
+    checkpoint_id: &str,
+    include_ancestors: bool,
+) -> Result<Vec<u8>> {
+    let paths = CheckpointPaths::new(&storage.claude_dir, project_id, session_id);
+    let timeline = storage.load_timeline(&paths.timeline_file)?;
+
+    // Walk up the parent chain, then flip it so the root comes first
+    let mut branch = vec![checkpoint_id.to_string()];
+    if include_ancestors {
+        let mut current = checkpoint_id.to_string();
+        while let Some(parent_id) = timeline
+            .find_checkpoint(&current)
+            .and_then(|node| node.checkpoint.parent_checkpoint_id.clone())
+        {
+            if branch.contains(&parent_id) {
+                anyhow::bail!("Cycle in checkpoint parents at {}", parent_id);
+            }
+            branch.push(parent_id.clone());
+            current = parent_id;
+        }
+        branch.reverse();
+    }
+
+    let mut checkpoints = Vec::new();
+    let mut blobs = BTreeMap::new();
+
+    for id in &branch {
+        let (checkpoint, snapshots, messages) = storage
+            .load_checkpoint(project_id, session_id, id)
+            .with_context(|| format!("Failed to load checkpoint {}", id))?;
+
+        let mut files = Vec::new();
+        for snapshot in snapshots {
+            if !snapshot.is_deleted {
+                // The pool tolerates missing blobs on load, so double check what we ship
+                let actual_hash = CheckpointStorage::calculate_file_hash(&snapshot.content);
+                if actual_hash != snapshot.hash {
+                    anyhow::bail!(
+                        "Content of {} in checkpoint {} does not match its hash",
+                        snapshot.file_path.display(),
+                        id
+                    );
+                }
+                blobs.insert(snapshot.hash.clone(), snapshot.content);
+            }
+            let hash = if snapshot.is_deleted {
+                CheckpointStorage::calculate_file_hash("")
+            } else {
+                snapshot.hash
+            };
+            files.push(ArchivedFile {
+                path: snapshot.file_path,
+                hash,
+                is_deleted: snapshot.is_deleted,
+                permissions: snapshot.permissions,
+                size: snapshot.size,
+            });
+        }
+
+        checkpoints.push(ArchivedCheckpoint {
+            checkpoint,
+            messages_hash: CheckpointStorage::calculate_file_hash(&messages),
+            messages,
+            files,
+        });
+    }
+
+    let archive = CheckpointArchive {
+        version: ARCHIVE_FORMAT_VERSION,
+        exported_at: Utc::now(),
+        source_project_id: project_id.to_string(),
+        source_session_id: session_id.to_string(),
+        checkpoints,
+        blobs,
+    };
+
+    let json = serde_json::to_vec(&archive).context("Failed to serialize archive")?;
+    encode_all(&json[..], 19).context("Failed to compress archive")
+}
+
+/// Whether a string is a lowercase hex SHA-256 digest, as used for pool file names
+fn is_sha256_hex(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Whether a string is a UUID in the hyphenated form checkpoint and session IDs use
+fn is_uuid(id: &str) -> bool {
+    Uuid::parse_str(id).is_ok_and(|uuid| uuid.to_string() == id)
+}
+
+/// Whether a project ID names a single directory under `~/.claude/projects`
+fn is_project_id(id: &str) -> bool {
+    !id.contains(['/', '\\'])
+        && matches!(
+            Path::new(id).components().collect::<Vec<_>>()[..],
+            [Component::Normal(_)]
+        )
+}
+
+/// Decompress at most `limit` bytes, failing on archives that expand past it
+fn decompress(data: &[u8], limit: u64) -> Result<Vec<u8>> {
+    let mut json = Vec::new();
+    Decoder::new(data)
+        .context("Failed to decompress archive")?
+        .take(limit + 1)
+        .read_to_end(&mut json)
+        .context("Failed to decompress archive")?;
+    if json.len() as u64 > limit {
+        anyhow::bail!("Archive expands to more than {} bytes", limit);
+    }
+    Ok(json)
+}
+
+/// Whether an archived path stays inside the project it is restored into
+fn is_safe_relative_path(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Decompress and parse an archive, verifying every hash it contains
+///
+/// Archives come from other machines, so everything that ends up in a file name is
+/// checked: blob hashes, checkpoint IDs and file paths.
+pub fn read_archive(data: &[u8]) -> Result<CheckpointArchive> {
+    let json = decompress(data, MAX_ARCHIVE_SIZE)?;
+    let archive: CheckpointArchive =
+        serde_json::from_slice(&json).context("Failed to parse archive")?;
+
+    if archive.version > ARCHIVE_FORMAT_VERSION {
+        anyhow::bail!(
+            "Unsupported archive version {} (expected {} or lower)",
+            archive.version,
+            ARCHIVE_FORMAT_VERSION
+        );
+    }
+
+    for (hash, content) in &archive.blobs {
+        if !is_sha256_hex(hash) {
+            anyhow::bail!("Invalid blob hash in archive: {:?}", hash);
+        }
+        if &CheckpointStorage::calculate_file_hash(content) != hash {
+            anyhow::bail!("Integrity check failed for file blob {}", hash);
+        }
+    }
+
+    for archived in &archive.checkpoints {
+        if !is_uuid(&archived.checkpoint.id) {
+            anyhow::bail!(
+                "Invalid checkpoint ID in archive: {:?}",
+                archived.checkpoint.id
+            );
+        }
+        if CheckpointStorage::calculate_file_hash(&archived.messages) != archived.messages_hash {
+            anyhow::bail!(
+                "Integrity check failed for messages of checkpoint {}",
+                archived.checkpoint.id
+            );
+        }
+        for file in &archived.files {
+            if !is_safe_relative_path(&file.path) {
+                anyhow::bail!(
+                    "Unsafe file path {} in checkpoint {}",
+                    file.path.display(),
+                    archived.checkpoint.id
+                );
+            }
+            if !is_sha256_hex(&file.hash) {
+                anyhow::bail!(
+                    "Invalid hash for {} in checkpoint {}",
+                    file.path.display(),
+                    archived.checkpoint.id
+                );
+            }
+            if !file.is_deleted && !archive.blobs.contains_key(&file.hash) {
+                anyhow::bail!(
+                    "Archive is missing content for {} in checkpoint {}",
+                    file.path.display(),
+                    archived.checkpoint.id
+                );
+            }
+        }
+    }
+
+    Ok(archive)
+}
+
+/// Import an archive into a new session of `project_id`
+///
+/// Checkpoint IDs are kept so the archive can be traced back to its origin. Git commits
+/// are dropped since they refer to the exporting machine's repository.
+pub fn import_archive(
+    storage: &CheckpointStorage,
+    data: &[u8],
+    project_id: &str,
+    session_id: &str,
+) -> Result<ArchiveImportResult> {
+    if !is_project_id(project_id) {
+        anyhow::bail!("Invalid project ID: {:?}", project_id);
+    }
+    if !is_uuid(session_id) {
+        anyhow::bail!("Session ID must be a UUID: {:?}", session_id);
+    }
+    let archive = read_archive(data)?;
+
+    let paths = CheckpointPaths::new(&storage.claude_dir, project_id, session_id);
+    if paths.timeline_file.exists() {
+        let timeline = storage.load_timeline(&paths.timeline_file)?;
+        if timeline.root_node.is_some() {
+            anyhow::bail!(
+                "Session {} already has checkpoints; import into a new session",
+                session_id
+            );
+        }
+    }
+    storage.init_storage(project_id, session_id)?;
+
+    let mut imported_ids = HashSet::new();
+    let mut checkpoints = Vec::new();
+    let mut warnings = Vec::new();
+
+    for archived in archive.checkpoints {
+        let mut checkpoint = archived.checkpoint;
+        checkpoint.project_id = project_id.to_string();
+        checkpoint.session_id = session_id.to_string();
+        checkpoint.git_commit = None;
+        // A lone checkpoint becomes the root of the imported timeline
+        if let Some(parent_id) = &checkpoint.parent_checkpoint_id {
+            if !imported_ids.contains(parent_id) {
+                checkpoint.parent_checkpoint_id = None;
+            }
+        }
+
+        let snapshots: Vec<FileSnapshot> = archived
+            .files
+            .into_iter()
+            .map(|file| FileSnapshot {
+                checkpoint_id: checkpoint.id.clone(),
+                content: archive.blobs.get(&file.hash).cloned().unwrap_or_default(),
+                file_path: file.path,
+                // Deleted files are stored without a hash
+                hash: if file.is_deleted {
+                    String::new()
+                } else {
+                    file.hash
+                },
+                is_deleted: file.is_deleted,
+                permissions: file.permissions,
+                size: file.size,
+            })
+            .collect();
+
+        let result = storage.save_checkpoint(
+            project_id,
+            session_id,
+            &checkpoint,
+            snapshots,
+            &archived.messages,
+        )?;
+        warnings.extend(result.warnings);
+
+        imported_ids.insert(checkpoint.id.clone());
+        checkpoints.push(result.checkpoint);
+    }
+
+    Ok(ArchiveImportResult {
+        project_id: project_id.to_string(),
+        session_id: session_id.to_string(),
+        checkpoints,
+        verified_blobs: archive.blobs.len(),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use std::fs;
+    use tempfile::TempDir;
+    use zstd::stream::decode_all;
+
+    const PROJECT_ID: &str = "test-project";
+    const SESSION_ID: &str = "5b0c6f3e-2f4a-4d7e-9a51-0c6b8f1d2e3a";
+
+    /// Export a two-checkpoint branch whose second checkpoint deletes a file
+    async fn exported_branch(temp_dir: &TempDir) -> (CheckpointStorage, Vec<u8>) {
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(project_path.join("src")).unwrap();
+        let manager = CheckpointManager::new(
+            PROJECT_ID.to_string(),
+            "source".to_string(),
+            project_path.clone(),
+            temp_dir.path().join("claude"),
+        )
+        .await
+        .unwrap();
+
+        fs::write(project_path.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(project_path.join("old.txt"), "stale").unwrap();
+        manager
+            .track_file_modification("src/main.rs")
+            .await
+            .unwrap();
+        manager.track_file_modification("old.txt").await.unwrap();
+        manager
+            .create_checkpoint(Some("first".into()), None)
+            .await
+            .unwrap();
+
+        fs::remove_file(project_path.join("old.txt")).unwrap();
+        manager.track_file_modification("old.txt").await.unwrap();
+        let last = manager
+            .create_checkpoint(Some("second".into()), None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        let storage = CheckpointStorage::new(temp_dir.path().join("claude"));
+        let data = export_archive(&storage, PROJECT_ID, "source", &last.id, true).unwrap();
+        (storage, data)
+    }
+
+    fn tamper(data: &[u8], edit: impl FnOnce(&mut CheckpointArchive)) -> Vec<u8> {
+        let mut archive: CheckpointArchive =
+            serde_json::from_slice(&decode_all(data).unwrap()).unwrap();
+        edit(&mut archive);
+        encode_all(&serde_json::to_vec(&archive).unwrap()[..], 3).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_archive_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let (storage, data) = exported_branch(&temp_dir).await;
+
+        let result = import_archive(&storage, &data, PROJECT_ID, SESSION_ID).unwrap();
+        assert_eq!(result.checkpoints.len(), 2);
+        assert_eq!(result.verified_blobs, 2);
+        assert_eq!(
+            result.checkpoints[1].parent_checkpoint_id.as_deref(),
+            Some(result.checkpoints[0].id.as_str())
+        );
+
+        let load = |index: usize| {
+            storage
+                .load_checkpoint(PROJECT_ID, SESSION_ID, &result.checkpoints[index].id)
+                .unwrap()
+                .1
+        };
+        let first = load(0);
+        let main = first
+            .iter()
+            .find(|f| f.file_path == Path::new("src/main.rs"))
+            .unwrap();
+        assert_eq!(main.content, "fn main() {}");
+        let second = load(1);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].file_path, Path::new("old.txt"));
+        assert!(second[0].is_deleted);
+
+        // Importing twice into the same session is refused
+        assert!(import_archive(&storage, &data, PROJECT_ID, SESSION_ID).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_ids_that_leave_the_projects_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let (storage, data) = exported_branch(&temp_dir).await;
+
+        for project_id in ["", ".", "..", "../evil", "a/b", "a\\b", "/tmp"] {
+            assert!(
+                import_archive(&storage, &data, project_id, SESSION_ID).is_err(),
+                "accepted project ID {:?}",
+                project_id
+            );
+        }
+        for session_id in ["imported", "../evil", "", &SESSION_ID.to_uppercase()] {
+            assert!(
+                import_archive(&storage, &data, PROJECT_ID, session_id).is_err(),
+                "accepted session ID {:?}",
+                session_id
+            );
+        }
+        assert!(!storage.claude_dir.join("evil").exists());
+    }
+
+    #[test]
+    fn test_decompression_is_bounded() {
+        let data = encode_all(&[b'x'; 4096][..], 3).unwrap();
+        assert_eq!(decompress(&data, 4096).unwrap().len(), 4096);
+        assert!(decompress(&data, 4095).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tampered_archives_are_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let (storage, data) = exported_branch(&temp_dir).await;
+
+        let tampered: Vec<(&str, Vec<u8>)> = vec![
+            (
+                "parent dir path",
+                tamper(&data, |a| {
+                    a.checkpoints[0].files[0].path = PathBuf::from("../../.bashrc")
+                }),
+            ),
+            (
+                "absolute path",
+                tamper(&data, |a| {
+                    a.checkpoints[0].files[0].path = PathBuf::from("/etc/passwd")
+                }),
+            ),
+            (
+                "deleted entry hash",
+                tamper(&data, |a| {
+                    let deleted = a.checkpoints[1]
+                        .files
+                        .iter_mut()
+                        .find(|f| f.is_deleted)
+                        .unwrap();
+                    deleted.hash = "../../../escape".into();
+                }),
+            ),
+            (
+                "blob hash",
+                tamper(&data, |a| {
+                    let (hash, content) = a.blobs.pop_first().unwrap();
+                    a.blobs.insert(hash.to_uppercase(), content);
+                }),
+            ),
+            (
+                "blob content",
+                tamper(&data, |a| {
+                    a.blobs.values_mut().next().unwrap().push('!');
+                }),
+            ),
+            (
+                "checkpoint id",
+                tamper(&data, |a| a.checkpoints[0].checkpoint.id = "../evil".into()),
+            ),
+        ];
+
+        for (what, data) in tampered {
+            let session_id = Uuid::new_v4().to_string();
+            assert!(
+                import_archive(&storage, &data, PROJECT_ID, &session_id).is_err(),
+                "accepted archive with tampered {}",
+                what
+            );
+            // Nothing is written for a rejected archive
+            let paths = CheckpointPaths::new(&storage.claude_dir, PROJECT_ID, &session_id);
+            assert!(!paths.timeline_file.exists(), "{}", what);
+        }
+    }
+}
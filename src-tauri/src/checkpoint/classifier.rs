@@ -0,0 +1,469 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+
+/// Default number of distinct files the recent turns may edit before it counts as a mass edit
+pub const DEFAULT_MASS_EDIT_THRESHOLD: usize = 5;
+
+/// Default number of tool-use turns whose edits are counted together towards a mass edit
+pub const DEFAULT_MASS_EDIT_WINDOW: usize = 3;
+
+/// Options of `git` itself that take the following word as their value
+const GIT_GLOBAL_OPTIONS_WITH_VALUE: &[&str] =
+    &["-C", "-c", "--git-dir", "--work-tree", "--namespace"];
+
+/// Options of `xargs` that take the following word as their value
+const XARGS_OPTIONS_WITH_VALUE: &[&str] = &[
+    "-a",
+    "-d",
+    "-E",
+    "-I",
+    "-L",
+    "-n",
+    "-P",
+    "-s",
+    "--arg-file",
+    "--delimiter",
+    "--max-args",
+    "--max-chars",
+    "--max-lines",
+    "--max-procs",
+];
+
+/// A destructive operation detected in a tool-use message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DestructiveOperation {
+    /// A file or directory is being removed
+    FileDeletion { path: String },
+    /// A shell command that discards working tree state (rm, git reset, git checkout, ...)
+    DestructiveCommand { command: String },
+    /// Many files are edited within a few consecutive turns
+    MassEdit { file_count: usize },
+    /// A file outside the project directory is being written
+    WriteOutsideProject { path: String },
+}
+
+impl DestructiveOperation {
+    /// Short human readable summary, used as the checkpoint description
+    pub fn describe(&self) -> String {
+        match self {
+            DestructiveOperation::FileDeletion { path } => format!("delete {}", path),
+            DestructiveOperation::DestructiveCommand { command } => command.clone(),
+            DestructiveOperation::MassEdit { file_count } => format!("edit {} files", file_count),
+            DestructiveOperation::WriteOutsideProject { path } => format!("write {}", path),
+        }
+    }
+}
+
+/// Classifies tool-use messages for the `Smart` checkpoint strategy
+///
+/// Files edited by the last few tool-use turns are remembered, so an edit spread over
+/// several messages is caught as a mass edit as well.
+#[derive(Debug, Clone)]
+pub struct SmartClassifier {
+    project_path: PathBuf,
+    mass_edit_threshold: usize,
+    mass_edit_window: usize,
+    recent_edits: VecDeque<HashSet<PathBuf>>,
+}
+
+impl SmartClassifier {
+    pub fn new(project_path: &Path) -> Self {
+        Self {
+            project_path: normalize_path(project_path),
+            mass_edit_threshold: DEFAULT_MASS_EDIT_THRESHOLD,
+            mass_edit_window: DEFAULT_MASS_EDIT_WINDOW,
+            recent_edits: VecDeque::new(),
+        }
+    }
+
+    /// Override the number of files that makes an edit a mass edit
+    pub fn with_mass_edit_threshold(mut self, threshold: usize) -> Self {
+        self.mass_edit_threshold = threshold.max(1);
+        self
+    }
+
+    /// Override the number of tool-use turns whose edits are counted together
+    pub fn with_mass_edit_window(mut self, turns: usize) -> Self {
+        self.mass_edit_window = turns.max(1);
+        self
+    }
+
+    /// Find destructive operations in a JSONL message
+    ///
+    /// Messages that are not valid JSON or carry no tool calls yield an empty list and do
+    /// not count as a turn. Once a mass edit is reported the window starts over.
+    pub fn classify_message(&mut self, message: &str) -> Vec<DestructiveOperation> {
+        let msg = match serde_json::from_str::<serde_json::Value>(message) {
+            Ok(msg) => msg,
+            Err(_) => return Vec::new(),
+        };
+
+        let content = match msg
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array())
+        {
+            Some(content) => content,
+            None => return Vec::new(),
+        };
+
+        let mut operations = Vec::new();
+        let mut edited_files = HashSet::new();
+        let mut has_tool_calls = false;
+
+        for item in content {
+            if item.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                continue;
+            }
+            has_tool_calls = true;
+            let tool_name = item.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            let input = match item.get("input") {
+                Some(input) => input,
+                None => continue,
+            };
+
+            match tool_name.to_lowercase().as_str() {
+                "write" | "edit" | "multiedit" | "notebookedit" => {
+                    let path = input
+                        .get("file_path")
+                        .or_else(|| input.get("notebook_path"))
+                        .and_then(|p| p.as_str());
+                    if let Some(path) = path {
+                        if !self.is_inside_project(path) {
+                            operations.push(DestructiveOperation::WriteOutsideProject {
+                                path: path.to_string(),
+                            });
+                        }
+                        edited_files.insert(self.resolve(path));
+                    }
+                }
+                "bash" => {
+                    if let Some(command) = input.get("command").and_then(|c| c.as_str()) {
+                        operations.extend(classify_command(command));
+                    }
+                }
+                name if name.contains("delete") || name.contains("remove") => {
+                    let path = ["file_path", "path", "target"]
+                        .iter()
+                        .find_map(|key| input.get(*key).and_then(|p| p.as_str()));
+                    if let Some(path) = path {
+                        operations.push(DestructiveOperation::FileDeletion {
+                            path: path.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !has_tool_calls {
+            return operations;
+        }
+
+        self.recent_edits.push_back(edited_files);
+        while self.recent_edits.len() > self.mass_edit_window {
+            self.recent_edits.pop_front();
+        }
+
+        let file_count = self
+            .recent_edits
+            .iter()
+            .flatten()
+            .collect::<HashSet<_>>()
+            .len();
+        if file_count >= self.mass_edit_threshold {
+            operations.push(DestructiveOperation::MassEdit { file_count });
+            self.recent_edits.clear();
+        }
+
+        operations
+    }
+
+    /// Resolve a tool path against the project directory
+    fn resolve(&self, path: &str) -> PathBuf {
+        normalize_path(&self.project_path.join(path))
+    }
+
+    fn is_inside_project(&self, path: &str) -> bool {
+        self.resolve(path).starts_with(&self.project_path)
+    }
+}
+
+/// Find destructive operations in a shell command line
+fn classify_command(command: &str) -> Vec<DestructiveOperation> {
+    let mut operations = Vec::new();
+
+    for segment in command.split([';', '&', '|', '\n', '(', ')']) {
+        let words: Vec<&str> = segment
+            .split_whitespace()
+            // Skip environment assignments and privilege wrappers in front of the program
+            .skip_while(|word| word.contains('=') || matches!(*word, "sudo" | "env" | "command"))
+            .collect();
+        operations.extend(classify_program(&words, &words.join(" ")));
+    }
+
+    operations
+}
+
+/// Classify a single program invocation, reporting destructive commands as `segment`
+///
+/// `xargs` is looked through so that `... | xargs rm` is caught like a plain `rm`.
+fn classify_program(words: &[&str], segment: &str) -> Vec<DestructiveOperation> {
+    let program = match program_name(words) {
+        Some(program) => program,
+        None => return Vec::new(),
+    };
+    let args = &words[1..];
+    let destructive = || {
+        vec![DestructiveOperation::DestructiveCommand {
+            command: segment.to_string(),
+        }]
+    };
+
+    match program {
+        "rm" => destructive(),
+        "unlink" | "rmdir" | "shred" => args
+            .iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .map(|path| DestructiveOperation::FileDeletion {
+                path: path.to_string(),
+            })
+            .collect(),
+        "find" if args.contains(&"-delete") => destructive(),
+        "xargs" => classify_program(skip_options(args, XARGS_OPTIONS_WITH_VALUE), segment),
+        "git" => {
+            let args = skip_options(args, GIT_GLOBAL_OPTIONS_WITH_VALUE);
+            let is_destructive = match args.first().copied() {
+                Some("reset") | Some("clean") | Some("restore") | Some("rm") => true,
+                // Creating a branch leaves the working tree alone
+                Some("checkout") => !args.iter().any(|arg| matches!(*arg, "-b" | "-B")),
+                Some("stash") => !args.iter().any(|arg| matches!(*arg, "list" | "show")),
+                _ => false,
+            };
+            if is_destructive {
+                destructive()
+            } else {
+                Vec::new()
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Program name of an invocation without its directory
+fn program_name<'a>(words: &[&'a str]) -> Option<&'a str> {
+    let program = words.first()?;
+    Some(program.rsplit('/').next().unwrap_or(program))
+}
+
+/// Skip leading options, including the values of options listed in `with_value`
+fn skip_options<'a, 'b>(args: &'b [&'a str], with_value: &[&str]) -> &'b [&'a str] {
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        if !arg.starts_with('-') {
+            break;
+        }
+        index += if with_value.contains(arg) { 2 } else { 1 };
+    }
+    &args[index.min(args.len())..]
+}
+
+/// Lexically normalize a path, resolving `.` and `..` without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = "/home/dev/projects/webapp";
+
+    fn classify_fixture(fixture: &str) -> Vec<Vec<DestructiveOperation>> {
+        let mut classifier = SmartClassifier::new(Path::new(PROJECT));
+        fixture
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| classifier.classify_message(line))
+            .collect()
+    }
+
+    #[test]
+    fn test_safe_session_has_no_destructive_operations() {
+        let results = classify_fixture(include_str!(
+            "../../tests/fixtures/smart_checkpoint/safe_session.jsonl"
+        ));
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|ops| ops.is_empty()), "{:?}", results);
+    }
+
+    #[test]
+    fn test_destructive_bash_commands() {
+        let results = classify_fixture(include_str!(
+            "../../tests/fixtures/smart_checkpoint/destructive_bash.jsonl"
+        ));
+        let operations: Vec<_> = results.into_iter().flatten().collect();
+
+        assert_eq!(
+            operations,
+            vec![
+                DestructiveOperation::DestructiveCommand {
+                    command: "rm -rf dist".to_string()
+                },
+                DestructiveOperation::DestructiveCommand {
+                    command: "git reset --hard HEAD~1".to_string()
+                },
+                DestructiveOperation::DestructiveCommand {
+                    command: "git checkout -- src/app.ts".to_string()
+                },
+                DestructiveOperation::FileDeletion {
+                    path: "old.log".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mass_edit_over_threshold() {
+        let results = classify_fixture(include_str!(
+            "../../tests/fixtures/smart_checkpoint/mass_edit.jsonl"
+        ));
+
+        assert_eq!(
+            results[0],
+            vec![DestructiveOperation::MassEdit { file_count: 6 }]
+        );
+        // The same file edited repeatedly counts once
+        assert!(results[1].is_empty());
+    }
+
+    #[test]
+    fn test_mass_edit_threshold_is_configurable() {
+        let mut classifier = SmartClassifier::new(Path::new(PROJECT)).with_mass_edit_threshold(10);
+        let fixture = include_str!("../../tests/fixtures/smart_checkpoint/mass_edit.jsonl");
+        let first = fixture.lines().next().unwrap();
+        assert!(classifier.classify_message(first).is_empty());
+    }
+
+    fn edit_message(files: &[&str]) -> String {
+        let content: Vec<_> = files
+            .iter()
+            .map(|file| {
+                serde_json::json!({
+                    "type": "tool_use",
+                    "name": "Edit",
+                    "input": { "file_path": format!("{}/{}", PROJECT, file) }
+                })
+            })
+            .collect();
+        serde_json::json!({ "type": "assistant", "message": { "content": content } }).to_string()
+    }
+
+    #[test]
+    fn test_mass_edit_spread_over_turns() {
+        let mut classifier = SmartClassifier::new(Path::new(PROJECT));
+        assert!(classifier
+            .classify_message(&edit_message(&["a.ts", "b.ts"]))
+            .is_empty());
+        // Messages without tool calls are not turns
+        assert!(classifier
+            .classify_message(r#"{"type":"user","message":{"content":"go on"}}"#)
+            .is_empty());
+        assert!(classifier
+            .classify_message(&edit_message(&["b.ts", "c.ts"]))
+            .is_empty());
+        assert_eq!(
+            classifier.classify_message(&edit_message(&["d.ts", "e.ts"])),
+            vec![DestructiveOperation::MassEdit { file_count: 5 }]
+        );
+        // The window starts over after a mass edit is reported
+        assert!(classifier
+            .classify_message(&edit_message(&["f.ts"]))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_mass_edit_window_drops_old_turns() {
+        let mut classifier = SmartClassifier::new(Path::new(PROJECT)).with_mass_edit_window(2);
+        for files in [["a.ts", "b.ts"], ["c.ts", "d.ts"], ["e.ts", "f.ts"]] {
+            assert!(classifier
+                .classify_message(&edit_message(&files))
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn test_writes_outside_project() {
+        let results = classify_fixture(include_str!(
+            "../../tests/fixtures/smart_checkpoint/outside_project.jsonl"
+        ));
+        let operations: Vec<_> = results.into_iter().flatten().collect();
+
+        assert_eq!(
+            operations,
+            vec![
+                DestructiveOperation::WriteOutsideProject {
+                    path: "/home/dev/.bashrc".to_string()
+                },
+                DestructiveOperation::WriteOutsideProject {
+                    path: "../shared/config.json".to_string()
+                },
+                // Sibling directories sharing the project name as a prefix are outside it
+                DestructiveOperation::WriteOutsideProject {
+                    path: "/home/dev/projects/webapp-old/src/app.ts".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_parsing() {
+        assert!(classify_command("git checkout -b feature/login").is_empty());
+        assert!(classify_command("git stash list").is_empty());
+        assert!(classify_command("echo rm -rf /").is_empty());
+        assert_eq!(
+            classify_command("cd build && FORCE=1 sudo /bin/rm -f out.o"),
+            vec![DestructiveOperation::DestructiveCommand {
+                command: "/bin/rm -f out.o".to_string()
+            }]
+        );
+        assert_eq!(
+            classify_command("git ls-files -z '*.orig' | xargs -0 -n 10 rm -f"),
+            vec![DestructiveOperation::DestructiveCommand {
+                command: "xargs -0 -n 10 rm -f".to_string()
+            }]
+        );
+        assert_eq!(
+            classify_command("git -C ../webapp reset --hard"),
+            vec![DestructiveOperation::DestructiveCommand {
+                command: "git -C ../webapp reset --hard".to_string()
+            }]
+        );
+        assert_eq!(
+            classify_command("git -c core.quotepath=off clean -fdx"),
+            vec![DestructiveOperation::DestructiveCommand {
+                command: "git -c core.quotepath=off clean -fdx".to_string()
+            }]
+        );
+        assert!(classify_command("git -C ../webapp status").is_empty());
+        assert!(classify_command("ls | xargs cat").is_empty());
+        assert_eq!(
+            classify_command("find . -name '*.tmp' -delete"),
+            vec![DestructiveOperation::DestructiveCommand {
+                command: "find . -name '*.tmp' -delete".to_string()
+            }]
+        );
+    }
+}
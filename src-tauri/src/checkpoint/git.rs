@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::Checkpoint;
+
+/// Prefix of the hidden refs that mirror session timelines in the project repo
+pub const MIRROR_REF_PREFIX: &str = "refs/organized-agents";
+
+/// Run a git command in the project directory and return its raw stdout
+fn run_git(project_path: &Path, index_file: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut cmd = Command::new("git");
+    cmd.args(args).current_dir(project_path);
+    if let Some(index_file) = index_file {
+        cmd.env("GIT_INDEX_FILE", index_file);
+    }
+
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Check whether the project directory is inside a git work tree
+pub fn is_git_repo(project_path: &Path) -> bool {
+    run_git(project_path, None, &["rev-parse", "--is-inside-work-tree"])
+        .map(|out| out.trim() == "true")
+        .unwrap_or(false)
+}
+
+/// Name of the hidden ref that mirrors a session's timeline
+pub fn mirror_ref(session_id: &str) -> String {
+    format!("{}/{}", MIRROR_REF_PREFIX, session_id)
+}
+
+/// Resolve a ref to a commit hash, if it exists
+fn resolve_commit(project_path: &Path, reference: &str) -> Option<String> {
+    run_git(
+        project_path,
+        None,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", reference),
+        ],
+    )
+    .ok()
+    .map(|out| out.trim().to_string())
+    .filter(|commit| !commit.is_empty())
+}
+
+/// Build the commit message for a mirrored checkpoint
+fn commit_message(checkpoint: &Checkpoint) -> String {
+    let title = checkpoint.description.clone().unwrap_or_else(|| {
+        format!(
+            "Checkpoint {}",
+            &checkpoint.id[..8.min(checkpoint.id.len())]
+        )
+    });
+
+    let mut message = format!("{}\n\n", title);
+    if !checkpoint.metadata.user_prompt.is_empty() {
+        let prompt: String = checkpoint.metadata.user_prompt.chars().take(500).collect();
+        message.push_str(&format!("Prompt: {}\n\n", prompt));
+    }
+    message.push_str(&format!("Checkpoint-Id: {}\n", checkpoint.id));
+    message.push_str(&format!("Session-Id: {}\n", checkpoint.session_id));
+    message
+}
+
+/// Mirror the current state of the project as a commit on the session's hidden ref
+///
+/// A throwaway index is used so the user's staging area and branches are left untouched;
+/// `git add -A` honours `.gitignore`. The commit's parent is `parent_commit` when given
+/// (so forks branch off the right commit), otherwise the current tip of the hidden ref.
+/// The ref keeps a reflog so commits of abandoned forks stay reachable.
+pub fn mirror_checkpoint(
+    project_path: &Path,
+    checkpoint: &Checkpoint,
+    parent_commit: Option<&str>,
+) -> Result<String> {
+    let git_dir = PathBuf::from(
+        run_git(project_path, None, &["rev-parse", "--absolute-git-dir"])?
+            .trim()
+            .to_string(),
+    );
+    let index_file = git_dir.join(format!("organized-agents-{}.index", checkpoint.id));
+    let ref_name = mirror_ref(&checkpoint.session_id);
+
+    let result = (|| -> Result<String> {
+        run_git(project_path, Some(&index_file), &["add", "-A", "--", "."])?;
+        let tree = run_git(project_path, Some(&index_file), &["write-tree"])?
+            .trim()
+            .to_string();
+
+        let parent = parent_commit
+            .map(str::to_string)
+            .or_else(|| resolve_commit(project_path, &ref_name));
+        let message = commit_message(checkpoint);
+
+        let mut args = vec!["commit-tree", tree.as_str(), "-m", message.as_str()];
+        if let Some(parent) = parent.as_deref() {
+            args.push("-p");
+            args.push(parent);
+        }
+        let commit = run_git(project_path, None, &args)?.trim().to_string();
+
+        let reflog_message = format!("checkpoint {}", checkpoint.id);
+        run_git(
+            project_path,
+            None,
+            &[
+                "update-ref",
+                "--create-reflog",
+                "-m",
+                &reflog_message,
+                &ref_name,
+                &commit,
+            ],
+        )?;
+
+        Ok(commit)
+    })();
+
+    let _ = fs::remove_file(&index_file);
+    result
+}
+
+/// Create a regular branch pointing at a mirrored checkpoint commit
+pub fn promote_to_branch(project_path: &Path, commit: &str, branch_name: &str) -> Result<()> {
+    run_git(
+        project_path,
+        None,
+        &["check-ref-format", "--branch", branch_name],
+    )
+    .with_context(|| format!("Invalid branch name: {}", branch_name))?;
+
+    run_git(project_path, None, &["branch", branch_name, commit])
+        .with_context(|| format!("Failed to create branch {}", branch_name))?;
+
+    Ok(())
+}
+
+/// List project-relative paths with uncommitted changes, including untracked files
+pub fn uncommitted_paths(project_path: &Path) -> Result<Vec<PathBuf>> {
+    // Porcelain output is relative to the repository root, not the project directory
+    let prefix = run_git(project_path, None, &["rev-parse", "--show-prefix"])?
+        .trim()
+        .to_string();
+    let status = run_git(
+        project_path,
+        None,
+        &[
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=all",
+            "--",
+            ".",
+        ],
+    )?;
+
+    let mut paths = Vec::new();
+    let mut entries = status.split('\0');
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+
+        let (code, path) = entry.split_at(3);
+        // Renames and copies are followed by an extra entry holding the original path
+        if code.starts_with('R') || code.starts_with('C') {
+            entries.next();
+        }
+
+        if let Some(relative) = path.strip_prefix(prefix.as_str()) {
+            paths.push(PathBuf::from(relative));
+        }
+    }
+
+    Ok(paths)
+}
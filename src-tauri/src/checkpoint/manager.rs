@@ -8,7 +8,10 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use super::{
+    classifier::{DestructiveOperation, SmartClassifier},
+    git,
     storage::{self, CheckpointStorage},
+    watcher::{self, ProjectWatcher},
     Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
     FileSnapshot, FileState, FileTracker, SessionTimeline,
 };
@@ -22,6 +25,9 @@ pub struct CheckpointManager {
     pub storage: Arc<CheckpointStorage>,
     timeline: Arc<RwLock<SessionTimeline>>,
     current_messages: Arc<RwLock<Vec<String>>>, // JSONL messages
+    loaded_lines: Arc<RwLock<usize>>,           // Transcript lines already scanned for tools
+    file_watcher: Arc<RwLock<Option<ProjectWatcher>>>,
+    smart_classifier: Arc<RwLock<SmartClassifier>>,
 }
 
 impl CheckpointManager {
@@ -48,6 +54,7 @@ impl CheckpointManager {
         let file_tracker = FileTracker {
             tracked_files: HashMap::new(),
         };
+        let smart_classifier = SmartClassifier::new(&project_path);
 
         Ok(Self {
             project_id,
@@ -57,6 +64,9 @@ impl CheckpointManager {
             storage,
             timeline: Arc::new(RwLock::new(timeline)),
             current_messages: Arc::new(RwLock::new(Vec::new())),
+            loaded_lines: Arc::new(RwLock::new(0)),
+            file_watcher: Arc::new(RwLock::new(None)),
+            smart_classifier: Arc::new(RwLock::new(smart_classifier)),
         })
     }
 
@@ -64,9 +74,26 @@ impl CheckpointManager {
     pub async fn track_message(&self, jsonl_message: String) -> Result<()> {
         let mut messages = self.current_messages.write().await;
         messages.push(jsonl_message.clone());
+        self.track_tool_usage(&jsonl_message).await
+    }
 
-        // Parse message to check for tool usage
-        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&jsonl_message) {
+    /// Replace the tracked messages with the session transcript
+    ///
+    /// Only lines past the ones already loaded are scanned for tool usage, so reloading
+    /// the transcript before each checkpoint doesn't re-track earlier operations.
+    pub async fn load_messages(&self, lines: Vec<String>) -> Result<()> {
+        let mut loaded = self.loaded_lines.write().await;
+        for line in lines.iter().skip(*loaded) {
+            self.track_tool_usage(line).await?;
+        }
+        *loaded = (*loaded).max(lines.len());
+        *self.current_messages.write().await = lines;
+        Ok(())
+    }
+
+    /// Track the file operations of any tool calls in a message
+    async fn track_tool_usage(&self, jsonl_message: &str) -> Result<()> {
+        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(jsonl_message) {
             if let Some(content) = msg.get("message").and_then(|m| m.get("content")) {
                 if let Some(content_array) = content.as_array() {
                     for item in content_array {
@@ -241,7 +268,7 @@ impl CheckpointManager {
         let file_snapshots = self.create_file_snapshots(&checkpoint_id).await?;
 
         // Generate checkpoint struct
-        let checkpoint = Checkpoint {
+        let mut checkpoint = Checkpoint {
             id: checkpoint_id.clone(),
             session_id: self.session_id.clone(),
             project_id: self.project_id.clone(),
@@ -267,17 +294,46 @@ impl CheckpointManager {
                     &file_snapshots,
                 ),
             },
+            tags: Vec::new(),
+            pinned: false,
+            git_commit: None,
         };
 
+        // Mirror the checkpoint into the project repo if enabled; failures are non-fatal
+        let mut git_warnings = Vec::new();
+        let (git_mirror_enabled, parent_git_commit) = {
+            let timeline = self.timeline.read().await;
+            let parent_git_commit = checkpoint
+                .parent_checkpoint_id
+                .as_deref()
+                .and_then(|id| timeline.find_checkpoint(id))
+                .and_then(|node| node.checkpoint.git_commit.clone());
+            (timeline.git_mirror_enabled, parent_git_commit)
+        };
+        if git_mirror_enabled {
+            match git::mirror_checkpoint(
+                &self.project_path,
+                &checkpoint,
+                parent_git_commit.as_deref(),
+            ) {
+                Ok(commit) => checkpoint.git_commit = Some(commit),
+                Err(e) => {
+                    log::warn!("Failed to mirror checkpoint to git: {}", e);
+                    git_warnings.push(format!("Failed to mirror checkpoint to git: {}", e));
+                }
+            }
+        }
+
         // Save checkpoint
         let messages_content = messages.join("\n");
-        let result = self.storage.save_checkpoint(
+        let mut result = self.storage.save_checkpoint(
             &self.project_id,
             &self.session_id,
             &checkpoint,
             file_snapshots,
             &messages_content,
         )?;
+        result.warnings.extend(git_warnings);
 
         // Reload timeline from disk so in-memory timeline has updated nodes and total_checkpoints
         let claude_dir = self.storage.claude_dir.clone();
@@ -450,10 +506,39 @@ impl CheckpointManager {
 
     /// Restore a checkpoint
     pub async fn restore_checkpoint(&self, checkpoint_id: &str) -> Result<CheckpointResult> {
+        let session_id = self.session_id.clone();
+        self.restore_from_session(&session_id, checkpoint_id).await
+    }
+
+    /// Restore the project to a checkpoint of any session in this project
+    ///
+    /// Restoring from another session leaves this session's timeline position untouched and
+    /// marks every restored file as modified, so the next checkpoint captures the full tree.
+    ///
+    /// Uncommitted changes that are not in any checkpoint are overwritten and listed in the
+    /// warnings; callers ask for confirmation first with `find_uncheckpointed_changes`.
+    pub async fn restore_from_session(
+        &self,
+        source_session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<CheckpointResult> {
+        let same_session = source_session_id == self.session_id;
+
+        // Note work that only exists in the working tree before anything is written
+        let mut check_warning = None;
+        let uncheckpointed = match self.find_uncheckpointed_changes().await {
+            Ok(paths) => paths,
+            Err(e) => {
+                log::warn!("Failed to check for uncheckpointed changes: {}", e);
+                check_warning = Some(format!("Failed to check for uncheckpointed changes: {}", e));
+                Vec::new()
+            }
+        };
+
         // Load checkpoint data
         let (checkpoint, file_snapshots, messages) =
             self.storage
-                .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
+                .load_checkpoint(&self.project_id, source_session_id, checkpoint_id)?;
 
         // First, collect all files currently in the project to handle deletions
         fn collect_all_project_files(
@@ -495,7 +580,16 @@ impl CheckpointManager {
         }
 
         // Delete files that exist now but shouldn't exist in the checkpoint
-        let mut warnings = Vec::new();
+        let mut warnings: Vec<String> = uncheckpointed
+            .iter()
+            .map(|path| {
+                format!(
+                    "Uncommitted changes to {} were not in any checkpoint and have been overwritten",
+                    path.display()
+                )
+            })
+            .collect();
+        warnings.extend(check_warning);
         let mut files_processed = 0;
 
         for current_file in current_files {
@@ -571,8 +665,10 @@ impl CheckpointManager {
         }
 
         // Update timeline
-        let mut timeline = self.timeline.write().await;
-        timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
+        if same_session {
+            let mut timeline = self.timeline.write().await;
+            timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
+        }
 
         // Update file tracker
         let mut tracker = self.file_tracker.write().await;
@@ -583,7 +679,7 @@ impl CheckpointManager {
                     snapshot.file_path.clone(),
                     FileState {
                         last_hash: snapshot.hash.clone(),
-                        is_modified: false,
+                        is_modified: !same_session,
                         last_modified: Utc::now(),
                         exists: true,
                     },
@@ -629,6 +725,192 @@ impl CheckpointManager {
         Ok(())
     }
 
+    /// Update the tags, pinned flag and description of a checkpoint
+    pub async fn annotate_checkpoint(
+        &self,
+        checkpoint_id: &str,
+        tags: Vec<String>,
+        pinned: bool,
+        description: Option<String>,
+    ) -> Result<Checkpoint> {
+        let checkpoint = self.storage.update_checkpoint_annotations(
+            &self.project_id,
+            &self.session_id,
+            checkpoint_id,
+            tags,
+            pinned,
+            description,
+        )?;
+
+        // Keep the in-memory timeline in sync with the one on disk
+        let mut timeline = self.timeline.write().await;
+        if let Some(node) = timeline.find_checkpoint_mut(checkpoint_id) {
+            node.checkpoint = checkpoint.clone();
+        }
+
+        Ok(checkpoint)
+    }
+
+    /// Find uncommitted changes in the project repo whose current content is not in any
+    /// checkpoint of any session of the project
+    ///
+    /// Returns an empty list when the project is not a git repository.
+    pub async fn find_uncheckpointed_changes(&self) -> Result<Vec<PathBuf>> {
+        if !git::is_git_repo(&self.project_path) {
+            return Ok(Vec::new());
+        }
+
+        let dirty = git::uncommitted_paths(&self.project_path)?;
+        if dirty.is_empty() {
+            return Ok(dirty);
+        }
+
+        let mut known: HashMap<PathBuf, std::collections::HashSet<String>> = HashMap::new();
+        for session_id in self.storage.list_session_ids(&self.project_id)? {
+            for (path, hashes) in self
+                .storage
+                .collect_snapshot_hashes(&self.project_id, &session_id)?
+            {
+                known.entry(path).or_default().extend(hashes);
+            }
+        }
+
+        Ok(dirty
+            .into_iter()
+            .filter(|rel_path| {
+                let full_path = self.project_path.join(rel_path);
+                // Deleted files are snapshotted with an empty hash
+                let hash = if full_path.exists() {
+                    match fs::read(&full_path) {
+                        Ok(content) => storage::CheckpointStorage::calculate_bytes_hash(&content),
+                        // Nothing can show that a checkpoint holds what we can't read
+                        Err(_) => return true,
+                    }
+                } else {
+                    String::new()
+                };
+                !known
+                    .get(rel_path)
+                    .is_some_and(|hashes| hashes.contains(&hash))
+            })
+            .collect())
+    }
+
+    /// Create a git branch from the commit mirroring a checkpoint
+    pub async fn promote_checkpoint_to_branch(
+        &self,
+        checkpoint_id: &str,
+        branch_name: &str,
+    ) -> Result<String> {
+        let commit = {
+            let timeline = self.timeline.read().await;
+            let node = timeline
+                .find_checkpoint(checkpoint_id)
+                .ok_or_else(|| anyhow::anyhow!("Checkpoint not found: {}", checkpoint_id))?;
+            node.checkpoint.git_commit.clone().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Checkpoint {} has no git commit; enable git mirroring before creating checkpoints",
+                    checkpoint_id
+                )
+            })?
+        };
+
+        git::promote_to_branch(&self.project_path, &commit, branch_name)?;
+        Ok(commit)
+    }
+
+    /// Enable or disable mirroring checkpoints into the project repo
+    pub async fn set_git_mirror_enabled(&self, enabled: bool) -> Result<()> {
+        if enabled && !git::is_git_repo(&self.project_path) {
+            anyhow::bail!(
+                "Project is not a git repository: {}",
+                self.project_path.display()
+            );
+        }
+
+        let mut timeline = self.timeline.write().await;
+        timeline.git_mirror_enabled = enabled;
+
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage
+            .save_timeline(&paths.timeline_file, &timeline)?;
+
+        Ok(())
+    }
+
+    /// Start watching the project directory so changes made outside tool calls are tracked
+    ///
+    /// The background task only holds a weak reference, so it stops once the manager is
+    /// dropped or the watcher is stopped.
+    pub async fn start_file_watcher(self: &Arc<Self>) -> Result<()> {
+        let mut file_watcher = self.file_watcher.write().await;
+        if file_watcher.is_some() {
+            return Ok(());
+        }
+
+        let (project_watcher, mut events) = ProjectWatcher::start(&self.project_path)?;
+        *file_watcher = Some(project_watcher);
+
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(batch) = watcher::next_batch(&mut events).await {
+                match manager.upgrade() {
+                    Some(manager) => manager.track_watched_paths(batch).await,
+                    None => break,
+                }
+            }
+        });
+
+        log::info!(
+            "Started file watcher for project: {}",
+            self.project_path.display()
+        );
+        Ok(())
+    }
+
+    /// Stop watching the project directory
+    pub async fn stop_file_watcher(&self) {
+        if self.file_watcher.write().await.take().is_some() {
+            log::info!(
+                "Stopped file watcher for project: {}",
+                self.project_path.display()
+            );
+        }
+    }
+
+    /// Enable or disable the project file watcher and persist the choice in the timeline
+    pub async fn set_file_watcher_enabled(self: &Arc<Self>, enabled: bool) -> Result<()> {
+        if enabled {
+            self.start_file_watcher().await?;
+        } else {
+            self.stop_file_watcher().await;
+        }
+
+        let mut timeline = self.timeline.write().await;
+        timeline.file_watcher_enabled = enabled;
+
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage
+            .save_timeline(&paths.timeline_file, &timeline)?;
+
+        Ok(())
+    }
+
+    /// Feed paths reported by the file watcher into the file tracker
+    ///
+    /// The watcher has already dropped ignored paths and expanded new directories.
+    async fn track_watched_paths(&self, paths: Vec<PathBuf>) {
+        for rel_path in paths {
+            if let Some(p) = rel_path.to_str() {
+                if let Err(e) = self.track_file_modification(p).await {
+                    log::warn!("Failed to track watched file {}: {}", p, e);
+                }
+            }
+        }
+    }
+
     /// Get the current timeline
     pub async fn get_timeline(&self) -> SessionTimeline {
         self.timeline.read().await.clone()
@@ -679,6 +961,33 @@ impl CheckpointManager {
             .await
     }
 
+    /// Start this session from a checkpoint of another session in the same project
+    ///
+    /// The restored state becomes the first checkpoint of this session's timeline.
+    pub async fn fork_from_session_checkpoint(
+        &self,
+        source_session_id: &str,
+        checkpoint_id: &str,
+        description: Option<String>,
+    ) -> Result<CheckpointResult> {
+        if source_session_id == self.session_id {
+            return self.fork_from_checkpoint(checkpoint_id, description).await;
+        }
+
+        self.restore_from_session(source_session_id, checkpoint_id)
+            .await?;
+
+        let fork_description = description.unwrap_or_else(|| {
+            format!(
+                "Fork from checkpoint {} of session {}",
+                &checkpoint_id[..8.min(checkpoint_id.len())],
+                &source_session_id[..8.min(source_session_id.len())]
+            )
+        });
+
+        self.create_checkpoint(Some(fork_description), None).await
+    }
+
     /// Check if auto-checkpoint should be triggered
     pub async fn should_auto_checkpoint(&self, message: &str) -> bool {
         let timeline = self.timeline.read().await;
@@ -715,36 +1024,34 @@ impl CheckpointManager {
                     false
                 }
             }
-            CheckpointStrategy::Smart => {
-                // Smart strategy: checkpoint after destructive operations
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                                let tool_name =
-                                    item.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                                matches!(
-                                    tool_name.to_lowercase().as_str(),
-                                    "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
-                                )
-                            } else {
-                                false
-                            }
-                        })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            }
+            // Classify a copy so that asking does not advance the mass edit window
+            CheckpointStrategy::Smart => !self
+                .smart_classifier
+                .read()
+                .await
+                .clone()
+                .classify_message(message)
+                .is_empty(),
         }
     }
 
+    /// Destructive operations in a message that warrant a checkpoint before they run
+    ///
+    /// Always empty unless auto-checkpointing is enabled with the `Smart` strategy.
+    pub async fn smart_checkpoint_operations(&self, message: &str) -> Vec<DestructiveOperation> {
+        let timeline = self.timeline.read().await;
+        if !timeline.auto_checkpoint_enabled
+            || !matches!(timeline.checkpoint_strategy, CheckpointStrategy::Smart)
+        {
+            return Vec::new();
+        }
+
+        self.smart_classifier
+            .write()
+            .await
+            .classify_message(message)
+    }
+
     /// Update checkpoint settings
     pub async fn update_settings(
         &self,
@@ -785,3 +1092,379 @@ impl CheckpointManager {
             .max()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::CheckpointSearchQuery;
+    use tempfile::TempDir;
+
+    const PROJECT_ID: &str = "test-project";
+
+    async fn new_manager(temp_dir: &TempDir, session_id: &str) -> CheckpointManager {
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        CheckpointManager::new(
+            PROJECT_ID.to_string(),
+            session_id.to_string(),
+            project_path,
+            temp_dir.path().join("claude"),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn write_file(manager: &CheckpointManager, rel_path: &str, content: &str) {
+        let path = manager.project_path.join(rel_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn init_repo(manager: &CheckpointManager) {
+        for args in [
+            &["init", "-q"][..],
+            &["config", "user.name", "test"],
+            &["config", "user.email", "test@example.com"],
+            &["add", "-A"],
+            &["commit", "-q", "--allow-empty", "-m", "initial"],
+        ] {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&manager.project_path)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        }
+    }
+
+    async fn checkpoint(manager: &CheckpointManager, description: &str) -> Checkpoint {
+        manager
+            .create_checkpoint(Some(description.to_string()), None)
+            .await
+            .unwrap()
+            .checkpoint
+    }
+
+    #[tokio::test]
+    async fn test_annotations_are_normalized_and_persisted() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.txt", "one");
+        let first = checkpoint(&manager, "first").await;
+
+        let annotated = manager
+            .annotate_checkpoint(
+                &first.id,
+                vec![
+                    " release ".into(),
+                    "Release".into(),
+                    "".into(),
+                    "wip".into(),
+                ],
+                true,
+                Some("  ".into()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(annotated.tags, vec!["release", "wip"]);
+        assert!(annotated.pinned);
+        assert_eq!(annotated.description, None);
+
+        // A fresh manager sees the annotations from disk
+        let reloaded = new_manager(&temp_dir, "session-a").await;
+        let listed = reloaded.list_checkpoints().await;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].tags, vec!["release", "wip"]);
+        assert!(listed[0].pinned);
+    }
+
+    #[tokio::test]
+    async fn test_search_filters_by_tags_pins_and_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.txt", "one");
+        let first = checkpoint(&manager, "add a").await;
+        write_file(&manager, "src/b.txt", "two");
+        let second = checkpoint(&manager, "add b").await;
+        manager
+            .annotate_checkpoint(&first.id, vec!["release".into()], true, None)
+            .await
+            .unwrap();
+
+        let search = |query: CheckpointSearchQuery| {
+            manager
+                .storage
+                .search_checkpoints(PROJECT_ID, &["session-a".to_string()], &query)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search(CheckpointSearchQuery::default()),
+            vec![second.id.clone(), first.id.clone()]
+        );
+        assert_eq!(
+            search(CheckpointSearchQuery {
+                tags: vec!["RELEASE".into()],
+                ..Default::default()
+            }),
+            vec![first.id.clone()]
+        );
+        assert_eq!(
+            search(CheckpointSearchQuery {
+                pinned_only: true,
+                ..Default::default()
+            }),
+            vec![first.id.clone()]
+        );
+        assert_eq!(
+            search(CheckpointSearchQuery {
+                file_path: Some("src/b".into()),
+                ..Default::default()
+            }),
+            vec![second.id.clone()]
+        );
+        assert_eq!(
+            search(CheckpointSearchQuery {
+                description: Some("ADD B".into()),
+                ..Default::default()
+            }),
+            vec![second.id.clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_never_removes_pinned_checkpoints() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.txt", "one");
+        let first = checkpoint(&manager, "first").await;
+        write_file(&manager, "a.txt", "two");
+        checkpoint(&manager, "second").await;
+        manager
+            .annotate_checkpoint(&first.id, Vec::new(), true, None)
+            .await
+            .unwrap();
+
+        let removed = manager
+            .storage
+            .cleanup_old_checkpoints(PROJECT_ID, "session-a", 0)
+            .unwrap();
+        assert_eq!(removed, 1);
+
+        let (restored, _, _) = manager
+            .storage
+            .load_checkpoint(PROJECT_ID, "session-a", &first.id)
+            .unwrap();
+        assert!(restored.pinned);
+    }
+
+    #[tokio::test]
+    async fn test_restore_reports_overwritten_uncheckpointed_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.txt", "one");
+        init_repo(&manager);
+        write_file(&manager, "a.txt", "two");
+        let first = checkpoint(&manager, "first").await;
+
+        // Edited after the last checkpoint and never committed
+        write_file(&manager, "a.txt", "three");
+        assert_eq!(
+            manager.find_uncheckpointed_changes().await.unwrap(),
+            vec![PathBuf::from("a.txt")]
+        );
+
+        let result = manager.restore_checkpoint(&first.id).await.unwrap();
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.contains("a.txt") && w.contains("not in any checkpoint")));
+        assert_eq!(
+            fs::read_to_string(manager.project_path.join("a.txt")).unwrap(),
+            "two"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uncheckpointed_changes_compare_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.bin", "one");
+        init_repo(&manager);
+        // Not UTF-8, so the snapshot can't hold it
+        fs::write(manager.project_path.join("a.bin"), [0xff, 0xfe, 0x00]).unwrap();
+        checkpoint(&manager, "first").await;
+
+        assert_eq!(
+            manager.find_uncheckpointed_changes().await.unwrap(),
+            vec![PathBuf::from("a.bin")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reloading_transcript_does_not_duplicate_messages() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        let transcript: Vec<String> = (0..3)
+            .map(|i| format!(r#"{{"type":"user","message":{{"content":"{}"}}}}"#, i))
+            .collect();
+
+        manager
+            .load_messages(transcript[..2].to_vec())
+            .await
+            .unwrap();
+        manager.load_messages(transcript.clone()).await.unwrap();
+
+        assert_eq!(*manager.current_messages.read().await, transcript);
+        assert_eq!(*manager.loaded_lines.read().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_restore_allows_changes_held_by_a_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.txt", "one");
+        init_repo(&manager);
+        write_file(&manager, "a.txt", "two");
+        let first = checkpoint(&manager, "first").await;
+        write_file(&manager, "a.txt", "three");
+        checkpoint(&manager, "second").await;
+
+        assert!(manager
+            .find_uncheckpointed_changes()
+            .await
+            .unwrap()
+            .is_empty());
+        manager.restore_checkpoint(&first.id).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(manager.project_path.join("a.txt")).unwrap(),
+            "two"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_git_mirror_records_commits_and_promotes_branches() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir, "session-a").await;
+        write_file(&manager, "a.txt", "one");
+        init_repo(&manager);
+        manager.set_git_mirror_enabled(true).await.unwrap();
+
+        write_file(&manager, "a.txt", "two");
+        let first = checkpoint(&manager, "first").await;
+        let commit = first
+            .git_commit
+            .clone()
+            .expect("checkpoint was not mirrored");
+
+        let resolve = |reference: &str| {
+            let output = std::process::Command::new("git")
+                .args(["rev-parse", "--verify", "--quiet", reference])
+                .current_dir(&manager.project_path)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        assert_eq!(resolve(&git::mirror_ref("session-a")), commit);
+
+        let promoted = manager
+            .promote_checkpoint_to_branch(&first.id, "from-checkpoint")
+            .await
+            .unwrap();
+        assert_eq!(promoted, commit);
+        assert_eq!(resolve("refs/heads/from-checkpoint"), commit);
+
+        // Mirroring never touches the user's working tree or index
+        assert_eq!(
+            fs::read_to_string(manager.project_path.join("a.txt")).unwrap(),
+            "two"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_watcher_tracks_external_edits() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = Arc::new(new_manager(&temp_dir, "session-a").await);
+        manager.set_file_watcher_enabled(true).await.unwrap();
+        assert!(manager.get_timeline().await.file_watcher_enabled);
+
+        write_file(&manager, "src/edited.txt", "outside a tool call");
+
+        let tracked = async {
+            loop {
+                let tracker = manager.file_tracker.read().await;
+                if tracker
+                    .tracked_files
+                    .get(&PathBuf::from("src/edited.txt"))
+                    .is_some_and(|state| state.is_modified)
+                {
+                    break;
+                }
+                drop(tracker);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), tracked)
+            .await
+            .expect("external edit was not tracked");
+
+        let created = checkpoint(&manager, "external").await;
+        let (_, files, _) = manager
+            .storage
+            .load_checkpoint(PROJECT_ID, "session-a", &created.id)
+            .unwrap();
+        assert!(files
+            .iter()
+            .any(|file| file.file_path == std::path::Path::new("src/edited.txt")));
+
+        manager.set_file_watcher_enabled(false).await.unwrap();
+        assert!(manager.file_watcher.read().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_project_listing_spans_past_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let past = new_manager(&temp_dir, "session-a").await;
+        write_file(&past, "a.txt", "one");
+        let first = checkpoint(&past, "first").await;
+        drop(past);
+
+        let current = new_manager(&temp_dir, "session-b").await;
+        write_file(&current, "a.txt", "two");
+        let second = checkpoint(&current, "second").await;
+
+        assert_eq!(
+            current.storage.list_session_ids(PROJECT_ID).unwrap(),
+            vec!["session-a".to_string(), "session-b".to_string()]
+        );
+        let timelines = current.storage.list_project_timelines(PROJECT_ID).unwrap();
+        assert_eq!(
+            timelines
+                .iter()
+                .map(|t| t.total_checkpoints)
+                .collect::<Vec<_>>(),
+            vec![1, 1]
+        );
+        let listed = current
+            .storage
+            .list_project_checkpoints(PROJECT_ID)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect::<Vec<_>>();
+        assert_eq!(listed, vec![first.id.clone(), second.id]);
+
+        // Forking from the past session restores its files into the current one
+        current
+            .fork_from_session_checkpoint("session-a", &first.id, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(current.project_path.join("a.txt")).unwrap(),
+            "one"
+        );
+    }
+}
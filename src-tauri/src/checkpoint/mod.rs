@@ -1,11 +1,16 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub mod archive;
+pub mod classifier;
+pub mod git;
 pub mod manager;
 pub mod state;
 pub mod storage;
+pub mod verify;
+pub mod watcher;
 
 /// Represents a checkpoint in the session timeline
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +32,15 @@ pub struct Checkpoint {
     pub parent_checkpoint_id: Option<String>,
     /// Metadata about the checkpoint
     pub metadata: CheckpointMetadata,
+    /// User-provided labels for finding the checkpoint later
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the checkpoint is pinned (pinned checkpoints survive cleanup)
+    #[serde(default)]
+    pub pinned: bool,
+    /// Commit mirroring this checkpoint in the project repo, if git mirroring was enabled
+    #[serde(default)]
+    pub git_commit: Option<String>,
 }
 
 /// Metadata associated with a checkpoint
@@ -93,10 +107,16 @@ pub struct SessionTimeline {
     pub checkpoint_strategy: CheckpointStrategy,
     /// Total number of checkpoints in timeline
     pub total_checkpoints: usize,
+    /// Whether checkpoints are mirrored as commits on a hidden ref in the project repo
+    #[serde(default)]
+    pub git_mirror_enabled: bool,
+    /// Whether the project directory is watched for changes made outside tool calls
+    #[serde(default)]
+    pub file_watcher_enabled: bool,
 }
 
 /// Strategy for automatic checkpoint creation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CheckpointStrategy {
     /// Only create checkpoints manually
@@ -106,6 +126,7 @@ pub enum CheckpointStrategy {
     /// Create checkpoint after each tool use
     PerToolUse,
     /// Create checkpoint after destructive operations
+    #[default]
     Smart,
 }
 
@@ -170,10 +191,90 @@ pub struct FileDiff {
     pub diff_content: Option<String>,
 }
 
-impl Default for CheckpointStrategy {
-    fn default() -> Self {
-        CheckpointStrategy::Smart
+/// Filters for searching checkpoints of a session or project
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CheckpointSearchQuery {
+    /// Checkpoints must carry all of these tags (case-insensitive)
+    pub tags: Vec<String>,
+    /// Substring to look for in the description (case-insensitive)
+    pub description: Option<String>,
+    /// Substring to look for in the user prompt (case-insensitive)
+    pub user_prompt: Option<String>,
+    /// Only return pinned checkpoints
+    pub pinned_only: bool,
+    /// Only return checkpoints created at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only return checkpoints created at or before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Only return checkpoints that snapshot a path containing this string
+    pub file_path: Option<String>,
+}
+
+impl CheckpointSearchQuery {
+    /// Check the checkpoint fields against the query.
+    ///
+    /// The file path filter needs the snapshot references from storage and is
+    /// applied separately by `CheckpointStorage::search_checkpoints`.
+    pub fn matches(&self, checkpoint: &Checkpoint) -> bool {
+        if self.pinned_only && !checkpoint.pinned {
+            return false;
+        }
+
+        if let Some(from) = self.from {
+            if checkpoint.timestamp < from {
+                return false;
+            }
+        }
+
+        if let Some(to) = self.to {
+            if checkpoint.timestamp > to {
+                return false;
+            }
+        }
+
+        let has_all_tags = self.tags.iter().all(|wanted| {
+            checkpoint
+                .tags
+                .iter()
+                .any(|tag| tag.eq_ignore_ascii_case(wanted.trim()))
+        });
+        if !has_all_tags {
+            return false;
+        }
+
+        if let Some(needle) = &self.description {
+            let description = checkpoint.description.as_deref().unwrap_or("");
+            if !contains_ignore_case(description, needle) {
+                return false;
+            }
+        }
+
+        if let Some(needle) = &self.user_prompt {
+            if !contains_ignore_case(&checkpoint.metadata.user_prompt, needle) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// Normalize user-provided tags: trim, drop empties and duplicates
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() || normalized.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            continue;
+        }
+        normalized.push(tag.to_string());
     }
+    normalized
 }
 
 impl SessionTimeline {
@@ -186,6 +287,8 @@ impl SessionTimeline {
             auto_checkpoint_enabled: false,
             checkpoint_strategy: CheckpointStrategy::default(),
             total_checkpoints: 0,
+            git_mirror_enabled: false,
+            file_watcher_enabled: false,
         }
     }
 
@@ -196,6 +299,30 @@ impl SessionTimeline {
             .and_then(|root| Self::find_in_tree(root, checkpoint_id))
     }
 
+    /// Find a checkpoint by ID in the timeline tree for modification
+    pub fn find_checkpoint_mut(&mut self, checkpoint_id: &str) -> Option<&mut TimelineNode> {
+        self.root_node
+            .as_mut()
+            .and_then(|root| Self::find_in_tree_mut(root, checkpoint_id))
+    }
+
+    fn find_in_tree_mut<'a>(
+        node: &'a mut TimelineNode,
+        checkpoint_id: &str,
+    ) -> Option<&'a mut TimelineNode> {
+        if node.checkpoint.id == checkpoint_id {
+            return Some(node);
+        }
+
+        for child in &mut node.children {
+            if let Some(found) = Self::find_in_tree_mut(child, checkpoint_id) {
+                return Some(found);
+            }
+        }
+
+        None
+    }
+
     fn find_in_tree<'a>(node: &'a TimelineNode, checkpoint_id: &str) -> Option<&'a TimelineNode> {
         if node.checkpoint.id == checkpoint_id {
             return Some(node);
@@ -219,7 +346,7 @@ pub struct CheckpointPaths {
 }
 
 impl CheckpointPaths {
-    pub fn new(claude_dir: &PathBuf, project_id: &str, session_id: &str) -> Self {
+    pub fn new(claude_dir: &Path, project_id: &str, session_id: &str) -> Self {
         let base_dir = claude_dir
             .join("projects")
             .join(project_id)
@@ -76,15 +76,49 @@ impl CheckpointState {
                 .await?;
 
         let manager_arc = Arc::new(manager);
+
+        // Resume watching the project if it was enabled for this session
+        if manager_arc.get_timeline().await.file_watcher_enabled {
+            if let Err(e) = manager_arc.start_file_watcher().await {
+                log::warn!("Failed to start file watcher: {}", e);
+            }
+        }
+
         managers.insert(session_id, Arc::clone(&manager_arc));
 
         Ok(manager_arc)
     }
 
+    /// Gets the active manager for a session, or opens a temporary one
+    ///
+    /// Used for sessions that may not be open, such as past sessions browsed from the
+    /// project timeline. A temporary manager is neither cached nor watching the project,
+    /// and is dropped once the caller is done with it.
+    pub async fn get_or_open_manager(
+        &self,
+        session_id: String,
+        project_id: String,
+        project_path: PathBuf,
+    ) -> Result<Arc<CheckpointManager>> {
+        if let Some(manager) = self.get_manager(&session_id).await {
+            return Ok(manager);
+        }
+
+        let claude_dir = {
+            let dir = self.claude_dir.read().await;
+            dir.as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Claude directory not set"))?
+                .clone()
+        };
+
+        let manager =
+            CheckpointManager::new(project_id, session_id, project_path, claude_dir).await?;
+        Ok(Arc::new(manager))
+    }
+
     /// Gets an existing CheckpointManager for a session
     ///
     /// Returns None if no manager exists for the session
-    #[allow(dead_code)]
     pub async fn get_manager(&self, session_id: &str) -> Option<Arc<CheckpointManager>> {
         let managers = self.managers.read().await;
         managers.get(session_id).map(Arc::clone)
@@ -181,4 +215,32 @@ mod tests {
 
         assert!(!Arc::ptr_eq(&manager1, &manager3));
     }
+
+    #[tokio::test]
+    async fn test_open_manager_for_past_session_is_not_cached() {
+        let state = CheckpointState::new();
+        let temp_dir = TempDir::new().unwrap();
+        state.set_claude_dir(temp_dir.path().to_path_buf()).await;
+        let project_path = temp_dir.path().join("project");
+        std::fs::create_dir_all(&project_path).unwrap();
+
+        let active = state
+            .get_or_create_manager("active".into(), "p".into(), project_path.clone())
+            .await
+            .unwrap();
+        let reused = state
+            .get_or_open_manager("active".into(), "p".into(), project_path.clone())
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&active, &reused));
+
+        state
+            .get_or_open_manager("past".into(), "p".into(), project_path)
+            .await
+            .unwrap();
+        assert_eq!(
+            state.list_active_sessions().await,
+            vec!["active".to_string()]
+        );
+    }
 }
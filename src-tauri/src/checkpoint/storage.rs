@@ -1,14 +1,20 @@
 use anyhow::{Context, Result};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use uuid::Uuid;
 use zstd::stream::{decode_all, encode_all};
 
 use super::{
-    Checkpoint, CheckpointPaths, CheckpointResult, FileSnapshot, SessionTimeline, TimelineNode,
+    Checkpoint, CheckpointPaths, CheckpointResult, CheckpointSearchQuery, FileSnapshot,
+    SessionTimeline, TimelineNode,
 };
 
+/// Per-session locks shared by every `CheckpointStorage` in the process
+static SESSION_LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+
 /// Manages checkpoint storage operations
 pub struct CheckpointStorage {
     pub claude_dir: PathBuf,
@@ -42,6 +48,28 @@ impl CheckpointStorage {
         Ok(())
     }
 
+    /// Run `f` while holding the storage lock of a session
+    ///
+    /// Blobs are written to the content pool before the refs pointing at them, so anything
+    /// that deletes unreferenced blobs must not run while a checkpoint is being saved.
+    pub fn with_session_lock<T>(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let lock = SESSION_LOCKS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(paths.files_dir)
+            .or_default()
+            .clone();
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        f()
+    }
+
     /// Save a checkpoint to disk
     pub fn save_checkpoint(
         &self,
@@ -50,6 +78,25 @@ impl CheckpointStorage {
         checkpoint: &Checkpoint,
         file_snapshots: Vec<FileSnapshot>,
         messages: &str, // JSONL content up to checkpoint
+    ) -> Result<CheckpointResult> {
+        self.with_session_lock(project_id, session_id, || {
+            self.save_checkpoint_locked(
+                project_id,
+                session_id,
+                checkpoint,
+                file_snapshots,
+                messages,
+            )
+        })
+    }
+
+    fn save_checkpoint_locked(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint: &Checkpoint,
+        file_snapshots: Vec<FileSnapshot>,
+        messages: &str,
     ) -> Result<CheckpointResult> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
         let checkpoint_dir = paths.checkpoint_dir(&checkpoint.id);
@@ -102,11 +149,11 @@ impl CheckpointStorage {
         let content_pool_dir = paths.files_dir.join("content_pool");
         fs::create_dir_all(&content_pool_dir).context("Failed to create content pool directory")?;
 
-        // Store the actual content in the content pool
+        // Store the actual content in the content pool; deleted files have none
         let content_file = content_pool_dir.join(&snapshot.hash);
 
         // Only write the content if it doesn't already exist
-        if !content_file.exists() {
+        if !snapshot.is_deleted && !content_file.exists() {
             // Compress and save file content
             let compressed_content =
                 encode_all(snapshot.content.as_bytes(), self.compression_level)
@@ -133,8 +180,7 @@ impl CheckpointStorage {
         let safe_filename = snapshot
             .file_path
             .to_string_lossy()
-            .replace('/', "_")
-            .replace('\\', "_");
+            .replace(['/', '\\'], "_");
         let ref_path = checkpoint_refs_dir.join(format!("{}.json", safe_filename));
 
         fs::write(&ref_path, serde_json::to_string_pretty(&ref_metadata)?)
@@ -207,9 +253,13 @@ impl CheckpointStorage {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("Missing hash in reference"))?;
 
+            let is_deleted = ref_metadata["is_deleted"].as_bool().unwrap_or(false);
+
             // Load content from pool
             let content_file = content_pool_dir.join(hash);
-            let content = if content_file.exists() {
+            let content = if is_deleted {
+                String::new()
+            } else if content_file.exists() {
                 let compressed_content =
                     fs::read(&content_file).context("Failed to read file content from pool")?;
                 String::from_utf8(
@@ -228,7 +278,7 @@ impl CheckpointStorage {
                 file_path: PathBuf::from(ref_metadata["path"].as_str().unwrap_or("")),
                 content,
                 hash: hash.to_string(),
-                is_deleted: ref_metadata["is_deleted"].as_bool().unwrap_or(false),
+                is_deleted,
                 permissions: ref_metadata["permissions"].as_u64().map(|p| p as u32),
                 size: ref_metadata["size"].as_u64().unwrap_or(0),
             });
@@ -237,6 +287,215 @@ impl CheckpointStorage {
         Ok(snapshots)
     }
 
+    /// List the project-relative paths snapshotted by a checkpoint without loading content
+    pub fn list_checkpoint_files(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<Vec<PathBuf>> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let refs_dir = paths.files_dir.join("refs").join(checkpoint_id);
+        if !refs_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&refs_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let ref_json = fs::read_to_string(&path).context("Failed to read file reference")?;
+            let ref_metadata: serde_json::Value =
+                serde_json::from_str(&ref_json).context("Failed to parse file reference")?;
+            if let Some(file_path) = ref_metadata["path"].as_str() {
+                files.push(PathBuf::from(file_path));
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Update the user annotations (tags, pinned flag, description) of a checkpoint
+    ///
+    /// Both the checkpoint metadata and its node in the session timeline are rewritten.
+    pub fn update_checkpoint_annotations(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+        tags: Vec<String>,
+        pinned: bool,
+        description: Option<String>,
+    ) -> Result<Checkpoint> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+
+        let metadata_path = paths.checkpoint_metadata_file(checkpoint_id);
+        let metadata_json =
+            fs::read_to_string(&metadata_path).context("Failed to read checkpoint metadata")?;
+        let mut checkpoint: Checkpoint =
+            serde_json::from_str(&metadata_json).context("Failed to parse checkpoint metadata")?;
+
+        checkpoint.tags = super::normalize_tags(tags);
+        checkpoint.pinned = pinned;
+        checkpoint.description = description.filter(|d| !d.trim().is_empty());
+
+        let metadata_json = serde_json::to_string_pretty(&checkpoint)
+            .context("Failed to serialize checkpoint metadata")?;
+        fs::write(&metadata_path, metadata_json).context("Failed to write checkpoint metadata")?;
+
+        let mut timeline = self.load_timeline(&paths.timeline_file)?;
+        let node = timeline.find_checkpoint_mut(checkpoint_id).ok_or_else(|| {
+            anyhow::anyhow!("Checkpoint not found in timeline: {}", checkpoint_id)
+        })?;
+        node.checkpoint = checkpoint.clone();
+        self.save_timeline(&paths.timeline_file, &timeline)?;
+
+        Ok(checkpoint)
+    }
+
+    /// Collect, per file path, every content hash snapshotted by any checkpoint of a session
+    pub fn collect_snapshot_hashes(
+        &self,
+        project_id: &str,
+        session_id: &str,
+    ) -> Result<HashMap<PathBuf, HashSet<String>>> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let refs_dir = paths.files_dir.join("refs");
+        let mut hashes: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+
+        if !refs_dir.exists() {
+            return Ok(hashes);
+        }
+
+        for checkpoint_entry in fs::read_dir(&refs_dir)? {
+            let checkpoint_dir = checkpoint_entry?.path();
+            if !checkpoint_dir.is_dir() {
+                continue;
+            }
+
+            for ref_entry in fs::read_dir(&checkpoint_dir)? {
+                let ref_path = ref_entry?.path();
+                if ref_path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+
+                if let Ok(ref_json) = fs::read_to_string(&ref_path) {
+                    if let Ok(ref_metadata) = serde_json::from_str::<serde_json::Value>(&ref_json) {
+                        if let (Some(path), Some(hash)) =
+                            (ref_metadata["path"].as_str(), ref_metadata["hash"].as_str())
+                        {
+                            hashes
+                                .entry(PathBuf::from(path))
+                                .or_default()
+                                .insert(hash.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(hashes)
+    }
+
+    /// List the IDs of all sessions of a project that have a timeline on disk
+    pub fn list_session_ids(&self, project_id: &str) -> Result<Vec<String>> {
+        let timelines_dir = self
+            .claude_dir
+            .join("projects")
+            .join(project_id)
+            .join(".timelines");
+        if !timelines_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut session_ids = Vec::new();
+        for entry in fs::read_dir(&timelines_dir)? {
+            let path = entry?.path();
+            if path.is_dir() && path.join("timeline.json").exists() {
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    session_ids.push(name.to_string());
+                }
+            }
+        }
+
+        session_ids.sort();
+        Ok(session_ids)
+    }
+
+    /// Load the timeline of every session in a project that has checkpoint storage
+    pub fn list_project_timelines(&self, project_id: &str) -> Result<Vec<SessionTimeline>> {
+        let mut timelines = Vec::new();
+        for session_id in self.list_session_ids(project_id)? {
+            let paths = CheckpointPaths::new(&self.claude_dir, project_id, &session_id);
+            match self.load_timeline(&paths.timeline_file) {
+                Ok(timeline) => timelines.push(timeline),
+                Err(e) => log::warn!("Skipping unreadable timeline for {}: {}", session_id, e),
+            }
+        }
+        Ok(timelines)
+    }
+
+    /// List the checkpoints of all sessions in a project, oldest first
+    pub fn list_project_checkpoints(&self, project_id: &str) -> Result<Vec<Checkpoint>> {
+        let mut checkpoints = Vec::new();
+        for timeline in self.list_project_timelines(project_id)? {
+            if let Some(root) = &timeline.root_node {
+                Self::collect_checkpoints(root, &mut checkpoints);
+            }
+        }
+
+        checkpoints.sort_by_key(|c| c.timestamp);
+        Ok(checkpoints)
+    }
+
+    /// Search the checkpoints of the given sessions, newest first
+    pub fn search_checkpoints(
+        &self,
+        project_id: &str,
+        session_ids: &[String],
+        query: &CheckpointSearchQuery,
+    ) -> Result<Vec<Checkpoint>> {
+        let mut results = Vec::new();
+
+        for session_id in session_ids {
+            let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+            if !paths.timeline_file.exists() {
+                continue;
+            }
+
+            let timeline = self.load_timeline(&paths.timeline_file)?;
+            let mut checkpoints = Vec::new();
+            if let Some(root) = &timeline.root_node {
+                Self::collect_checkpoints(root, &mut checkpoints);
+            }
+
+            for checkpoint in checkpoints {
+                if !query.matches(&checkpoint) {
+                    continue;
+                }
+
+                if let Some(needle) = &query.file_path {
+                    let files =
+                        self.list_checkpoint_files(project_id, session_id, &checkpoint.id)?;
+                    let touched = files
+                        .iter()
+                        .any(|f| f.to_string_lossy().contains(needle.as_str()));
+                    if !touched {
+                        continue;
+                    }
+                }
+
+                results.push(checkpoint);
+            }
+        }
+
+        results.sort_by_key(|c| std::cmp::Reverse(c.timestamp));
+        Ok(results)
+    }
+
     /// Save timeline to disk
     pub fn save_timeline(&self, timeline_path: &Path, timeline: &SessionTimeline) -> Result<()> {
         let timeline_json =
@@ -314,8 +573,13 @@ impl CheckpointStorage {
 
     /// Calculate hash of file content
     pub fn calculate_file_hash(content: &str) -> String {
+        Self::calculate_bytes_hash(content.as_bytes())
+    }
+
+    /// Calculate the hash of raw file bytes, matching `calculate_file_hash` for UTF-8 text
+    pub fn calculate_bytes_hash(content: &[u8]) -> String {
         let mut hasher = Sha256::new();
-        hasher.update(content.as_bytes());
+        hasher.update(content);
         format!("{:x}", hasher.finalize())
     }
 
@@ -334,11 +598,24 @@ impl CheckpointStorage {
     }
 
     /// Clean up old checkpoints based on retention policy
+    ///
+    /// Pinned checkpoints are never removed and don't count towards `keep_count`.
     pub fn cleanup_old_checkpoints(
         &self,
         project_id: &str,
         session_id: &str,
         keep_count: usize,
+    ) -> Result<usize> {
+        self.with_session_lock(project_id, session_id, || {
+            self.cleanup_old_checkpoints_locked(project_id, session_id, keep_count)
+        })
+    }
+
+    fn cleanup_old_checkpoints_locked(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        keep_count: usize,
     ) -> Result<usize> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
         let timeline = self.load_timeline(&paths.timeline_file)?;
@@ -348,9 +625,10 @@ impl CheckpointStorage {
         if let Some(root) = &timeline.root_node {
             Self::collect_checkpoints(root, &mut all_checkpoints);
         }
+        all_checkpoints.retain(|c| !c.pinned);
 
         // Sort by timestamp (oldest first)
-        all_checkpoints.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        all_checkpoints.sort_by_key(|a| a.timestamp);
 
         // Keep only the most recent checkpoints
         let to_remove = all_checkpoints.len().saturating_sub(keep_count);
@@ -364,7 +642,7 @@ impl CheckpointStorage {
 
         // Run garbage collection to clean up orphaned content
         if removed_count > 0 {
-            match self.garbage_collect_content(project_id, session_id) {
+            match self.garbage_collect_content_locked(&paths) {
                 Ok(gc_count) => {
                     log::info!("Garbage collected {} orphaned content files", gc_count);
                 }
@@ -407,7 +685,13 @@ impl CheckpointStorage {
 
     /// Garbage collect unreferenced content from the content pool
     pub fn garbage_collect_content(&self, project_id: &str, session_id: &str) -> Result<usize> {
-        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        self.with_session_lock(project_id, session_id, || {
+            let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+            self.garbage_collect_content_locked(&paths)
+        })
+    }
+
+    fn garbage_collect_content_locked(&self, paths: &CheckpointPaths) -> Result<usize> {
         let content_pool_dir = paths.files_dir.join("content_pool");
         let refs_dir = paths.files_dir.join("refs");
 
@@ -446,10 +730,8 @@ impl CheckpointStorage {
             let content_file = entry?.path();
             if content_file.is_file() {
                 if let Some(hash) = content_file.file_name().and_then(|n| n.to_str()) {
-                    if !referenced_hashes.contains(hash) {
-                        if fs::remove_file(&content_file).is_ok() {
-                            removed_count += 1;
-                        }
+                    if !referenced_hashes.contains(hash) && fs::remove_file(&content_file).is_ok() {
+                        removed_count += 1;
                     }
                 }
             }
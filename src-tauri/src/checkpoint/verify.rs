@@ -0,0 +1,605 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use zstd::stream::decode_all;
+
+use super::{storage::CheckpointStorage, Checkpoint, CheckpointPaths, TimelineNode};
+
+/// Kind of problem found in checkpoint storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageIssueKind {
+    /// The timeline file cannot be read or parsed
+    UnreadableTimeline,
+    /// A timeline node has no readable checkpoint directory
+    MissingCheckpoint,
+    /// A checkpoint directory is not referenced by the timeline
+    UntrackedCheckpoint,
+    /// A checkpoint's messages cannot be decompressed
+    CorruptMessages,
+    /// A file reference cannot be parsed
+    InvalidRef,
+    /// A file reference points at a blob that is not in the content pool
+    DanglingRef,
+    /// A blob cannot be decompressed or its content does not match its hash
+    CorruptBlob,
+    /// A blob is not referenced by any checkpoint
+    OrphanedBlob,
+    /// `total_checkpoints` or `current_checkpoint_id` disagree with the tree
+    TimelineMismatch,
+}
+
+/// A single problem found while verifying checkpoint storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageIssue {
+    pub kind: StorageIssueKind,
+    pub session_id: String,
+    pub checkpoint_id: Option<String>,
+    pub detail: String,
+    /// Whether the problem was fixed by pruning the broken entry
+    pub repaired: bool,
+}
+
+/// Result of verifying the checkpoint storage of one or more sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageVerificationReport {
+    pub project_id: String,
+    pub sessions_checked: usize,
+    pub checkpoints_checked: usize,
+    pub refs_checked: usize,
+    pub blobs_checked: usize,
+    pub issues: Vec<StorageIssue>,
+}
+
+impl StorageVerificationReport {
+    /// Whether no problems were found
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Verify the checkpoint storage of a project, optionally limited to one session
+///
+/// With `repair` broken entries are pruned: corrupt and orphaned blobs are deleted,
+/// untracked checkpoint directories and stray refs removed, and checkpoints that can no
+/// longer be restored faithfully (missing metadata or messages, unreadable refs, refs to
+/// missing blobs) are dropped as a whole, with their children re-attached to the parent.
+pub fn verify_project(
+    storage: &CheckpointStorage,
+    project_id: &str,
+    session_id: Option<&str>,
+    repair: bool,
+) -> Result<StorageVerificationReport> {
+    let session_ids = match session_id {
+        Some(session_id) => vec![session_id.to_string()],
+        None => storage.list_session_ids(project_id)?,
+    };
+
+    let mut report = StorageVerificationReport {
+        project_id: project_id.to_string(),
+        ..Default::default()
+    };
+    for session_id in session_ids {
+        // Checkpoints saved meanwhile would otherwise show up with refs to "orphaned" blobs
+        storage.with_session_lock(project_id, &session_id, || {
+            verify_session(storage, project_id, &session_id, repair, &mut report)
+        })?;
+    }
+
+    Ok(report)
+}
+
+/// A parsed file reference of a checkpoint
+struct FileRef {
+    file_path: String,
+    hash: String,
+    is_deleted: bool,
+}
+
+fn verify_session(
+    storage: &CheckpointStorage,
+    project_id: &str,
+    session_id: &str,
+    repair: bool,
+    report: &mut StorageVerificationReport,
+) -> Result<()> {
+    let paths = CheckpointPaths::new(&storage.claude_dir, project_id, session_id);
+    report.sessions_checked += 1;
+
+    let mut issues = Vec::new();
+    let mut issue = |kind, checkpoint_id: Option<&str>, detail: String, repaired| {
+        issues.push(StorageIssue {
+            kind,
+            session_id: session_id.to_string(),
+            checkpoint_id: checkpoint_id.map(str::to_string),
+            detail,
+            repaired,
+        });
+    };
+
+    let mut timeline = match storage.load_timeline(&paths.timeline_file) {
+        Ok(timeline) => timeline,
+        Err(e) => {
+            issue(
+                StorageIssueKind::UnreadableTimeline,
+                None,
+                format!("{:#}", e),
+                false,
+            );
+            report.issues.extend(issues);
+            return Ok(());
+        }
+    };
+
+    // Checkpoints that cannot be restored faithfully and are pruned as a whole
+    let mut broken = HashSet::new();
+
+    // Checkpoint directories: metadata and messages must be readable
+    let mut valid_checkpoints = HashSet::new();
+    let mut checkpoints_on_disk = 0;
+    if paths.checkpoints_dir.exists() {
+        for entry in fs::read_dir(&paths.checkpoints_dir)? {
+            let dir = entry?.path();
+            let checkpoint_id = match dir.file_name().and_then(|n| n.to_str()) {
+                Some(name) if dir.is_dir() => name.to_string(),
+                _ => continue,
+            };
+            checkpoints_on_disk += 1;
+
+            let metadata = fs::read_to_string(paths.checkpoint_metadata_file(&checkpoint_id))
+                .ok()
+                .and_then(|json| serde_json::from_str::<Checkpoint>(&json).ok());
+            if metadata.is_none() {
+                // Reported below as a missing checkpoint if the timeline knows about it
+                continue;
+            }
+
+            let messages_ok = fs::read(paths.checkpoint_messages_file(&checkpoint_id))
+                .ok()
+                .and_then(|data| decode_all(&data[..]).ok())
+                .is_some_and(|data| String::from_utf8(data).is_ok());
+            if !messages_ok {
+                issue(
+                    StorageIssueKind::CorruptMessages,
+                    Some(&checkpoint_id),
+                    "Messages are missing or cannot be decompressed".to_string(),
+                    repair,
+                );
+                broken.insert(checkpoint_id.clone());
+            }
+
+            valid_checkpoints.insert(checkpoint_id);
+        }
+    }
+    report.checkpoints_checked += checkpoints_on_disk;
+
+    // Content pool: every blob must decompress to content matching its hash
+    let content_pool_dir = paths.files_dir.join("content_pool");
+    let mut good_blobs = HashSet::new();
+    let mut blob_paths = HashMap::new();
+    if content_pool_dir.exists() {
+        for entry in fs::read_dir(&content_pool_dir)? {
+            let blob_path = entry?.path();
+            let hash = match blob_path.file_name().and_then(|n| n.to_str()) {
+                Some(name) if blob_path.is_file() => name.to_string(),
+                _ => continue,
+            };
+            report.blobs_checked += 1;
+
+            match blob_content_hash(&blob_path) {
+                Ok(actual) if actual == hash => {
+                    good_blobs.insert(hash.clone());
+                    blob_paths.insert(hash, blob_path);
+                }
+                result => {
+                    let detail = match result {
+                        Ok(actual) => format!("Content hash is {}", actual),
+                        Err(e) => format!("{:#}", e),
+                    };
+                    let repaired = repair && fs::remove_file(&blob_path).is_ok();
+                    issue(
+                        StorageIssueKind::CorruptBlob,
+                        None,
+                        format!("Blob {}: {}", hash, detail),
+                        repaired,
+                    );
+                }
+            }
+        }
+    }
+
+    // Refs: must parse and point at a good blob (deleted files have no content)
+    let refs_dir = paths.files_dir.join("refs");
+    let mut refs_by_checkpoint: HashMap<String, Vec<FileRef>> = HashMap::new();
+    let mut ref_dirs = Vec::new();
+    if refs_dir.exists() {
+        for checkpoint_entry in fs::read_dir(&refs_dir)? {
+            let checkpoint_refs = checkpoint_entry?.path();
+            let checkpoint_id = match checkpoint_refs.file_name().and_then(|n| n.to_str()) {
+                Some(name) if checkpoint_refs.is_dir() => name.to_string(),
+                _ => continue,
+            };
+            ref_dirs.push((checkpoint_id.clone(), checkpoint_refs.clone()));
+
+            let refs = refs_by_checkpoint.entry(checkpoint_id.clone()).or_default();
+            for ref_entry in fs::read_dir(&checkpoint_refs)? {
+                let ref_path = ref_entry?.path();
+                if ref_path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                report.refs_checked += 1;
+
+                let file_ref = fs::read_to_string(&ref_path)
+                    .ok()
+                    .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+                    .and_then(|meta| {
+                        Some(FileRef {
+                            file_path: meta["path"].as_str()?.to_string(),
+                            hash: meta["hash"].as_str()?.to_string(),
+                            is_deleted: meta["is_deleted"].as_bool().unwrap_or(false),
+                        })
+                    })
+                    .filter(|file_ref| !file_ref.file_path.is_empty());
+
+                match file_ref {
+                    Some(file_ref) => {
+                        if !file_ref.is_deleted && !good_blobs.contains(&file_ref.hash) {
+                            issue(
+                                StorageIssueKind::DanglingRef,
+                                Some(&checkpoint_id),
+                                format!(
+                                    "{} points at missing or corrupt blob {}",
+                                    file_ref.file_path, file_ref.hash
+                                ),
+                                repair,
+                            );
+                            broken.insert(checkpoint_id.clone());
+                        }
+                        refs.push(file_ref);
+                    }
+                    None => {
+                        issue(
+                            StorageIssueKind::InvalidRef,
+                            Some(&checkpoint_id),
+                            format!("Unreadable file reference {}", ref_path.display()),
+                            repair,
+                        );
+                        broken.insert(checkpoint_id.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // Timeline nodes must have checkpoint data, and every checkpoint must be in the timeline
+    let mut timeline_ids = Vec::new();
+    if let Some(root) = &timeline.root_node {
+        collect_ids(root, &mut timeline_ids);
+    }
+    let mut timeline_changed = false;
+
+    for checkpoint_id in &timeline_ids {
+        let missing = !valid_checkpoints.contains(checkpoint_id);
+        if missing {
+            issue(
+                StorageIssueKind::MissingCheckpoint,
+                Some(checkpoint_id),
+                "Timeline node has no readable checkpoint metadata".to_string(),
+                repair,
+            );
+        }
+        if repair && (missing || broken.contains(checkpoint_id)) {
+            timeline.root_node = timeline
+                .root_node
+                .take()
+                .and_then(|root| remove_node(root, checkpoint_id));
+            let _ = fs::remove_dir_all(paths.checkpoint_dir(checkpoint_id));
+            timeline_changed = true;
+        }
+    }
+
+    let timeline_set: HashSet<&String> = timeline_ids.iter().collect();
+    for checkpoint_id in &valid_checkpoints {
+        if !timeline_set.contains(checkpoint_id) {
+            let repaired =
+                repair && fs::remove_dir_all(paths.checkpoint_dir(checkpoint_id)).is_ok();
+            issue(
+                StorageIssueKind::UntrackedCheckpoint,
+                Some(checkpoint_id),
+                "Checkpoint directory is not part of the timeline".to_string(),
+                repaired,
+            );
+        }
+    }
+
+    // Checkpoints that are still in the timeline after repair
+    let mut remaining_ids = Vec::new();
+    if let Some(root) = &timeline.root_node {
+        collect_ids(root, &mut remaining_ids);
+    }
+    let live_checkpoints: HashSet<&String> = remaining_ids
+        .iter()
+        .filter(|id| valid_checkpoints.contains(*id))
+        .collect();
+
+    // Refs of checkpoints that are gone, then blobs no remaining checkpoint refers to
+    let mut referenced = HashSet::new();
+    for (checkpoint_id, checkpoint_refs) in ref_dirs {
+        if live_checkpoints.contains(&checkpoint_id) {
+            for file_ref in &refs_by_checkpoint[&checkpoint_id] {
+                if !file_ref.is_deleted {
+                    referenced.insert(file_ref.hash.clone());
+                }
+            }
+            continue;
+        }
+
+        let repaired = repair && fs::remove_dir_all(&checkpoint_refs).is_ok();
+        if !timeline_set.contains(&checkpoint_id) {
+            issue(
+                StorageIssueKind::DanglingRef,
+                Some(&checkpoint_id),
+                "File references belong to a checkpoint that does not exist".to_string(),
+                repaired,
+            );
+        }
+    }
+
+    for hash in good_blobs.difference(&referenced) {
+        let repaired = repair && fs::remove_file(&blob_paths[hash]).is_ok();
+        issue(
+            StorageIssueKind::OrphanedBlob,
+            None,
+            format!("Blob {} is not referenced by any checkpoint", hash),
+            repaired,
+        );
+    }
+
+    // Timeline bookkeeping must agree with the tree
+    if timeline.total_checkpoints != remaining_ids.len() {
+        issue(
+            StorageIssueKind::TimelineMismatch,
+            None,
+            format!(
+                "Timeline counts {} checkpoints but contains {}",
+                timeline.total_checkpoints,
+                remaining_ids.len()
+            ),
+            repair,
+        );
+        timeline.total_checkpoints = remaining_ids.len();
+        timeline_changed = true;
+    }
+    if let Some(current) = timeline.current_checkpoint_id.clone() {
+        if !remaining_ids.contains(&current) {
+            issue(
+                StorageIssueKind::TimelineMismatch,
+                Some(&current),
+                "Current checkpoint is not in the timeline".to_string(),
+                repair,
+            );
+            timeline.current_checkpoint_id = remaining_ids.last().cloned();
+            timeline_changed = true;
+        }
+    }
+
+    if repair && timeline_changed {
+        storage
+            .save_timeline(&paths.timeline_file, &timeline)
+            .context("Failed to save repaired timeline")?;
+    }
+
+    report.issues.extend(issues);
+    Ok(())
+}
+
+/// Decompress a content-pool blob and hash its content
+fn blob_content_hash(blob_path: &Path) -> Result<String> {
+    let compressed = fs::read(blob_path).context("Failed to read blob")?;
+    let content = String::from_utf8(decode_all(&compressed[..]).context("Failed to decompress")?)
+        .context("Invalid UTF-8 in blob")?;
+    Ok(CheckpointStorage::calculate_file_hash(&content))
+}
+
+fn collect_ids(node: &TimelineNode, ids: &mut Vec<String>) {
+    ids.push(node.checkpoint.id.clone());
+    for child in &node.children {
+        collect_ids(child, ids);
+    }
+}
+
+/// Remove a node from the tree, re-attaching its children to its parent
+///
+/// When the root itself is removed its first child takes its place.
+fn remove_node(mut node: TimelineNode, checkpoint_id: &str) -> Option<TimelineNode> {
+    if node.checkpoint.id == checkpoint_id {
+        let parent_id = node.checkpoint.parent_checkpoint_id.clone();
+        let mut children = node.children.into_iter();
+        let mut new_root = children.next()?;
+        new_root.checkpoint.parent_checkpoint_id = parent_id;
+        for mut child in children {
+            child.checkpoint.parent_checkpoint_id = Some(new_root.checkpoint.id.clone());
+            new_root.children.push(child);
+        }
+        return Some(new_root);
+    }
+
+    let mut children = Vec::new();
+    for child in node.children {
+        if child.checkpoint.id == checkpoint_id {
+            for mut grandchild in child.children {
+                grandchild.checkpoint.parent_checkpoint_id = Some(node.checkpoint.id.clone());
+                children.push(grandchild);
+            }
+        } else if let Some(child) = remove_node(child, checkpoint_id) {
+            children.push(child);
+        }
+    }
+    node.children = children;
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use tempfile::TempDir;
+
+    const PROJECT_ID: &str = "test-project";
+    const SESSION_ID: &str = "session-a";
+
+    async fn new_manager(temp_dir: &TempDir) -> CheckpointManager {
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        CheckpointManager::new(
+            PROJECT_ID.to_string(),
+            SESSION_ID.to_string(),
+            project_path,
+            temp_dir.path().join("claude"),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn checkpoint_file(
+        manager: &CheckpointManager,
+        temp_dir: &TempDir,
+        file: &str,
+        content: &str,
+    ) -> String {
+        fs::write(temp_dir.path().join("project").join(file), content).unwrap();
+        manager.track_file_modification(file).await.unwrap();
+        manager
+            .create_checkpoint(Some(file.to_string()), None)
+            .await
+            .unwrap()
+            .checkpoint
+            .id
+    }
+
+    fn storage(temp_dir: &TempDir) -> (CheckpointStorage, CheckpointPaths) {
+        let storage = CheckpointStorage::new(temp_dir.path().join("claude"));
+        let paths = CheckpointPaths::new(&storage.claude_dir, PROJECT_ID, SESSION_ID);
+        (storage, paths)
+    }
+
+    fn kinds(report: &StorageVerificationReport) -> Vec<StorageIssueKind> {
+        report.issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[tokio::test]
+    async fn test_healthy_storage_has_no_issues() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir).await;
+        checkpoint_file(&manager, &temp_dir, "a.txt", "one").await;
+        checkpoint_file(&manager, &temp_dir, "b.txt", "two").await;
+
+        let (storage, _) = storage(&temp_dir);
+        let report = verify_project(&storage, PROJECT_ID, None, false).unwrap();
+        assert!(report.is_healthy(), "{:?}", report.issues);
+        assert_eq!(report.checkpoints_checked, 2);
+        assert_eq!(report.blobs_checked, 2);
+    }
+
+    #[tokio::test]
+    async fn test_repair_prunes_checkpoints_with_dangling_refs() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir).await;
+        let first = checkpoint_file(&manager, &temp_dir, "a.txt", "one").await;
+        let second = checkpoint_file(&manager, &temp_dir, "b.txt", "two").await;
+        let third = checkpoint_file(&manager, &temp_dir, "a.txt", "three").await;
+
+        let (storage, paths) = storage(&temp_dir);
+        let missing_blob = CheckpointStorage::calculate_file_hash("two");
+        fs::remove_file(paths.files_dir.join("content_pool").join(&missing_blob)).unwrap();
+
+        let report = verify_project(&storage, PROJECT_ID, None, false).unwrap();
+        assert_eq!(kinds(&report), vec![StorageIssueKind::DanglingRef]);
+        assert_eq!(report.issues[0].checkpoint_id.as_deref(), Some(&*second));
+
+        let report = verify_project(&storage, PROJECT_ID, None, true).unwrap();
+        assert!(report.issues.iter().all(|issue| issue.repaired));
+
+        // The whole checkpoint is gone rather than silently missing b.txt
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert!(timeline.find_checkpoint(&second).is_none());
+        assert!(!paths.checkpoint_dir(&second).exists());
+        assert!(!paths.files_dir.join("refs").join(&second).exists());
+        assert_eq!(timeline.total_checkpoints, 2);
+        assert_eq!(
+            timeline
+                .find_checkpoint(&third)
+                .unwrap()
+                .checkpoint
+                .parent_checkpoint_id
+                .as_deref(),
+            Some(&*first)
+        );
+
+        let (_, files, _) = storage
+            .load_checkpoint(PROJECT_ID, SESSION_ID, &third)
+            .unwrap();
+        assert_eq!(files[0].content, "three");
+        assert!(verify_project(&storage, PROJECT_ID, None, false)
+            .unwrap()
+            .is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_repair_prunes_checkpoints_with_corrupt_messages() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir).await;
+        let first = checkpoint_file(&manager, &temp_dir, "a.txt", "one").await;
+        let second = checkpoint_file(&manager, &temp_dir, "b.txt", "two").await;
+
+        let (storage, paths) = storage(&temp_dir);
+        fs::write(paths.checkpoint_messages_file(&second), b"not zstd").unwrap();
+
+        let report = verify_project(&storage, PROJECT_ID, None, true).unwrap();
+        assert_eq!(
+            kinds(&report),
+            vec![
+                StorageIssueKind::CorruptMessages,
+                StorageIssueKind::OrphanedBlob,
+                StorageIssueKind::TimelineMismatch,
+                StorageIssueKind::TimelineMismatch,
+            ]
+        );
+        assert!(report.issues.iter().all(|issue| issue.repaired));
+
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert!(timeline.find_checkpoint(&second).is_none());
+        assert_eq!(timeline.current_checkpoint_id.as_deref(), Some(&*first));
+        assert!(verify_project(&storage, PROJECT_ID, None, false)
+            .unwrap()
+            .is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_repair_never_collects_blobs_of_checkpoints_being_saved() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = new_manager(&temp_dir).await;
+        checkpoint_file(&manager, &temp_dir, "a.txt", "0").await;
+
+        let claude_dir = temp_dir.path().join("claude");
+        let verifier = std::thread::spawn(move || {
+            let storage = CheckpointStorage::new(claude_dir);
+            for _ in 0..50 {
+                verify_project(&storage, PROJECT_ID, None, true).unwrap();
+            }
+        });
+        for i in 1..50 {
+            checkpoint_file(&manager, &temp_dir, "a.txt", &i.to_string()).await;
+        }
+        verifier.join().unwrap();
+
+        let (storage, _) = storage(&temp_dir);
+        let report = verify_project(&storage, PROJECT_ID, None, false).unwrap();
+        assert!(report.is_healthy(), "{:?}", report.issues);
+        assert_eq!(report.checkpoints_checked, 50);
+    }
+}
@@ -0,0 +1,489 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{gitconfig_excludes_path, Gitignore, GitignoreBuilder};
+use ignore::Match;
+use notify::{
+    event::ModifyKind, Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{mpsc as std_mpsc, Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::git;
+
+/// How long to wait for further events before handing a batch of changes to the tracker
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Watches a project directory for changes made outside of agent tool calls
+///
+/// Each directory that is not ignored gets its own watch, so trees like `node_modules` or
+/// `target` never cost a watch or an event. Directories created later are walked and
+/// watched as they appear. Changed paths are reported relative to the project root.
+/// Dropping the watcher stops watching and closes the event channel.
+pub struct ProjectWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+impl ProjectWatcher {
+    /// Start watching the project directory and every directory below it that is not ignored
+    pub fn start(project_path: &Path) -> Result<(Self, mpsc::UnboundedReceiver<PathBuf>)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (raw_tx, raw_rx) = std_mpsc::channel();
+
+        let watcher = Arc::new(Mutex::new(
+            RecommendedWatcher::new(raw_tx, Config::default())
+                .context("Failed to create file watcher")?,
+        ));
+
+        let mut ignore = IgnoreRules::load(project_path);
+        {
+            let mut watcher = watcher.lock().unwrap();
+            let mut first_error = None;
+            walk(project_path, project_path, &mut ignore, &mut |dir| {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    first_error.get_or_insert(e);
+                }
+            });
+            if let Some(e) = first_error {
+                return Err(e)
+                    .with_context(|| format!("Failed to watch {}", project_path.display()));
+            }
+        }
+
+        // Events are handled off the notify thread, which has to stay free to add watches
+        let worker = EventWorker {
+            project_path: project_path.to_path_buf(),
+            roots: watch_roots(project_path),
+            ignore,
+            watcher: Arc::downgrade(&watcher),
+            tx,
+        };
+        std::thread::Builder::new()
+            .name("project-watcher".to_string())
+            .spawn(move || worker.run(raw_rx))
+            .context("Failed to start file watcher thread")?;
+
+        Ok((Self { _watcher: watcher }, rx))
+    }
+}
+
+/// Paths events may be reported against: the project path and its canonical form
+/// (e.g. symlinked temp dirs)
+fn watch_roots(project_path: &Path) -> Vec<PathBuf> {
+    let mut roots = vec![project_path.to_path_buf()];
+    if let Ok(canonical) = project_path.canonicalize() {
+        if canonical != project_path {
+            roots.push(canonical);
+        }
+    }
+    roots
+}
+
+/// Turns raw notify events into project-relative paths for the tracker
+struct EventWorker {
+    project_path: PathBuf,
+    roots: Vec<PathBuf>,
+    ignore: IgnoreRules,
+    watcher: Weak<Mutex<RecommendedWatcher>>,
+    tx: mpsc::UnboundedSender<PathBuf>,
+}
+
+impl EventWorker {
+    /// Handle events until the watcher is dropped
+    fn run(mut self, events: std_mpsc::Receiver<notify::Result<Event>>) {
+        for res in events {
+            match res {
+                Ok(event) if is_content_change(&event.kind) => {
+                    for path in &event.paths {
+                        self.handle_path(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("File watcher error: {}", e),
+            }
+        }
+    }
+
+    fn handle_path(&mut self, path: &Path) {
+        let Some(relative) = self
+            .roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .map(Path::to_path_buf)
+        else {
+            return;
+        };
+        if relative.as_os_str().is_empty() || is_git_internal(&relative) {
+            return;
+        }
+
+        let full_path = self.project_path.join(&relative);
+        if relative.file_name() == Some(".gitignore".as_ref()) {
+            if let Some(dir) = full_path.parent() {
+                self.ignore.reload_dir(dir);
+            }
+        }
+
+        let is_dir = fs::symlink_metadata(&full_path).is_ok_and(|m| m.is_dir());
+        if self.ignore.is_ignored(&relative, is_dir) {
+            return;
+        }
+
+        if !is_dir {
+            let _ = self.tx.send(relative);
+            return;
+        }
+
+        // A new or moved-in directory: watch it and report the files already inside, which
+        // can land before the watch does
+        let Some(watcher) = self.watcher.upgrade() else {
+            return;
+        };
+        let mut watcher = watcher.lock().unwrap();
+        let files = walk(
+            &self.project_path,
+            &full_path,
+            &mut self.ignore,
+            &mut |dir| {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("Failed to watch {}: {}", dir.display(), e);
+                }
+            },
+        );
+        for file in files {
+            let _ = self.tx.send(file);
+        }
+    }
+}
+
+/// Wait for the next batch of changed paths, coalescing bursts of events
+///
+/// Returns `None` once the watcher has been dropped.
+pub async fn next_batch(events: &mut mpsc::UnboundedReceiver<PathBuf>) -> Option<Vec<PathBuf>> {
+    let mut batch = BTreeSet::new();
+    batch.insert(events.recv().await?);
+
+    while let Ok(Some(path)) = tokio::time::timeout(DEBOUNCE_INTERVAL, events.recv()).await {
+        batch.insert(path);
+    }
+
+    Some(batch.into_iter().collect())
+}
+
+/// Walk a directory tree, skipping `.git`, ignored entries and symlinked directories
+///
+/// Calls `on_dir` for every directory visited, starting with `dir`, and returns the files
+/// found relative to the project root.
+fn walk(
+    project_path: &Path,
+    dir: &Path,
+    ignore: &mut IgnoreRules,
+    on_dir: &mut dyn FnMut(&Path),
+) -> Vec<PathBuf> {
+    fn visit(
+        project_path: &Path,
+        dir: &Path,
+        ignore: &mut IgnoreRules,
+        on_dir: &mut dyn FnMut(&Path),
+        files: &mut Vec<PathBuf>,
+    ) {
+        ignore.load_dir(dir);
+        on_dir(dir);
+
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(relative) = path.strip_prefix(project_path) else {
+                continue;
+            };
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if ignore.is_ignored(relative, is_dir) {
+                continue;
+            }
+            if is_dir {
+                visit(project_path, &path, ignore, on_dir, files);
+            } else {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    visit(project_path, dir, ignore, on_dir, &mut files);
+    files
+}
+
+/// The project's gitignore rules, read once per directory and reloaded when a `.gitignore`
+/// changes
+///
+/// Projects that are not git repositories ignore nothing.
+struct IgnoreRules {
+    project_path: PathBuf,
+    enabled: bool,
+    /// Rules of each directory with a `.gitignore`, keyed by that directory
+    dirs: HashMap<PathBuf, Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Load the project-wide rules: the global excludes file, `.git/info/exclude` and the
+    /// top-level `.gitignore`
+    fn load(project_path: &Path) -> Self {
+        let mut rules = Self {
+            project_path: project_path.to_path_buf(),
+            enabled: git::is_git_repo(project_path),
+            dirs: HashMap::new(),
+        };
+        rules.reload_dir(project_path);
+        rules
+    }
+
+    /// Read a directory's `.gitignore` unless it has been read already
+    fn load_dir(&mut self, dir: &Path) {
+        if self.enabled && !self.dirs.contains_key(dir) {
+            self.reload_dir(dir);
+        }
+    }
+
+    /// Read a directory's `.gitignore` again
+    fn reload_dir(&mut self, dir: &Path) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        if dir == self.project_path {
+            // Later files take precedence, matching git's order
+            if let Some(global) = gitconfig_excludes_path().filter(|path| path.is_file()) {
+                builder.add(global);
+            }
+            builder.add(dir.join(".git/info/exclude"));
+        }
+        builder.add(dir.join(".gitignore"));
+
+        match builder.build() {
+            Ok(gitignore) => {
+                self.dirs.insert(dir.to_path_buf(), gitignore);
+            }
+            Err(e) => log::warn!("Failed to read ignore rules in {}: {}", dir.display(), e),
+        }
+    }
+
+    /// Whether a project-relative path, or any directory above it, is ignored
+    fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let components: Vec<_> = relative.components().collect();
+        let mut path = self.project_path.clone();
+        for (i, component) in components.iter().enumerate() {
+            path.push(component);
+            let last = i + 1 == components.len();
+            if self.matches(&path, !last || is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check a path against the rules of the directories above it, nearest first
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        for dir in path.ancestors().skip(1) {
+            if let Some(gitignore) = self.dirs.get(dir) {
+                match gitignore.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            if dir == self.project_path {
+                break;
+            }
+        }
+        false
+    }
+}
+
+/// Whether an event may have changed file contents or existence
+fn is_content_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether a project-relative path points into the `.git` directory
+fn is_git_internal(path: &Path) -> bool {
+    path.components()
+        .any(|component| component == Component::Normal(".git".as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(project_path: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(project_path)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reports_relative_paths_and_skips_git_internals() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+        fs::create_dir_all(project_path.join(".git")).unwrap();
+        let (_watcher, mut events) = ProjectWatcher::start(project_path).unwrap();
+
+        fs::write(project_path.join(".git/index"), "internal").unwrap();
+        fs::write(project_path.join("a.txt"), "one").unwrap();
+
+        let batch = tokio::time::timeout(Duration::from_secs(5), next_batch(&mut events))
+            .await
+            .expect("no file events within timeout")
+            .unwrap();
+        assert!(batch.contains(&PathBuf::from("a.txt")));
+        assert!(batch.iter().all(|path| !is_git_internal(path)));
+    }
+
+    #[tokio::test]
+    async fn test_next_batch_coalesces_duplicates_and_ends_when_dropped() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(PathBuf::from("b.txt")).unwrap();
+        tx.send(PathBuf::from("a.txt")).unwrap();
+        tx.send(PathBuf::from("b.txt")).unwrap();
+        drop(tx);
+
+        assert_eq!(
+            next_batch(&mut rx).await,
+            Some(vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")])
+        );
+        assert_eq!(next_batch(&mut rx).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_skips_ignored_trees_and_reports_new_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+        git(project_path, &["init", "-q"]);
+        fs::write(project_path.join(".gitignore"), "node_modules/\n*.log\n").unwrap();
+        fs::create_dir_all(project_path.join("node_modules/dep")).unwrap();
+        let (_watcher, mut events) = ProjectWatcher::start(project_path).unwrap();
+
+        fs::write(project_path.join("node_modules/dep/index.js"), "").unwrap();
+        fs::write(project_path.join("app.log"), "").unwrap();
+        fs::create_dir_all(project_path.join("src/nested")).unwrap();
+        fs::write(project_path.join("src/nested/lib.rs"), "").unwrap();
+        fs::write(project_path.join("main.rs"), "").unwrap();
+
+        let mut seen = BTreeSet::new();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !(seen.contains(&PathBuf::from("main.rs"))
+            && seen.contains(&PathBuf::from("src/nested/lib.rs")))
+        {
+            let batch = tokio::time::timeout_at(deadline, next_batch(&mut events))
+                .await
+                .expect("no file events within timeout")
+                .unwrap();
+            seen.extend(batch);
+        }
+        assert!(seen
+            .iter()
+            .all(|path| !path.starts_with("node_modules") && path != Path::new("app.log")));
+    }
+
+    #[test]
+    fn test_walk_skips_git_and_ignored_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+        git(project_path, &["init", "-q"]);
+        fs::write(project_path.join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir_all(project_path.join("src/nested")).unwrap();
+        fs::create_dir_all(project_path.join("target/debug")).unwrap();
+        fs::write(project_path.join("src/main.rs"), "").unwrap();
+        fs::write(project_path.join("src/nested/lib.rs"), "").unwrap();
+        fs::write(project_path.join("target/debug/app"), "").unwrap();
+
+        let mut ignore = IgnoreRules::load(project_path);
+        let mut dirs = Vec::new();
+        let mut files = walk(project_path, project_path, &mut ignore, &mut |dir| {
+            dirs.push(dir.strip_prefix(project_path).unwrap().to_path_buf())
+        });
+        files.sort();
+        dirs.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from(".gitignore"),
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("src/nested/lib.rs"),
+            ]
+        );
+        assert_eq!(
+            dirs,
+            vec![
+                PathBuf::new(),
+                PathBuf::from("src"),
+                PathBuf::from("src/nested")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignore_rules_honour_gitignore() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+        fs::write(project_path.join(".gitignore"), "*.log\n").unwrap();
+
+        // Outside a repository nothing is ignored
+        assert!(!IgnoreRules::load(project_path).is_ignored(Path::new("app.log"), false));
+
+        git(project_path, &["init", "-q"]);
+        fs::create_dir_all(project_path.join("docs")).unwrap();
+        fs::write(project_path.join("docs/.gitignore"), "!keep.log\ndrafts/\n").unwrap();
+        let mut ignore = IgnoreRules::load(project_path);
+        ignore.load_dir(&project_path.join("docs"));
+
+        assert!(ignore.is_ignored(Path::new("app.log"), false));
+        assert!(!ignore.is_ignored(Path::new("main.rs"), false));
+        // Nested rules override the ones above them and apply to everything below
+        assert!(!ignore.is_ignored(Path::new("docs/keep.log"), false));
+        assert!(ignore.is_ignored(Path::new("docs/drafts/a.md"), false));
+
+        // Changes to a .gitignore take effect once it is reloaded
+        fs::write(project_path.join(".gitignore"), "*.rs\n").unwrap();
+        ignore.reload_dir(project_path);
+        assert!(!ignore.is_ignored(Path::new("app.log"), false));
+        assert!(ignore.is_ignored(Path::new("main.rs"), false));
+    }
+
+    #[test]
+    fn test_metadata_changes_are_not_content_changes() {
+        use notify::event::{CreateKind, DataChange, MetadataKind};
+
+        assert!(is_content_change(&EventKind::Create(CreateKind::File)));
+        assert!(is_content_change(&EventKind::Modify(ModifyKind::Data(
+            DataChange::Content
+        ))));
+        assert!(!is_content_change(&EventKind::Modify(
+            ModifyKind::Metadata(MetadataKind::Permissions)
+        )));
+        assert!(!is_content_change(&EventKind::Access(
+            notify::event::AccessKind::Any
+        )));
+    }
+}
@@ -1,9 +1,10 @@
+use crate::sandbox::policy::SandboxPolicy;
 use crate::sandbox::profile::ProfileBuilder;
 use anyhow::Result;
 use chrono;
 use log::{debug, error, info, warn};
 use reqwest;
-use rusqlite::{params, Connection, Result as SqliteResult};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::path::PathBuf;
@@ -34,6 +35,78 @@ pub struct Agent {
     pub enable_network: bool,
     pub created_at: String,
     pub updated_at: String,
+    /// Overrides the global sandbox policy when set
+    #[serde(default)]
+    pub sandbox_policy: Option<SandboxPolicy>,
+    /// Project the agent belongs to, None for global agents
+    #[serde(default)]
+    pub project_path: Option<String>,
+    /// Definition file of a project agent, relative to its project
+    #[serde(default)]
+    pub source_file: Option<String>,
+    /// Looser sandbox settings the definition file asks for, applied once the user approves
+    #[serde(default)]
+    pub pending_sandbox: Option<crate::agents::project::SandboxSettings>,
+}
+
+/// Columns `Agent::from_row` expects, in order
+const AGENT_COLUMNS: &str = "id, name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, created_at, updated_at, sandbox_policy, project_path, source_file, pending_sandbox";
+
+impl Agent {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Agent {
+            id: Some(row.get(0)?),
+            name: row.get(1)?,
+            icon: row.get(2)?,
+            system_prompt: row.get(3)?,
+            default_task: row.get(4)?,
+            model: row
+                .get::<_, String>(5)
+                .unwrap_or_else(|_| "sonnet".to_string()),
+            sandbox_enabled: row.get::<_, bool>(6).unwrap_or(true),
+            enable_file_read: row.get::<_, bool>(7).unwrap_or(true),
+            enable_file_write: row.get::<_, bool>(8).unwrap_or(true),
+            enable_network: row.get::<_, bool>(9).unwrap_or(false),
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+            sandbox_policy: SandboxPolicy::from_stored(row.get(12)?),
+            project_path: row.get(13)?,
+            source_file: row.get(14)?,
+            pending_sandbox: crate::agents::project::SandboxSettings::from_stored(row.get(15)?),
+        })
+    }
+
+    /// The agent with the settings it had in an earlier version
+    fn at_version(self, snapshot: crate::agents::versions::AgentSnapshot) -> Self {
+        Agent {
+            name: snapshot.name,
+            icon: snapshot.icon,
+            system_prompt: snapshot.system_prompt,
+            default_task: snapshot.default_task,
+            model: snapshot.model,
+            sandbox_enabled: snapshot.sandbox_enabled,
+            enable_file_read: snapshot.enable_file_read,
+            enable_file_write: snapshot.enable_file_write,
+            enable_network: snapshot.enable_network,
+            sandbox_policy: snapshot.sandbox_policy,
+            ..self
+        }
+    }
+
+    /// The agent as `AgentExport` holds it
+    fn to_data(&self) -> AgentData {
+        AgentData {
+            name: self.name.clone(),
+            icon: self.icon.clone(),
+            system_prompt: self.system_prompt.clone(),
+            default_task: self.default_task.clone(),
+            model: self.model.clone(),
+            sandbox_enabled: self.sandbox_enabled,
+            enable_file_read: self.enable_file_read,
+            enable_file_write: self.enable_file_write,
+            enable_network: self.enable_network,
+        }
+    }
 }
 
 /// Represents an agent execution run
@@ -47,11 +120,60 @@ pub struct AgentRun {
     pub model: String,
     pub project_path: String,
     pub session_id: String, // UUID session ID from Claude Code
-    pub status: String,     // 'pending', 'running', 'completed', 'failed', 'cancelled'
+    pub status: String,     // 'queued', 'pending', 'running', 'completed', 'failed', 'cancelled'
     pub pid: Option<u32>,
     pub process_started_at: Option<String>,
     pub created_at: String,
     pub completed_at: Option<String>,
+    /// Why the run failed, if it did
+    #[serde(default)]
+    pub failure_reason: Option<String>,
+    /// Whether the agent actually ran inside a sandbox
+    #[serde(default)]
+    pub ran_sandboxed: bool,
+    /// Whether the run's memory and process limits were enforced, unknown without any
+    #[serde(default)]
+    pub limits_enforced: Option<bool>,
+    /// Queue priority, higher runs first
+    #[serde(default)]
+    pub priority: i64,
+    /// Version of the agent the run was started with, unknown for runs from before versions
+    #[serde(default)]
+    pub agent_version_id: Option<i64>,
+}
+
+/// Columns `AgentRun::from_row` expects, in order
+const AGENT_RUN_COLUMNS: &str = "id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, failure_reason, ran_sandboxed, priority, agent_version_id, limits_enforced";
+
+impl AgentRun {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(AgentRun {
+            id: Some(row.get(0)?),
+            agent_id: row.get(1)?,
+            agent_name: row.get(2)?,
+            agent_icon: row.get(3)?,
+            task: row.get(4)?,
+            model: row.get(5)?,
+            project_path: row.get(6)?,
+            session_id: row.get(7)?,
+            status: row
+                .get::<_, String>(8)
+                .unwrap_or_else(|_| "pending".to_string()),
+            pid: row
+                .get::<_, Option<i64>>(9)
+                .ok()
+                .flatten()
+                .map(|p| p as u32),
+            process_started_at: row.get(10)?,
+            created_at: row.get(11)?,
+            completed_at: row.get(12)?,
+            failure_reason: row.get(13)?,
+            ran_sandboxed: row.get::<_, bool>(14).unwrap_or(false),
+            priority: row.get::<_, i64>(15).unwrap_or(0),
+            agent_version_id: row.get(16)?,
+            limits_enforced: row.get(17)?,
+        })
+    }
 }
 
 /// Represents runtime metrics calculated from JSONL
@@ -73,7 +195,7 @@ pub struct AgentRunWithMetrics {
 }
 
 /// Agent export format
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentExport {
     pub version: u32,
     pub exported_at: String,
@@ -81,7 +203,7 @@ pub struct AgentExport {
 }
 
 /// Agent data within export
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentData {
     pub name: String,
     pub icon: String,
@@ -168,8 +290,8 @@ impl AgentRunMetrics {
     }
 }
 
-/// Read JSONL content from a session file
-pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<String, String> {
+/// Path of the JSONL file Claude Code writes a session to
+fn session_jsonl_path(session_id: &str, project_path: &str) -> Result<PathBuf, String> {
     let claude_dir = dirs::home_dir()
         .ok_or("Failed to get home directory")?
         .join(".claude")
@@ -178,7 +300,12 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
     // Encode project path to match Claude Code's directory naming
     let encoded_project = project_path.replace('/', "-");
     let project_dir = claude_dir.join(&encoded_project);
-    let session_file = project_dir.join(format!("{}.jsonl", session_id));
+    Ok(project_dir.join(format!("{}.jsonl", session_id)))
+}
+
+/// Read JSONL content from a session file
+pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<String, String> {
+    let session_file = session_jsonl_path(session_id, project_path)?;
 
     if !session_file.exists() {
         return Err(format!(
@@ -215,6 +342,21 @@ pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
     }
 }
 
+/// Columns of `sandbox_violations`, which outlive the profile, agent and run they name
+const SANDBOX_VIOLATIONS_SCHEMA: &str = "
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    profile_id INTEGER,
+    agent_id INTEGER,
+    agent_run_id INTEGER,
+    operation_type TEXT NOT NULL,
+    pattern_value TEXT,
+    process_name TEXT,
+    pid INTEGER,
+    denied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (profile_id) REFERENCES sandbox_profiles(id) ON DELETE SET NULL,
+    FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE SET NULL,
+    FOREIGN KEY (agent_run_id) REFERENCES agent_runs(id) ON DELETE SET NULL";
+
 /// Initialize the agents database
 pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
     let app_dir = app
@@ -271,6 +413,15 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agents ADD COLUMN enable_network BOOLEAN DEFAULT 0",
         [],
     );
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN sandbox_policy TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN project_path TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN source_file TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN pending_sandbox TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agents ADD COLUMN violation_kill_threshold INTEGER",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN removed_at TEXT", []);
 
     // Create agent_runs table
     conn.execute(
@@ -304,6 +455,28 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agent_runs ADD COLUMN process_started_at TEXT",
         [],
     );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN failure_reason TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN ran_sandboxed BOOLEAN NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN priority INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN limits_enforced BOOLEAN", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN queue_rank INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN learning_mode BOOLEAN NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN agent_version_id INTEGER REFERENCES agent_versions(id)",
+        [],
+    );
 
     // Drop old columns that are no longer needed (data is now read from JSONL files)
     // Note: SQLite doesn't support DROP COLUMN, so we'll ignore errors for existing columns
@@ -329,6 +502,42 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
+    // Create agent versions table, a snapshot of the agent on every change
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_versions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            version INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            icon TEXT NOT NULL,
+            system_prompt TEXT NOT NULL,
+            default_task TEXT,
+            model TEXT NOT NULL,
+            sandbox_enabled BOOLEAN NOT NULL,
+            enable_file_read BOOLEAN NOT NULL,
+            enable_file_write BOOLEAN NOT NULL,
+            enable_network BOOLEAN NOT NULL,
+            sandbox_policy TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (agent_id, version),
+            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Runs point at the version they used, so versions never change
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS agent_versions_immutable 
+         BEFORE UPDATE ON agent_versions 
+         BEGIN
+             SELECT RAISE(ABORT, 'agent versions cannot be changed');
+         END",
+        [],
+    )?;
+
+    // Agents from before versions were kept start at version 1 as they are now
+    crate::agents::versions::record_missing_versions(&conn)?;
+
     // Create sandbox profiles table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sandbox_profiles (
@@ -343,6 +552,32 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
+    // Add resource limit columns to sandbox_profiles if they don't exist
+    let _ = conn.execute(
+        "ALTER TABLE sandbox_profiles ADD COLUMN cpu_time_limit_secs INTEGER",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE sandbox_profiles ADD COLUMN memory_limit_mb INTEGER",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE sandbox_profiles ADD COLUMN max_processes INTEGER",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE sandbox_profiles ADD COLUMN max_open_files INTEGER",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE sandbox_profiles ADD COLUMN parent_id INTEGER REFERENCES sandbox_profiles(id)",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE sandbox_profiles ADD COLUMN violation_kill_threshold INTEGER",
+        [],
+    );
+
     // Create sandbox rules table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sandbox_rules (
@@ -372,23 +607,37 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
 
     // Create sandbox violations table
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS sandbox_violations (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            profile_id INTEGER,
-            agent_id INTEGER,
-            agent_run_id INTEGER,
-            operation_type TEXT NOT NULL,
-            pattern_value TEXT,
-            process_name TEXT,
-            pid INTEGER,
-            denied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (profile_id) REFERENCES sandbox_profiles(id) ON DELETE CASCADE,
-            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE,
-            FOREIGN KEY (agent_run_id) REFERENCES agent_runs(id) ON DELETE CASCADE
-        )",
+        &format!(
+            "CREATE TABLE IF NOT EXISTS sandbox_violations ({})",
+            SANDBOX_VIOLATIONS_SCHEMA
+        ),
         [],
     )?;
 
+    // Violations used to be deleted along with their profile, agent or run. Foreign keys
+    // can't be altered in place, so the table is rebuilt.
+    let violations_sql: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'sandbox_violations'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if violations_sql.is_some_and(|sql| sql.contains("ON DELETE CASCADE")) {
+        conn.execute_batch(&format!(
+            "BEGIN;
+             CREATE TABLE sandbox_violations_new ({});
+             INSERT INTO sandbox_violations_new
+                 (id, profile_id, agent_id, agent_run_id, operation_type, pattern_value, process_name, pid, denied_at)
+                 SELECT id, profile_id, agent_id, agent_run_id, operation_type, pattern_value, process_name, pid, denied_at
+                 FROM sandbox_violations;
+             DROP TABLE sandbox_violations;
+             ALTER TABLE sandbox_violations_new RENAME TO sandbox_violations;
+             COMMIT;",
+            SANDBOX_VIOLATIONS_SCHEMA
+        ))?;
+    }
+
     // Create index for efficient querying
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_sandbox_violations_denied_at 
@@ -421,37 +670,44 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
+    // SQLite leaves foreign keys off by default. Agents are only ever marked removed, so
+    // the cascades from them never run, while deleting a profile takes its rules along.
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
     Ok(conn)
 }
 
-/// List all agents
+/// List the global agents, and those of `project_path` synced from its definition files
 #[tauri::command]
-pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
+pub async fn list_agents(
+    db: State<'_, AgentDb>,
+    project_path: Option<String>,
+) -> Result<Vec<Agent>, String> {
+    // Read the files before locking the database, which other commands wait on
+    let agent_files = project_path
+        .as_deref()
+        .map(crate::agents::project::read_project_agents);
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
+    if let (Some(project_path), Some(agent_files)) = (&project_path, agent_files) {
+        // Still list what was synced before when the files can't be read
+        let synced = agent_files.and_then(|agent_files| {
+            crate::agents::project::sync_project_agents(&conn, project_path, agent_files)
+        });
+        if let Err(e) = synced {
+            warn!("Failed to sync the agents of {}: {}", project_path, e);
+        }
+    }
+
     let mut stmt = conn
-        .prepare("SELECT id, name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, created_at, updated_at FROM agents ORDER BY created_at DESC")
+        .prepare(&format!(
+            "SELECT {} FROM agents WHERE removed_at IS NULL AND (project_path IS NULL OR project_path = ?1) ORDER BY created_at DESC",
+            AGENT_COLUMNS
+        ))
         .map_err(|e| e.to_string())?;
 
     let agents = stmt
-        .query_map([], |row| {
-            Ok(Agent {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                icon: row.get(2)?,
-                system_prompt: row.get(3)?,
-                default_task: row.get(4)?,
-                model: row
-                    .get::<_, String>(5)
-                    .unwrap_or_else(|_| "sonnet".to_string()),
-                sandbox_enabled: row.get::<_, bool>(6).unwrap_or(true),
-                enable_file_read: row.get::<_, bool>(7).unwrap_or(true),
-                enable_file_write: row.get::<_, bool>(8).unwrap_or(true),
-                enable_network: row.get::<_, bool>(9).unwrap_or(false),
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-            })
-        })
+        .query_map(params![project_path], Agent::from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -460,6 +716,7 @@ pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
 }
 
 /// Create a new agent
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn create_agent(
     db: State<'_, AgentDb>,
@@ -487,28 +744,14 @@ pub async fn create_agent(
     .map_err(|e| e.to_string())?;
 
     let id = conn.last_insert_rowid();
+    crate::agents::versions::record_version(&conn, id).map_err(|e| e.to_string())?;
 
     // Fetch the created agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    sandbox_enabled: row.get(6)?,
-                    enable_file_read: row.get(7)?,
-                    enable_file_write: row.get(8)?,
-                    enable_network: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| e.to_string())?;
 
@@ -516,6 +759,7 @@ pub async fn create_agent(
 }
 
 /// Update an existing agent
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn update_agent(
     db: State<'_, AgentDb>,
@@ -530,87 +774,142 @@ pub async fn update_agent(
     enable_file_write: Option<bool>,
     enable_network: Option<bool>,
 ) -> Result<Agent, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    let model = model.unwrap_or_else(|| "sonnet".to_string());
+    let current = get_agent(db.clone(), id).await?;
+    let agent = Agent {
+        name,
+        icon,
+        system_prompt,
+        default_task,
+        model: model.unwrap_or_else(|| "sonnet".to_string()),
+        sandbox_enabled: sandbox_enabled.unwrap_or(current.sandbox_enabled),
+        enable_file_read: enable_file_read.unwrap_or(current.enable_file_read),
+        enable_file_write: enable_file_write.unwrap_or(current.enable_file_write),
+        enable_network: enable_network.unwrap_or(current.enable_network),
+        ..current
+    };
+
+    // The file goes first, so a failed write leaves the agent as it was. The user chose
+    // these settings, so nothing is left waiting for approval.
+    let previous_file = write_project_agent_file(&agent)?;
+    let updated = db.0.lock().map_err(|e| e.to_string()).and_then(|conn| {
+        conn.execute(
+            "UPDATE agents SET name = ?1, icon = ?2, system_prompt = ?3, default_task = ?4,
+                 model = ?5, sandbox_enabled = ?6, enable_file_read = ?7, enable_file_write = ?8,
+                 enable_network = ?9, pending_sandbox = NULL
+             WHERE id = ?10",
+            params![
+                agent.name,
+                agent.icon,
+                agent.system_prompt,
+                agent.default_task,
+                agent.model,
+                agent.sandbox_enabled,
+                agent.enable_file_read,
+                agent.enable_file_write,
+                agent.enable_network,
+                id,
+            ],
+        )
+        .and_then(|_| crate::agents::versions::record_version(&conn, id))
+        .map_err(|e| e.to_string())
+    });
+    if let Err(e) = updated {
+        if let Some(previous_file) = previous_file {
+            previous_file.restore();
+        }
+        return Err(e);
+    }
 
-    // Build dynamic query based on provided parameters
-    let mut query =
-        "UPDATE agents SET name = ?1, icon = ?2, system_prompt = ?3, default_task = ?4, model = ?5"
-            .to_string();
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![
-        Box::new(name),
-        Box::new(icon),
-        Box::new(system_prompt),
-        Box::new(default_task),
-        Box::new(model),
-    ];
-    let mut param_count = 5;
-
-    if let Some(se) = sandbox_enabled {
-        param_count += 1;
-        query.push_str(&format!(", sandbox_enabled = ?{}", param_count));
-        params_vec.push(Box::new(se));
-    }
-    if let Some(efr) = enable_file_read {
-        param_count += 1;
-        query.push_str(&format!(", enable_file_read = ?{}", param_count));
-        params_vec.push(Box::new(efr));
-    }
-    if let Some(efw) = enable_file_write {
-        param_count += 1;
-        query.push_str(&format!(", enable_file_write = ?{}", param_count));
-        params_vec.push(Box::new(efw));
-    }
-    if let Some(en) = enable_network {
-        param_count += 1;
-        query.push_str(&format!(", enable_network = ?{}", param_count));
-        params_vec.push(Box::new(en));
-    }
-
-    param_count += 1;
-    query.push_str(&format!(" WHERE id = ?{}", param_count));
-    params_vec.push(Box::new(id));
+    get_agent(db, id).await
+}
 
-    conn.execute(
-        &query,
-        rusqlite::params_from_iter(params_vec.iter().map(|p| p.as_ref())),
-    )
-    .map_err(|e| e.to_string())?;
+/// A project agent's definition file as it was before it was rewritten
+struct PreviousAgentFile {
+    path: PathBuf,
+    contents: Option<Vec<u8>>,
+}
 
-    // Fetch the updated agent
-    let agent = conn
-        .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, created_at, updated_at FROM agents WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    sandbox_enabled: row.get(6)?,
-                    enable_file_read: row.get(7)?,
-                    enable_file_write: row.get(8)?,
-                    enable_network: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+impl PreviousAgentFile {
+    /// Put the file back after the change it was rewritten for failed
+    fn restore(self) {
+        let restored = match &self.contents {
+            Some(contents) => std::fs::write(&self.path, contents),
+            None => std::fs::remove_file(&self.path),
+        };
+        if let Err(e) = restored {
+            warn!("Failed to restore {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Write a project agent to its definition file, so the change reaches the repo
+///
+/// Call before changing the agent in the database, and restore the returned file if that
+/// fails. Global agents have no file and return None.
+fn write_project_agent_file(agent: &Agent) -> Result<Option<PreviousAgentFile>, String> {
+    let (Some(project_path), Some(source_file)) = (&agent.project_path, &agent.source_file) else {
+        return Ok(None);
+    };
+    let path = std::path::Path::new(project_path).join(source_file);
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    crate::agents::project::write_agent_file(&path, &agent.to_data())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(Some(PreviousAgentFile { path, contents }))
+}
+
+/// Set or clear the agent's own sandbox policy, None following the global policy
+#[tauri::command]
+pub async fn set_agent_sandbox_policy(
+    db: State<'_, AgentDb>,
+    id: i64,
+    policy: Option<SandboxPolicy>,
+) -> Result<Agent, String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agents SET sandbox_policy = ?1 WHERE id = ?2",
+            params![policy.map(SandboxPolicy::as_str), id],
         )
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| format!("Failed to save sandbox policy: {}", e))?;
+        crate::agents::versions::record_version(&conn, id).map_err(|e| e.to_string())?;
+    }
 
-    Ok(agent)
+    get_agent(db, id).await
 }
 
 /// Delete an agent
+///
+/// The agent is only marked removed, so its runs, versions and their output stay around.
 #[tauri::command]
 pub async fn delete_agent(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
-    conn.execute("DELETE FROM agents WHERE id = ?1", params![id])
-        .map_err(|e| e.to_string())?;
+    // Project agents come back on the next sync for as long as their file exists
+    let source_file: Option<String> = conn
+        .query_row(
+            "SELECT source_file FROM agents WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    if let Some(source_file) = source_file {
+        return Err(format!(
+            "This agent is defined in the project by {}, delete that file instead",
+            source_file
+        ));
+    }
+
+    conn.execute(
+        "UPDATE agents SET removed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND removed_at IS NULL",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
 
     Ok(())
 }
@@ -622,137 +921,251 @@ pub async fn get_agent(db: State<'_, AgentDb>, id: i64) -> Result<Agent, String>
 
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get::<_, String>(5).unwrap_or_else(|_| "sonnet".to_string()),
-                    sandbox_enabled: row.get::<_, bool>(6).unwrap_or(true),
-                    enable_file_read: row.get::<_, bool>(7).unwrap_or(true),
-                    enable_file_write: row.get::<_, bool>(8).unwrap_or(true),
-                    enable_network: row.get::<_, bool>(9).unwrap_or(false),
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| e.to_string())?;
 
     Ok(agent)
 }
 
-/// List agent runs (optionally filtered by agent_id)
+/// List the versions of an agent, newest first
 #[tauri::command]
-pub async fn list_agent_runs(
+pub async fn list_agent_versions(
     db: State<'_, AgentDb>,
-    agent_id: Option<i64>,
-) -> Result<Vec<AgentRun>, String> {
+    agent_id: i64,
+) -> Result<Vec<crate::agents::versions::AgentVersion>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::agents::versions::list_versions(&conn, agent_id).map_err(|e| e.to_string())
+}
 
-    let query = if agent_id.is_some() {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC"
-    } else {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs ORDER BY created_at DESC"
-    };
+/// Get a single version of an agent, such as the one a run used
+#[tauri::command]
+pub async fn get_agent_version(
+    db: State<'_, AgentDb>,
+    version_id: i64,
+) -> Result<crate::agents::versions::AgentVersion, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::agents::versions::get_version(&conn, version_id)
+        .map_err(|e| format!("Failed to load agent version {}: {}", version_id, e))
+}
 
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+/// Compare two versions of an agent
+#[tauri::command]
+pub async fn diff_agent_versions(
+    db: State<'_, AgentDb>,
+    from_version_id: i64,
+    to_version_id: i64,
+) -> Result<crate::agents::versions::VersionDiff, String> {
+    use crate::agents::versions;
 
-    let run_mapper = |row: &rusqlite::Row| -> rusqlite::Result<AgentRun> {
-        Ok(AgentRun {
-            id: Some(row.get(0)?),
-            agent_id: row.get(1)?,
-            agent_name: row.get(2)?,
-            agent_icon: row.get(3)?,
-            task: row.get(4)?,
-            model: row.get(5)?,
-            project_path: row.get(6)?,
-            session_id: row.get(7)?,
-            status: row
-                .get::<_, String>(8)
-                .unwrap_or_else(|_| "pending".to_string()),
-            pid: row
-                .get::<_, Option<i64>>(9)
-                .ok()
-                .flatten()
-                .map(|p| p as u32),
-            process_started_at: row.get(10)?,
-            created_at: row.get(11)?,
-            completed_at: row.get(12)?,
-        })
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let load = |version_id| {
+        versions::get_version(&conn, version_id)
+            .map_err(|e| format!("Failed to load agent version {}: {}", version_id, e))
     };
-
-    let runs = if let Some(aid) = agent_id {
-        stmt.query_map(params![aid], run_mapper)
-    } else {
-        stmt.query_map(params![], run_mapper)
+    let (from, to) = (load(from_version_id)?, load(to_version_id)?);
+    if from.agent_id != to.agent_id {
+        return Err("Only versions of the same agent can be compared".to_string());
     }
-    .map_err(|e| e.to_string())?
-    .collect::<Result<Vec<_>, _>>()
-    .map_err(|e| e.to_string())?;
 
-    Ok(runs)
+    Ok(versions::diff_versions(&from, &to))
 }
 
-/// Get a single agent run by ID
+/// Roll an agent back to an earlier version, recorded as a new version
 #[tauri::command]
-pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub async fn rollback_agent(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    version_id: i64,
+) -> Result<Agent, String> {
+    let version = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::agents::versions::get_version(&conn, version_id)
+            .map_err(|e| format!("Failed to load agent version {}: {}", version_id, e))?
+    };
+    if version.agent_id != agent_id {
+        return Err(format!(
+            "Version {} is not a version of agent {}",
+            version_id, agent_id
+        ));
+    }
 
-    let run = conn
-        .query_row(
-            "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-             FROM agent_runs WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(AgentRun {
-                    id: Some(row.get(0)?),
-                    agent_id: row.get(1)?,
-                    agent_name: row.get(2)?,
-                    agent_icon: row.get(3)?,
-                    task: row.get(4)?,
-                    model: row.get(5)?,
-                    project_path: row.get(6)?,
-                    session_id: row.get(7)?,
-                    status: row.get::<_, String>(8).unwrap_or_else(|_| "pending".to_string()),
-                    pid: row.get::<_, Option<i64>>(9).ok().flatten().map(|p| p as u32),
-                    process_started_at: row.get(10)?,
-                    created_at: row.get(11)?,
-                    completed_at: row.get(12)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+    // As with edits, the file goes first and is put back if the database can't follow
+    let current = get_agent(db.clone(), agent_id).await?;
+    let previous_file = write_project_agent_file(&current.at_version(version.snapshot))?;
+    let rolled_back = db.0.lock().map_err(|e| e.to_string()).and_then(|conn| {
+        crate::agents::versions::rollback(&conn, agent_id, version_id)
+            .map_err(|e| format!("Failed to roll back agent {}: {}", agent_id, e))
+    });
+    if let Err(e) = rolled_back {
+        if let Some(previous_file) = previous_file {
+            previous_file.restore();
+        }
+        return Err(e);
+    }
 
-    Ok(run)
+    info!("Rolled agent {} back to version {}", agent_id, version_id);
+    get_agent(db, agent_id).await
 }
 
-/// Get agent run with real-time metrics from JSONL
+/// Sync the agents of a project with its definition files, reporting name conflicts
 #[tauri::command]
-pub async fn get_agent_run_with_real_time_metrics(
+pub async fn sync_project_agents(
     db: State<'_, AgentDb>,
-    id: i64,
-) -> Result<AgentRunWithMetrics, String> {
-    let run = get_agent_run(db, id).await?;
-    Ok(get_agent_run_with_metrics(run).await)
+    project_path: String,
+) -> Result<crate::agents::project::ProjectAgentSync, String> {
+    let agent_files = crate::agents::project::read_project_agents(&project_path)?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::agents::project::sync_project_agents(&conn, &project_path, agent_files)
 }
 
-/// List agent runs with real-time metrics from JSONL
+/// Give a project agent the looser sandbox settings its definition file asks for
+///
+/// `settings` are the ones the user was shown, and are only applied while the file still
+/// asks for them.
 #[tauri::command]
-pub async fn list_agent_runs_with_metrics(
+pub async fn approve_agent_sandbox(
     db: State<'_, AgentDb>,
-    agent_id: Option<i64>,
-) -> Result<Vec<AgentRunWithMetrics>, String> {
-    let runs = list_agent_runs(db, agent_id).await?;
-    let mut runs_with_metrics = Vec::new();
-
-    for run in runs {
+    id: i64,
+    settings: crate::agents::project::SandboxSettings,
+) -> Result<Agent, String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let approved = crate::agents::project::approve_sandbox(&conn, id, settings)
+            .map_err(|e| format!("Failed to approve the sandbox settings: {}", e))?;
+        if !approved {
+            return Err(
+                "The definition file changed its sandbox settings, review them again".to_string(),
+            );
+        }
+    }
+
+    info!("Approved the sandbox settings of agent {}", id);
+    get_agent(db, id).await
+}
+
+/// Share an agent with a project by writing its definition file there
+///
+/// Returns the project agent synced from the new file. The original agent is left as it is.
+#[tauri::command]
+pub async fn add_agent_to_project(
+    db: State<'_, AgentDb>,
+    id: i64,
+    project_path: String,
+) -> Result<Agent, String> {
+    use crate::agents::project;
+
+    let agent = get_agent(db.clone(), id).await?;
+    let source_file = format!(
+        "{}/{}",
+        project::PROJECT_AGENTS_DIR,
+        project::file_name_for(&agent.name)
+    );
+    let path = std::path::Path::new(&project_path).join(&source_file);
+    if path.exists() {
+        return Err(format!(
+            "{} already exists in the project, rename the agent or remove the file first",
+            source_file
+        ));
+    }
+    let data = agent.to_data();
+    project::write_agent_file(&path, &data)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let agent_files = project::read_project_agents(&project_path)?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    project::sync_project_agents(&conn, &project_path, agent_files)?;
+    let id = conn
+        .query_row(
+            "SELECT id FROM agents WHERE project_path = ?1 AND source_file = ?2",
+            params![project_path, source_file],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| format!("Failed to fetch the project agent: {}", e))?;
+
+    // Sharing the agent is the user's choice, so its sandbox settings come along as they are
+    project::approve_sandbox(&conn, id, project::SandboxSettings::of(&data))
+        .map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
+        params![id],
+        Agent::from_row,
+    )
+    .map_err(|e| format!("Failed to fetch the project agent: {}", e))
+}
+
+/// List agent runs (optionally filtered by agent_id)
+#[tauri::command]
+pub async fn list_agent_runs(
+    db: State<'_, AgentDb>,
+    agent_id: Option<i64>,
+) -> Result<Vec<AgentRun>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    let query = if agent_id.is_some() {
+        format!(
+            "SELECT {} FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC",
+            AGENT_RUN_COLUMNS
+        )
+    } else {
+        format!(
+            "SELECT {} FROM agent_runs ORDER BY created_at DESC",
+            AGENT_RUN_COLUMNS
+        )
+    };
+
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    let runs = if let Some(aid) = agent_id {
+        stmt.query_map(params![aid], AgentRun::from_row)
+    } else {
+        stmt.query_map(params![], AgentRun::from_row)
+    }
+    .map_err(|e| e.to_string())?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| e.to_string())?;
+
+    Ok(runs)
+}
+
+/// Get a single agent run by ID
+#[tauri::command]
+pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    let run = conn
+        .query_row(
+            &format!("SELECT {} FROM agent_runs WHERE id = ?1", AGENT_RUN_COLUMNS),
+            params![id],
+            AgentRun::from_row,
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(run)
+}
+
+/// Get agent run with real-time metrics from JSONL
+#[tauri::command]
+pub async fn get_agent_run_with_real_time_metrics(
+    db: State<'_, AgentDb>,
+    id: i64,
+) -> Result<AgentRunWithMetrics, String> {
+    let run = get_agent_run(db, id).await?;
+    Ok(get_agent_run_with_metrics(run).await)
+}
+
+/// List agent runs with real-time metrics from JSONL
+#[tauri::command]
+pub async fn list_agent_runs_with_metrics(
+    db: State<'_, AgentDb>,
+    agent_id: Option<i64>,
+) -> Result<Vec<AgentRunWithMetrics>, String> {
+    let runs = list_agent_runs(db, agent_id).await?;
+    let mut runs_with_metrics = Vec::new();
+
+    for run in runs {
         let run_with_metrics = get_agent_run_with_metrics(run).await;
         runs_with_metrics.push(run_with_metrics);
     }
@@ -760,7 +1173,8 @@ pub async fn list_agent_runs_with_metrics(
     Ok(runs_with_metrics)
 }
 
-/// Execute a CC agent with streaming output
+/// Queue a run of a CC agent, starting it right away if there is a free slot
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn execute_agent(
     app: AppHandle,
@@ -768,8 +1182,9 @@ pub async fn execute_agent(
     project_path: String,
     task: String,
     model: Option<String>,
+    learning_mode: Option<bool>,
+    priority: Option<i64>,
     db: State<'_, AgentDb>,
-    registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
     info!("Executing agent {} with task: {}", agent_id, task);
 
@@ -777,21 +1192,257 @@ pub async fn execute_agent(
     let agent = get_agent(db.clone(), agent_id).await?;
     let execution_model = model.unwrap_or(agent.model.clone());
 
-    // Create a new run record
+    // Learning runs are unrestricted but traced, to propose a profile from what they touch
+    let learning_mode = learning_mode.unwrap_or(false);
+    if learning_mode && !crate::sandbox::learning::is_tracing_available() {
+        return Err("Learning runs need strace, which is not available on this system".to_string());
+    }
+
+    // Create a new run record, waiting in the queue for a slot
     let run_id = {
         let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let removed: bool = conn
+            .query_row(
+                "SELECT removed_at IS NOT NULL FROM agents WHERE id = ?1",
+                params![agent_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if removed {
+            return Err(format!("Agent '{}' was removed", agent.name));
+        }
+        let version_id = crate::agents::versions::record_version(&conn, agent_id)
+            .map_err(|e| format!("Failed to record the agent version: {}", e))?;
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, priority, learning_mode, agent_version_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'queued', ?8, ?9, ?10)",
+            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, "", priority.unwrap_or(0), learning_mode, version_id],
+        )
+        .map_err(|e| e.to_string())?;
+        let run_id = conn.last_insert_rowid();
         conn.execute(
-            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
+            "UPDATE agent_runs SET queue_rank = id WHERE id = ?1",
+            params![run_id],
         )
         .map_err(|e| e.to_string())?;
-        conn.last_insert_rowid()
+        run_id
+    };
+    let _ = app.emit("agent-queue-changed", run_id);
+
+    // Start this run here so that failing to start it is reported to the caller
+    let mut start_now = false;
+    for run in claim_queued_runs(&app) {
+        if run.run_id == run_id {
+            start_now = true;
+        } else {
+            tauri::async_runtime::spawn(start_claimed_run(app.clone(), run.run_id));
+        }
+    }
+    if start_now {
+        start_claimed_run(app, run_id).await?;
+    } else {
+        info!("⏳ Run {} is queued until a slot frees up", run_id);
+    }
+
+    Ok(run_id)
+}
+
+/// Take slots for queued runs, marking them as no longer queued
+fn claim_queued_runs(app: &AppHandle) -> Vec<crate::process::queue::QueuedRun> {
+    let db = app.state::<AgentDb>();
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+
+    let (queue, limits) = match db.0.lock() {
+        Ok(conn) => match crate::process::queue::list_queued(&conn) {
+            Ok(queue) => (queue, crate::process::queue::load_limits(&conn)),
+            Err(e) => {
+                error!("Failed to read the run queue: {}", e);
+                return Vec::new();
+            }
+        },
+        Err(e) => {
+            error!("Failed to read the run queue: {}", e);
+            return Vec::new();
+        }
+    };
+    if queue.is_empty() {
+        return Vec::new();
+    }
+
+    let claimed = match registry.0.claim_slots(queue, &limits) {
+        Ok(claimed) => claimed,
+        Err(e) => {
+            error!("Failed to claim slots for queued runs: {}", e);
+            return Vec::new();
+        }
+    };
+    if let Ok(conn) = db.0.lock() {
+        for run in &claimed {
+            let _ = conn.execute(
+                "UPDATE agent_runs SET status = 'pending' WHERE id = ?1 AND status = 'queued'",
+                params![run.run_id],
+            );
+        }
+    }
+    if !claimed.is_empty() {
+        let _ = app.emit("agent-queue-changed", claimed.len());
+    }
+    claimed
+}
+
+/// Start queued runs for as long as there are free slots
+fn dispatch_queued_runs(app: &AppHandle) {
+    for run in claim_queued_runs(app) {
+        info!(
+            "▶️ Starting queued run {} of '{}'",
+            run.run_id, run.agent_name
+        );
+        tauri::async_runtime::spawn(start_claimed_run(app.clone(), run.run_id));
+    }
+}
+
+/// Start a run holding a slot, giving the slot back if it doesn't start
+async fn start_claimed_run(app: AppHandle, run_id: i64) -> Result<(), String> {
+    let result = start_run(app.clone(), run_id).await;
+    if let Err(e) = &result {
+        if let Ok(conn) = app.state::<AgentDb>().0.lock() {
+            let _ = conn.execute(
+                "UPDATE agent_runs SET status = 'failed', failure_reason = ?1, completed_at = CURRENT_TIMESTAMP WHERE id = ?2 AND status = 'pending'",
+                params![e, run_id],
+            );
+        }
+        let registry = app.state::<crate::process::ProcessRegistryState>();
+        let _ = registry.0.release_slot(run_id);
+        let _ = app.emit(&format!("agent-complete:{}", run_id), false);
+        dispatch_queued_runs(&app);
+    }
+    result
+}
+
+/// Give up the slot of a run whose process is done and start the next queued runs
+fn finish_run(app: &AppHandle, registry: &crate::process::ProcessRegistry, run_id: i64) {
+    let _ = registry.unregister_process(run_id);
+    dispatch_queued_runs(app);
+}
+
+/// Directory the full output of every run is written to
+fn run_output_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?
+        .join("agent-output"))
+}
+
+/// Output buffer for a run, written to disk as well where possible
+fn open_run_output(app: &AppHandle, run_id: i64) -> crate::process::output::RunOutput {
+    let cap_bytes = match app.state::<AgentDb>().0.lock() {
+        Ok(conn) => crate::process::output::load_live_output_cap(&conn),
+        Err(_) => crate::process::output::DEFAULT_LIVE_OUTPUT_CAP,
+    };
+    let spilled = run_output_dir(app).and_then(|dir| {
+        crate::process::output::RunOutput::with_spill(&dir, run_id, cap_bytes)
+            .map_err(|e| e.to_string())
+    });
+    spilled.unwrap_or_else(|e| {
+        warn!("Keeping the output of run {} in memory only: {}", run_id, e);
+        crate::process::output::RunOutput::new(cap_bytes)
+    })
+}
+
+/// Spawn the process of a run that left the queue, with streaming output
+async fn start_run(app: AppHandle, run_id: i64) -> Result<(), String> {
+    let handle = app.clone();
+    let db = handle.state::<AgentDb>();
+    let registry = handle.state::<crate::process::ProcessRegistryState>();
+
+    let (agent_id, project_path, task, execution_model, learning_mode, version_id) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT agent_id, project_path, task, model, learning_mode, agent_version_id FROM agent_runs WHERE id = ?1",
+            params![run_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, bool>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
     };
+    let mut agent = get_agent(db.clone(), agent_id).await?;
+
+    // Run the agent as it was when the run was queued, not as it was edited since
+    if let Some(version_id) = version_id {
+        let version = {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            crate::agents::versions::get_version(&conn, version_id)
+        };
+        match version {
+            Ok(version) => agent = agent.at_version(version.snapshot),
+            Err(e) => {
+                return Err(fail_run(
+                    &db,
+                    run_id,
+                    format!("Failed to load version {} of the agent: {}", version_id, e),
+                ))
+            }
+        }
+    }
+    info!("Starting run {} of agent '{}'", run_id, agent.name);
+
+    // With a required sandbox, runs that can't be sandboxed fail instead of running openly
+    let sandbox_policy = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::sandbox::policy::effective_policy(&conn, agent.sandbox_policy)
+    };
+    let sandboxing_supported =
+        crate::sandbox::platform::get_platform_capabilities().sandboxing_supported;
+    if sandbox_policy == SandboxPolicy::Required {
+        let reason = if learning_mode {
+            Some("Learning runs are not sandboxed".to_string())
+        } else if !agent.sandbox_enabled {
+            Some(format!("Sandboxing is disabled for agent '{}'", agent.name))
+        } else if !sandboxing_supported {
+            Some(format!(
+                "Sandboxing is not supported on {}",
+                std::env::consts::OS
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(fail_run(
+                &db,
+                run_id,
+                format!("{}, but the sandbox policy requires a sandbox", reason),
+            ));
+        }
+    }
 
     // Create sandbox rules based on agent-specific permissions (no database dependency)
-    let sandbox_profile = if !agent.sandbox_enabled {
+    let sandbox_profile = if learning_mode {
+        info!(
+            "🔍 Agent '{}': Learning run, tracing accesses without sandbox",
+            agent.name
+        );
+        None
+    } else if sandbox_policy == SandboxPolicy::Off {
+        info!("🔓 Agent '{}': Sandbox policy is off", agent.name);
+        None
+    } else if !agent.sandbox_enabled {
         info!("🔓 Agent '{}': Sandbox DISABLED", agent.name);
         None
+    } else if !sandboxing_supported {
+        warn!(
+            "🔓 Agent '{}': Sandboxing is not supported on {}, running unsandboxed",
+            agent.name,
+            std::env::consts::OS
+        );
+        None
     } else {
         info!(
             "🔒 Agent '{}': Sandbox enabled | File Read: {} | File Write: {} | Network: {}",
@@ -955,107 +1606,42 @@ pub async fn execute_agent(
             created_at: String::new(),
         });
 
-        Some(("Agent-specific".to_string(), rules))
-    };
-
-    // Build the command
-    let mut cmd = if let Some((_profile_name, rules)) = sandbox_profile {
-        info!("🧪 DEBUG: Testing Claude command first without sandbox...");
-        // Quick test to see if Claude is accessible at all
-        let claude_path = match find_claude_binary(&app) {
-            Ok(path) => path,
-            Err(e) => {
-                error!("❌ Claude binary not found: {}", e);
-                return Err(e);
-            }
-        };
-        match std::process::Command::new(&claude_path)
-            .arg("--version")
-            .output()
+        // Host allow-lists and the environment policy come from the agent's sandbox profile
         {
-            Ok(output) => {
-                if output.status.success() {
-                    info!(
-                        "✅ Claude command works: {}",
-                        String::from_utf8_lossy(&output.stdout).trim()
-                    );
-                } else {
-                    warn!("⚠️ Claude command failed with status: {}", output.status);
-                    warn!("   stdout: {}", String::from_utf8_lossy(&output.stdout));
-                    warn!("   stderr: {}", String::from_utf8_lossy(&output.stderr));
-                }
-            }
-            Err(e) => {
-                error!("❌ Claude command not found or not executable: {}", e);
-                error!("   This could be why the agent is failing to start");
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            if agent.enable_network {
+                rules.extend(load_domain_rules(&conn, agent_id));
             }
+            rules.extend(load_environment_rules(&conn, agent_id));
         }
 
-        // Test if Claude can actually start a session (this might reveal auth issues)
-        info!("🧪 Testing Claude with exact same arguments as agent (without sandbox env vars)...");
-        let mut test_cmd = std::process::Command::new(&claude_path);
-        test_cmd
-            .arg("-p")
-            .arg(&task)
-            .arg("--system-prompt")
-            .arg(&agent.system_prompt)
-            .arg("--model")
-            .arg(&execution_model)
-            .arg("--output-format")
-            .arg("stream-json")
-            .arg("--verbose")
-            .arg("--dangerously-skip-permissions")
-            .current_dir(&project_path);
-
-        info!("🧪 Testing command: claude -p \"{}\" --system-prompt \"{}\" --model {} --output-format stream-json --verbose --dangerously-skip-permissions", 
-              task, agent.system_prompt, execution_model);
-
-        // Start the test process and give it 5 seconds to produce output
-        match test_cmd.spawn() {
-            Ok(mut child) => {
-                // Wait for 5 seconds to see if it produces output
-                let start = std::time::Instant::now();
-                let mut output_received = false;
-
-                while start.elapsed() < std::time::Duration::from_secs(5) {
-                    match child.try_wait() {
-                        Ok(Some(status)) => {
-                            info!("🧪 Test process exited with status: {}", status);
-                            output_received = true;
-                            break;
-                        }
-                        Ok(None) => {
-                            // Still running
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                        }
-                        Err(e) => {
-                            warn!("🧪 Error checking test process: {}", e);
-                            break;
-                        }
-                    }
-                }
+        Some(("Agent-specific".to_string(), rules))
+    };
 
-                if !output_received {
-                    warn!("🧪 Test process is still running after 5 seconds - this suggests Claude might be waiting for input");
-                    // Kill the test process
-                    let _ = child.kill();
-                    let _ = child.wait();
-                } else {
-                    info!("🧪 Test process completed quickly - command seems to work");
-                }
-            }
-            Err(e) => {
-                error!("❌ Failed to spawn test Claude process: {}", e);
-            }
-        }
+    let db_path = app
+        .path()
+        .app_data_dir()
+        .expect("Failed to get app data dir")
+        .join("agents.db");
 
-        info!("🧪 End of Claude test, proceeding with sandbox...");
+    // Build the command
+    let mut run_sandboxed = false;
+    let mut violation_monitor = None;
+    let mut egress_proxy = None;
+    let mut run_cgroup = None;
+    let mut limits_enforced = None;
+    let mut cmd = if let Some((_profile_name, rules)) = sandbox_profile {
+        let claude_path = find_claude_binary(&app).map_err(|e| fail_run(&db, run_id, e))?;
 
         // Build the gaol profile using agent-specific permissions
         let project_path_buf = PathBuf::from(&project_path);
 
         match ProfileBuilder::new(project_path_buf.clone()) {
             Ok(builder) => {
+                // Claude's own state and install stay reachable whatever the permissions
+                let builder = builder.with_runtime_rules(
+                    crate::sandbox::profile::claude_runtime_rules(&claude_path),
+                );
                 // Build agent-specific profile with permission filtering
                 match builder.build_agent_profile(
                     rules,
@@ -1065,6 +1651,38 @@ pub async fn execute_agent(
                     agent.enable_network,
                 ) {
                     Ok(build_result) => {
+                        let allowed_domains = build_result.serialized.allowed_domains();
+                        let env_policy = build_result.env_policy;
+
+                        // Record denials the commands of the sandboxed run report on their
+                        // stderr, and the hosts the egress proxy blocks. Tool results in the
+                        // transcript and Claude's own stderr are model-controlled text and
+                        // could be made to look like denials.
+                        let monitor = {
+                            let conn = db.0.lock().map_err(|e| e.to_string())?;
+                            let profile_id = assigned_profile_id(&conn, agent_id);
+                            let kill_threshold = crate::sandbox::violations::resolve_kill_threshold(
+                                &conn, agent_id, profile_id,
+                            );
+                            std::sync::Arc::new(Mutex::new(
+                                crate::sandbox::violations::ViolationMonitor::new(
+                                    db_path.clone(),
+                                    crate::sandbox::violations::ViolationContext {
+                                        profile_id,
+                                        agent_id: Some(agent_id),
+                                        agent_run_id: Some(run_id),
+                                        pid: None,
+                                    },
+                                )
+                                .with_kill_threshold(
+                                    kill_threshold,
+                                    build_result.serialized.clone(),
+                                    project_path_buf.clone(),
+                                ),
+                            ))
+                        };
+                        violation_monitor = Some(monitor.clone());
+
                         // Create the enhanced sandbox executor
                         #[cfg(unix)]
                         let executor =
@@ -1096,71 +1714,80 @@ pub async fn execute_agent(
                             "--dangerously-skip-permissions",
                         ];
 
-                        let claude_path = match find_claude_binary(&app) {
-                            Ok(path) => path,
-                            Err(e) => {
-                                error!("Failed to find claude binary: {}", e);
-                                return Err(e);
-                            }
+                        // Force network traffic through a proxy enforcing the allow-list
+                        let executor = if allowed_domains.is_empty() {
+                            executor
+                        } else {
+                            let proxy = start_egress_proxy(
+                                &app,
+                                run_id,
+                                allowed_domains,
+                                monitor,
+                                db_path.clone(),
+                            )
+                            .await
+                            .map_err(|e| fail_run(&db, run_id, e))?;
+                            let executor = executor.with_egress_proxy(proxy.url());
+                            egress_proxy = Some(proxy);
+                            executor
                         };
-                        executor.prepare_sandboxed_command(&claude_path, &args, &project_path_buf)
-                    }
-                    Err(e) => {
-                        error!("Failed to build agent-specific sandbox profile: {}, falling back to non-sandboxed", e);
-                        let claude_path = match find_claude_binary(&app) {
-                            Ok(path) => path,
-                            Err(e) => {
-                                error!("Failed to find claude binary: {}", e);
-                                return Err(e);
+
+                        let executor = executor.with_env_policy(env_policy);
+
+                        // Cap the run's resources, in a cgroup of its own where possible
+                        let resource_limits = {
+                            let conn = db.0.lock().map_err(|e| e.to_string())?;
+                            load_resource_limits(&conn, agent_id)
+                        };
+                        let executor = if resource_limits.is_unlimited() {
+                            executor
+                        } else {
+                            run_cgroup = create_run_cgroup(run_id, &resource_limits);
+                            let enforced =
+                                !resource_limits.needs_cgroup() || run_cgroup.is_some();
+                            if !enforced && sandbox_policy == SandboxPolicy::Required {
+                                return Err(fail_run(
+                                    &db,
+                                    run_id,
+                                    "Memory and process limits can't be enforced, but the sandbox policy requires a sandbox".to_string(),
+                                ));
                             }
+                            limits_enforced = Some(enforced);
+                            executor.with_resource_limits(resource_limits, run_cgroup.as_ref())
                         };
-                        let mut cmd = create_command_with_env(&claude_path);
-                        cmd.arg("-p")
-                            .arg(&task)
-                            .arg("--system-prompt")
-                            .arg(&agent.system_prompt)
-                            .arg("--model")
-                            .arg(&execution_model)
-                            .arg("--output-format")
-                            .arg("stream-json")
-                            .arg("--verbose")
-                            .arg("--dangerously-skip-permissions")
-                            .current_dir(&project_path)
-                            .stdout(Stdio::piped())
-                            .stderr(Stdio::piped());
+                        let (cmd, backend) = executor.prepare_sandboxed_command(
+                            &claude_path,
+                            &args,
+                            &project_path_buf,
+                        );
+                        match backend {
+                            Some(backend) => info!("🔒 Run {} is sandboxed by {}", run_id, backend),
+                            None => {
+                                return Err(fail_run(
+                                    &db,
+                                    run_id,
+                                    "No sandbox backend could restrict the run".to_string(),
+                                ));
+                            }
+                        }
+                        run_sandboxed = true;
                         cmd
                     }
+                    Err(e) => {
+                        return Err(fail_run(
+                            &db,
+                            run_id,
+                            format!("Failed to build sandbox profile: {}", e),
+                        ));
+                    }
                 }
             }
             Err(e) => {
-                error!(
-                    "Failed to create ProfileBuilder: {}, falling back to non-sandboxed",
-                    e
-                );
-
-                // Fall back to non-sandboxed command
-                let claude_path = match find_claude_binary(&app) {
-                    Ok(path) => path,
-                    Err(e) => {
-                        error!("Failed to find claude binary: {}", e);
-                        return Err(e);
-                    }
-                };
-                let mut cmd = create_command_with_env(&claude_path);
-                cmd.arg("-p")
-                    .arg(&task)
-                    .arg("--system-prompt")
-                    .arg(&agent.system_prompt)
-                    .arg("--model")
-                    .arg(&execution_model)
-                    .arg("--output-format")
-                    .arg("stream-json")
-                    .arg("--verbose")
-                    .arg("--dangerously-skip-permissions")
-                    .current_dir(&project_path)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped());
-                cmd
+                return Err(fail_run(
+                    &db,
+                    run_id,
+                    format!("Failed to set up the sandbox: {}", e),
+                ));
             }
         }
     } else {
@@ -1169,14 +1796,22 @@ pub async fn execute_agent(
             "🚨 Running agent '{}' WITHOUT SANDBOX - full system access!",
             agent.name
         );
-        let claude_path = match find_claude_binary(&app) {
-            Ok(path) => path,
-            Err(e) => {
-                error!("Failed to find claude binary: {}", e);
-                return Err(e);
+        let claude_path = find_claude_binary(&app).map_err(|e| fail_run(&db, run_id, e))?;
+        let mut cmd = if learning_mode {
+            let app_dir = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+            let trace_path = crate::sandbox::learning::trace_path(&app_dir, run_id);
+            if let Some(parent) = trace_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create learning directory: {}", e))?;
             }
+            info!("🔍 Tracing learning run to {:?}", trace_path);
+            create_traced_command(&claude_path, &trace_path)
+        } else {
+            create_command_with_env(&claude_path)
         };
-        let mut cmd = create_command_with_env(&claude_path);
         cmd.arg("-p")
             .arg(&task)
             .arg("--system-prompt")
@@ -1194,11 +1829,32 @@ pub async fn execute_agent(
         cmd
     };
 
+    // Give the run a process group of its own, to cancel the tools it starts along with it
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    // Violations are read from the stderr of the commands the run's tools execute
+    #[cfg(unix)]
+    let tool_stderr = if run_sandboxed {
+        match crate::sandbox::capture::ToolStderrCapture::new() {
+            Ok(capture) => {
+                capture.attach(&mut cmd);
+                Some(capture)
+            }
+            Err(e) => {
+                warn!("Failed to capture tool stderr, violations won't be recorded: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     // Spawn the process
     info!("🚀 Spawning Claude process...");
     let mut child = cmd.spawn().map_err(|e| {
         error!("❌ Failed to spawn Claude process: {}", e);
-        format!("Failed to spawn Claude: {}", e)
+        fail_run(&db, run_id, format!("Failed to spawn Claude: {}", e))
     })?;
 
     info!("🔌 Using Stdio::null() for stdin - no input expected");
@@ -1212,12 +1868,20 @@ pub async fn execute_agent(
     {
         let conn = db.0.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
-            params![pid as i64, now, run_id],
+            "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2, ran_sandboxed = ?3, limits_enforced = ?4 WHERE id = ?5",
+            params![pid as i64, now, run_sandboxed, limits_enforced, run_id],
         ).map_err(|e| e.to_string())?;
         info!("📝 Updated database with running status and PID");
     }
 
+    // Violations recorded from here on are attributed to the Claude process
+    let violation_monitor = violation_monitor.filter(|_| run_sandboxed);
+    if let Some(monitor) = &violation_monitor {
+        if let Ok(mut monitor) = monitor.lock() {
+            monitor.set_pid(pid as i32);
+        }
+    }
+
     // Get stdout and stderr
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
     let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
@@ -1227,20 +1891,39 @@ pub async fn execute_agent(
     let stdout_reader = BufReader::new(stdout);
     let stderr_reader = BufReader::new(stderr);
 
-    // Shared state for collecting session ID and live output
+    // Register the process before reading its output, so that none of it is missed
+    registry
+        .0
+        .register_process(
+            run_id,
+            agent_id,
+            agent.name.clone(),
+            pid,
+            project_path.clone(),
+            task.clone(),
+            execution_model.clone(),
+            child,
+            open_run_output(&app, run_id),
+        )
+        .map_err(|e| format!("Failed to register process: {}", e))?;
+    info!("📋 Registered process in registry");
+
+    // Shared state for collecting session ID
     let session_id = std::sync::Arc::new(Mutex::new(String::new()));
-    let live_output = std::sync::Arc::new(Mutex::new(String::new()));
     let start_time = std::time::Instant::now();
 
     // Spawn tasks to read stdout and stderr
     let app_handle = app.clone();
     let session_id_clone = session_id.clone();
-    let live_output_clone = live_output.clone();
     let registry_clone = registry.0.clone();
     let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_output_clone = first_output.clone();
+    let db_path_stdout = db_path.clone();
 
     let stdout_task = tokio::spawn(async move {
+        // Keep the egress proxy up for as long as the child is producing output
+        let _egress_proxy = egress_proxy;
+        let _run_cgroup = run_cgroup;
         info!("📖 Starting to read Claude stdout...");
         let mut lines = stdout_reader.lines();
         let mut line_count = 0;
@@ -1263,14 +1946,12 @@ pub async fn execute_agent(
                 debug!("stdout[{}]: {}", line_count, line);
             }
 
-            // Store live output in both local buffer and registry
-            if let Ok(mut output) = live_output_clone.lock() {
-                output.push_str(&line);
-                output.push('\n');
-            }
-
-            // Also store in process registry for cross-session access
-            let _ = registry_clone.append_live_output(run_id, &line);
+            // Store in process registry for cross-session access, and on disk
+            let _ = registry_clone.append_output(
+                run_id,
+                crate::process::output::OutputStream::Stdout,
+                &line,
+            );
 
             // Extract session ID from JSONL output
             if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
@@ -1279,6 +1960,13 @@ pub async fn execute_agent(
                         if current_session_id.is_empty() {
                             *current_session_id = sid.to_string();
                             info!("🔑 Extracted session ID: {}", sid);
+                            // Record it right away, to find the session again after a restart
+                            if let Ok(conn) = Connection::open(&db_path_stdout) {
+                                let _ = conn.execute(
+                                    "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+                                    params![sid, run_id],
+                                );
+                            }
                         }
                     }
                 }
@@ -1296,9 +1984,35 @@ pub async fn execute_agent(
         );
     });
 
+    #[cfg(unix)]
+    let tool_stderr_task = match (tool_stderr, violation_monitor) {
+        (Some(capture), Some(monitor)) => match capture.into_reader() {
+            Ok((receiver, script)) => Some(tokio::spawn(read_tool_stderr(
+                app.clone(),
+                registry.0.clone(),
+                receiver,
+                script,
+                monitor,
+                db_path.clone(),
+                run_id,
+            ))),
+            Err(e) => {
+                warn!("Failed to read tool stderr, violations won't be recorded: {}", e);
+                None
+            }
+        },
+        _ => None,
+    };
+    #[cfg(not(unix))]
+    let tool_stderr_task: Option<tokio::task::JoinHandle<()>> = {
+        drop(violation_monitor);
+        None
+    };
+
     let app_handle_stderr = app.clone();
     let first_error = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_error_clone = first_error.clone();
+    let registry_stderr = registry.0.clone();
 
     let stderr_task = tokio::spawn(async move {
         info!("📖 Starting to read Claude stderr...");
@@ -1315,6 +2029,12 @@ pub async fn execute_agent(
             }
 
             error!("stderr[{}]: {}", error_count, line);
+            let _ = registry_stderr.append_output(
+                run_id,
+                crate::process::output::OutputStream::Stderr,
+                &line,
+            );
+
             // Emit error lines to the frontend with run_id for isolation
             let _ = app_handle_stderr.emit(&format!("agent-error:{}", run_id), &line);
             // Also emit to the generic event for backward compatibility
@@ -1331,30 +2051,8 @@ pub async fn execute_agent(
         }
     });
 
-    // Register the process in the registry for live output tracking (after stdout/stderr setup)
-    registry
-        .0
-        .register_process(
-            run_id,
-            agent_id,
-            agent.name.clone(),
-            pid,
-            project_path.clone(),
-            task.clone(),
-            execution_model.clone(),
-            child,
-        )
-        .map_err(|e| format!("Failed to register process: {}", e))?;
-    info!("📋 Registered process in registry");
-
-    // Create variables we need for the spawned task
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data dir");
-    let db_path = app_dir.join("agents.db");
-
     // Monitor process status and wait for completion
+    let registry_monitor = registry.0.clone();
     tokio::spawn(async move {
         info!("🕐 Starting process monitoring...");
 
@@ -1390,78 +2088,352 @@ pub async fn execute_agent(
             warn!("   4. Network connectivity issues");
             warn!("   5. Authentication issues (API key not found/invalid)");
 
-            // Process timed out - kill it via PID
+            // Mark the run failed first. If it was cancelled meanwhile, the cancellation
+            // owns its process and the PID may no longer be the run's by now.
+            let timed_out = match Connection::open(&db_path) {
+                Ok(conn) => conn
+                    .execute(
+                        "UPDATE agent_runs SET status = 'failed', failure_reason = 'No output from Claude within 30 seconds', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+                        params![run_id],
+                    )
+                    .map_or(true, |updated| updated > 0),
+                Err(_) => true,
+            };
+
+            if timed_out {
+                // Process timed out - kill it via PID
+                warn!(
+                    "🔍 Process likely stuck waiting for input, attempting to kill PID: {}",
+                    pid
+                );
+                let kill_result = std::process::Command::new("kill")
+                    .arg("-TERM")
+                    .arg(pid.to_string())
+                    .output();
+
+                match kill_result {
+                    Ok(output) if output.status.success() => {
+                        warn!("🔍 Successfully sent TERM signal to process");
+                    }
+                    Ok(_) => {
+                        warn!("🔍 Failed to kill process with TERM, trying KILL");
+                        let _ = std::process::Command::new("kill")
+                            .arg("-KILL")
+                            .arg(pid.to_string())
+                            .output();
+                    }
+                    Err(e) => {
+                        warn!("🔍 Error killing process: {}", e);
+                    }
+                }
+            } else {
+                info!(
+                    "Run {} is no longer running, not killing PID {}",
+                    run_id, pid
+                );
+            }
+
+            finish_run(&app, &registry_monitor, run_id);
+            let _ = app.emit("agent-complete", false);
+            let _ = app.emit(&format!("agent-complete:{}", run_id), false);
+            return;
+        }
+
+        // Wait for reading tasks to complete
+        info!("⏳ Waiting for stdout/stderr reading to complete...");
+        let _ = stdout_task.await;
+        let _ = stderr_task.await;
+        // Background processes of the run may hold the capture pipe open indefinitely
+        if let Some(mut task) = tool_stderr_task {
+            let drain = std::time::Duration::from_secs(1);
+            if tokio::time::timeout(drain, &mut task).await.is_err() {
+                task.abort();
+            }
+        }
+
+        let duration_ms = start_time.elapsed().as_millis() as i64;
+        info!("⏱️ Process execution took {} ms", duration_ms);
+
+        // Get the session ID that was extracted
+        let extracted_session_id = if let Ok(sid) = session_id.lock() {
+            sid.clone()
+        } else {
+            String::new()
+        };
+
+        // Wait for process completion and update status
+        info!("✅ Claude process execution monitoring complete");
+
+        // Update the run record with session ID and mark as completed - open a new connection
+        if let Ok(conn) = Connection::open(&db_path) {
+            let _ = conn.execute(
+                "UPDATE agent_runs SET session_id = ?1, status = CASE WHEN status = 'running' THEN 'completed' ELSE status END, completed_at = COALESCE(completed_at, CURRENT_TIMESTAMP) WHERE id = ?2",
+                params![extracted_session_id, run_id],
+            );
+        }
+
+        finish_run(&app, &registry_monitor, run_id);
+        let _ = app.emit("agent-complete", true);
+        let _ = app.emit(&format!("agent-complete:{}", run_id), true);
+    });
+
+    Ok(())
+}
+
+/// Mark a run that could not be started as failed, returning the reason
+fn fail_run(db: &AgentDb, run_id: i64, reason: String) -> String {
+    error!("❌ Run {} failed: {}", run_id, reason);
+    match db.0.lock() {
+        Ok(conn) => {
+            if let Err(e) = conn.execute(
+                "UPDATE agent_runs SET status = 'failed', failure_reason = ?1, completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
+                params![reason, run_id],
+            ) {
+                warn!("Failed to record failure of run {}: {}", run_id, e);
+            }
+        }
+        Err(e) => warn!("Failed to record failure of run {}: {}", run_id, e),
+    }
+    reason
+}
+
+/// How often a re-adopted run's process and session JSONL are checked
+const ADOPTED_RUN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How long the output a run wrote to disk is kept once the run has finished writing it
+const SPILL_RETENTION: std::time::Duration = std::time::Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Runs an earlier instance of the app left recorded as active
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ReconciledRuns {
+    /// Still running, and followed through their session JSONL again
+    pub adopted: Vec<i64>,
+    pub completed: Vec<i64>,
+    pub failed: Vec<i64>,
+}
+
+/// Settle the runs an earlier instance of the app left recorded as active
+///
+/// Call once at startup, after `AgentDb` and `ProcessRegistryState` are managed. A run whose
+/// PID still belongs to the process spawned for it, going by the process start time, is
+/// re-adopted. The others are marked completed or failed from how their session ended, and
+/// runs that never got to spawn fail. Queued runs start once this is done.
+pub async fn reconcile_orphaned_runs(app: &AppHandle) -> Result<ReconciledRuns, String> {
+    use crate::process::orphans::{self, Liveness, RunOutcome};
+
+    let db = app.state::<AgentDb>();
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    let mut reconciled = ReconciledRuns::default();
+
+    let pruned = run_output_dir(app).and_then(|dir| {
+        crate::process::output::prune_spills(&dir, SPILL_RETENTION).map_err(|e| e.to_string())
+    });
+    match pruned {
+        Ok(0) => {}
+        Ok(pruned) => info!("Removed {} output files of old runs", pruned),
+        Err(e) => warn!("Failed to remove the output of old runs: {}", e),
+    }
+
+    let orphans = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM agent_runs WHERE status = 'pending'")
+            .map_err(|e| e.to_string())?;
+        reconciled.failed = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_runs SET status = 'failed', failure_reason = 'The app exited before the run started', completed_at = CURRENT_TIMESTAMP WHERE status = 'pending'",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM agent_runs WHERE status = 'running'",
+                AGENT_RUN_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let runs = stmt
+            .query_map([], AgentRun::from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        runs
+    };
+
+    for run in orphans {
+        let Some(run_id) = run.id else {
+            continue;
+        };
+        let liveness = match (run.pid, run.process_started_at.as_deref()) {
+            (Some(pid), Some(started_at)) => orphans::check_process(pid, started_at),
+            _ => Liveness::Gone,
+        };
+
+        if liveness == Liveness::Alive {
+            let pid = run.pid.unwrap_or(0);
+            let started_at = run.process_started_at.clone().unwrap_or_default();
+            info!("🔗 Re-adopting run {} (PID {})", run_id, pid);
+            registry.0.adopt_process(
+                crate::process::ProcessInfo {
+                    run_id,
+                    agent_id: run.agent_id,
+                    agent_name: run.agent_name.clone(),
+                    pid,
+                    started_at: chrono::DateTime::parse_from_rfc3339(&started_at)
+                        .map(|started| started.with_timezone(&chrono::Utc))
+                        .unwrap_or_else(|_| chrono::Utc::now()),
+                    project_path: run.project_path.clone(),
+                    task: run.task.clone(),
+                    model: run.model.clone(),
+                },
+                open_run_output(app, run_id),
+            )?;
+            tauri::async_runtime::spawn(tail_adopted_run(app.clone(), run, pid, started_at));
+            reconciled.adopted.push(run_id);
+            continue;
+        }
+
+        if liveness == Liveness::Unknown {
             warn!(
-                "🔍 Process likely stuck waiting for input, attempting to kill PID: {}",
-                pid
+                "Can't tell whether run {} is still running, settling it from its session",
+                run_id
             );
-            let kill_result = std::process::Command::new("kill")
-                .arg("-TERM")
-                .arg(pid.to_string())
-                .output();
-
-            match kill_result {
-                Ok(output) if output.status.success() => {
-                    warn!("🔍 Successfully sent TERM signal to process");
-                }
-                Ok(_) => {
-                    warn!("🔍 Failed to kill process with TERM, trying KILL");
-                    let _ = std::process::Command::new("kill")
-                        .arg("-KILL")
-                        .arg(pid.to_string())
-                        .output();
-                }
-                Err(e) => {
-                    warn!("🔍 Error killing process: {}", e);
-                }
-            }
-
-            // Update database
-            if let Ok(conn) = Connection::open(&db_path) {
-                let _ = conn.execute(
-                    "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                    params![run_id],
-                );
-            }
-
-            let _ = app.emit("agent-complete", false);
-            let _ = app.emit(&format!("agent-complete:{}", run_id), false);
-            return;
         }
+        let outcome = session_outcome(&run).await;
+        {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            record_outcome(&conn, run_id, &outcome).map_err(|e| e.to_string())?;
+        }
+        match outcome {
+            RunOutcome::Completed => reconciled.completed.push(run_id),
+            RunOutcome::Failed(_) => reconciled.failed.push(run_id),
+        }
+    }
 
-        // Wait for reading tasks to complete
-        info!("⏳ Waiting for stdout/stderr reading to complete...");
-        let _ = stdout_task.await;
-        let _ = stderr_task.await;
+    info!(
+        "Reconciled agent runs: {} re-adopted, {} completed, {} failed",
+        reconciled.adopted.len(),
+        reconciled.completed.len(),
+        reconciled.failed.len()
+    );
+    let _ = app.emit("agent-runs-reconciled", &reconciled);
+    dispatch_queued_runs(app);
+    Ok(reconciled)
+}
 
-        let duration_ms = start_time.elapsed().as_millis() as i64;
-        info!("⏱️ Process execution took {} ms", duration_ms);
+/// Follow a re-adopted run through its session JSONL until its process exits
+async fn tail_adopted_run(app: AppHandle, run: AgentRun, pid: u32, started_at: String) {
+    use crate::process::orphans::{self, JsonlTail, Liveness, RunOutcome};
 
-        // Get the session ID that was extracted
-        let extracted_session_id = if let Ok(sid) = session_id.lock() {
-            sid.clone()
-        } else {
-            String::new()
-        };
+    let Some(run_id) = run.id else {
+        return;
+    };
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+    // Output the earlier instance already wrote to disk isn't forwarded a second time
+    let spilled = run_output_dir(&app)
+        .map(|dir| {
+            crate::process::output::spill_path(
+                &dir,
+                run_id,
+                crate::process::output::OutputStream::Stdout,
+            )
+        })
+        .and_then(|path| std::fs::metadata(path).map_err(|e| e.to_string()))
+        .is_ok_and(|metadata| metadata.len() > 0);
+    // Without a session ID there is nothing to follow, only the process to wait for
+    let mut tail = if run.session_id.is_empty() {
+        None
+    } else {
+        session_jsonl_path(&run.session_id, &run.project_path)
+            .ok()
+            .and_then(|path| {
+                if !spilled {
+                    return Some(JsonlTail::new(path));
+                }
+                JsonlTail::from_end(path)
+                    .map_err(|e| warn!("Failed to read the session of run {}: {}", run_id, e))
+                    .ok()
+            })
+    };
 
-        // Wait for process completion and update status
-        info!("✅ Claude process execution monitoring complete");
+    let forward_output = |tail: &mut Option<JsonlTail>| {
+        let Some(tail) = tail.as_mut() else {
+            return;
+        };
+        match tail.read_new_lines() {
+            Ok(lines) => {
+                for line in lines {
+                    let _ = registry.append_output(
+                        run_id,
+                        crate::process::output::OutputStream::Stdout,
+                        &line,
+                    );
+                    let _ = app.emit(&format!("agent-output:{}", run_id), &line);
+                }
+            }
+            Err(e) => warn!("Failed to read the session of run {}: {}", run_id, e),
+        }
+    };
 
-        // Update the run record with session ID and mark as completed - open a new connection
-        if let Ok(conn) = Connection::open(&db_path) {
-            let _ = conn.execute(
-                "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                params![extracted_session_id, run_id],
-            );
+    loop {
+        forward_output(&mut tail);
+        if orphans::check_process(pid, &started_at) != Liveness::Alive {
+            break;
+        }
+        tokio::time::sleep(ADOPTED_RUN_POLL_INTERVAL).await;
+    }
+    forward_output(&mut tail);
+
+    let outcome = session_outcome(&run).await;
+    let completed = outcome == RunOutcome::Completed;
+    info!("Re-adopted run {} exited: {:?}", run_id, outcome);
+    if let Ok(conn) = app.state::<AgentDb>().0.lock() {
+        if let Err(e) = record_outcome(&conn, run_id, &outcome) {
+            warn!("Failed to record the outcome of run {}: {}", run_id, e);
         }
+    }
 
-        // Cleanup will be handled by the cleanup_finished_processes function
+    finish_run(&app, &registry, run_id);
+    let _ = app.emit("agent-complete", completed);
+    let _ = app.emit(&format!("agent-complete:{}", run_id), completed);
+}
 
-        let _ = app.emit("agent-complete", true);
-        let _ = app.emit(&format!("agent-complete:{}", run_id), true);
-    });
+/// How a run that exited unobserved ended, going by its session JSONL
+async fn session_outcome(run: &AgentRun) -> crate::process::orphans::RunOutcome {
+    use crate::process::orphans::{self, RunOutcome};
 
-    Ok(run_id)
+    if run.session_id.is_empty() {
+        return RunOutcome::Failed("The app exited before the run reported a session".to_string());
+    }
+    match read_session_jsonl(&run.session_id, &run.project_path).await {
+        Ok(jsonl) => orphans::final_state(&jsonl),
+        Err(e) => RunOutcome::Failed(format!("The session of the run could not be read: {}", e)),
+    }
+}
+
+/// Mark a run still recorded as running with how it ended
+fn record_outcome(
+    conn: &Connection,
+    run_id: i64,
+    outcome: &crate::process::orphans::RunOutcome,
+) -> rusqlite::Result<usize> {
+    match outcome {
+        crate::process::orphans::RunOutcome::Completed => conn.execute(
+            "UPDATE agent_runs SET status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+        ),
+        crate::process::orphans::RunOutcome::Failed(reason) => conn.execute(
+            "UPDATE agent_runs SET status = 'failed', failure_reason = ?1, completed_at = CURRENT_TIMESTAMP WHERE id = ?2 AND status = 'running'",
+            params![reason, run_id],
+        ),
+    }
 }
 
 /// List all currently running agent sessions
@@ -1469,35 +2441,15 @@ pub async fn execute_agent(
 pub async fn list_running_sessions(db: State<'_, AgentDb>) -> Result<Vec<AgentRun>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
-    let mut stmt = conn.prepare(
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE status = 'running' ORDER BY process_started_at DESC"
-    ).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_runs WHERE status = 'running' ORDER BY process_started_at DESC",
+            AGENT_RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
 
     let runs = stmt
-        .query_map([], |row| {
-            Ok(AgentRun {
-                id: Some(row.get(0)?),
-                agent_id: row.get(1)?,
-                agent_name: row.get(2)?,
-                agent_icon: row.get(3)?,
-                task: row.get(4)?,
-                model: row.get(5)?,
-                project_path: row.get(6)?,
-                session_id: row.get(7)?,
-                status: row
-                    .get::<_, String>(8)
-                    .unwrap_or_else(|_| "pending".to_string()),
-                pid: row
-                    .get::<_, Option<i64>>(9)
-                    .ok()
-                    .flatten()
-                    .map(|p| p as u32),
-                process_started_at: row.get(10)?,
-                created_at: row.get(11)?,
-                completed_at: row.get(12)?,
-            })
-        })
+        .query_map([], AgentRun::from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -1505,21 +2457,179 @@ pub async fn list_running_sessions(db: State<'_, AgentDb>) -> Result<Vec<AgentRu
     Ok(runs)
 }
 
-/// Kill a running agent session
+/// The run queue with the slots it is waiting for
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunQueue {
+    pub limits: crate::process::queue::QueueLimits,
+    /// Runs holding a slot, starting or running
+    pub active: usize,
+    /// Queued runs in the order they get slots
+    pub queued: Vec<crate::process::queue::QueuedRun>,
+}
+
+/// Get the queued runs and the concurrency limits
+#[tauri::command]
+pub async fn get_run_queue(
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+) -> Result<RunQueue, String> {
+    let (limits, queued) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        (
+            crate::process::queue::load_limits(&conn),
+            crate::process::queue::list_queued(&conn).map_err(|e| e.to_string())?,
+        )
+    };
+
+    Ok(RunQueue {
+        limits,
+        active: registry.0.occupied_slots()?,
+        queued,
+    })
+}
+
+/// Set how many runs may run at once, overall and per project
+#[tauri::command]
+pub async fn set_run_queue_limits(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    max_concurrent: usize,
+    max_per_project: usize,
+) -> Result<crate::process::queue::QueueLimits, String> {
+    if max_concurrent == 0 || max_per_project == 0 {
+        return Err("Run limits must be at least 1".to_string());
+    }
+    let limits = crate::process::queue::QueueLimits {
+        max_concurrent,
+        max_per_project,
+    };
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::process::queue::save_limits(&conn, &limits).map_err(|e| e.to_string())?;
+    }
+
+    // Raised limits may let queued runs start
+    dispatch_queued_runs(&app);
+    Ok(limits)
+}
+
+/// Change the priority of a queued run
+#[tauri::command]
+pub async fn set_queued_run_priority(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    run_id: i64,
+    priority: i64,
+) -> Result<bool, String> {
+    let updated = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::process::queue::set_priority(&conn, run_id, priority).map_err(|e| e.to_string())?
+    };
+    if updated {
+        let _ = app.emit("agent-queue-changed", run_id);
+    }
+    Ok(updated)
+}
+
+/// Move a queued run in front of another queued run, or to the back of the queue
+#[tauri::command]
+pub async fn move_queued_run(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    run_id: i64,
+    before_run_id: Option<i64>,
+) -> Result<bool, String> {
+    let moved = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::process::queue::move_run(&conn, run_id, before_run_id).map_err(|e| e.to_string())?
+    };
+    if moved {
+        let _ = app.emit("agent-queue-changed", run_id);
+    }
+    Ok(moved)
+}
+
+/// Get how many seconds a cancelled run gets to exit before it is killed
+#[tauri::command]
+pub async fn get_agent_cancel_grace_period(db: State<'_, AgentDb>) -> Result<u64, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(crate::process::cancel::load_cancel_grace(&conn).as_secs())
+}
+
+/// Set how many seconds a cancelled run gets to exit before it is killed
+#[tauri::command]
+pub async fn set_agent_cancel_grace_period(
+    db: State<'_, AgentDb>,
+    grace_secs: u64,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::process::cancel::save_cancel_grace(&conn, std::time::Duration::from_secs(grace_secs))
+        .map_err(|e| e.to_string())
+}
+
+/// Cancel a running agent session, or take a queued run off the queue
+///
+/// The process group of the run gets SIGTERM and `grace_secs`, by default the configured
+/// grace period, to exit before it is killed.
 #[tauri::command]
 pub async fn kill_agent_session(
     app: AppHandle,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
     run_id: i64,
+    grace_secs: Option<u64>,
 ) -> Result<bool, String> {
     info!("Attempting to kill agent session {}", run_id);
 
-    // First try to kill using the process registry
-    let killed_via_registry = match registry.0.kill_process(run_id).await {
+    // Queued runs have no process yet and only leave the queue
+    let dequeued = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'queued'",
+            params![run_id],
+        )
+        .map_err(|e| e.to_string())?
+    };
+    if dequeued > 0 {
+        info!("Removed run {} from the queue", run_id);
+        let _ = app.emit("agent-queue-changed", run_id);
+        let _ = app.emit(&format!("agent-cancelled:{}", run_id), true);
+        return Ok(true);
+    }
+
+    // Record the cancellation first, so the run isn't marked completed once its process exits
+    let (cancelled, pid, grace) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let pid = conn
+            .query_row(
+                "SELECT pid, process_started_at FROM agent_runs WHERE id = ?1 AND status = 'running'",
+                params![run_id],
+                |row| {
+                    Ok(row
+                        .get::<_, Option<i64>>(0)?
+                        .zip(row.get::<_, Option<String>>(1)?))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .flatten();
+        let cancelled = conn
+            .execute(
+                "UPDATE agent_runs SET status = 'cancelled' WHERE id = ?1 AND status = 'running'",
+                params![run_id],
+            )
+            .map_err(|e| e.to_string())?;
+        let grace = grace_secs
+            .map(std::time::Duration::from_secs)
+            .unwrap_or_else(|| crate::process::cancel::load_cancel_grace(&conn));
+        (cancelled > 0, pid, grace)
+    };
+
+    // First try to cancel using the process registry
+    let killed_via_registry = match registry.0.kill_process(run_id, grace).await {
         Ok(success) => {
             if success {
-                info!("Successfully killed process {} via registry", run_id);
+                info!("Successfully cancelled process {} via registry", run_id);
                 true
             } else {
                 warn!("Process {} not found in registry", run_id);
@@ -1527,40 +2637,36 @@ pub async fn kill_agent_session(
             }
         }
         Err(e) => {
-            warn!("Failed to kill process {} via registry: {}", run_id, e);
+            warn!("Failed to cancel process {} via registry: {}", run_id, e);
             false
         }
     };
 
     // If registry kill didn't work, try fallback with PID from database
     if !killed_via_registry {
-        let pid_result = {
-            let conn = db.0.lock().map_err(|e| e.to_string())?;
-            conn.query_row(
-                "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
-                params![run_id],
-                |row| row.get::<_, Option<i64>>(0),
-            )
-            .map_err(|e| e.to_string())?
-        };
-
-        if let Some(pid) = pid_result {
-            info!("Attempting fallback kill for PID {} from database", pid);
-            let _ = registry.0.kill_process_by_pid(run_id, pid as u32)?;
+        if let Some((pid, started_at)) = pid {
+            info!("Attempting fallback cancel for PID {} from database", pid);
+            let _ = registry
+                .0
+                .kill_process_by_pid(run_id, pid as u32, &started_at, grace)
+                .await?;
         }
     }
 
-    // Update the database to mark as cancelled
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    let updated = conn.execute(
-        "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
-        params![run_id],
-    ).map_err(|e| e.to_string())?;
+    // The run's monitor keeps whatever output it produced while shutting down
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_runs SET completed_at = COALESCE(completed_at, CURRENT_TIMESTAMP) WHERE id = ?1 AND status = 'cancelled'",
+            params![run_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
 
     // Emit cancellation event with run_id for proper isolation
     let _ = app.emit(&format!("agent-cancelled:{}", run_id), true);
 
-    Ok(updated > 0 || killed_via_registry)
+    Ok(cancelled || killed_via_registry)
 }
 
 /// Get the status of a specific agent session
@@ -1657,36 +2763,101 @@ pub async fn get_live_session_output(
     registry.0.get_live_output(run_id)
 }
 
+/// Output of a session, either all of it or a page
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum SessionOutput {
+    Full(String),
+    Page(crate::process::output::OutputPage),
+}
+
 /// Get real-time output for a running session by reading its JSONL file with live output fallback
+///
+/// With a `range`, returns that page of the output the run wrote to disk instead. Runs from
+/// before output was written to disk are paged through their session JSONL.
 #[tauri::command]
 pub async fn get_session_output(
+    app: AppHandle,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
     run_id: i64,
-) -> Result<String, String> {
+    range: Option<crate::process::output::OutputRange>,
+    stream: Option<crate::process::output::OutputStream>,
+) -> Result<SessionOutput, String> {
+    use crate::process::output::{self, OutputStream};
+
+    let cap_bytes = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        output::load_live_output_cap(&conn) as u64
+    };
     // Get the session information
     let run = get_agent_run(db, run_id).await?;
+    let spill_path =
+        |stream| run_output_dir(&app).map(|dir| output::spill_path(&dir, run_id, stream));
+
+    if let Some(range) = range {
+        let stream = stream.unwrap_or_default();
+        let spilled = spill_path(stream)?;
+        let path =
+            if spilled.exists() || stream == OutputStream::Stderr || run.session_id.is_empty() {
+                spilled
+            } else {
+                session_jsonl_path(&run.session_id, &run.project_path)?
+            };
+        return output::read_page(&path, range)
+            .map(SessionOutput::Page)
+            .map_err(|e| format!("Failed to read the output of run {}: {}", run_id, e));
+    }
+
+    // Without a session, use the end of the output on disk, or the live output if there is
+    // none. Earlier output is paged through with a `range`.
+    let fallback_output = || -> Result<SessionOutput, String> {
+        let spilled = spill_path(OutputStream::Stdout)
+            .ok()
+            .and_then(|path| output::read_tail(&path, cap_bytes).ok())
+            .unwrap_or_default();
+        if !spilled.is_empty() {
+            return Ok(SessionOutput::Full(spilled));
+        }
+        Ok(SessionOutput::Full(registry.0.get_live_output(run_id)?))
+    };
 
     // If no session ID yet, try to get live output from registry
     if run.session_id.is_empty() {
-        let live_output = registry.0.get_live_output(run_id)?;
-        if !live_output.is_empty() {
-            return Ok(live_output);
-        }
-        return Ok(String::new());
+        return fallback_output();
     }
 
-    // Read the JSONL content
-    match read_session_jsonl(&run.session_id, &run.project_path).await {
-        Ok(content) => Ok(content),
+    // The end of the session JSONL, earlier lines are paged through with a `range`
+    let session_file = session_jsonl_path(&run.session_id, &run.project_path)?;
+    match output::read_tail(&session_file, cap_bytes) {
+        Ok(content) => Ok(SessionOutput::Full(content)),
         Err(_) => {
             // Fallback to live output if JSONL file doesn't exist yet
-            let live_output = registry.0.get_live_output(run_id)?;
-            Ok(live_output)
+            fallback_output()
         }
     }
 }
 
+/// Get how many bytes of live output a run keeps in memory
+#[tauri::command]
+pub async fn get_agent_live_output_cap(db: State<'_, AgentDb>) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(crate::process::output::load_live_output_cap(&conn))
+}
+
+/// Set how many bytes of live output a run keeps in memory, for runs started after
+#[tauri::command]
+pub async fn set_agent_live_output_cap(
+    db: State<'_, AgentDb>,
+    cap_bytes: usize,
+) -> Result<(), String> {
+    if cap_bytes == 0 {
+        return Err("The live output cap must be at least 1 byte".to_string());
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::process::output::save_live_output_cap(&conn, cap_bytes).map_err(|e| e.to_string())
+}
+
 /// Stream real-time session output by watching the JSONL file
 #[tauri::command]
 pub async fn stream_session_output(
@@ -1891,6 +3062,204 @@ pub async fn list_claude_installations(
     Ok(installations)
 }
 
+/// ID of the agent's sandbox profile, or of the default profile if it has none
+fn assigned_profile_id(conn: &Connection, agent_id: i64) -> Option<i64> {
+    let assigned: Option<i64> = conn
+        .query_row(
+            "SELECT sandbox_profile_id FROM agents WHERE id = ?1",
+            params![agent_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(None);
+    assigned.or_else(|| {
+        crate::sandbox::profile::load_default_profile(conn)
+            .ok()
+            .and_then(|profile| profile.id)
+    })
+}
+
+/// Effective rules of the agent's sandbox profile, or of the default profile
+fn load_assigned_rules(
+    conn: &Connection,
+    agent_id: i64,
+) -> Vec<crate::sandbox::profile::SandboxRule> {
+    assigned_profile_id(conn, agent_id)
+        .and_then(|id| crate::sandbox::profile::load_effective_rules(conn, id).ok())
+        .unwrap_or_default()
+}
+
+/// Domain allow-list rules of the agent's sandbox profile, or of the default profile
+fn load_domain_rules(
+    conn: &Connection,
+    agent_id: i64,
+) -> Vec<crate::sandbox::profile::SandboxRule> {
+    load_assigned_rules(conn, agent_id)
+        .into_iter()
+        .filter(|rule| rule.operation_type == "network_outbound" && rule.pattern_type == "domain")
+        .collect()
+}
+
+/// Environment rules of the agent's sandbox profile, or of the default profile
+fn load_environment_rules(
+    conn: &Connection,
+    agent_id: i64,
+) -> Vec<crate::sandbox::profile::SandboxRule> {
+    load_assigned_rules(conn, agent_id)
+        .into_iter()
+        .filter(|rule| rule.operation_type == crate::sandbox::environment::ENVIRONMENT_OPERATION)
+        .collect()
+}
+
+/// Resource limits of the agent's sandbox profile, or of the default profile
+fn load_resource_limits(
+    conn: &Connection,
+    agent_id: i64,
+) -> crate::sandbox::limits::ResourceLimits {
+    assigned_profile_id(conn, agent_id)
+        .and_then(|id| crate::sandbox::profile::load_profile(conn, id).ok())
+        .map(|profile| profile.resource_limits)
+        .unwrap_or_default()
+}
+
+/// Create a cgroup for a run, or None if its memory and process limits can't be enforced
+fn create_run_cgroup(
+    run_id: i64,
+    limits: &crate::sandbox::limits::ResourceLimits,
+) -> Option<crate::sandbox::limits::Cgroup> {
+    let name = format!("organized-agents-run-{}", run_id);
+    let error = match crate::sandbox::limits::Cgroup::create(&name, limits) {
+        Ok(cgroup) => return Some(cgroup),
+        Err(e) => e,
+    };
+    if !limits.needs_cgroup() {
+        return None;
+    }
+    match crate::sandbox::limits::Cgroup::create_scope(&name, limits) {
+        Ok(cgroup) => Some(cgroup),
+        Err(e) => {
+            warn!(
+                "Memory and process limits of run {} are not enforced: {:#}; {:#}",
+                run_id, error, e
+            );
+            None
+        }
+    }
+}
+
+/// Record the denials in the stderr the run's tool commands copy into the capture pipe
+#[cfg(unix)]
+async fn read_tool_stderr(
+    app: AppHandle,
+    registry: std::sync::Arc<crate::process::ProcessRegistry>,
+    receiver: tokio::net::unix::pipe::Receiver,
+    _script: crate::sandbox::capture::CaptureScript,
+    monitor: std::sync::Arc<Mutex<crate::sandbox::violations::ViolationMonitor>>,
+    db_path: PathBuf,
+    run_id: i64,
+) {
+    let mut lines = BufReader::new(receiver).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!("tool stderr[{}]: {}", run_id, line);
+        if let Ok(mut monitor) = monitor.lock() {
+            if let Some(violation) = monitor.inspect_stderr_line(&line) {
+                warn!("🚫 Sandbox violation detected: {:?}", violation);
+                let _ = app.emit(&format!("sandbox-violation:{}", run_id), &violation);
+            }
+        }
+        handle_violation_alerts(&app, &registry, &monitor, &db_path, run_id).await;
+    }
+}
+
+/// Announce first-seen violations and stop the run once it exceeds the kill threshold
+async fn handle_violation_alerts(
+    app: &AppHandle,
+    registry: &crate::process::ProcessRegistry,
+    monitor: &Mutex<crate::sandbox::violations::ViolationMonitor>,
+    db_path: &std::path::Path,
+    run_id: i64,
+) {
+    let (first_seen, kill) = match monitor.lock() {
+        Ok(mut monitor) => (monitor.take_first_seen(), monitor.take_kill_request()),
+        Err(_) => return,
+    };
+
+    for violation in first_seen {
+        info!("🆕 First sandbox violation of its kind: {:?}", violation);
+        let _ = app.emit(&format!("sandbox-violation-new:{}", run_id), &violation);
+    }
+
+    if !kill {
+        return;
+    }
+    warn!(
+        "🛑 Run {} exceeded the sandbox violation threshold, stopping it",
+        run_id
+    );
+    let grace = match Connection::open(db_path) {
+        Ok(conn) => {
+            let _ = conn.execute(
+                "UPDATE agent_runs SET status = 'failed', failure_reason = 'Exceeded the sandbox violation threshold', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+                params![run_id],
+            );
+            crate::process::cancel::load_cancel_grace(&conn)
+        }
+        Err(_) => crate::process::cancel::DEFAULT_CANCEL_GRACE,
+    };
+    if let Err(e) = registry.kill_process(run_id, grace).await {
+        error!("Failed to stop run {}: {}", run_id, e);
+    }
+    let _ = app.emit(&format!("sandbox-violation-threshold:{}", run_id), true);
+}
+
+/// Start the egress proxy for a run, reporting blocked hosts as sandbox violations
+///
+/// Blocked hosts are recorded through the run's violation monitor, so they are announced
+/// when first seen and count towards its kill threshold.
+async fn start_egress_proxy(
+    app: &AppHandle,
+    run_id: i64,
+    allowed_domains: Vec<String>,
+    monitor: std::sync::Arc<Mutex<crate::sandbox::violations::ViolationMonitor>>,
+    db_path: PathBuf,
+) -> Result<crate::sandbox::proxy::EgressProxy, String> {
+    let app_handle = app.clone();
+    let registry = app.state::<crate::process::ProcessRegistryState>().0.clone();
+    let alert_monitor = monitor.clone();
+    let on_blocked: crate::sandbox::proxy::BlockedCallback =
+        std::sync::Arc::new(move |violation| {
+            if let Some(violation) = violation {
+                warn!("🚫 Egress proxy blocked: {:?}", violation.pattern_value);
+                let _ = app_handle.emit(&format!("sandbox-violation:{}", run_id), &violation);
+            }
+            let app = app_handle.clone();
+            let registry = registry.clone();
+            let monitor = alert_monitor.clone();
+            let db_path = db_path.clone();
+            tokio::spawn(async move {
+                handle_violation_alerts(&app, &registry, &monitor, &db_path, run_id).await;
+            });
+        });
+
+    crate::sandbox::proxy::EgressProxy::start(allowed_domains, monitor, Some(on_blocked))
+        .await
+        .map_err(|e| format!("Failed to start egress proxy: {}", e))
+}
+
+/// Create a command running `program` under strace, with the same environment
+/// `create_command_with_env` would give it
+fn create_traced_command(program: &str, trace_path: &std::path::Path) -> Command {
+    let env_cmd = create_command_with_env(program);
+    let mut cmd = Command::new("strace");
+    for (key, value) in env_cmd.as_std().get_envs() {
+        if let Some(value) = value {
+            cmd.env(key, value);
+        }
+    }
+    cmd.args(crate::sandbox::learning::strace_args(trace_path))
+        .arg(program);
+    cmd
+}
+
 /// Helper function to create a tokio Command with proper environment variables
 /// This ensures commands like Claude can find Node.js and other dependencies
 fn create_command_with_env(program: &str) -> Command {
@@ -1925,7 +3294,7 @@ fn create_command_with_env(program: &str) -> Command {
         if let Some(node_bin_dir) = std::path::Path::new(program).parent() {
             let current_path = std::env::var("PATH").unwrap_or_default();
             let node_bin_str = node_bin_dir.to_string_lossy();
-            if !current_path.contains(&node_bin_str.as_ref()) {
+            if !current_path.contains(node_bin_str.as_ref()) {
                 let new_path = format!("{}:{}", node_bin_str, current_path);
                 tokio_cmd.env("PATH", new_path);
             }
@@ -1967,10 +3336,10 @@ pub async fn import_agent(db: State<'_, AgentDb>, json_data: String) -> Result<A
     let agent_data = export_data.agent;
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
-    // Check if an agent with the same name already exists
+    // Check if a global agent with the same name already exists
     let existing_count: i64 = conn
         .query_row(
-            "SELECT COUNT(*) FROM agents WHERE name = ?1",
+            "SELECT COUNT(*) FROM agents WHERE name = ?1 AND project_path IS NULL AND removed_at IS NULL",
             params![agent_data.name],
             |row| row.get(0),
         )
@@ -2001,28 +3370,14 @@ pub async fn import_agent(db: State<'_, AgentDb>, json_data: String) -> Result<A
     .map_err(|e| format!("Failed to create agent: {}", e))?;
 
     let id = conn.last_insert_rowid();
+    crate::agents::versions::record_version(&conn, id).map_err(|e| e.to_string())?;
 
     // Fetch the created agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, sandbox_enabled, enable_file_read, enable_file_write, enable_network, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    sandbox_enabled: row.get(6)?,
-                    enable_file_read: row.get(7)?,
-                    enable_file_write: row.get(8)?,
-                    enable_network: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| format!("Failed to fetch created agent: {}", e))?;
 
@@ -2197,7 +3552,7 @@ pub async fn import_preinstalled_agents(db: State<'_, AgentDb>) -> Result<Vec<St
         let path = entry.path();
         
         if path.extension().and_then(|s| s.to_str()) == Some("json") 
-            && path.file_name().and_then(|s| s.to_str()).map_or(false, |s| s.contains("claudia")) {
+            && path.file_name().and_then(|s| s.to_str()).is_some_and(|s| s.contains("claudia")) {
             
             info!("Importing agent from: {:?}", path);
             
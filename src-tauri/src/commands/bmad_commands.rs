@@ -129,6 +129,7 @@ pub async fn get_agent_recommendations(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let workflow_manager = WorkflowManager::new(&project.path);
@@ -152,6 +153,7 @@ pub async fn transition_bmad_phase(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let workflow_manager = WorkflowManager::new(&project.path);
@@ -173,6 +175,7 @@ pub async fn start_story(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let workflow_manager = WorkflowManager::new(&project.path);
@@ -194,6 +197,7 @@ pub async fn complete_story(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let workflow_manager = WorkflowManager::new(&project.path);
@@ -216,6 +220,7 @@ pub async fn report_blocker(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let workflow_manager = WorkflowManager::new(&project.path);
@@ -234,6 +239,7 @@ pub async fn get_workflow_summary(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let workflow_manager = WorkflowManager::new(&project.path);
@@ -253,6 +259,7 @@ pub async fn get_project_messages(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let comm_manager = CommunicationManager::new(&project.path);
@@ -281,6 +288,7 @@ pub async fn send_agent_message(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let comm_manager = CommunicationManager::new(&project.path);
@@ -303,38 +311,13 @@ pub async fn mark_message_read(
         .lock()
         .unwrap()
         .get_project(uuid)
+        .cloned()
         .ok_or("Project not found")?;
     
     let comm_manager = CommunicationManager::new(&project.path);
     comm_manager.mark_message_read(msg_uuid).map_err(|e| e.to_string())
 }
 
-// IDE Integration Commands
-
-#[tauri::command]
-pub async fn launch_ide_with_context(
-    project_id: String,
-    ide_name: String,
-    story_context: Option<String>,
-) -> Result<(), String> {
-    // This will be implemented later with actual IDE detection and launching
-    tracing::info!("Launching {} for project {} with context: {:?}", 
-        ide_name, project_id, story_context);
-    
-    // For now, just return success
-    Ok(())
-}
-
-#[tauri::command]
-pub async fn detect_installed_ides() -> Result<Vec<String>, String> {
-    // This will be implemented later with actual IDE detection
-    Ok(vec![
-        "VS Code".to_string(),
-        "Cursor".to_string(),
-        "Windsurf".to_string(),
-    ])
-}
-
 // File Operations
 
 #[tauri::command]
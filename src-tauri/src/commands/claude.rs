@@ -148,19 +148,17 @@ fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, Strin
     let entries = fs::read_dir(project_dir)
         .map_err(|e| format!("Failed to read project directory: {}", e))?;
 
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                // Read the first line of the JSONL file
-                if let Ok(file) = fs::File::open(&path) {
-                    let reader = BufReader::new(file);
-                    if let Some(Ok(first_line)) = reader.lines().next() {
-                        // Parse the JSON and extract cwd
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&first_line) {
-                            if let Some(cwd) = json.get("cwd").and_then(|v| v.as_str()) {
-                                return Ok(cwd.to_string());
-                            }
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
+            // Read the first line of the JSONL file
+            if let Ok(file) = fs::File::open(&path) {
+                let reader = BufReader::new(file);
+                if let Some(Ok(first_line)) = reader.lines().next() {
+                    // Parse the JSON and extract cwd
+                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&first_line) {
+                        if let Some(cwd) = json.get("cwd").and_then(|v| v.as_str()) {
+                            return Ok(cwd.to_string());
                         }
                     }
                 }
@@ -171,6 +169,15 @@ fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, Strin
     Err("Could not determine project path from session files".to_string())
 }
 
+/// Encodes a project path into its directory name under ~/.claude/projects
+///
+/// Claude Code replaces every character that is not ASCII alphanumeric with a hyphen.
+fn encode_project_path(path: &str) -> String {
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
 /// Decodes a project directory name back to its original path
 /// The directory names in ~/.claude/projects are encoded paths
 /// DEPRECATED: Use get_project_path_from_sessions instead when possible
@@ -190,27 +197,25 @@ fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<S
 
     let reader = BufReader::new(file);
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if let Ok(entry) = serde_json::from_str::<JsonlEntry>(&line) {
-                if let Some(message) = entry.message {
-                    if message.role.as_deref() == Some("user") {
-                        if let Some(content) = message.content {
-                            // Skip if it contains the caveat message
-                            if content.contains("Caveat: The messages below were generated by the user while running local commands") {
-                                continue;
-                            }
-
-                            // Skip if it starts with command tags
-                            if content.starts_with("<command-name>")
-                                || content.starts_with("<local-command-stdout>")
-                            {
-                                continue;
-                            }
+    for line in reader.lines().map_while(Result::ok) {
+        if let Ok(entry) = serde_json::from_str::<JsonlEntry>(&line) {
+            if let Some(message) = entry.message {
+                if message.role.as_deref() == Some("user") {
+                    if let Some(content) = message.content {
+                        // Skip if it contains the caveat message
+                        if content.contains("Caveat: The messages below were generated by the user while running local commands") {
+                            continue;
+                        }
 
-                            // Found a valid user message
-                            return (Some(content), entry.timestamp);
+                        // Skip if it starts with command tags
+                        if content.starts_with("<command-name>")
+                            || content.starts_with("<local-command-stdout>")
+                        {
+                            continue;
                         }
+
+                        // Found a valid user message
+                        return (Some(content), entry.timestamp);
                     }
                 }
             }
@@ -254,7 +259,7 @@ fn create_command_with_env(program: &str) -> Command {
         if let Some(node_bin_dir) = std::path::Path::new(program).parent() {
             let current_path = std::env::var("PATH").unwrap_or_default();
             let node_bin_str = node_bin_dir.to_string_lossy();
-            if !current_path.contains(&node_bin_str.as_ref()) {
+            if !current_path.contains(node_bin_str.as_ref()) {
                 let new_path = format!("{}:{}", node_bin_str, current_path);
                 tokio_cmd.env("PATH", new_path);
             }
@@ -340,7 +345,7 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
     }
 
     // Sort projects by creation time (newest first)
-    projects.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    projects.sort_by_key(|p| std::cmp::Reverse(p.created_at));
 
     log::info!("Found {} projects", projects.len());
     Ok(projects)
@@ -423,7 +428,7 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
     }
 
     // Sort sessions by creation time (newest first)
-    sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
 
     log::info!(
         "Found {} sessions for project {}",
@@ -774,11 +779,9 @@ pub async fn load_session_history(
     let reader = BufReader::new(file);
     let mut messages = Vec::new();
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                messages.push(json);
-            }
+    for line in reader.lines().map_while(Result::ok) {
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
+            messages.push(json);
         }
     }
 
@@ -821,7 +824,7 @@ pub async fn execute_claude_code(
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
-    spawn_claude_process(app, cmd).await
+    spawn_claude_process(app, cmd, project_path).await
 }
 
 /// Continue an existing Claude Code conversation with streaming output
@@ -861,7 +864,7 @@ pub async fn continue_claude_code(
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
-    spawn_claude_process(app, cmd).await
+    spawn_claude_process(app, cmd, project_path).await
 }
 
 /// Resume an existing Claude Code session by ID with streaming output
@@ -904,7 +907,7 @@ pub async fn resume_claude_code(
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
-    spawn_claude_process(app, cmd).await
+    spawn_claude_process(app, cmd, project_path).await
 }
 
 /// Cancel the currently running Claude Code execution
@@ -1012,27 +1015,20 @@ fn create_sandboxed_claude_command(app: &AppHandle, project_path: &str) -> Resul
                 profile_id
             );
 
-            // Get all rules for this profile
-            let mut stmt = conn
-                .prepare(
-                    "SELECT operation_type, pattern_type, pattern_value, enabled, platform_support 
-                 FROM sandbox_rules WHERE profile_id = ?1 AND enabled = 1",
-                )
-                .map_err(|e| e.to_string())?;
-
-            let rules = stmt
-                .query_map(rusqlite::params![profile_id], |row| {
-                    Ok((
-                        row.get::<_, String>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, bool>(3)?,
-                        row.get::<_, Option<String>>(4)?,
-                    ))
-                })
+            // Get all rules for this profile, including inherited ones
+            let rules: Vec<_> = crate::sandbox::profile::load_effective_rules(&conn, profile_id)
                 .map_err(|e| e.to_string())?
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|e| e.to_string())?;
+                .into_iter()
+                .map(|rule| {
+                    (
+                        rule.operation_type,
+                        rule.pattern_type,
+                        rule.pattern_value,
+                        rule.enabled,
+                        rule.platform_support,
+                    )
+                })
+                .collect();
 
             log::info!("Building sandbox profile with {} rules", rules.len());
 
@@ -1083,6 +1079,10 @@ fn create_sandboxed_claude_command(app: &AppHandle, project_path: &str) -> Resul
                         sandbox_rules.push(rule);
                     }
 
+                    #[cfg(unix)]
+                    let env_policy =
+                        crate::sandbox::environment::EnvPolicy::from_rules(&sandbox_rules);
+
                     // Try to build the profile
                     match builder.build_profile(sandbox_rules) {
                         Ok(profile) => {
@@ -1091,13 +1091,19 @@ fn create_sandboxed_claude_command(app: &AppHandle, project_path: &str) -> Resul
                             // Use the helper function to create sandboxed command
                             let claude_path = find_claude_binary(app)?;
                             #[cfg(unix)]
-                            return Ok(create_sandboxed_command(
-                                &claude_path,
-                                &[],
-                                &project_path_buf,
-                                profile,
-                                project_path_buf.clone(),
-                            ));
+                            {
+                                // The sessions here are interactive, so a missing backend
+                                // is only logged
+                                let (command, _backend) = create_sandboxed_command(
+                                    &claude_path,
+                                    &[],
+                                    &project_path_buf,
+                                    profile,
+                                    project_path_buf.clone(),
+                                    env_policy,
+                                );
+                                Ok(command)
+                            }
 
                             #[cfg(not(unix))]
                             {
@@ -1151,7 +1157,11 @@ fn get_claude_settings_sync(_app: &AppHandle) -> Result<ClaudeSettings, String>
 }
 
 /// Helper function to spawn Claude process and handle streaming
-async fn spawn_claude_process(app: AppHandle, mut cmd: Command) -> Result<(), String> {
+async fn spawn_claude_process(
+    app: AppHandle,
+    mut cmd: Command,
+    project_path: String,
+) -> Result<(), String> {
     use tokio::io::{AsyncBufReadExt, BufReader};
 
     // Generate a unique session ID for this Claude Code session
@@ -1161,7 +1171,7 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command) -> Result<(), St
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis(),
-        uuid::Uuid::new_v4().to_string()
+        uuid::Uuid::new_v4()
     );
 
     // Spawn the process
@@ -1202,8 +1212,38 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command) -> Result<(), St
     let session_id_clone = session_id.clone();
     let stdout_task = tokio::spawn(async move {
         let mut lines = stdout_reader.lines();
+        // Claude's own session ID, reported by the init message
+        let mut claude_session_id: Option<String> = None;
         while let Ok(Some(line)) = lines.next_line().await {
             log::debug!("Claude stdout: {}", line);
+            if claude_session_id.is_none() {
+                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
+                    if msg.get("type").and_then(|t| t.as_str()) == Some("system") {
+                        claude_session_id = msg
+                            .get("session_id")
+                            .and_then(|s| s.as_str())
+                            .map(str::to_string);
+                    }
+                }
+            }
+            if let Some(claude_session_id) = claude_session_id.as_deref() {
+                if line.contains("\"tool_use\"") {
+                    // Checkpoint off the streaming loop so output keeps flowing
+                    let app_handle = app_handle.clone();
+                    let project_path = project_path.clone();
+                    let claude_session_id = claude_session_id.to_string();
+                    let line = line.clone();
+                    tokio::spawn(async move {
+                        checkpoint_before_destructive_operation(
+                            &app_handle,
+                            &project_path,
+                            &claude_session_id,
+                            &line,
+                        )
+                        .await;
+                    });
+                }
+            }
             // Emit the line to the frontend with session isolation
             let _ = app_handle.emit(&format!("claude-output:{}", session_id_clone), &line);
             // Also emit to the generic event for backward compatibility
@@ -1377,7 +1417,7 @@ pub async fn search_files(base_path: String, query: String) -> Result<Vec<FileEn
     let query_lower = query.to_lowercase();
     let mut results = Vec::new();
 
-    search_files_recursive(&path, &path, &query_lower, &mut results, 0)?;
+    search_files_recursive(&path, &query_lower, &mut results, 0)?;
 
     // Sort by relevance: exact matches first, then by name
     results.sort_by(|a, b| {
@@ -1399,7 +1439,6 @@ pub async fn search_files(base_path: String, query: String) -> Result<Vec<FileEn
 
 fn search_files_recursive(
     current_path: &PathBuf,
-    base_path: &PathBuf,
     query: &str,
     results: &mut Vec<FileEntry>,
     depth: usize,
@@ -1459,7 +1498,7 @@ fn search_files_recursive(
                 }
             }
 
-            search_files_recursive(&entry_path, base_path, query, results, depth + 1)?;
+            search_files_recursive(&entry_path, query, results, depth + 1)?;
         }
     }
 
@@ -1492,10 +1531,25 @@ pub async fn create_checkpoint(
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
     // Always load current session messages from the JSONL file
+    load_session_messages(&manager, &project_id, &session_id, message_index).await?;
+
+    manager
+        .create_checkpoint(description, None)
+        .await
+        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+}
+
+/// Loads the session's JSONL messages (up to `message_index`) into a checkpoint manager
+async fn load_session_messages(
+    manager: &crate::checkpoint::manager::CheckpointManager,
+    project_id: &str,
+    session_id: &str,
+    message_index: Option<usize>,
+) -> Result<(), String> {
     let session_path = get_claude_dir()
         .map_err(|e| e.to_string())?
         .join("projects")
-        .join(&project_id)
+        .join(project_id)
         .join(format!("{}.jsonl", session_id));
 
     if session_path.exists() {
@@ -1503,30 +1557,101 @@ pub async fn create_checkpoint(
             .map_err(|e| format!("Failed to open session file: {}", e))?;
         let reader = BufReader::new(file);
 
-        let mut line_count = 0;
-        for line in reader.lines() {
-            if let Some(index) = message_index {
-                if line_count > index {
-                    break;
-                }
+        let lines: Vec<String> = reader
+            .lines()
+            .take(message_index.map_or(usize::MAX, |index| index + 1))
+            .map_while(Result::ok)
+            .collect();
+        manager
+            .load_messages(lines)
+            .await
+            .map_err(|e| format!("Failed to track message: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Creates a checkpoint before a destructive tool call runs, for sessions using the Smart strategy
+///
+/// The tool executes inside the Claude process concurrently with this, so it is best
+/// effort: the snapshot is taken as soon as the tool call is streamed, which normally
+/// lands before the tool result. Sessions without checkpoint settings are left alone.
+async fn checkpoint_before_destructive_operation(
+    app: &AppHandle,
+    project_path: &str,
+    session_id: &str,
+    line: &str,
+) {
+    let state = app.state::<crate::checkpoint::state::CheckpointState>();
+    let project_id = encode_project_path(project_path);
+
+    let manager = match state.get_manager(session_id).await {
+        Some(manager) => manager,
+        None => {
+            let claude_dir = match get_claude_dir() {
+                Ok(dir) => dir,
+                Err(_) => return,
+            };
+            let paths =
+                crate::checkpoint::CheckpointPaths::new(&claude_dir, &project_id, session_id);
+            if !paths.timeline_file.exists() {
+                return;
             }
-            if let Ok(line) = line {
-                manager
-                    .track_message(line)
-                    .await
-                    .map_err(|e| format!("Failed to track message: {}", e))?;
+            match state
+                .get_or_create_manager(
+                    session_id.to_string(),
+                    project_id.clone(),
+                    PathBuf::from(project_path),
+                )
+                .await
+            {
+                Ok(manager) => manager,
+                Err(e) => {
+                    log::warn!("Failed to get checkpoint manager: {}", e);
+                    return;
+                }
             }
-            line_count += 1;
         }
+    };
+
+    let operations = manager.smart_checkpoint_operations(line).await;
+    if operations.is_empty() {
+        return;
     }
 
-    manager
-        .create_checkpoint(description, None)
-        .await
-        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+    let description = format!(
+        "Before {}",
+        operations
+            .iter()
+            .map(|op| op.describe())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    log::info!(
+        "Creating smart checkpoint for session {}: {}",
+        session_id,
+        description
+    );
+
+    if let Err(e) = load_session_messages(&manager, &project_id, session_id, None).await {
+        log::warn!("Failed to load session messages: {}", e);
+    }
+
+    match manager.create_checkpoint(Some(description), None).await {
+        Ok(result) => {
+            let _ = app.emit(
+                &format!("checkpoint-created:{}", session_id),
+                &result.checkpoint,
+            );
+        }
+        Err(e) => log::warn!("Failed to create smart checkpoint: {}", e),
+    }
 }
 
 /// Restores a session to a specific checkpoint
+///
+/// Uncommitted changes that are not in any checkpoint are overwritten and listed in the
+/// warnings, so the UI checks `get_uncheckpointed_changes` first and asks before restoring.
 #[tauri::command]
 pub async fn restore_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1541,8 +1666,9 @@ pub async fn restore_checkpoint(
         session_id
     );
 
+    // The session may be a past one that is not open, so don't keep its manager around
     let manager = app
-        .get_or_create_manager(
+        .get_or_open_manager(
             session_id.clone(),
             project_id.clone(),
             PathBuf::from(&project_path),
@@ -1597,6 +1723,117 @@ pub async fn list_checkpoints(
     Ok(manager.list_checkpoints().await)
 }
 
+/// Updates the tags, pinned flag and description of a checkpoint
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn update_checkpoint_annotations(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    tags: Vec<String>,
+    pinned: bool,
+    description: Option<String>,
+) -> Result<crate::checkpoint::Checkpoint, String> {
+    log::info!(
+        "Updating annotations for checkpoint: {} in session: {}",
+        checkpoint_id,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .annotate_checkpoint(&checkpoint_id, tags, pinned, description)
+        .await
+        .map_err(|e| format!("Failed to update checkpoint annotations: {}", e))
+}
+
+/// Searches checkpoints of one session, or of every session in the project
+#[tauri::command]
+pub async fn search_checkpoints(
+    project_id: String,
+    session_id: Option<String>,
+    query: crate::checkpoint::CheckpointSearchQuery,
+) -> Result<Vec<crate::checkpoint::Checkpoint>, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    log::info!(
+        "Searching checkpoints in project: {} (session: {:?})",
+        project_id,
+        session_id
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir);
+
+    let session_ids = match session_id {
+        Some(id) => vec![id],
+        None => storage
+            .list_session_ids(&project_id)
+            .map_err(|e| format!("Failed to list sessions: {}", e))?,
+    };
+
+    storage
+        .search_checkpoints(&project_id, &session_ids, &query)
+        .map_err(|e| format!("Failed to search checkpoints: {}", e))
+}
+
+/// Creates a git branch in the project repo from a mirrored checkpoint
+#[tauri::command]
+pub async fn promote_checkpoint_to_branch(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    branch_name: String,
+) -> Result<String, String> {
+    log::info!(
+        "Promoting checkpoint: {} to branch: {}",
+        checkpoint_id,
+        branch_name
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .promote_checkpoint_to_branch(&checkpoint_id, &branch_name)
+        .await
+        .map_err(|e| format!("Failed to promote checkpoint: {}", e))
+}
+
+/// Lists uncommitted changes in the project repo that are not captured by any checkpoint
+#[tauri::command]
+pub async fn get_uncheckpointed_changes(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<Vec<String>, String> {
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    let changes = manager
+        .find_uncheckpointed_changes()
+        .await
+        .map_err(|e| format!("Failed to check for uncheckpointed changes: {}", e))?;
+
+    Ok(changes
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect())
+}
+
 /// Forks a new timeline branch from a checkpoint
 #[tauri::command]
 pub async fn fork_from_checkpoint(
@@ -1616,35 +1853,185 @@ pub async fn fork_from_checkpoint(
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
 
-    // First, copy the session file to the new session
-    let source_session_path = claude_dir
-        .join("projects")
-        .join(&project_id)
-        .join(format!("{}.jsonl", session_id));
-    let new_session_path = claude_dir
-        .join("projects")
-        .join(&project_id)
-        .join(format!("{}.jsonl", new_session_id));
-
-    if source_session_path.exists() {
-        fs::copy(&source_session_path, &new_session_path)
-            .map_err(|e| format!("Failed to copy session file: {}", e))?;
-    }
-
     // Create manager for the new session
     let manager = app
         .get_or_create_manager(
             new_session_id.clone(),
-            project_id,
+            project_id.clone(),
             PathBuf::from(&project_path),
         )
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    manager
-        .fork_from_checkpoint(&checkpoint_id, description)
+    // The source may be any session of the project, including ones not currently open
+    let result = manager
+        .fork_from_session_checkpoint(&session_id, &checkpoint_id, description)
         .await
-        .map_err(|e| format!("Failed to fork checkpoint: {}", e))
+        .map_err(|e| format!("Failed to fork checkpoint: {}", e))?;
+
+    // Seed the new session file with the conversation up to the forked checkpoint
+    let (_, _, messages) = manager
+        .storage
+        .load_checkpoint(&project_id, &session_id, &checkpoint_id)
+        .map_err(|e| format!("Failed to load checkpoint data: {}", e))?;
+    let new_session_path = claude_dir
+        .join("projects")
+        .join(&project_id)
+        .join(format!("{}.jsonl", new_session_id));
+
+    fs::write(&new_session_path, messages)
+        .map_err(|e| format!("Failed to write session file: {}", e))?;
+
+    Ok(result)
+}
+
+/// Exports a checkpoint, optionally with its ancestors, to a compressed archive file
+#[tauri::command]
+pub async fn export_checkpoint_archive(
+    project_id: String,
+    session_id: String,
+    checkpoint_id: String,
+    include_ancestors: bool,
+    file_path: String,
+) -> Result<(), String> {
+    use crate::checkpoint::{archive, storage::CheckpointStorage};
+
+    log::info!(
+        "Exporting checkpoint {} of session {} to {}",
+        checkpoint_id,
+        session_id,
+        file_path
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir);
+
+    let data = archive::export_archive(
+        &storage,
+        &project_id,
+        &session_id,
+        &checkpoint_id,
+        include_ancestors,
+    )
+    .map_err(|e| format!("Failed to export checkpoint: {}", e))?;
+
+    fs::write(&file_path, data).map_err(|e| format!("Failed to write file: {}", e))
+}
+
+/// Imports a checkpoint archive into a new session of a project
+///
+/// The session file is seeded with the messages of the last imported checkpoint so the
+/// session can be opened and resumed right away.
+#[tauri::command]
+pub async fn import_checkpoint_archive(
+    file_path: String,
+    project_id: String,
+    session_id: Option<String>,
+) -> Result<crate::checkpoint::archive::ArchiveImportResult, String> {
+    use crate::checkpoint::{archive, storage::CheckpointStorage};
+
+    let session_id = session_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    log::info!(
+        "Importing checkpoint archive {} into session {} of project {}",
+        file_path,
+        session_id,
+        project_id
+    );
+
+    let data = fs::read(&file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir.clone());
+
+    let result = archive::import_archive(&storage, &data, &project_id, &session_id)
+        .map_err(|e| format!("Failed to import checkpoint archive: {}", e))?;
+
+    let session_path = claude_dir
+        .join("projects")
+        .join(&project_id)
+        .join(format!("{}.jsonl", session_id));
+    if let Some(last) = result.checkpoints.last() {
+        if !session_path.exists() {
+            let (_, _, messages) = storage
+                .load_checkpoint(&project_id, &session_id, &last.id)
+                .map_err(|e| format!("Failed to load checkpoint data: {}", e))?;
+            fs::write(&session_path, messages)
+                .map_err(|e| format!("Failed to write session file: {}", e))?;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Verifies checkpoint storage of a project (or one session), optionally pruning broken entries
+#[tauri::command]
+pub async fn verify_checkpoint_storage(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    project_id: String,
+    session_id: Option<String>,
+    repair: bool,
+) -> Result<crate::checkpoint::verify::StorageVerificationReport, String> {
+    use crate::checkpoint::{storage::CheckpointStorage, verify};
+
+    log::info!(
+        "Verifying checkpoint storage for project: {} (repair: {})",
+        project_id,
+        repair
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir);
+
+    let report = verify::verify_project(&storage, &project_id, session_id.as_deref(), repair)
+        .map_err(|e| format!("Failed to verify checkpoint storage: {}", e))?;
+
+    // Drop cached managers of repaired sessions so they reload the fixed timeline
+    if repair {
+        let repaired_sessions: std::collections::HashSet<&String> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.repaired)
+            .map(|issue| &issue.session_id)
+            .collect();
+        for session_id in repaired_sessions {
+            app.remove_manager(session_id).await;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Lists the checkpoint timelines of every session in a project, including past sessions
+#[tauri::command]
+pub async fn list_project_timelines(
+    project_id: String,
+) -> Result<Vec<crate::checkpoint::SessionTimeline>, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    log::info!("Listing checkpoint timelines for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir);
+
+    storage
+        .list_project_timelines(&project_id)
+        .map_err(|e| format!("Failed to list timelines: {}", e))
+}
+
+/// Lists checkpoints across all sessions of a project in chronological order
+#[tauri::command]
+pub async fn list_project_checkpoints(
+    project_id: String,
+) -> Result<Vec<crate::checkpoint::Checkpoint>, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    log::info!("Listing checkpoints for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir);
+
+    storage
+        .list_project_checkpoints(&project_id)
+        .map_err(|e| format!("Failed to list checkpoints: {}", e))
 }
 
 /// Gets the timeline for a session
@@ -1670,6 +2057,7 @@ pub async fn get_session_timeline(
 }
 
 /// Updates checkpoint settings for a session
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn update_checkpoint_settings(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1678,6 +2066,8 @@ pub async fn update_checkpoint_settings(
     project_path: String,
     auto_checkpoint_enabled: bool,
     checkpoint_strategy: String,
+    git_mirror_enabled: Option<bool>,
+    file_watcher_enabled: Option<bool>,
 ) -> Result<(), String> {
     use crate::checkpoint::CheckpointStrategy;
 
@@ -1704,7 +2094,23 @@ pub async fn update_checkpoint_settings(
     manager
         .update_settings(auto_checkpoint_enabled, strategy)
         .await
-        .map_err(|e| format!("Failed to update settings: {}", e))
+        .map_err(|e| format!("Failed to update settings: {}", e))?;
+
+    if let Some(enabled) = git_mirror_enabled {
+        manager
+            .set_git_mirror_enabled(enabled)
+            .await
+            .map_err(|e| format!("Failed to update git mirroring: {}", e))?;
+    }
+
+    if let Some(enabled) = file_watcher_enabled {
+        manager
+            .set_file_watcher_enabled(enabled)
+            .await
+            .map_err(|e| format!("Failed to update file watcher: {}", e))?;
+    }
+
+    Ok(())
 }
 
 /// Gets diff between two checkpoints
@@ -1774,7 +2180,7 @@ pub async fn get_checkpoint_diff(
     }
 
     // Check for added files
-    for (path, _) in &to_map {
+    for path in to_map.keys() {
         if !from_map.contains_key(path) {
             added_files.push(path.clone());
         }
@@ -1887,6 +2293,8 @@ pub async fn get_checkpoint_settings(
         "checkpoint_strategy": timeline.checkpoint_strategy,
         "total_checkpoints": timeline.total_checkpoints,
         "current_checkpoint_id": timeline.current_checkpoint_id,
+        "git_mirror_enabled": timeline.git_mirror_enabled,
+        "file_watcher_enabled": timeline.file_watcher_enabled,
     }))
 }
 
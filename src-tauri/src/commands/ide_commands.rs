@@ -2,8 +2,7 @@ use crate::bmad::{StateManager, types::*};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use tauri::Manager;
-use tracing::{debug, error, info, warn};
+use tracing::info;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IDEInfo {
@@ -1,3 +1,7 @@
 // BMAD Desktop simplified commands
+#[allow(unused, clippy::all)]
 pub mod bmad_commands;
 pub mod ide_commands;
+pub mod agents;
+pub mod claude;
+pub mod sandbox;
@@ -1,13 +1,21 @@
 use crate::{
     commands::agents::AgentDb,
     sandbox::{
+        environment,
+        learning::{self, RuleDiff},
+        limits::ResourceLimits,
         platform::PlatformCapabilities,
+        policy::{self, SandboxPolicy},
+        probe::{self, ProbeCheck},
         profile::{SandboxProfile, SandboxRule},
+        variables::{self, PathVariables},
+        violations::{self, TimeBucket, ViolationAnalytics, ViolationFilter},
     },
 };
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use std::path::Path;
+use tauri::{AppHandle, Manager, State};
 
 /// Represents a sandbox violation event
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +31,40 @@ pub struct SandboxViolation {
     pub denied_at: String,
 }
 
+/// Newest sandbox profile export format version
+///
+/// Version 2 added `file_write` and `file_create` rules, version 3 added
+/// `network_outbound` rules with the `domain` pattern type, version 4 added resource
+/// limits, version 5 added `environment` rules and version 6 added `{{NAME}}` variables
+/// in path patterns. Exports only use a newer version when they contain such rules or
+/// limits, so older builds reject them instead of silently dropping the restrictions.
+const SANDBOX_EXPORT_VERSION: u32 = 6;
+
+/// Export format version needed to represent the given profiles
+fn export_version(profiles: &[SandboxProfileWithRules]) -> u32 {
+    let rules = || profiles.iter().flat_map(|p| &p.rules);
+    if rules().any(|rule| variables::is_path_rule(rule) && rule.pattern_value.contains("{{")) {
+        SANDBOX_EXPORT_VERSION
+    } else if rules().any(|rule| rule.operation_type == environment::ENVIRONMENT_OPERATION) {
+        5
+    } else if profiles
+        .iter()
+        .any(|p| !p.profile.resource_limits.is_unlimited())
+    {
+        4
+    } else if rules()
+        .any(|rule| rule.operation_type == "network_outbound" && rule.pattern_type == "domain")
+    {
+        3
+    } else if rules()
+        .any(|rule| matches!(rule.operation_type.as_str(), "file_write" | "file_create"))
+    {
+        2
+    } else {
+        1
+    }
+}
+
 /// Represents sandbox profile export data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SandboxProfileExport {
@@ -48,13 +90,65 @@ pub struct ImportResult {
     pub new_name: Option<String>,
 }
 
+/// Outcome of running the sandbox probe under a profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxTestReport {
+    pub profile_name: String,
+    pub platform: String,
+    /// Number of effective rules, including inherited ones
+    pub rule_count: usize,
+    pub summary: String,
+    /// Environment of sandboxed processes, with secrets redacted
+    pub environment: String,
+    /// One entry per probed operation
+    pub checks: Vec<ProbeCheck>,
+    /// Operations whose outcome contradicts the rules
+    pub mismatches: usize,
+}
+
+/// Profile proposed from what a learning run touched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxLearningProposal {
+    pub run_id: i64,
+    pub observed_paths: usize,
+    pub observed_endpoints: usize,
+    /// Proposed rules compared to the agent's current profile
+    pub diff: RuleDiff,
+    /// The proposal, ready for `import_sandbox_profiles`
+    pub export: SandboxProfileExport,
+}
+
+/// What a violation kill threshold override applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdScope {
+    Agent,
+    Profile,
+}
+
+impl ThresholdScope {
+    fn table(self) -> &'static str {
+        match self {
+            ThresholdScope::Agent => "agents",
+            ThresholdScope::Profile => "sandbox_profiles",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ThresholdScope::Agent => "agent",
+            ThresholdScope::Profile => "sandbox profile",
+        }
+    }
+}
+
 /// List all sandbox profiles
 #[tauri::command]
 pub async fn list_sandbox_profiles(db: State<'_, AgentDb>) -> Result<Vec<SandboxProfile>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     let mut stmt = conn
-        .prepare("SELECT id, name, description, is_active, is_default, created_at, updated_at FROM sandbox_profiles ORDER BY name")
+        .prepare("SELECT id, name, description, is_active, is_default, created_at, updated_at, cpu_time_limit_secs, memory_limit_mb, max_processes, max_open_files, parent_id FROM sandbox_profiles ORDER BY name")
         .map_err(|e| e.to_string())?;
 
     let profiles = stmt
@@ -67,6 +161,8 @@ pub async fn list_sandbox_profiles(db: State<'_, AgentDb>) -> Result<Vec<Sandbox
                 is_default: row.get(4)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
+                resource_limits: ResourceLimits::from_row(row, 7)?,
+                parent_id: row.get(11)?,
             })
         })
         .map_err(|e| e.to_string())?
@@ -96,7 +192,7 @@ pub async fn create_sandbox_profile(
     // Fetch the created profile
     let profile = conn
         .query_row(
-            "SELECT id, name, description, is_active, is_default, created_at, updated_at FROM sandbox_profiles WHERE id = ?1",
+            "SELECT id, name, description, is_active, is_default, created_at, updated_at, cpu_time_limit_secs, memory_limit_mb, max_processes, max_open_files, parent_id FROM sandbox_profiles WHERE id = ?1",
             params![id],
             |row| {
                 Ok(SandboxProfile {
@@ -107,6 +203,8 @@ pub async fn create_sandbox_profile(
                     is_default: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    resource_limits: ResourceLimits::from_row(row, 7)?,
+                    parent_id: row.get(11)?,
                 })
             },
         )
@@ -145,7 +243,7 @@ pub async fn update_sandbox_profile(
     // Fetch the updated profile
     let profile = conn
         .query_row(
-            "SELECT id, name, description, is_active, is_default, created_at, updated_at FROM sandbox_profiles WHERE id = ?1",
+            "SELECT id, name, description, is_active, is_default, created_at, updated_at, cpu_time_limit_secs, memory_limit_mb, max_processes, max_open_files, parent_id FROM sandbox_profiles WHERE id = ?1",
             params![id],
             |row| {
                 Ok(SandboxProfile {
@@ -156,6 +254,8 @@ pub async fn update_sandbox_profile(
                     is_default: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    resource_limits: ResourceLimits::from_row(row, 7)?,
+                    parent_id: row.get(11)?,
                 })
             },
         )
@@ -164,6 +264,65 @@ pub async fn update_sandbox_profile(
     Ok(profile)
 }
 
+/// Set the resource limits of a sandbox profile
+#[tauri::command]
+pub async fn set_sandbox_profile_limits(
+    db: State<'_, AgentDb>,
+    id: i64,
+    limits: ResourceLimits,
+) -> Result<SandboxProfile, String> {
+    limits.validate().map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let to_sql = |value: Option<u64>| value.map(|v| v.min(i64::MAX as u64) as i64);
+    conn.execute(
+        "UPDATE sandbox_profiles SET cpu_time_limit_secs = ?1, memory_limit_mb = ?2, max_processes = ?3, max_open_files = ?4 WHERE id = ?5",
+        params![
+            to_sql(limits.cpu_time_secs),
+            to_sql(limits.memory_mb),
+            to_sql(limits.max_processes),
+            to_sql(limits.max_open_files),
+            id
+        ],
+    )
+    .map_err(|e| format!("Failed to update resource limits: {}", e))?;
+
+    crate::sandbox::profile::load_profile(&conn, id).map_err(|e| e.to_string())
+}
+
+/// Make a sandbox profile inherit the rules of another, or stop inheriting with `None`
+#[tauri::command]
+pub async fn set_sandbox_profile_parent(
+    db: State<'_, AgentDb>,
+    id: i64,
+    parent_id: Option<i64>,
+) -> Result<SandboxProfile, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    if let Some(parent_id) = parent_id {
+        crate::sandbox::profile::check_parent(&conn, id, parent_id).map_err(|e| e.to_string())?;
+    }
+
+    conn.execute(
+        "UPDATE sandbox_profiles SET parent_id = ?1 WHERE id = ?2",
+        params![parent_id, id],
+    )
+    .map_err(|e| format!("Failed to update parent profile: {}", e))?;
+
+    crate::sandbox::profile::load_profile(&conn, id).map_err(|e| e.to_string())
+}
+
+/// List the rules a sandbox profile ends up with, including inherited ones
+#[tauri::command]
+pub async fn get_effective_sandbox_rules(
+    db: State<'_, AgentDb>,
+    profile_id: i64,
+) -> Result<Vec<SandboxRule>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::sandbox::profile::load_effective_rules(&conn, profile_id)
+        .map_err(|e| format!("Failed to resolve profile rules: {}", e))
+}
+
 /// Delete a sandbox profile
 #[tauri::command]
 pub async fn delete_sandbox_profile(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
@@ -182,6 +341,27 @@ pub async fn delete_sandbox_profile(db: State<'_, AgentDb>, id: i64) -> Result<(
         return Err("Cannot delete the default profile".to_string());
     }
 
+    let children: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sandbox_profiles WHERE parent_id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    if children > 0 {
+        return Err(format!(
+            "Cannot delete a profile that {} other profile(s) inherit from",
+            children
+        ));
+    }
+
+    // Agents using the profile go back to the default one
+    conn.execute(
+        "UPDATE agents SET sandbox_profile_id = NULL WHERE sandbox_profile_id = ?1",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
     conn.execute("DELETE FROM sandbox_profiles WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
 
@@ -198,7 +378,7 @@ pub async fn get_sandbox_profile(
 
     let profile = conn
         .query_row(
-            "SELECT id, name, description, is_active, is_default, created_at, updated_at FROM sandbox_profiles WHERE id = ?1",
+            "SELECT id, name, description, is_active, is_default, created_at, updated_at, cpu_time_limit_secs, memory_limit_mb, max_processes, max_open_files, parent_id FROM sandbox_profiles WHERE id = ?1",
             params![id],
             |row| {
                 Ok(SandboxProfile {
@@ -209,6 +389,8 @@ pub async fn get_sandbox_profile(
                     is_default: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    resource_limits: ResourceLimits::from_row(row, 7)?,
+                    parent_id: row.get(11)?,
                 })
             },
         )
@@ -260,6 +442,7 @@ pub async fn create_sandbox_rule(
     enabled: bool,
     platform_support: Option<String>,
 ) -> Result<SandboxRule, String> {
+    variables::validate_pattern(&pattern_value).map_err(|e| e.to_string())?;
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     // Validate rule doesn't conflict
@@ -307,6 +490,7 @@ pub async fn update_sandbox_rule(
     enabled: bool,
     platform_support: Option<String>,
 ) -> Result<SandboxRule, String> {
+    variables::validate_pattern(&pattern_value).map_err(|e| e.to_string())?;
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     conn.execute(
@@ -355,146 +539,189 @@ pub async fn get_platform_capabilities() -> Result<PlatformCapabilities, String>
     Ok(crate::sandbox::platform::get_platform_capabilities())
 }
 
-/// Test a sandbox profile by creating a simple test process
+/// Test a sandbox profile by running a probe under it
+///
+/// The probe tries to read inside and outside the allowed paths, write and create files,
+/// connect to a local listener, read system info and spawn a subprocess. Each outcome is
+/// compared with what the profile's rules claim, so enforcement gaps of this platform
+/// show up as mismatches.
 #[tauri::command]
 pub async fn test_sandbox_profile(
+    app: AppHandle,
     db: State<'_, AgentDb>,
     profile_id: i64,
-) -> Result<String, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-
+) -> Result<SandboxTestReport, String> {
     // Load the profile and rules
-    let profile = crate::sandbox::profile::load_profile(&conn, profile_id)
-        .map_err(|e| format!("Failed to load profile: {}", e))?;
+    let (profile, rules) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let profile = crate::sandbox::profile::load_profile(&conn, profile_id)
+            .map_err(|e| format!("Failed to load profile: {}", e))?;
+        let rules = crate::sandbox::profile::load_effective_rules(&conn, profile_id)
+            .map_err(|e| format!("Failed to load profile rules: {}", e))?;
+        (profile, rules)
+    };
+
+    let platform_caps = crate::sandbox::platform::get_platform_capabilities();
+    let mut report = SandboxTestReport {
+        profile_name: profile.name.clone(),
+        platform: platform_caps.os.clone(),
+        rule_count: rules.len(),
+        summary: String::new(),
+        environment: String::new(),
+        checks: Vec::new(),
+        mismatches: 0,
+    };
 
     if !profile.is_active {
-        return Ok(format!(
+        report.summary = format!(
             "Profile '{}' is currently inactive. Activate it to use with agents.",
             profile.name
-        ));
+        );
+        return Ok(report);
     }
 
-    let rules = crate::sandbox::profile::load_profile_rules(&conn, profile_id)
-        .map_err(|e| format!("Failed to load profile rules: {}", e))?;
-
     if rules.is_empty() {
-        return Ok(format!(
+        report.summary = format!(
             "Profile '{}' has no rules configured. Add rules to define sandbox permissions.",
             profile.name
-        ));
+        );
+        return Ok(report);
     }
 
-    // Try to build the gaol profile
-    let test_path = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("/tmp"));
-
-    let builder = crate::sandbox::profile::ProfileBuilder::new(test_path.clone())
-        .map_err(|e| format!("Failed to create profile builder: {}", e))?;
-
-    let build_result = builder
-        .build_profile_with_serialization(rules.clone())
-        .map_err(|e| format!("Failed to build sandbox profile: {}", e))?;
-
-    // Check platform support
-    let platform_caps = crate::sandbox::platform::get_platform_capabilities();
     if !platform_caps.sandboxing_supported {
-        return Ok(format!(
+        report.summary = format!(
             "Profile '{}' validated successfully. {} rules loaded.\n\nNote: Sandboxing is not supported on {} platform. The profile configuration is valid but sandbox enforcement will not be active.",
             profile.name,
             rules.len(),
             platform_caps.os
-        ));
+        );
+        return Ok(report);
     }
 
-    // Try to execute a simple command in the sandbox
+    let probe = probe::probe_binary()
+        .ok_or_else(|| "Sandbox probe binary not found next to the application".to_string())?;
+
+    // Probe files live outside the temp directory, which sandboxes always allow
+    let root = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?
+        .join("sandbox-probe")
+        .join(format!(
+            "{}-{}",
+            profile_id,
+            chrono::Utc::now().timestamp_millis()
+        ));
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0))
+        .map_err(|e| format!("Failed to start probe listener: {}", e))?;
+    let connect = listener.local_addr().map_err(|e| e.to_string())?;
+
+    let result = probe::ProbeTargets::prepare(&root, connect)
+        .map_err(|e| format!("Failed to prepare probe files: {}", e));
+    let result = match result {
+        Ok(targets) => run_probe(&probe, rules, &targets, &mut report).await,
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_dir_all(&root);
+    result?;
+
+    Ok(report)
+}
+
+/// Run the probe under the profile built from `rules` and record its outcomes
+///
+/// The probe is killed if it runs longer than [`probe::PROBE_TIMEOUT`].
+async fn run_probe(
+    probe_path: &Path,
+    rules: Vec<SandboxRule>,
+    targets: &probe::ProbeTargets,
+    report: &mut SandboxTestReport,
+) -> Result<(), String> {
+    let builder = crate::sandbox::profile::ProfileBuilder::new(targets.project.clone())
+        .map_err(|e| format!("Failed to create profile builder: {}", e))?;
+    let build_result = builder
+        .build_profile_with_serialization(rules)
+        .map_err(|e| format!("Failed to build sandbox profile: {}", e))?;
+
+    report.checks = probe::planned_checks(&build_result.serialized, targets);
+
     let executor = crate::sandbox::executor::SandboxExecutor::new_with_serialization(
         build_result.profile,
-        test_path.clone(),
+        targets.project.clone(),
         build_result.serialized,
-    );
-
-    // Use a simple echo command for testing
-    let test_command = if cfg!(windows) { "cmd" } else { "echo" };
-
-    let test_args = if cfg!(windows) {
-        vec!["/C", "echo", "sandbox test successful"]
-    } else {
-        vec!["sandbox test successful"]
+    )
+    .with_env_policy(build_result.env_policy);
+    report.environment = describe_environment(&executor.effective_env());
+
+    let args: Vec<String> = targets.plan().into_iter().map(|(_, arg)| arg).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = match executor.prepare_sandboxed_std_command(
+        &probe_path.to_string_lossy(),
+        &args,
+        &targets.project,
+    ) {
+        Ok(command) => output_with_timeout(command, probe::PROBE_TIMEOUT).await,
+        Err(e) => Err(e.to_string()),
     };
 
-    match executor.execute_sandboxed_spawn(test_command, &test_args, &test_path) {
-        Ok(mut child) => {
-            // Wait for the process to complete with a timeout
-            match child.wait() {
-                Ok(status) => {
-                    if status.success() {
-                        Ok(format!(
-                            "✅ Profile '{}' tested successfully!\n\n\
-                            • {} rules loaded and validated\n\
-                            • Sandbox activation: Success\n\
-                            • Test process execution: Success\n\
-                            • Platform: {} (fully supported)",
-                            profile.name,
-                            rules.len(),
-                            platform_caps.os
-                        ))
-                    } else {
-                        Ok(format!(
-                            "⚠️ Profile '{}' validated with warnings.\n\n\
-                            • {} rules loaded and validated\n\
-                            • Sandbox activation: Success\n\
-                            • Test process exit code: {}\n\
-                            • Platform: {}",
-                            profile.name,
-                            rules.len(),
-                            status.code().unwrap_or(-1),
-                            platform_caps.os
-                        ))
-                    }
-                }
-                Err(e) => Ok(format!(
-                    "⚠️ Profile '{}' validated with warnings.\n\n\
-                        • {} rules loaded and validated\n\
-                        • Sandbox activation: Partial\n\
-                        • Test process: Could not get exit status ({})\n\
-                        • Platform: {}",
-                    profile.name,
-                    rules.len(),
-                    e,
-                    platform_caps.os
-                )),
-            }
-        }
-        Err(e) => {
-            // Check if it's a permission error or platform limitation
-            let error_str = e.to_string();
-            if error_str.contains("permission") || error_str.contains("denied") {
-                Ok(format!(
-                    "⚠️ Profile '{}' validated with limitations.\n\n\
-                    • {} rules loaded and validated\n\
-                    • Sandbox configuration: Valid\n\
-                    • Sandbox enforcement: Limited by system permissions\n\
-                    • Platform: {}\n\n\
-                    Note: The sandbox profile is correctly configured but may require elevated privileges or system configuration to fully enforce on this platform.",
-                    profile.name,
-                    rules.len(),
-                    platform_caps.os
-                ))
+    report.summary = match output {
+        Ok(output) => {
+            probe::apply_output(&mut report.checks, &String::from_utf8_lossy(&output.stdout));
+            report.mismatches = report
+                .checks
+                .iter()
+                .filter(|check| check.matches == Some(false))
+                .count();
+
+            if report.mismatches == 0 {
+                format!(
+                    "✅ Profile '{}' is enforced as configured on {}",
+                    report.profile_name, report.platform
+                )
             } else {
-                Ok(format!(
-                    "⚠️ Profile '{}' validated with limitations.\n\n\
-                    • {} rules loaded and validated\n\
-                    • Sandbox configuration: Valid\n\
-                    • Test execution: Failed ({})\n\
-                    • Platform: {}\n\n\
-                    The sandbox profile is correctly configured. The test execution failed due to platform-specific limitations, but the profile can still be used.",
-                    profile.name,
-                    rules.len(),
-                    e,
-                    platform_caps.os
-                ))
+                format!(
+                    "⚠️ {} of {} probed operations on {} did not match the rules of profile '{}'",
+                    report.mismatches,
+                    report.checks.len(),
+                    report.platform,
+                    report.profile_name
+                )
             }
         }
+        Err(e) => format!(
+            "⚠️ The probe could not run under profile '{}': {}",
+            report.profile_name, e
+        ),
+    };
+    Ok(())
+}
+
+/// Run `command` to completion, killing it once `timeout` has passed
+async fn output_with_timeout(
+    command: std::process::Command,
+    timeout: std::time::Duration,
+) -> Result<std::process::Output, String> {
+    let mut command = tokio::process::Command::from(command);
+    command.stdin(std::process::Stdio::null()).kill_on_drop(true);
+    let child = command.spawn().map_err(|e| e.to_string())?;
+
+    tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("timed out after {} seconds", timeout.as_secs()))?
+        .map_err(|e| e.to_string())
+}
+
+/// Describe the environment a sandboxed process gets, with secret values redacted
+fn describe_environment(env: &std::collections::BTreeMap<String, String>) -> String {
+    let mut description = format!("Effective environment ({} variables):", env.len());
+    for (name, value) in env {
+        description.push_str(&format!(
+            "\n• {}={}",
+            name,
+            environment::display_value(name, value)
+        ));
     }
+    description
 }
 
 /// List sandbox violations with optional filtering
@@ -704,6 +931,7 @@ pub async fn list_sandbox_violations(
 }
 
 /// Log a sandbox violation
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn log_sandbox_violation(
     db: State<'_, AgentDb>,
@@ -800,46 +1028,205 @@ pub async fn get_sandbox_violation_stats(
     }))
 }
 
+/// Get violation trends, the most denied paths and hosts, and first-seen violations
+///
+/// Defaults to hourly buckets over the last 24 hours and the top 10 targets.
+#[tauri::command]
+pub async fn get_sandbox_violation_analytics(
+    db: State<'_, AgentDb>,
+    bucket: Option<TimeBucket>,
+    agent_id: Option<i64>,
+    profile_id: Option<i64>,
+    since_hours: Option<i64>,
+    limit: Option<usize>,
+) -> Result<ViolationAnalytics, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let filter = ViolationFilter {
+        agent_id,
+        profile_id,
+        since_hours: Some(since_hours.unwrap_or(24)),
+    };
+
+    violations::violation_analytics(
+        &conn,
+        bucket.unwrap_or_default(),
+        &filter,
+        limit.unwrap_or(10),
+    )
+    .map_err(|e| format!("Failed to compute violation analytics: {}", e))
+}
+
+/// Get the violations per minute that stop a sandboxed run, None when disabled
+#[tauri::command]
+pub async fn get_sandbox_violation_kill_threshold(
+    db: State<'_, AgentDb>,
+) -> Result<Option<usize>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(violations::load_kill_threshold(&conn))
+}
+
+/// Set the violations per minute that stop a sandboxed run, None or 0 to disable
+#[tauri::command]
+pub async fn set_sandbox_violation_kill_threshold(
+    db: State<'_, AgentDb>,
+    threshold: Option<usize>,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    match threshold.filter(|threshold| *threshold > 0) {
+        Some(threshold) => conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![violations::KILL_THRESHOLD_SETTING, threshold.to_string()],
+        ),
+        None => conn.execute(
+            "DELETE FROM app_settings WHERE key = ?1",
+            params![violations::KILL_THRESHOLD_SETTING],
+        ),
+    }
+    .map_err(|e| format!("Failed to save violation threshold: {}", e))?;
+
+    Ok(())
+}
+
+/// Get the violations per minute that stop runs of an agent, or of agents using a sandbox
+/// profile, None when they follow the next level and 0 when stopping is disabled
+#[tauri::command]
+pub async fn get_violation_kill_threshold_override(
+    db: State<'_, AgentDb>,
+    scope: ThresholdScope,
+    id: i64,
+) -> Result<Option<usize>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let threshold: Option<i64> = conn
+        .query_row(
+            &format!(
+                "SELECT violation_kill_threshold FROM {} WHERE id = ?1",
+                scope.table()
+            ),
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to load violation threshold: {}", e))?;
+    Ok(threshold.and_then(|threshold| usize::try_from(threshold).ok()))
+}
+
+/// Set the violations per minute that stop runs of an agent, or of agents using a sandbox
+/// profile. None follows the next level, 0 disables stopping.
+///
+/// An agent's threshold wins over its profile's, which wins over the global one.
+#[tauri::command]
+pub async fn set_violation_kill_threshold_override(
+    db: State<'_, AgentDb>,
+    scope: ThresholdScope,
+    id: i64,
+    threshold: Option<usize>,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let threshold = threshold.map(|threshold| threshold.min(i64::MAX as usize) as i64);
+    let updated = conn
+        .execute(
+            &format!(
+                "UPDATE {} SET violation_kill_threshold = ?1 WHERE id = ?2",
+                scope.table()
+            ),
+            params![threshold, id],
+        )
+        .map_err(|e| format!("Failed to save violation threshold: {}", e))?;
+    if updated == 0 {
+        return Err(format!("No {} with id {}", scope.name(), id));
+    }
+
+    Ok(())
+}
+
+/// Get the global sandbox policy agents follow unless they set their own
+#[tauri::command]
+pub async fn get_sandbox_policy(db: State<'_, AgentDb>) -> Result<SandboxPolicy, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(policy::load_global_policy(&conn))
+}
+
+/// Set the global sandbox policy
+#[tauri::command]
+pub async fn set_sandbox_policy(
+    db: State<'_, AgentDb>,
+    policy: SandboxPolicy,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![policy::SANDBOX_POLICY_SETTING, policy.as_str()],
+    )
+    .map_err(|e| format!("Failed to save sandbox policy: {}", e))?;
+
+    Ok(())
+}
+
+/// Path variables absolute paths in exported rules are rewritten into
+fn export_variables(project_path: Option<String>) -> Result<PathVariables, String> {
+    PathVariables::current(project_path.map(std::path::PathBuf::from)).map_err(|e| e.to_string())
+}
+
 /// Export a single sandbox profile with its rules
+///
+/// Inherited rules are flattened into the profile, so it works the same when imported
+/// without its parents. Absolute paths under the home, temp or Cargo directory, or under
+/// `project_path` if given, are rewritten into `{{HOME}}`-style variables so the export
+/// works on other machines.
 #[tauri::command]
 pub async fn export_sandbox_profile(
     db: State<'_, AgentDb>,
     profile_id: i64,
+    project_path: Option<String>,
 ) -> Result<SandboxProfileExport, String> {
+    let variables = export_variables(project_path)?;
+
     // Get the profile
-    let profile = {
+    let mut profile = {
         let conn = db.0.lock().map_err(|e| e.to_string())?;
         crate::sandbox::profile::load_profile(&conn, profile_id).map_err(|e| e.to_string())?
     };
+    profile.parent_id = None;
 
     // Get the rules
-    let rules = list_sandbox_rules(db.clone(), profile_id).await?;
+    let rules =
+        variables.collapse_rules(get_effective_sandbox_rules(db.clone(), profile_id).await?);
+
+    let profiles = vec![SandboxProfileWithRules { profile, rules }];
 
     Ok(SandboxProfileExport {
-        version: 1,
+        version: export_version(&profiles),
         exported_at: chrono::Utc::now().to_rfc3339(),
         platform: std::env::consts::OS.to_string(),
-        profiles: vec![SandboxProfileWithRules { profile, rules }],
+        profiles,
     })
 }
 
 /// Export all sandbox profiles
+///
+/// Paths are rewritten into variables as in `export_sandbox_profile`.
 #[tauri::command]
 pub async fn export_all_sandbox_profiles(
     db: State<'_, AgentDb>,
+    project_path: Option<String>,
 ) -> Result<SandboxProfileExport, String> {
+    let variables = export_variables(project_path)?;
     let profiles = list_sandbox_profiles(db.clone()).await?;
     let mut profile_exports = Vec::new();
 
-    for profile in profiles {
+    for mut profile in profiles {
         if let Some(id) = profile.id {
-            let rules = list_sandbox_rules(db.clone(), id).await?;
+            let rules =
+                variables.collapse_rules(get_effective_sandbox_rules(db.clone(), id).await?);
+            profile.parent_id = None;
             profile_exports.push(SandboxProfileWithRules { profile, rules });
         }
     }
 
     Ok(SandboxProfileExport {
-        version: 1,
+        version: export_version(&profile_exports),
         exported_at: chrono::Utc::now().to_rfc3339(),
         platform: std::env::consts::OS.to_string(),
         profiles: profile_exports,
@@ -855,13 +1242,25 @@ pub async fn import_sandbox_profiles(
     let mut results = Vec::new();
 
     // Validate version
-    if export_data.version != 1 {
+    if export_data.version == 0 || export_data.version > SANDBOX_EXPORT_VERSION {
         return Err(format!(
             "Unsupported export version: {}",
             export_data.version
         ));
     }
 
+    // Reject the whole import rather than leave a profile without some of its rules
+    for profile_export in &export_data.profiles {
+        for rule in &profile_export.rules {
+            variables::validate_pattern(&rule.pattern_value).map_err(|e| {
+                format!(
+                    "Invalid rule in profile '{}': {}",
+                    profile_export.profile.name, e
+                )
+            })?;
+        }
+    }
+
     for profile_export in export_data.profiles {
         let mut profile = profile_export.profile;
         let original_name = profile.name.clone();
@@ -918,6 +1317,10 @@ pub async fn import_sandbox_profiles(
                     }
                 }
 
+                if !profile.resource_limits.is_unlimited() {
+                    set_sandbox_profile_limits(db.clone(), new_id, profile.resource_limits).await?;
+                }
+
                 // Update profile status if needed
                 if profile.is_active {
                     let _ = update_sandbox_profile(
@@ -945,3 +1348,98 @@ pub async fn import_sandbox_profiles(
 
     Ok(results)
 }
+
+/// Propose a minimal sandbox profile from the trace of a learning run
+///
+/// The proposal is diffed against the effective rules, inherited ones included, of the
+/// profile assigned to the run's agent, or the default profile if it has none. Nothing is saved until the returned export is
+/// imported.
+#[tauri::command]
+pub async fn propose_sandbox_profile(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    run_id: i64,
+) -> Result<SandboxLearningProposal, String> {
+    let (agent_id, agent_name, project_path): (i64, String, String) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT agent_id, agent_name, project_path FROM agent_runs WHERE id = ?1",
+            params![run_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Failed to load agent run {}: {}", run_id, e))?
+    };
+
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    let trace_path = learning::trace_path(&app_dir, run_id);
+    let trace = std::fs::read_to_string(&trace_path)
+        .map_err(|e| format!("Run {} has no learning trace: {}", run_id, e))?;
+
+    let project_path = Path::new(&project_path);
+    let home_dir = dirs::home_dir().ok_or("Failed to get home directory")?;
+    let observed = learning::parse_trace(&trace, project_path);
+    let proposed = learning::propose_rules(&observed, project_path, &home_dir);
+
+    let current = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let assigned: Option<i64> = conn
+            .query_row(
+                "SELECT sandbox_profile_id FROM agents WHERE id = ?1",
+                params![agent_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .flatten();
+        let current_profile_id = match assigned {
+            Some(id) => Some(id),
+            None => conn
+                .query_row(
+                    "SELECT id FROM sandbox_profiles WHERE is_default = 1 AND is_active = 1",
+                    [],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?,
+        };
+        match current_profile_id {
+            Some(id) => crate::sandbox::profile::load_effective_rules(&conn, id)
+                .map_err(|e| format!("Failed to resolve profile rules: {}", e))?,
+            None => Vec::new(),
+        }
+    };
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let profiles = vec![SandboxProfileWithRules {
+        profile: SandboxProfile {
+            id: None,
+            name: format!("{} (learned)", agent_name),
+            description: Some(format!("Learned from agent run {}", run_id)),
+            is_active: false,
+            is_default: false,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            resource_limits: ResourceLimits::default(),
+            parent_id: None,
+        },
+        rules: proposed.clone(),
+    }];
+
+    Ok(SandboxLearningProposal {
+        run_id,
+        observed_paths: observed.read_paths.len()
+            + observed.written_paths.len()
+            + observed.created_paths.len(),
+        observed_endpoints: observed.network_endpoints.len() + observed.local_sockets.len(),
+        diff: learning::diff_rules(&current, &proposed),
+        export: SandboxProfileExport {
+            version: export_version(&profiles),
+            exported_at: now,
+            platform: std::env::consts::OS.to_string(),
+            profiles,
+        },
+    })
+}
@@ -1,14 +1,317 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 // Declare modules
+#[allow(unused, clippy::all)]
 pub mod bmad;
 pub mod commands;
+#[allow(unused, clippy::all)]
 pub mod education;
+#[allow(unused, clippy::all)]
 pub mod academy;
+pub mod agents;
+pub mod checkpoint;
+pub mod claude_binary;
+pub mod process;
+pub mod sandbox;
+
+use checkpoint::state::CheckpointState;
+use commands::agents::{
+    init_database, AgentDb, add_agent_to_project, approve_agent_sandbox,
+    cleanup_finished_processes, create_agent,
+    delete_agent, diff_agent_versions, execute_agent, export_agent, export_agent_to_file,
+    fetch_github_agent_content, fetch_github_agents, get_agent, get_agent_cancel_grace_period,
+    get_agent_live_output_cap, get_agent_run, get_agent_run_with_real_time_metrics,
+    get_agent_version, get_claude_binary_path, get_live_session_output, get_run_queue,
+    get_session_output, get_session_status, import_agent, import_agent_from_file,
+    import_agent_from_github, import_preinstalled_agents, kill_agent_session, list_agent_runs,
+    list_agent_runs_with_metrics, list_agent_versions, list_agents, list_claude_installations,
+    list_running_sessions, move_queued_run, rollback_agent, set_agent_cancel_grace_period,
+    set_agent_live_output_cap, set_agent_sandbox_policy, set_claude_binary_path,
+    set_queued_run_priority, set_run_queue_limits, stream_session_output, sync_project_agents,
+    update_agent,
+};
+use commands::claude::{
+    ClaudeProcessState, cancel_claude_execution, check_auto_checkpoint, check_claude_version,
+    cleanup_old_checkpoints, clear_checkpoint_manager, continue_claude_code, create_checkpoint,
+    execute_claude_code, export_checkpoint_archive, find_claude_md_files, fork_from_checkpoint,
+    get_checkpoint_diff, get_checkpoint_settings, get_checkpoint_state_stats, get_claude_settings,
+    get_project_sessions, get_recently_modified_files, get_session_timeline, get_system_prompt,
+    get_uncheckpointed_changes, import_checkpoint_archive, list_checkpoints,
+    list_directory_contents, list_project_checkpoints, list_project_timelines, list_projects,
+    load_session_history, open_new_session, promote_checkpoint_to_branch, read_claude_md_file,
+    restore_checkpoint, resume_claude_code, save_claude_md_file, save_claude_settings,
+    save_system_prompt, search_checkpoints, search_files, track_checkpoint_message,
+    track_session_messages, update_checkpoint_annotations, update_checkpoint_settings,
+    verify_checkpoint_storage,
+};
+use commands::sandbox::{
+    clear_sandbox_violations, create_sandbox_profile, create_sandbox_rule, delete_sandbox_profile,
+    delete_sandbox_rule, export_all_sandbox_profiles, export_sandbox_profile,
+    get_effective_sandbox_rules, get_platform_capabilities, get_sandbox_policy, get_sandbox_profile,
+    get_sandbox_violation_analytics, get_sandbox_violation_kill_threshold,
+    get_sandbox_violation_stats, get_violation_kill_threshold_override, import_sandbox_profiles,
+    list_sandbox_profiles, list_sandbox_rules, list_sandbox_violations, log_sandbox_violation,
+    propose_sandbox_profile, set_sandbox_policy, set_sandbox_profile_limits,
+    set_sandbox_profile_parent, set_sandbox_violation_kill_threshold,
+    set_violation_kill_threshold_override, test_sandbox_profile, update_sandbox_profile,
+    update_sandbox_rule,
+};
+use education::EducationDB;
+use education::commands::{
+    get_education_sessions, start_education_session, complete_education_session,
+    reset_education_progress, initialize_education_system,
+};
+use academy::commands::{
+    get_academy_modules, get_academy_lessons, get_lesson_with_progress,
+    start_academy_lesson, complete_academy_lesson, submit_exercise_solution,
+    get_user_academy_stats, get_user_achievements, initialize_academy_system,
+    test_academy_database, initialize_academy_database, get_academy_stats,
+};
+use bmad::{ProjectManager};
+use commands::bmad_commands::*;
+use commands::ide_commands::*;
+use process::ProcessRegistryState;
+use std::sync::Mutex;
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Initialize logger
+    tracing_subscriber::fmt::init();
+
+    // A sandboxed child re-executes this binary with the sandbox markers set
+    if sandbox::should_activate_sandbox() {
+        if let Err(e) = sandbox::SandboxExecutor::activate_sandbox_in_child() {
+            tracing::error!("Failed to activate sandbox: {}", e);
+            std::process::exit(1);
+        }
+    }
+
     tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .setup(|app| {
+            // Initialize BMAD Project Manager
+            let project_manager = ProjectManager::new()
+                .expect("Failed to initialize BMAD Project Manager");
+            app.manage(Mutex::new(project_manager));
+
+            // Initialize education database
+            let app_data_dir = app.path().app_data_dir()
+                .expect("Failed to get app data directory");
+            
+            // Ensure the app data directory exists
+            std::fs::create_dir_all(&app_data_dir)
+                .expect("Failed to create app data directory");
+                
+            let education_db_path = app_data_dir.join("education.db");
+            let education_db = EducationDB::new(education_db_path)
+                .expect("Failed to initialize education database");
+            
+            // Initialize sessions on startup
+            education_db.initialize_sessions()
+                .expect("Failed to initialize education sessions");
+                
+            app.manage(Mutex::new(education_db));
+
+            // Initialize academy system
+            if let Err(e) = academy::get_connection().and_then(|conn| academy::seed_academy_content(&conn)) {
+                tracing::error!("Failed to initialize academy system: {}", e);
+            }
+
+            // Initialize agents database
+            let conn = init_database(app.handle()).expect("Failed to initialize agents database");
+            app.manage(AgentDb(Mutex::new(conn)));
+
+            // Initialize checkpoint state
+            let checkpoint_state = CheckpointState::new();
+            if let Some(claude_dir) = dirs::home_dir().map(|home| home.join(".claude")) {
+                let state = checkpoint_state.clone();
+                tauri::async_runtime::spawn(async move {
+                    state.set_claude_dir(claude_dir).await;
+                });
+            }
+            app.manage(checkpoint_state);
+
+            app.manage(ProcessRegistryState::default());
+            app.manage(ClaudeProcessState::default());
+
+            // Settle the runs an earlier instance left behind, which also starts queued runs
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = commands::agents::reconcile_orphaned_runs(&handle).await {
+                    tracing::error!("Failed to reconcile agent runs: {}", e);
+                }
+            });
+
+            tracing::info!("BMAD Desktop initialized successfully");
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            // BMAD Desktop Commands
+            discover_bmad_projects,
+            create_bmad_project,
+            list_bmad_projects,
+            get_bmad_project,
+            set_active_bmad_project,
+            get_active_bmad_project,
+            delete_bmad_project,
+            get_agent_recommendations,
+            transition_bmad_phase,
+            start_story,
+            complete_story,
+            report_blocker,
+            get_workflow_summary,
+            get_project_messages,
+            send_agent_message,
+            mark_message_read,
+            launch_ide_with_context,
+            detect_installed_ides,
+            get_ide_preferences,
+            save_ide_preferences,
+            set_default_ide,
+            get_agent_ide_recommendation,
+            validate_bmad_project,
+            create_bmad_structure,
+            get_project_statistics,
+            // Education system (keep existing)
+            get_education_sessions,
+            start_education_session,
+            complete_education_session,
+            reset_education_progress,
+            initialize_education_system,
+            // Academy system (keep existing)
+            get_academy_modules,
+            get_academy_lessons,
+            get_lesson_with_progress,
+            start_academy_lesson,
+            complete_academy_lesson,
+            submit_exercise_solution,
+            get_user_academy_stats,
+            get_user_achievements,
+            initialize_academy_system,
+            test_academy_database,
+            initialize_academy_database,
+            get_academy_stats,
+            // Agents
+            add_agent_to_project,
+            approve_agent_sandbox,
+            cleanup_finished_processes,
+            create_agent,
+            delete_agent,
+            diff_agent_versions,
+            execute_agent,
+            export_agent,
+            export_agent_to_file,
+            fetch_github_agent_content,
+            fetch_github_agents,
+            get_agent,
+            get_agent_cancel_grace_period,
+            get_agent_live_output_cap,
+            get_agent_run,
+            get_agent_run_with_real_time_metrics,
+            get_agent_version,
+            get_claude_binary_path,
+            get_live_session_output,
+            get_run_queue,
+            get_session_output,
+            get_session_status,
+            import_agent,
+            import_agent_from_file,
+            import_agent_from_github,
+            import_preinstalled_agents,
+            kill_agent_session,
+            list_agent_runs,
+            list_agent_runs_with_metrics,
+            list_agent_versions,
+            list_agents,
+            list_claude_installations,
+            list_running_sessions,
+            move_queued_run,
+            rollback_agent,
+            set_agent_cancel_grace_period,
+            set_agent_live_output_cap,
+            set_agent_sandbox_policy,
+            set_claude_binary_path,
+            set_queued_run_priority,
+            set_run_queue_limits,
+            stream_session_output,
+            sync_project_agents,
+            update_agent,
+            // Claude sessions and checkpoints
+            cancel_claude_execution,
+            check_auto_checkpoint,
+            check_claude_version,
+            cleanup_old_checkpoints,
+            clear_checkpoint_manager,
+            continue_claude_code,
+            create_checkpoint,
+            execute_claude_code,
+            export_checkpoint_archive,
+            find_claude_md_files,
+            fork_from_checkpoint,
+            get_checkpoint_diff,
+            get_checkpoint_settings,
+            get_checkpoint_state_stats,
+            get_claude_settings,
+            get_project_sessions,
+            get_recently_modified_files,
+            get_session_timeline,
+            get_system_prompt,
+            get_uncheckpointed_changes,
+            import_checkpoint_archive,
+            list_checkpoints,
+            list_directory_contents,
+            list_project_checkpoints,
+            list_project_timelines,
+            list_projects,
+            load_session_history,
+            open_new_session,
+            promote_checkpoint_to_branch,
+            read_claude_md_file,
+            restore_checkpoint,
+            resume_claude_code,
+            save_claude_md_file,
+            save_claude_settings,
+            save_system_prompt,
+            search_checkpoints,
+            search_files,
+            track_checkpoint_message,
+            track_session_messages,
+            update_checkpoint_annotations,
+            update_checkpoint_settings,
+            verify_checkpoint_storage,
+            // Sandbox
+            clear_sandbox_violations,
+            create_sandbox_profile,
+            create_sandbox_rule,
+            delete_sandbox_profile,
+            delete_sandbox_rule,
+            export_all_sandbox_profiles,
+            export_sandbox_profile,
+            get_effective_sandbox_rules,
+            get_platform_capabilities,
+            get_sandbox_policy,
+            get_sandbox_profile,
+            get_sandbox_violation_analytics,
+            get_sandbox_violation_kill_threshold,
+            get_sandbox_violation_stats,
+            get_violation_kill_threshold_override,
+            import_sandbox_profiles,
+            list_sandbox_profiles,
+            list_sandbox_rules,
+            list_sandbox_violations,
+            log_sandbox_violation,
+            propose_sandbox_profile,
+            set_sandbox_policy,
+            set_sandbox_profile_limits,
+            set_sandbox_profile_parent,
+            set_sandbox_violation_kill_threshold,
+            set_violation_kill_threshold_override,
+            test_sandbox_profile,
+            update_sandbox_profile,
+            update_sandbox_rule
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
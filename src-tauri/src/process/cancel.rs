@@ -0,0 +1,243 @@
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use std::time::Duration;
+
+/// `app_settings` key holding how many seconds a cancelled run gets to exit
+pub const CANCEL_GRACE_SETTING: &str = "agent_cancel_grace_secs";
+
+/// Time a cancelled run gets to exit before it is killed, unless configured otherwise
+pub const DEFAULT_CANCEL_GRACE: Duration = Duration::from_secs(10);
+
+/// How often a terminating process is checked for having exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How a cancelled process went away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// Everything in its process group exited within the grace period
+    Exited,
+    /// Something was still running after the grace period and got killed
+    Killed,
+}
+
+/// Load the grace period, falling back to the default
+pub fn load_cancel_grace(conn: &Connection) -> Duration {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![CANCEL_GRACE_SETTING],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .and_then(|value| value.parse().ok())
+    .map(Duration::from_secs)
+    .unwrap_or(DEFAULT_CANCEL_GRACE)
+}
+
+/// Store the grace period, in whole seconds
+pub fn save_cancel_grace(conn: &Connection, grace: Duration) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![CANCEL_GRACE_SETTING, grace.as_secs().to_string()],
+    )?;
+    Ok(())
+}
+
+/// Ask the process group led by `pid` to exit, killing it if it hasn't after `grace`
+///
+/// Agents are spawned in a process group of their own, so the tools and builds they start
+/// get the signals too. `leader_exited` tells whether `pid` itself is gone, and should reap
+/// it when it is our child.
+pub async fn terminate_group(
+    pid: u32,
+    grace: Duration,
+    mut leader_exited: impl FnMut() -> bool,
+) -> Termination {
+    info!(
+        "Sending SIGTERM to process group {}, grace period {:?}",
+        pid, grace
+    );
+    signal_group(pid, Signal::Terminate);
+
+    let deadline = tokio::time::Instant::now() + grace;
+    loop {
+        if leader_exited() && !group_alive(pid) {
+            info!("Process group {} exited after SIGTERM", pid);
+            return Termination::Exited;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            break;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+
+    warn!(
+        "Process group {} still running after {:?}, sending SIGKILL",
+        pid, grace
+    );
+    signal_group(pid, Signal::Kill);
+    // Give the leader a moment to be reaped
+    for _ in 0..10 {
+        if leader_exited() {
+            break;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+    Termination::Killed
+}
+
+/// Whether a process with `pid` exists
+pub fn is_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        exists(pid as libc::pid_t)
+    }
+
+    #[cfg(windows)]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid)))
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Signal {
+    Terminate,
+    Kill,
+}
+
+/// Signal every process in the group led by `pid`, or just `pid` when it leads none
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: Signal) {
+    let signal = match signal {
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    let pid = pid as libc::pid_t;
+    if pid <= 0 {
+        return;
+    }
+    // Runs spawned before agents got their own process group only have the one process
+    if unsafe { libc::kill(-pid, signal) } != 0 {
+        unsafe { libc::kill(pid, signal) };
+    }
+}
+
+/// Signal the process tree of `pid`
+///
+/// Windows has no SIGTERM for console programs, so the tree is asked to close and then
+/// killed.
+#[cfg(windows)]
+fn signal_group(pid: u32, signal: Signal) {
+    let mut args = vec!["/T", "/PID"];
+    if matches!(signal, Signal::Kill) {
+        args.insert(0, "/F");
+    }
+    let _ = std::process::Command::new("taskkill")
+        .args(&args)
+        .arg(pid.to_string())
+        .output();
+}
+
+/// Whether anything is left in the process group led by `pid`
+fn group_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        exists(-(pid as libc::pid_t))
+    }
+
+    #[cfg(windows)]
+    {
+        is_alive(pid)
+    }
+}
+
+/// Whether `kill(target, 0)` finds a process, for a PID or a negated group ID
+#[cfg(unix)]
+fn exists(target: libc::pid_t) -> bool {
+    if unsafe { libc::kill(target, 0) } == 0 {
+        return true;
+    }
+    // Someone else's process still exists
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grace_falls_back_to_default() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(load_cancel_grace(&conn), DEFAULT_CANCEL_GRACE);
+
+        save_cancel_grace(&conn, Duration::from_secs(3)).unwrap();
+        assert_eq!(load_cancel_grace(&conn), Duration::from_secs(3));
+    }
+
+    /// Spawn `script` in a process group of its own, once it has set up its traps
+    ///
+    /// The shell keeps a short `sleep` in the foreground rather than waiting on a long one
+    /// in the background. A signal landing between forking and exec'ing a `sleep 30` leaves
+    /// it running, and `wait` can miss a trap that is already pending.
+    #[cfg(unix)]
+    fn spawn_group(script: &str) -> std::process::Child {
+        use std::io::BufRead;
+        use std::os::unix::process::CommandExt;
+
+        let mut child = std::process::Command::new("sh")
+            .args([
+                "-c",
+                &format!("{}; echo ready; while :; do sleep 0.1; done", script),
+            ])
+            .stdout(std::process::Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut ready = String::new();
+        std::io::BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut ready)
+            .unwrap();
+        child
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminate_group_lets_processes_exit() {
+        let mut child = spawn_group("trap 'exit 0' TERM");
+        let pid = child.id();
+
+        let termination = terminate_group(pid, Duration::from_secs(5), || {
+            matches!(child.try_wait(), Ok(Some(_)))
+        })
+        .await;
+
+        assert_eq!(termination, Termination::Exited);
+        assert!(!group_alive(pid));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminate_group_kills_after_grace() {
+        let mut child = spawn_group("trap '' TERM");
+        let pid = child.id();
+
+        let termination = terminate_group(pid, Duration::from_millis(300), || {
+            matches!(child.try_wait(), Ok(Some(_)))
+        })
+        .await;
+
+        assert_eq!(termination, Termination::Killed);
+        assert!(matches!(child.try_wait(), Ok(Some(_))));
+    }
+}
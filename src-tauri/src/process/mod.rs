@@ -1,3 +1,7 @@
+pub mod cancel;
+pub mod orphans;
+pub mod output;
+pub mod queue;
 pub mod registry;
 
 pub use registry::*;
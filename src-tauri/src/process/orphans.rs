@@ -0,0 +1,333 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// How far the start time the OS reports may be from the one recorded at spawn
+///
+/// The run is recorded right after spawning, and Linux only reports boot time to the second.
+const START_TIME_TOLERANCE_SECS: i64 = 5;
+
+/// What became of the process of a run recorded as running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    /// The process that was spawned for the run is still running
+    Alive,
+    /// Nothing runs under the PID, or a later process reuses it
+    Gone,
+    /// The platform can't tell the process apart from one reusing its PID, or a process
+    /// runs under the PID but its start time can't be read, e.g. with `/proc` mounted
+    /// with `hidepid`
+    Unknown,
+}
+
+/// How a run that finished while nobody was watching ended, according to its session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome {
+    Completed,
+    Failed(String),
+}
+
+/// Check whether `pid` is still the process spawned at `recorded_start`, an RFC 3339 time
+pub fn check_process(pid: u32, recorded_start: &str) -> Liveness {
+    let Ok(recorded) = DateTime::parse_from_rfc3339(recorded_start) else {
+        return Liveness::Unknown;
+    };
+
+    if !cfg!(any(target_os = "linux", target_os = "macos")) {
+        return Liveness::Unknown;
+    }
+    match process_start_time(pid) {
+        Some(started) if is_same_process(started, recorded.with_timezone(&Utc)) => Liveness::Alive,
+        Some(_) => Liveness::Gone,
+        None if super::cancel::is_alive(pid) => Liveness::Unknown,
+        None => Liveness::Gone,
+    }
+}
+
+/// Whether a process the OS says started at `os_start` is the one recorded at `recorded`
+pub fn is_same_process(os_start: DateTime<Utc>, recorded: DateTime<Utc>) -> bool {
+    (recorded - os_start).num_seconds().abs() <= START_TIME_TOLERANCE_SECS
+}
+
+/// When the process with `pid` started, if it exists
+#[cfg(target_os = "linux")]
+pub fn process_start_time(pid: u32) -> Option<DateTime<Utc>> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name in parentheses may itself contain spaces and parentheses
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    // `starttime` is the 22nd field, the 20th after the command name
+    let start_ticks: i64 = fields.get(19)?.parse().ok()?;
+
+    let boot_time: i64 = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+
+    let millis = boot_time * 1000 + start_ticks * 1000 / ticks_per_sec as i64;
+    DateTime::from_timestamp_millis(millis)
+}
+
+/// When the process with `pid` started, if it exists
+#[cfg(target_os = "macos")]
+pub fn process_start_time(pid: u32) -> Option<DateTime<Utc>> {
+    use chrono::{Local, NaiveDateTime, TimeZone};
+
+    let output = std::process::Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // For example `Sat Oct 18 09:05:01 2026`, in local time
+    let started = String::from_utf8_lossy(&output.stdout);
+    let started = NaiveDateTime::parse_from_str(started.trim(), "%a %b %e %H:%M:%S %Y").ok()?;
+    Local
+        .from_local_datetime(&started)
+        .single()
+        .map(|started| started.with_timezone(&Utc))
+}
+
+/// When the process with `pid` started, if it exists
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn process_start_time(_pid: u32) -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Work out how a run ended from its session JSONL
+///
+/// A run finished when Claude reported a result or its last message ended the turn. Anything
+/// else means the process went away in the middle of the run.
+pub fn final_state(jsonl: &str) -> RunOutcome {
+    let mut last_message = None;
+    for line in jsonl.lines() {
+        let Ok(entry) = serde_json::from_str::<JsonValue>(line) else {
+            continue;
+        };
+        match entry.get("type").and_then(|t| t.as_str()) {
+            Some("result") => {
+                let failed = entry.get("is_error").and_then(|e| e.as_bool()) == Some(true);
+                let subtype = entry
+                    .get("subtype")
+                    .and_then(|s| s.as_str())
+                    .unwrap_or("success");
+                if failed || subtype != "success" {
+                    return RunOutcome::Failed(format!("Claude reported {}", subtype));
+                }
+                return RunOutcome::Completed;
+            }
+            Some("user") | Some("assistant") => last_message = Some(entry),
+            _ => {}
+        }
+    }
+
+    let Some(last_message) = last_message else {
+        return RunOutcome::Failed("The session has no messages".to_string());
+    };
+    let message = last_message.get("message");
+    let is_assistant = last_message.get("type").and_then(|t| t.as_str()) == Some("assistant");
+    let ended_turn = matches!(
+        message
+            .and_then(|m| m.get("stop_reason"))
+            .and_then(|s| s.as_str()),
+        Some("end_turn") | Some("stop_sequence")
+    );
+    let uses_tool = message
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .is_some_and(|content| {
+            content
+                .iter()
+                .any(|block| block.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+        });
+
+    if is_assistant && ended_turn && !uses_tool {
+        RunOutcome::Completed
+    } else {
+        RunOutcome::Failed("The run stopped before Claude finished its turn".to_string())
+    }
+}
+
+/// Follows a session JSONL as it grows, a whole line at a time
+pub struct JsonlTail {
+    path: PathBuf,
+    offset: u64,
+}
+
+impl JsonlTail {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, offset: 0 }
+    }
+
+    /// Follows only the lines appended after the last whole line already in the file
+    pub fn from_end(path: PathBuf) -> std::io::Result<Self> {
+        let mut file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new(path)),
+            Err(e) => return Err(e),
+        };
+
+        // Step back over a line still being written, a block at a time
+        let mut end = file.metadata()?.len();
+        let mut block = [0u8; 8192];
+        let offset = loop {
+            if end == 0 {
+                break 0;
+            }
+            let start = end.saturating_sub(block.len() as u64);
+            let block = &mut block[..(end - start) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(block)?;
+            if let Some(newline) = block.iter().rposition(|b| *b == b'\n') {
+                break start + newline as u64 + 1;
+            }
+            end = start;
+        };
+        Ok(Self { path, offset })
+    }
+
+    /// Lines appended since the last call
+    ///
+    /// A line still being written is left for the next call. A missing file has no lines
+    /// yet.
+    pub fn read_new_lines(&mut self) -> std::io::Result<Vec<String>> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+
+        let Some(end) = appended.iter().rposition(|b| *b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        self.offset += end as u64 + 1;
+        Ok(String::from_utf8_lossy(&appended[..end])
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_is_same_process_allows_spawn_delay() {
+        let recorded = DateTime::parse_from_rfc3339("2026-10-18T09:00:02Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let started = DateTime::parse_from_rfc3339("2026-10-18T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(is_same_process(started, recorded));
+
+        let reused = DateTime::parse_from_rfc3339("2026-10-18T11:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(!is_same_process(reused, recorded));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_own_process_is_alive() {
+        let started = process_start_time(std::process::id()).unwrap();
+        assert_eq!(
+            check_process(std::process::id(), &started.to_rfc3339()),
+            Liveness::Alive
+        );
+        assert_eq!(
+            check_process(std::process::id(), "2001-01-01T00:00:00Z"),
+            Liveness::Gone
+        );
+        assert_eq!(
+            check_process(std::process::id(), "yesterday"),
+            Liveness::Unknown
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exited_process_is_gone() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        assert!(!crate::process::cancel::is_alive(pid));
+        assert_eq!(check_process(pid, &Utc::now().to_rfc3339()), Liveness::Gone);
+    }
+
+    #[test]
+    fn test_final_state() {
+        let finished = r#"{"type":"user","message":{"role":"user","content":"Fix it"}}
+{"type":"assistant","message":{"stop_reason":"end_turn","content":[{"type":"text","text":"Done"}]}}
+{"type":"summary","summary":"Fixed it"}"#;
+        assert_eq!(final_state(finished), RunOutcome::Completed);
+
+        let mid_tool = r#"{"type":"assistant","message":{"stop_reason":"tool_use","content":[{"type":"tool_use","name":"Bash"}]}}"#;
+        assert!(matches!(final_state(mid_tool), RunOutcome::Failed(_)));
+
+        let errored = r#"{"type":"assistant","message":{"stop_reason":"end_turn","content":[]}}
+{"type":"result","subtype":"error_max_turns","is_error":true}"#;
+        assert_eq!(
+            final_state(errored),
+            RunOutcome::Failed("Claude reported error_max_turns".to_string())
+        );
+
+        assert!(matches!(final_state(""), RunOutcome::Failed(_)));
+    }
+
+    #[test]
+    fn test_tail_returns_whole_lines_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let mut tail = JsonlTail::new(path.clone());
+        assert!(tail.read_new_lines().unwrap().is_empty());
+
+        let mut file = std::fs::File::create(&path).unwrap();
+        write!(file, "{{\"a\":1}}\n{{\"b\":").unwrap();
+        assert_eq!(tail.read_new_lines().unwrap(), vec!["{\"a\":1}"]);
+        assert!(tail.read_new_lines().unwrap().is_empty());
+
+        writeln!(file, "2}}").unwrap();
+        assert_eq!(tail.read_new_lines().unwrap(), vec!["{\"b\":2}"]);
+    }
+
+    #[test]
+    fn test_tail_from_end_skips_existing_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        assert!(JsonlTail::from_end(path.clone())
+            .unwrap()
+            .read_new_lines()
+            .unwrap()
+            .is_empty());
+
+        let mut file = std::fs::File::create(&path).unwrap();
+        write!(file, "{{\"a\":1}}\n{{\"b\":").unwrap();
+        let mut tail = JsonlTail::from_end(path).unwrap();
+        assert!(tail.read_new_lines().unwrap().is_empty());
+
+        writeln!(file, "2}}\n{{\"c\":3}}").unwrap();
+        assert_eq!(
+            tail.read_new_lines().unwrap(),
+            vec!["{\"b\":2}", "{\"c\":3}"]
+        );
+    }
+}
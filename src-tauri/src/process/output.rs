@@ -0,0 +1,522 @@
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// `app_settings` key holding how many bytes of live output a run keeps in memory
+pub const LIVE_OUTPUT_CAP_SETTING: &str = "agent_live_output_cap_bytes";
+
+/// Bytes of live output a run keeps in memory, unless configured otherwise
+pub const DEFAULT_LIVE_OUTPUT_CAP: usize = 1024 * 1024;
+
+/// Which output of a run a line came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+/// Part of a run's output to read, by byte offset or by line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "unit", rename_all = "lowercase")]
+pub enum OutputRange {
+    Bytes { offset: u64, limit: u64 },
+    Lines { start: usize, count: usize },
+}
+
+/// A page of a run's output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputPage {
+    pub content: String,
+    /// Byte offset the page starts at
+    pub offset: u64,
+    /// Byte offset the next page starts at
+    pub next_offset: u64,
+    /// Line the page starts at, when paging by line
+    pub first_line: Option<usize>,
+    /// Size of all the output so far
+    pub total_bytes: u64,
+    /// Whether the page reaches the end of the output so far
+    pub eof: bool,
+}
+
+/// Files the output of a run is appended to, one per stream
+pub struct OutputSpill {
+    stdout: Option<File>,
+    stderr: Option<File>,
+}
+
+impl OutputSpill {
+    /// Open the files for `run_id` in `dir`, appending to any already there
+    pub fn open(dir: &Path, run_id: i64) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let open = |stream| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(spill_path(dir, run_id, stream))
+        };
+        Ok(Self {
+            stdout: Some(open(OutputStream::Stdout)?),
+            stderr: Some(open(OutputStream::Stderr)?),
+        })
+    }
+
+    /// Append a line, giving up on the stream's file once writing to it fails
+    pub fn write(&mut self, stream: OutputStream, line: &str) {
+        let file = match stream {
+            OutputStream::Stdout => &mut self.stdout,
+            OutputStream::Stderr => &mut self.stderr,
+        };
+        if let Some(handle) = file {
+            if let Err(e) = writeln!(handle, "{}", line) {
+                // Keep the run going with the in-memory view only
+                warn!("Failed to write run output to disk: {}", e);
+                *file = None;
+            }
+        }
+    }
+}
+
+/// Output of a run: the tail of its stdout in memory, and all of it on disk
+///
+/// The in-memory view drops its oldest lines once it grows past the cap. Every line of
+/// stdout and stderr is appended to a file per stream, which outlives the run until
+/// `prune_spills` removes it.
+pub struct RunOutput {
+    lines: VecDeque<String>,
+    buffered_bytes: usize,
+    cap_bytes: usize,
+    spill: Option<Arc<Mutex<OutputSpill>>>,
+}
+
+impl RunOutput {
+    /// Output kept in memory only
+    pub fn new(cap_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            buffered_bytes: 0,
+            cap_bytes,
+            spill: None,
+        }
+    }
+
+    /// Output also written to files for `run_id` in `dir`, appending to any already there
+    pub fn with_spill(dir: &Path, run_id: i64, cap_bytes: usize) -> std::io::Result<Self> {
+        Ok(Self {
+            spill: Some(Arc::new(Mutex::new(OutputSpill::open(dir, run_id)?))),
+            ..Self::new(cap_bytes)
+        })
+    }
+
+    /// The files the output is written to, to write to them without holding this output
+    pub fn spill(&self) -> Option<Arc<Mutex<OutputSpill>>> {
+        self.spill.clone()
+    }
+
+    /// Record a line of output in memory, callers write it to `spill` themselves
+    pub fn push_live(&mut self, stream: OutputStream, line: &str) {
+        if stream != OutputStream::Stdout {
+            return;
+        }
+        self.buffered_bytes += line.len() + 1;
+        self.lines.push_back(line.to_string());
+        // The newest line stays even when it alone is over the cap
+        while self.buffered_bytes > self.cap_bytes && self.lines.len() > 1 {
+            if let Some(dropped) = self.lines.pop_front() {
+                self.buffered_bytes -= dropped.len() + 1;
+            }
+        }
+    }
+
+    /// The stdout lines still in memory, each ending in a newline
+    pub fn live(&self) -> String {
+        let mut live = String::with_capacity(self.buffered_bytes);
+        for line in &self.lines {
+            live.push_str(line);
+            live.push('\n');
+        }
+        live
+    }
+}
+
+/// File the `stream` output of `run_id` is written to in `dir`
+pub fn spill_path(dir: &Path, run_id: i64, stream: OutputStream) -> PathBuf {
+    match stream {
+        OutputStream::Stdout => dir.join(format!("{}.stdout.jsonl", run_id)),
+        OutputStream::Stderr => dir.join(format!("{}.stderr.log", run_id)),
+    }
+}
+
+/// Remove the output files of `run_id` in `dir`
+pub fn remove_spills(dir: &Path, run_id: i64) -> std::io::Result<()> {
+    for stream in [OutputStream::Stdout, OutputStream::Stderr] {
+        match std::fs::remove_file(spill_path(dir, run_id, stream)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Remove the output files in `dir` nothing was written to for `max_age` or longer,
+/// returning how many were removed
+pub fn prune_spills(dir: &Path, max_age: Duration) -> std::io::Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        // Times in the future count as just written
+        let age = metadata.modified().ok().map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default()
+        });
+        if metadata.is_file() && age.is_some_and(|age| age >= max_age) {
+            std::fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// The end of the output in `path`, at most `max_bytes` of it starting at a whole line
+pub fn read_tail(path: &Path, max_bytes: u64) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let total_bytes = file.metadata()?.len();
+    let offset = total_bytes.saturating_sub(max_bytes);
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    if offset > 0 {
+        let start = bytes
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(bytes.len(), |end| end + 1);
+        bytes.drain(..start);
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Read a page of the output in `path`
+///
+/// Pages by byte end after the last whole line that fits, unless not even one line fits,
+/// and never in the middle of a UTF-8 character. A page of nothing is an error, since
+/// paging through it would never reach the end.
+pub fn read_page(path: &Path, range: OutputRange) -> std::io::Result<OutputPage> {
+    if matches!(
+        range,
+        OutputRange::Bytes { limit: 0, .. } | OutputRange::Lines { count: 0, .. }
+    ) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "A page must hold at least one byte or line",
+        ));
+    }
+
+    let mut file = File::open(path)?;
+    let total_bytes = file.metadata()?.len();
+
+    match range {
+        OutputRange::Bytes { offset, limit } => {
+            let offset = offset.min(total_bytes);
+            file.seek(SeekFrom::Start(offset))?;
+            let mut bytes = Vec::new();
+            (&mut file).take(limit).read_to_end(&mut bytes)?;
+
+            let reaches_end = offset + bytes.len() as u64 >= total_bytes;
+            if !reaches_end {
+                if let Some(end) = bytes.iter().rposition(|b| *b == b'\n') {
+                    bytes.truncate(end + 1);
+                }
+            }
+            match complete_chars_len(&bytes) {
+                // The limit doesn't fit even the first character, so it gets the whole one
+                0 if !bytes.is_empty() => {
+                    let missing = char_width(bytes[0]).saturating_sub(bytes.len());
+                    file.take(missing as u64).read_to_end(&mut bytes)?;
+                }
+                complete => bytes.truncate(complete),
+            }
+            let next_offset = offset + bytes.len() as u64;
+            Ok(OutputPage {
+                content: String::from_utf8_lossy(&bytes).into_owned(),
+                offset,
+                next_offset,
+                first_line: None,
+                total_bytes,
+                eof: next_offset >= total_bytes,
+            })
+        }
+        OutputRange::Lines { start, count } => {
+            let mut reader = BufReader::new(file);
+            let mut offset = 0;
+            let mut line = Vec::new();
+            for _ in 0..start {
+                line.clear();
+                let read = reader.read_until(b'\n', &mut line)?;
+                if read == 0 {
+                    break;
+                }
+                offset += read as u64;
+            }
+
+            let mut bytes = Vec::new();
+            for _ in 0..count {
+                if reader.read_until(b'\n', &mut bytes)? == 0 {
+                    break;
+                }
+            }
+            let next_offset = offset + bytes.len() as u64;
+            Ok(OutputPage {
+                content: String::from_utf8_lossy(&bytes).into_owned(),
+                offset,
+                next_offset,
+                first_line: Some(start),
+                total_bytes,
+                eof: next_offset >= total_bytes,
+            })
+        }
+    }
+}
+
+/// Length of `bytes` without a UTF-8 character cut off at its end
+fn complete_chars_len(bytes: &[u8]) -> usize {
+    let len = bytes.len();
+    for back in 1..=len.min(4) {
+        let byte = bytes[len - back];
+        // Continuation bytes, the character starts further back
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        return if back < char_width(byte) {
+            len - back
+        } else {
+            len
+        };
+    }
+    len
+}
+
+/// Bytes in the UTF-8 character starting with `first`
+fn char_width(first: u8) -> usize {
+    match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+/// Load the in-memory cap, falling back to the default
+pub fn load_live_output_cap(conn: &Connection) -> usize {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![LIVE_OUTPUT_CAP_SETTING],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .and_then(|value| value.parse().ok())
+    .filter(|cap| *cap > 0)
+    .unwrap_or(DEFAULT_LIVE_OUTPUT_CAP)
+}
+
+/// Store the in-memory cap
+pub fn save_live_output_cap(conn: &Connection, cap_bytes: usize) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![LIVE_OUTPUT_CAP_SETTING, cap_bytes.to_string()],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Record a line the way the process registry does
+    fn push(output: &mut RunOutput, stream: OutputStream, line: &str) {
+        if let Some(spill) = output.spill() {
+            spill.lock().unwrap().write(stream, line);
+        }
+        output.push_live(stream, line);
+    }
+
+    #[test]
+    fn test_live_view_keeps_newest_lines_under_cap() {
+        let mut output = RunOutput::new(12);
+        for line in ["aaaa", "bbbb", "cccc"] {
+            push(&mut output, OutputStream::Stdout, line);
+        }
+        assert_eq!(output.live(), "bbbb\ncccc\n");
+
+        push(&mut output, OutputStream::Stderr, "not in the live view");
+        push(
+            &mut output,
+            OutputStream::Stdout,
+            "a line longer than the cap",
+        );
+        assert_eq!(output.live(), "a line longer than the cap\n");
+    }
+
+    #[test]
+    fn test_spill_keeps_everything_per_stream() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut output = RunOutput::with_spill(dir.path(), 7, 4).unwrap();
+            push(&mut output, OutputStream::Stdout, "one");
+            push(&mut output, OutputStream::Stdout, "two");
+            push(&mut output, OutputStream::Stderr, "oops");
+        }
+        // Re-adopting a run appends to what it wrote before
+        let mut output = RunOutput::with_spill(dir.path(), 7, 4).unwrap();
+        push(&mut output, OutputStream::Stdout, "three");
+
+        let read = |stream| std::fs::read_to_string(spill_path(dir.path(), 7, stream)).unwrap();
+        assert_eq!(read(OutputStream::Stdout), "one\ntwo\nthree\n");
+        assert_eq!(read(OutputStream::Stderr), "oops\n");
+    }
+
+    #[test]
+    fn test_read_page() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.jsonl");
+        std::fs::write(&path, "first\nsecond\nthird\n").unwrap();
+
+        let page = read_page(
+            &path,
+            OutputRange::Bytes {
+                offset: 0,
+                limit: 9,
+            },
+        )
+        .unwrap();
+        assert_eq!(page.content, "first\n");
+        assert_eq!(page.next_offset, 6);
+        assert!(!page.eof);
+
+        let page = read_page(
+            &path,
+            OutputRange::Bytes {
+                offset: page.next_offset,
+                limit: 100,
+            },
+        )
+        .unwrap();
+        assert_eq!(page.content, "second\nthird\n");
+        assert!(page.eof);
+
+        let page = read_page(&path, OutputRange::Lines { start: 1, count: 1 }).unwrap();
+        assert_eq!(page.content, "second\n");
+        assert_eq!((page.offset, page.next_offset), (6, 13));
+        assert_eq!(page.first_line, Some(1));
+        assert_eq!(page.total_bytes, 19);
+
+        let page = read_page(&path, OutputRange::Lines { start: 5, count: 2 }).unwrap();
+        assert_eq!(page.content, "");
+        assert!(page.eof);
+    }
+
+    #[test]
+    fn test_byte_pages_keep_characters_whole() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.jsonl");
+        // "ü" is 2 bytes and "€" 3, neither line ends within the limits below
+        std::fs::write(&path, "aü€b").unwrap();
+
+        let page = |offset, limit| read_page(&path, OutputRange::Bytes { offset, limit }).unwrap();
+        let first = page(0, 4);
+        assert_eq!(first.content, "aü");
+        assert_eq!(first.next_offset, 3);
+
+        // A limit smaller than the next character still returns all of it
+        let second = page(first.next_offset, 1);
+        assert_eq!(second.content, "€");
+        assert_eq!(second.next_offset, 6);
+
+        let last = page(second.next_offset, 10);
+        assert_eq!(last.content, "b");
+        assert!(last.eof);
+    }
+
+    #[test]
+    fn test_empty_pages_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.jsonl");
+        std::fs::write(&path, "first\n").unwrap();
+
+        for range in [
+            OutputRange::Bytes {
+                offset: 0,
+                limit: 0,
+            },
+            OutputRange::Lines { start: 0, count: 0 },
+        ] {
+            let error = read_page(&path, range).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_read_tail_starts_at_a_whole_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.jsonl");
+        std::fs::write(&path, "first\nsecond\nthird\n").unwrap();
+
+        assert_eq!(read_tail(&path, 10).unwrap(), "third\n");
+        assert_eq!(read_tail(&path, 100).unwrap(), "first\nsecond\nthird\n");
+    }
+
+    #[test]
+    fn test_spills_are_removed() {
+        let dir = TempDir::new().unwrap();
+        let mut output = RunOutput::with_spill(dir.path(), 3, 100).unwrap();
+        push(&mut output, OutputStream::Stdout, "line");
+        push(&mut output, OutputStream::Stderr, "line");
+        drop(output);
+
+        assert_eq!(
+            prune_spills(dir.path(), Duration::from_secs(3600)).unwrap(),
+            0
+        );
+        assert_eq!(prune_spills(dir.path(), Duration::ZERO).unwrap(), 2);
+        assert!(!spill_path(dir.path(), 3, OutputStream::Stdout).exists());
+
+        RunOutput::with_spill(dir.path(), 4, 100).unwrap();
+        remove_spills(dir.path(), 4).unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        // Nothing left to remove is fine
+        remove_spills(dir.path(), 4).unwrap();
+    }
+
+    #[test]
+    fn test_range_from_json() {
+        let range: OutputRange =
+            serde_json::from_str(r#"{"unit":"lines","start":10,"count":50}"#).unwrap();
+        assert_eq!(
+            range,
+            OutputRange::Lines {
+                start: 10,
+                count: 50
+            }
+        );
+    }
+}
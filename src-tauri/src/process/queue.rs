@@ -0,0 +1,378 @@
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// `app_settings` key holding the maximum number of runs at once
+pub const MAX_CONCURRENT_SETTING: &str = "agent_queue_max_concurrent";
+/// `app_settings` key holding the maximum number of runs at once in one project
+pub const MAX_PER_PROJECT_SETTING: &str = "agent_queue_max_per_project";
+
+/// How many agent runs may run at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueLimits {
+    pub max_concurrent: usize,
+    pub max_per_project: usize,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            max_per_project: 2,
+        }
+    }
+}
+
+/// A run waiting for a slot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedRun {
+    pub run_id: i64,
+    pub agent_id: i64,
+    pub agent_name: String,
+    pub project_path: String,
+    pub task: String,
+    pub priority: i64,
+    pub queued_at: String,
+}
+
+/// Load the concurrency limits, falling back to the defaults
+pub fn load_limits(conn: &Connection) -> QueueLimits {
+    let defaults = QueueLimits::default();
+    let load = |key: &str| -> Option<usize> {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+        .and_then(|value| value.parse().ok())
+        .filter(|limit| *limit > 0)
+    };
+
+    QueueLimits {
+        max_concurrent: load(MAX_CONCURRENT_SETTING).unwrap_or(defaults.max_concurrent),
+        max_per_project: load(MAX_PER_PROJECT_SETTING).unwrap_or(defaults.max_per_project),
+    }
+}
+
+/// Store the concurrency limits
+pub fn save_limits(conn: &Connection, limits: &QueueLimits) -> SqliteResult<()> {
+    for (key, value) in [
+        (MAX_CONCURRENT_SETTING, limits.max_concurrent),
+        (MAX_PER_PROJECT_SETTING, limits.max_per_project),
+    ] {
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![key, value.to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Queued runs in the order they get slots
+///
+/// Higher priorities go first. Within a priority agents take turns, so one agent queueing
+/// many runs doesn't starve the others. Each agent's runs keep their queue rank, which is
+/// the order they were queued in unless they were moved since, and the agent whose next
+/// run ranks first goes first.
+pub fn list_queued(conn: &Connection) -> SqliteResult<Vec<QueuedRun>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agent_id, agent_name, project_path, task, priority, created_at
+         FROM agent_runs WHERE status = 'queued'
+         ORDER BY priority DESC, queue_rank ASC, id ASC",
+    )?;
+    let runs = stmt
+        .query_map([], |row| {
+            Ok(QueuedRun {
+                run_id: row.get(0)?,
+                agent_id: row.get(1)?,
+                agent_name: row.get(2)?,
+                project_path: row.get(3)?,
+                task: row.get(4)?,
+                priority: row.get(5)?,
+                queued_at: row.get(6)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(take_turns(runs))
+}
+
+/// Interleave runs ordered by priority and rank so that agents take turns per priority
+fn take_turns(runs: Vec<QueuedRun>) -> Vec<QueuedRun> {
+    let mut ordered = Vec::with_capacity(runs.len());
+    let mut runs = runs.into_iter().peekable();
+    while let Some(priority) = runs.peek().map(|run| run.priority) {
+        // Agents in the order of their first run at this priority
+        let mut per_agent: Vec<(i64, VecDeque<QueuedRun>)> = Vec::new();
+        while let Some(run) = runs.next_if(|run| run.priority == priority) {
+            match per_agent
+                .iter_mut()
+                .find(|(agent_id, _)| *agent_id == run.agent_id)
+            {
+                Some((_, queued)) => queued.push_back(run),
+                None => per_agent.push((run.agent_id, VecDeque::from([run]))),
+            }
+        }
+
+        while !per_agent.is_empty() {
+            per_agent.retain_mut(|(_, queued)| match queued.pop_front() {
+                Some(run) => {
+                    ordered.push(run);
+                    true
+                }
+                None => false,
+            });
+        }
+    }
+    ordered
+}
+
+/// Runs from `queue` that fit in the free slots, in queue order
+///
+/// `occupied` holds the project of every run holding a slot. A run whose project is full
+/// is skipped so that it doesn't hold up runs in other projects behind it.
+pub fn select_runnable(
+    queue: &[QueuedRun],
+    occupied: &[String],
+    limits: &QueueLimits,
+) -> Vec<QueuedRun> {
+    let mut per_project: HashMap<&str, usize> = HashMap::new();
+    for project in occupied {
+        *per_project.entry(project.as_str()).or_default() += 1;
+    }
+
+    let mut free = limits.max_concurrent.saturating_sub(occupied.len());
+    let mut runnable = Vec::new();
+    for run in queue {
+        if free == 0 {
+            break;
+        }
+        let in_project = per_project.entry(run.project_path.as_str()).or_default();
+        if *in_project >= limits.max_per_project {
+            continue;
+        }
+        *in_project += 1;
+        free -= 1;
+        runnable.push(run.clone());
+    }
+    runnable
+}
+
+/// Change the priority of a queued run, returning whether it was still queued
+pub fn set_priority(conn: &Connection, run_id: i64, priority: i64) -> SqliteResult<bool> {
+    let updated = conn.execute(
+        "UPDATE agent_runs SET priority = ?1 WHERE id = ?2 AND status = 'queued'",
+        params![priority, run_id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Move a queued run in front of another, or to the back of the queue
+///
+/// Moving in front of a run also takes on its priority, so the move sticks. Returns whether
+/// both runs were still queued.
+pub fn move_run(conn: &Connection, run_id: i64, before_run_id: Option<i64>) -> SqliteResult<bool> {
+    let position = |id: i64| {
+        conn.query_row(
+            "SELECT priority, queue_rank FROM agent_runs WHERE id = ?1 AND status = 'queued'",
+            params![id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .optional()
+    };
+
+    if position(run_id)?.is_none() {
+        return Ok(false);
+    }
+
+    match before_run_id {
+        Some(before) => {
+            let Some((priority, rank)) = position(before)? else {
+                return Ok(false);
+            };
+            conn.execute(
+                "UPDATE agent_runs SET queue_rank = queue_rank + 1
+                 WHERE status = 'queued' AND queue_rank >= ?1 AND id != ?2",
+                params![rank, run_id],
+            )?;
+            conn.execute(
+                "UPDATE agent_runs SET priority = ?1, queue_rank = ?2 WHERE id = ?3",
+                params![priority, rank, run_id],
+            )?;
+        }
+        None => {
+            let (lowest, last): (i64, i64) = conn.query_row(
+                "SELECT MIN(priority), MAX(queue_rank) FROM agent_runs WHERE status = 'queued'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            conn.execute(
+                "UPDATE agent_runs SET priority = ?1, queue_rank = ?2 WHERE id = ?3",
+                params![lowest, last + 1, run_id],
+            )?;
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE agent_runs (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 agent_id INTEGER NOT NULL,
+                 agent_name TEXT NOT NULL,
+                 task TEXT NOT NULL,
+                 project_path TEXT NOT NULL,
+                 status TEXT NOT NULL,
+                 priority INTEGER NOT NULL DEFAULT 0,
+                 queue_rank INTEGER NOT NULL DEFAULT 0,
+                 created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+             );",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn enqueue(conn: &Connection, agent_id: i64, project: &str, priority: i64) -> i64 {
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, task, project_path, status, priority)
+             VALUES (?1, 'agent', 'task', ?2, 'queued', ?3)",
+            params![agent_id, project, priority],
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            "UPDATE agent_runs SET queue_rank = id WHERE id = ?1",
+            params![id],
+        )
+        .unwrap();
+        id
+    }
+
+    fn order(conn: &Connection) -> Vec<i64> {
+        list_queued(conn)
+            .unwrap()
+            .iter()
+            .map(|run| run.run_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_queue_orders_by_priority_then_arrival() {
+        let conn = queue_db();
+        let first = enqueue(&conn, 1, "/a", 0);
+        let second = enqueue(&conn, 2, "/a", 0);
+        let urgent = enqueue(&conn, 1, "/b", 5);
+
+        assert_eq!(order(&conn), vec![urgent, first, second]);
+
+        assert!(set_priority(&conn, second, 9).unwrap());
+        assert_eq!(order(&conn), vec![second, urgent, first]);
+    }
+
+    #[test]
+    fn test_agents_take_turns_within_a_priority() {
+        let conn = queue_db();
+        let busy: Vec<i64> = (0..3).map(|_| enqueue(&conn, 1, "/a", 0)).collect();
+        let other = enqueue(&conn, 2, "/a", 0);
+        let third = enqueue(&conn, 3, "/b", 0);
+        let later = enqueue(&conn, 2, "/b", 0);
+        let urgent = enqueue(&conn, 1, "/a", 5);
+
+        assert_eq!(
+            order(&conn),
+            vec![urgent, busy[0], other, third, busy[1], later, busy[2]]
+        );
+
+        // Moving a run to the front makes its agent go first, its other runs stay in order
+        assert!(move_run(&conn, later, Some(busy[0])).unwrap());
+        assert_eq!(
+            order(&conn),
+            vec![urgent, later, busy[0], third, other, busy[1], busy[2]]
+        );
+    }
+
+    #[test]
+    fn test_move_run() {
+        let conn = queue_db();
+        let a = enqueue(&conn, 1, "/a", 0);
+        let b = enqueue(&conn, 1, "/a", 0);
+        let c = enqueue(&conn, 2, "/a", 3);
+
+        assert!(move_run(&conn, b, Some(c)).unwrap());
+        assert_eq!(order(&conn), vec![b, c, a]);
+
+        assert!(move_run(&conn, b, None).unwrap());
+        assert_eq!(order(&conn), vec![c, a, b]);
+
+        conn.execute(
+            "UPDATE agent_runs SET status = 'running' WHERE id = ?1",
+            [a],
+        )
+        .unwrap();
+        assert!(!move_run(&conn, a, None).unwrap());
+        assert!(!move_run(&conn, b, Some(a)).unwrap());
+    }
+
+    #[test]
+    fn test_select_runnable_respects_limits() {
+        let run = |run_id: i64, project: &str| QueuedRun {
+            run_id,
+            agent_id: 1,
+            agent_name: "agent".to_string(),
+            project_path: project.to_string(),
+            task: "task".to_string(),
+            priority: 0,
+            queued_at: String::new(),
+        };
+        let queue = vec![run(1, "/a"), run(2, "/a"), run(3, "/b"), run(4, "/c")];
+        let limits = QueueLimits {
+            max_concurrent: 3,
+            max_per_project: 1,
+        };
+
+        let ids = |runs: Vec<QueuedRun>| runs.iter().map(|r| r.run_id).collect::<Vec<_>>();
+        assert_eq!(ids(select_runnable(&queue, &[], &limits)), vec![1, 3, 4]);
+        assert_eq!(
+            ids(select_runnable(&queue, &["/a".to_string()], &limits)),
+            vec![3, 4]
+        );
+        assert_eq!(
+            ids(select_runnable(
+                &queue,
+                &["/x".to_string(), "/y".to_string(), "/z".to_string()],
+                &limits
+            )),
+            Vec::<i64>::new()
+        );
+    }
+
+    #[test]
+    fn test_limits_fall_back_to_defaults() {
+        let conn = queue_db();
+        assert_eq!(load_limits(&conn), QueueLimits::default());
+
+        let limits = QueueLimits {
+            max_concurrent: 8,
+            max_per_project: 3,
+        };
+        save_limits(&conn, &limits).unwrap();
+        assert_eq!(load_limits(&conn), limits);
+
+        conn.execute(
+            "UPDATE app_settings SET value = '0' WHERE key = ?1",
+            params![MAX_PER_PROJECT_SETTING],
+        )
+        .unwrap();
+        assert_eq!(load_limits(&conn).max_per_project, 2);
+    }
+}
@@ -2,8 +2,13 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::process::Child;
 
+use super::cancel;
+use super::output::{OutputSpill, OutputStream, RunOutput};
+use super::queue::{self, QueueLimits, QueuedRun};
+
 /// Information about a running agent process
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessInfo {
@@ -22,22 +27,75 @@ pub struct ProcessInfo {
 pub struct ProcessHandle {
     pub info: ProcessInfo,
     pub child: Arc<Mutex<Option<Child>>>,
-    pub live_output: Arc<Mutex<String>>,
+    pub live_output: Arc<Mutex<RunOutput>>,
+    /// Files the output goes to, written outside the registry and live output locks
+    pub spill: Option<Arc<Mutex<OutputSpill>>>,
 }
 
 /// Registry for tracking active agent processes
+///
+/// Runs hold a slot from the moment they leave the queue, first while they start and then
+/// while their process is registered, so the registry decides how many more may start.
 pub struct ProcessRegistry {
     processes: Arc<Mutex<HashMap<i64, ProcessHandle>>>, // run_id -> ProcessHandle
+    starting: Arc<Mutex<HashMap<i64, String>>>,         // run_id -> project_path
 }
 
 impl ProcessRegistry {
     pub fn new() -> Self {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
+            starting: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Take slots for as many runs from `queue` as the limits allow
+    ///
+    /// The claimed runs hold their slot until their process is registered or
+    /// `release_slot` is called.
+    pub fn claim_slots(
+        &self,
+        queue: Vec<QueuedRun>,
+        limits: &QueueLimits,
+    ) -> Result<Vec<QueuedRun>, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let mut starting = self.starting.lock().map_err(|e| e.to_string())?;
+
+        let occupied: Vec<String> = processes
+            .values()
+            .map(|handle| handle.info.project_path.clone())
+            .chain(starting.values().cloned())
+            .collect();
+        let waiting: Vec<QueuedRun> = queue
+            .into_iter()
+            .filter(|run| {
+                !processes.contains_key(&run.run_id) && !starting.contains_key(&run.run_id)
+            })
+            .collect();
+
+        let claimed = queue::select_runnable(&waiting, &occupied, limits);
+        for run in &claimed {
+            starting.insert(run.run_id, run.project_path.clone());
+        }
+        Ok(claimed)
+    }
+
+    /// Give up the slot of a run that failed to start
+    pub fn release_slot(&self, run_id: i64) -> Result<(), String> {
+        let mut starting = self.starting.lock().map_err(|e| e.to_string())?;
+        starting.remove(&run_id);
+        Ok(())
+    }
+
+    /// Number of runs holding a slot
+    pub fn occupied_slots(&self) -> Result<usize, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let starting = self.starting.lock().map_err(|e| e.to_string())?;
+        Ok(processes.len() + starting.len())
+    }
+
     /// Register a new running process
+    #[allow(clippy::too_many_arguments)]
     pub fn register_process(
         &self,
         run_id: i64,
@@ -48,6 +106,7 @@ impl ProcessRegistry {
         task: String,
         model: String,
         child: Child,
+        output: RunOutput,
     ) -> Result<(), String> {
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
 
@@ -65,15 +124,37 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(Some(child))),
-            live_output: Arc::new(Mutex::new(String::new())),
+            spill: output.spill(),
+            live_output: Arc::new(Mutex::new(output)),
         };
 
         processes.insert(run_id, process_handle);
+        self.starting
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(&run_id);
+        Ok(())
+    }
+
+    /// Track a process this instance of the app didn't spawn, such as one left running
+    /// by an earlier instance
+    ///
+    /// Without a child handle it can only be killed by PID.
+    pub fn adopt_process(&self, info: ProcessInfo, output: RunOutput) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        processes.insert(
+            info.run_id,
+            ProcessHandle {
+                info,
+                child: Arc::new(Mutex::new(None)),
+                spill: output.spill(),
+                live_output: Arc::new(Mutex::new(output)),
+            },
+        );
         Ok(())
     }
 
     /// Unregister a process (called when it completes)
-    #[allow(dead_code)]
     pub fn unregister_process(&self, run_id: i64) -> Result<(), String> {
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
         processes.remove(&run_id);
@@ -97,9 +178,12 @@ impl ProcessRegistry {
         Ok(processes.get(&run_id).map(|handle| handle.info.clone()))
     }
 
-    /// Kill a running process with proper cleanup
-    pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
-        use log::{error, info, warn};
+    /// Cancel a running process, giving its process group `grace` to exit before killing it
+    ///
+    /// The process stays registered until whoever watches its run sees it exit, so output it
+    /// flushes while shutting down still arrives.
+    pub async fn kill_process(&self, run_id: i64, grace: Duration) -> Result<bool, String> {
+        use log::info;
 
         // First check if the process exists and get its PID
         let (pid, child_arc) = {
@@ -111,167 +195,64 @@ impl ProcessRegistry {
             }
         };
 
-        info!(
-            "Attempting graceful shutdown of process {} (PID: {})",
-            run_id, pid
-        );
-
-        // Send kill signal to the process
-        let kill_sent = {
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(child) = child_guard.as_mut() {
-                match child.start_kill() {
-                    Ok(_) => {
-                        info!("Successfully sent kill signal to process {}", run_id);
-                        true
-                    }
-                    Err(e) => {
-                        error!("Failed to send kill signal to process {}: {}", run_id, e);
-                        return Err(format!("Failed to kill process: {}", e));
-                    }
-                }
-            } else {
-                false // Process already killed
-            }
+        // Processes adopted from an earlier instance of the app have no child handle
+        let running = match child_arc.lock().map_err(|e| e.to_string())?.as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => cancel::is_alive(pid),
         };
-
-        if !kill_sent {
+        if !running {
             return Ok(false);
         }
 
-        // Wait for the process to exit (with timeout)
-        let wait_result = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
-            loop {
-                // Check if process has exited
-                let status = {
-                    let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-                    if let Some(child) = child_guard.as_mut() {
-                        match child.try_wait() {
-                            Ok(Some(status)) => {
-                                info!("Process {} exited with status: {:?}", run_id, status);
-                                *child_guard = None; // Clear the child handle
-                                Some(Ok::<(), String>(()))
-                            }
-                            Ok(None) => {
-                                // Still running
-                                None
-                            }
-                            Err(e) => {
-                                error!("Error checking process status: {}", e);
-                                Some(Err(e.to_string()))
-                            }
-                        }
-                    } else {
-                        // Process already gone
-                        Some(Ok(()))
-                    }
-                };
-
-                match status {
-                    Some(result) => return result,
-                    None => {
-                        // Still running, wait a bit
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        info!("Cancelling process {} (PID: {})", run_id, pid);
+        let termination = cancel::terminate_group(pid, grace, || match child_arc.lock() {
+            Ok(mut child_guard) => match child_guard.as_mut() {
+                Some(child) => match child.try_wait() {
+                    Ok(None) => false,
+                    Ok(Some(_)) | Err(_) => {
+                        *child_guard = None; // Clear the child handle
+                        true
                     }
-                }
-            }
+                },
+                None => !cancel::is_alive(pid),
+            },
+            Err(_) => true,
         })
         .await;
-
-        match wait_result {
-            Ok(Ok(_)) => {
-                info!("Process {} exited gracefully", run_id);
-            }
-            Ok(Err(e)) => {
-                error!("Error waiting for process {}: {}", run_id, e);
-            }
-            Err(_) => {
-                warn!("Process {} didn't exit within 5 seconds after kill", run_id);
-                // Force clear the handle
-                if let Ok(mut child_guard) = child_arc.lock() {
-                    *child_guard = None;
-                }
-            }
-        }
-
-        // Remove from registry after killing
-        self.unregister_process(run_id)?;
+        info!("Process {} cancelled: {:?}", run_id, termination);
 
         Ok(true)
     }
 
-    /// Kill a process by PID using system commands (fallback method)
-    pub fn kill_process_by_pid(&self, run_id: i64, pid: u32) -> Result<bool, String> {
-        use log::{error, info, warn};
-
-        info!("Attempting to kill process {} by PID {}", run_id, pid);
-
-        let kill_result = if cfg!(target_os = "windows") {
-            std::process::Command::new("taskkill")
-                .args(["/F", "/PID", &pid.to_string()])
-                .output()
-        } else {
-            // First try SIGTERM
-            let term_result = std::process::Command::new("kill")
-                .args(["-TERM", &pid.to_string()])
-                .output();
-
-            match &term_result {
-                Ok(output) if output.status.success() => {
-                    info!("Sent SIGTERM to PID {}", pid);
-                    // Give it 2 seconds to exit gracefully
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-
-                    // Check if still running
-                    let check_result = std::process::Command::new("kill")
-                        .args(["-0", &pid.to_string()])
-                        .output();
-
-                    if let Ok(output) = check_result {
-                        if output.status.success() {
-                            // Still running, send SIGKILL
-                            warn!(
-                                "Process {} still running after SIGTERM, sending SIGKILL",
-                                pid
-                            );
-                            std::process::Command::new("kill")
-                                .args(["-KILL", &pid.to_string()])
-                                .output()
-                        } else {
-                            term_result
-                        }
-                    } else {
-                        term_result
-                    }
-                }
-                _ => {
-                    // SIGTERM failed, try SIGKILL directly
-                    warn!("SIGTERM failed for PID {}, trying SIGKILL", pid);
-                    std::process::Command::new("kill")
-                        .args(["-KILL", &pid.to_string()])
-                        .output()
-                }
-            }
-        };
-
-        match kill_result {
-            Ok(output) => {
-                if output.status.success() {
-                    info!("Successfully killed process with PID {}", pid);
-                    // Remove from registry
-                    self.unregister_process(run_id)?;
-                    Ok(true)
-                } else {
-                    let error_msg = String::from_utf8_lossy(&output.stderr);
-                    warn!("Failed to kill PID {}: {}", pid, error_msg);
-                    Ok(false)
-                }
-            }
-            Err(e) => {
-                error!("Failed to execute kill command for PID {}: {}", pid, e);
-                Err(format!("Failed to execute kill command: {}", e))
+    /// Cancel a process by PID, for runs missing from the registry (fallback method)
+    ///
+    /// `started_at` is the start time recorded for the run's process. The PID is only
+    /// signalled while it still belongs to that process, never to one reusing it.
+    pub async fn kill_process_by_pid(
+        &self,
+        run_id: i64,
+        pid: u32,
+        started_at: &str,
+        grace: Duration,
+    ) -> Result<bool, String> {
+        use log::{info, warn};
+
+        info!("Attempting to cancel process {} by PID {}", run_id, pid);
+        match super::orphans::check_process(pid, started_at) {
+            super::orphans::Liveness::Alive => {}
+            liveness => {
+                warn!(
+                    "Not cancelling PID {} of run {}, it is not the run's process: {:?}",
+                    pid, run_id, liveness
+                );
+                return Ok(false);
             }
         }
+
+        let termination = cancel::terminate_group(pid, grace, || !cancel::is_alive(pid)).await;
+        info!("PID {} cancelled: {:?}", pid, termination);
+        self.unregister_process(run_id)?;
+        Ok(true)
     }
 
     /// Check if a process is still running by trying to get its status
@@ -309,23 +290,38 @@ impl ProcessRegistry {
         }
     }
 
-    /// Append to live output for a process
-    pub fn append_live_output(&self, run_id: i64, output: &str) -> Result<(), String> {
-        let processes = self.processes.lock().map_err(|e| e.to_string())?;
-        if let Some(handle) = processes.get(&run_id) {
-            let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            live_output.push_str(output);
-            live_output.push('\n');
+    /// Record a line of output of a process
+    pub fn append_output(
+        &self,
+        run_id: i64,
+        stream: OutputStream,
+        line: &str,
+    ) -> Result<(), String> {
+        let (live_output, spill) = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => (handle.live_output.clone(), handle.spill.clone()),
+                None => return Ok(()),
+            }
+        };
+
+        // Only the run's own output waits on the disk
+        if let Some(spill) = spill {
+            spill.lock().map_err(|e| e.to_string())?.write(stream, line);
         }
+        live_output
+            .lock()
+            .map_err(|e| e.to_string())?
+            .push_live(stream, line);
         Ok(())
     }
 
-    /// Get live output for a process
+    /// Get live output for a process, the newest stdout lines that fit under its cap
     pub fn get_live_output(&self, run_id: i64) -> Result<String, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            Ok(live_output.clone())
+            Ok(live_output.live())
         } else {
             Ok(String::new())
         }
@@ -339,9 +335,10 @@ impl ProcessRegistry {
 
         // First, identify finished processes
         {
-            let processes = processes_lock.lock().map_err(|e| e.to_string())?;
-            let run_ids: Vec<i64> = processes.keys().cloned().collect();
-            drop(processes);
+            let run_ids: Vec<i64> = {
+                let processes = processes_lock.lock().map_err(|e| e.to_string())?;
+                processes.keys().cloned().collect()
+            };
 
             for run_id in run_ids {
                 if !self.is_process_running(run_id).await? {
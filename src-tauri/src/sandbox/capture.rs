@@ -0,0 +1,107 @@
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+
+/// Descriptor the tools of a run find the capture pipe on
+///
+/// High enough that neither Claude nor the shells it starts use it for anything else.
+pub const TOOL_STDERR_FD: i32 = 198;
+
+/// Set once a shell copies its stderr, so nested shells don't copy every line again
+const CAPTURE_GUARD_VAR: &str = "ORGANIZED_AGENTS_TOOL_STDERR";
+
+/// Sourced by every non-interactive bash through `BASH_ENV`
+///
+/// Duplicates stderr into the capture pipe while leaving it where it was, so the tool
+/// output Claude sees is unchanged. A plain read loop avoids opening any path, which
+/// the sandbox might deny.
+const CAPTURE_SCRIPT: &str = r#"if [ -z "${ORGANIZED_AGENTS_TOOL_STDERR:-}" ] && { : >&198; } 2>/dev/null; then
+    export ORGANIZED_AGENTS_TOOL_STDERR=1
+    exec 2> >(while IFS= read -r line || [ -n "$line" ]; do
+        printf '%s\n' "$line" >&2
+        printf '%s\n' "$line" >&198
+    done)
+fi
+"#;
+
+/// Copies the stderr of the commands a run's tools execute into a pipe
+///
+/// This is what the violation monitor reads, rather than Claude's own stderr: the
+/// permission errors there come from the processes the sandbox actually restricted.
+/// The write end is handed to the child as [`TOOL_STDERR_FD`] and `BASH_ENV` makes
+/// every bash it starts copy its stderr there. Commands run through other shells are
+/// not captured.
+pub struct ToolStderrCapture {
+    reader: OwnedFd,
+    writer: OwnedFd,
+    script: CaptureScript,
+}
+
+impl ToolStderrCapture {
+    pub fn new() -> io::Result<Self> {
+        // Both ends are close-on-exec, only the run's child gets the write end
+        let (reader, writer) = io::pipe()?;
+
+        // Every shell of the run sources the script, so nobody else may write it. It
+        // lives in the temp directory since the sandbox always lets the run read that.
+        let script_dir =
+            std::env::temp_dir().join(format!("organized-agents-{}", uuid::Uuid::new_v4()));
+        fs::DirBuilder::new().mode(0o700).create(&script_dir)?;
+        let script = CaptureScript(script_dir);
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(script.path())
+            .and_then(|mut file| io::Write::write_all(&mut file, CAPTURE_SCRIPT.as_bytes()))?;
+
+        Ok(Self {
+            reader: reader.into(),
+            writer: writer.into(),
+            script,
+        })
+    }
+
+    /// Hand the pipe to `cmd`'s child and have its shells copy their stderr into it
+    pub fn attach(&self, cmd: &mut tokio::process::Command) {
+        cmd.env("BASH_ENV", self.script.path())
+            .env_remove(CAPTURE_GUARD_VAR);
+        let writer = self.writer.as_raw_fd();
+        unsafe {
+            cmd.pre_exec(move || {
+                // `dup2` clears close-on-exec on the copy
+                if libc::dup2(writer, TOOL_STDERR_FD) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// The read end, once the child holds the write end
+    ///
+    /// Reading ends when every process of the run has exited. The returned guard keeps
+    /// the capture script around until then.
+    pub fn into_reader(self) -> io::Result<(tokio::net::unix::pipe::Receiver, CaptureScript)> {
+        drop(self.writer);
+        let receiver = tokio::net::unix::pipe::Receiver::from_owned_fd(self.reader)?;
+        Ok((receiver, self.script))
+    }
+}
+
+/// The script shells source to copy their stderr, removed when dropped
+pub struct CaptureScript(PathBuf);
+
+impl CaptureScript {
+    fn path(&self) -> PathBuf {
+        self.0.join("tool-stderr.sh")
+    }
+}
+
+impl Drop for CaptureScript {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -63,6 +63,21 @@ fn create_standard_profile(conn: &Connection) -> Result<()> {
             true,
             Some(r#"["linux", "macos"]"#),
         ),
+        // Writes stay inside the project (enforced by Landlock)
+        (
+            "file_write",
+            "subpath",
+            "{{PROJECT_PATH}}",
+            true,
+            Some(r#"["linux"]"#),
+        ),
+        (
+            "file_create",
+            "subpath",
+            "{{PROJECT_PATH}}",
+            true,
+            Some(r#"["linux"]"#),
+        ),
         (
             "file_read_all",
             "subpath",
@@ -174,6 +189,21 @@ fn create_development_profile(conn: &Connection) -> Result<()> {
             true,
             Some(r#"["linux", "macos"]"#),
         ),
+        // Writes stay inside the project (enforced by Landlock)
+        (
+            "file_write",
+            "subpath",
+            "{{PROJECT_PATH}}",
+            true,
+            Some(r#"["linux"]"#),
+        ),
+        (
+            "file_create",
+            "subpath",
+            "{{PROJECT_PATH}}",
+            true,
+            Some(r#"["linux"]"#),
+        ),
         (
             "file_read_all",
             "subpath",
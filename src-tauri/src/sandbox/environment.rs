@@ -0,0 +1,149 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::profile::SandboxRule;
+
+/// Rule operation type holding a profile's environment policy
+pub const ENVIRONMENT_OPERATION: &str = "environment";
+
+/// Variables always passed to the child, which `allow` rules add to
+///
+/// `ANTHROPIC_API_KEY` is named exactly so Claude can still authenticate, since secret
+/// variables never match a wildcard.
+pub const DEFAULT_ALLOW: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "SHELL",
+    "LANG",
+    "LC_*",
+    "NODE_PATH",
+    "NVM_DIR",
+    "NVM_BIN",
+    "ANTHROPIC_API_KEY",
+];
+
+/// Name suffixes marking a variable as secret
+const SECRET_SUFFIXES: &[&str] = &[
+    "_TOKEN",
+    "_KEY",
+    "_SECRET",
+    "_PASSWORD",
+    "_PASSWD",
+    "_CREDENTIALS",
+];
+
+/// Placeholder shown instead of the value of a secret variable
+pub const REDACTED: &str = "[redacted]";
+
+/// Which environment variables a sandboxed child gets
+///
+/// Built from `environment` rules, whose pattern type is `allow` or `deny` with a
+/// variable name pattern (`*` matches any run of characters), or `set` with a
+/// `NAME=value` pattern to inject.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvPolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub set: Vec<(String, String)>,
+}
+
+impl EnvPolicy {
+    /// Collect the policy from the `environment` rules among `rules`
+    pub fn from_rules(rules: &[SandboxRule]) -> Self {
+        let mut policy = Self::default();
+        for rule in rules
+            .iter()
+            .filter(|rule| rule.enabled && rule.operation_type == ENVIRONMENT_OPERATION)
+        {
+            let pattern = rule.pattern_value.trim();
+            match rule.pattern_type.as_str() {
+                "allow" => policy.allow.push(pattern.to_string()),
+                "deny" => policy.deny.push(pattern.to_string()),
+                "set" => match pattern.split_once('=') {
+                    Some((name, value)) if !name.is_empty() => {
+                        policy.set.push((name.to_string(), value.to_string()))
+                    }
+                    _ => warn!("Ignoring environment rule without NAME=value: {}", pattern),
+                },
+                other => warn!("Unknown environment pattern type: {}", other),
+            }
+        }
+        policy
+    }
+
+    /// Environment of the child, given the parent's variables
+    ///
+    /// A variable is passed through when it matches [`DEFAULT_ALLOW`] or an allow pattern,
+    /// and no deny pattern. Secret variables are only passed through by an allow pattern
+    /// naming them exactly. Injected values are added last and win over everything else.
+    pub fn effective_env(
+        &self,
+        parent: impl IntoIterator<Item = (String, String)>,
+    ) -> BTreeMap<String, String> {
+        let allow: Vec<&str> = DEFAULT_ALLOW
+            .iter()
+            .copied()
+            .chain(self.allow.iter().map(String::as_str))
+            .collect();
+
+        let mut env: BTreeMap<String, String> = parent
+            .into_iter()
+            .filter(|(name, _)| {
+                let allowed = if is_secret(name) {
+                    allow.iter().any(|pattern| *pattern == name)
+                } else {
+                    allow.iter().any(|pattern| matches_pattern(pattern, name))
+                };
+                allowed
+                    && !self
+                        .deny
+                        .iter()
+                        .any(|pattern| matches_pattern(pattern, name))
+            })
+            .collect();
+
+        for (name, value) in &self.set {
+            env.insert(name.clone(), value.clone());
+        }
+        env
+    }
+}
+
+/// Whether a variable name looks like it holds a secret
+pub fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    SECRET_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// The value to show for a variable, hiding secrets
+pub fn display_value<'a>(name: &str, value: &'a str) -> &'a str {
+    if is_secret(name) {
+        REDACTED
+    } else {
+        value
+    }
+}
+
+/// Match a variable name against a pattern where `*` stands for any run of characters
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
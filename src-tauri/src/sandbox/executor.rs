@@ -9,12 +9,63 @@ use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
 
+use super::environment::{self, EnvPolicy};
+use super::limits::{Cgroup, ResourceLimits};
+
 /// Sandbox executor for running commands in a sandboxed environment
 pub struct SandboxExecutor {
     #[cfg(unix)]
     profile: gaol::profile::Profile,
     project_path: PathBuf,
     serialized_profile: Option<SerializedProfile>,
+    egress_proxy: Option<String>,
+    resource_limits: ResourceLimits,
+    cgroup_procs: Option<PathBuf>,
+    env_policy: EnvPolicy,
+}
+
+impl SandboxExecutor {
+    /// Route the child's network traffic through an egress proxy
+    ///
+    /// Sets the proxy variables for the child, and on Linux denies TCP connections to
+    /// anything but the proxy's port.
+    pub fn with_egress_proxy(mut self, proxy_url: String) -> Self {
+        self.egress_proxy = Some(proxy_url);
+        self
+    }
+
+    /// Apply resource limits to the child, inside `cgroup` when the run has one
+    ///
+    /// The cgroup must outlive the child, so callers keep it until the run ends.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits, cgroup: Option<&Cgroup>) -> Self {
+        self.resource_limits = limits;
+        self.cgroup_procs = cgroup.map(Cgroup::procs_path);
+        self
+    }
+
+    /// Control which environment variables the child gets
+    ///
+    /// Windows runs are not sandboxed and keep inheriting the whole environment.
+    pub fn with_env_policy(mut self, env_policy: EnvPolicy) -> Self {
+        self.env_policy = env_policy;
+        self
+    }
+
+    /// Environment of the child under the policy, before any proxy variables
+    pub fn effective_env(&self) -> std::collections::BTreeMap<String, String> {
+        self.env_policy.effective_env(env::vars())
+    }
+
+    /// Environment variables pointing the child at the egress proxy, if there is one
+    fn proxy_env(&self) -> Vec<(&'static str, &str)> {
+        match self.egress_proxy {
+            Some(ref url) => ["HTTPS_PROXY", "HTTP_PROXY", "https_proxy", "http_proxy"]
+                .into_iter()
+                .map(|key| (key, url.as_str()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
 }
 
 #[cfg(unix)]
@@ -25,6 +76,10 @@ impl SandboxExecutor {
             profile,
             project_path,
             serialized_profile: None,
+            egress_proxy: None,
+            resource_limits: ResourceLimits::default(),
+            cgroup_procs: None,
+            env_policy: EnvPolicy::default(),
         }
     }
 
@@ -38,6 +93,10 @@ impl SandboxExecutor {
             profile,
             project_path,
             serialized_profile: Some(serialized_profile),
+            egress_proxy: None,
+            resource_limits: ResourceLimits::default(),
+            cgroup_procs: None,
+            env_policy: EnvPolicy::default(),
         }
     }
 
@@ -71,6 +130,15 @@ impl SandboxExecutor {
             }
         }
 
+        // Prefer Landlock when the kernel supports it, since it keeps the Child handle
+        #[cfg(target_os = "linux")]
+        if let Some(sandbox) = self.landlock_sandbox(command) {
+            return self
+                .landlock_std_command(command, args, cwd, sandbox)
+                .spawn()
+                .context("Failed to spawn process with Landlock sandbox");
+        }
+
         // Create the sandbox
         let sandbox = Sandbox::new(self.profile.clone());
 
@@ -88,24 +156,14 @@ impl SandboxExecutor {
             self.project_path.to_string_lossy().as_ref(),
         );
 
-        // Inherit specific parent environment variables that are safe
-        for (key, value) in env::vars() {
-            // Only pass through safe environment variables
-            if key.starts_with("PATH")
-                || key.starts_with("HOME")
-                || key.starts_with("USER")
-                || key == "SHELL"
-                || key == "LANG"
-                || key == "LC_ALL"
-                || key.starts_with("LC_")
-            {
-                gaol_command.env(&key, &value);
-            }
+        // Inherit the parent environment variables the profile allows
+        for (key, value) in self.effective_env() {
+            gaol_command.env(&key, &value);
         }
 
         // Try to start the sandboxed process using gaol
         match sandbox.start(&mut gaol_command) {
-            Ok(process) => {
+            Ok(_process) => {
                 debug!("Successfully started sandboxed process using gaol");
                 // Unfortunately, gaol doesn't expose the underlying Child process
                 // So we need to use a different approach for now
@@ -116,9 +174,6 @@ impl SandboxExecutor {
                     "Gaol started the process but we can't get the Child handle - using fallback"
                 );
 
-                // Drop the process to avoid zombie
-                drop(process);
-
                 // Fall through to fallback
             }
             Err(e) => {
@@ -129,7 +184,60 @@ impl SandboxExecutor {
 
         // Fallback: Use regular process spawn with sandbox activation in child
         info!("Using child-side sandbox activation as fallback");
+        self.activation_std_command(command, args, cwd)?
+            .spawn()
+            .context("Failed to spawn process with sandbox environment")
+    }
+
+    /// Build the command `execute_sandboxed_spawn` runs, without spawning it
+    ///
+    /// Lets callers wait on the child asynchronously through
+    /// `tokio::process::Command::from`. Gaol's own attempt at starting the process is
+    /// skipped, since it never hands back a Child anyway.
+    pub fn prepare_sandboxed_std_command(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &Path,
+    ) -> Result<std::process::Command> {
+        #[cfg(target_os = "linux")]
+        if let Some(sandbox) = self.landlock_sandbox(command) {
+            return Ok(self.landlock_std_command(command, args, cwd, sandbox));
+        }
+        self.activation_std_command(command, args, cwd)
+    }
 
+    /// A command restricted by `sandbox` right before it execs
+    #[cfg(target_os = "linux")]
+    fn landlock_std_command(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &Path,
+        sandbox: super::landlock::LandlockSandbox,
+    ) -> std::process::Command {
+        let mut std_command = std::process::Command::new(command);
+        std_command
+            .args(args)
+            .current_dir(cwd)
+            .env_clear()
+            .envs(self.effective_env())
+            .envs(self.proxy_env())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        self.apply_std_limits(&mut std_command);
+        super::landlock::restrict_std_on_exec(&mut std_command, sandbox);
+        std_command
+    }
+
+    /// A command carrying the serialized rules, for the child to activate the sandbox itself
+    fn activation_std_command(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &Path,
+    ) -> Result<std::process::Command> {
         // Serialize the sandbox rules for the child process
         let rules_json = if let Some(ref serialized) = self.serialized_profile {
             serde_json::to_string(serialized)?
@@ -142,6 +250,8 @@ impl SandboxExecutor {
         std_command
             .args(args)
             .current_dir(cwd)
+            .env_clear()
+            .envs(self.effective_env())
             .env("GAOL_SANDBOX_ACTIVE", "1")
             .env(
                 "GAOL_PROJECT_PATH",
@@ -151,40 +261,57 @@ impl SandboxExecutor {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-
-        std_command
-            .spawn()
-            .context("Failed to spawn process with sandbox environment")
+        self.apply_std_limits(&mut std_command);
+        Ok(std_command)
     }
 
     /// Prepare a tokio Command for sandboxed execution
     /// The sandbox will be activated in the child process
-    pub fn prepare_sandboxed_command(&self, command: &str, args: &[&str], cwd: &Path) -> Command {
+    ///
+    /// Also returns the backend that restricts the command, named as in
+    /// `PlatformCapabilities::backend`, or None when nothing does.
+    pub fn prepare_sandboxed_command(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &Path,
+    ) -> (Command, Option<&'static str>) {
         info!("Preparing sandboxed command: {} {:?}", command, args);
 
         let mut cmd = Command::new(command);
-        cmd.args(args).current_dir(cwd);
+        cmd.args(args).current_dir(cwd).env_clear();
 
-        // Inherit essential environment variables from parent process
+        // Inherit the environment variables the profile allows
         // This is crucial for commands like Claude that need to find Node.js
-        for (key, value) in env::vars() {
-            // Pass through PATH and other essential environment variables
-            if key == "PATH"
-                || key == "HOME"
-                || key == "USER"
-                || key == "SHELL"
-                || key == "LANG"
-                || key == "LC_ALL"
-                || key.starts_with("LC_")
-                || key == "NODE_PATH"
-                || key == "NVM_DIR"
-                || key == "NVM_BIN"
-            {
-                debug!("Inheriting env var: {}={}", key, value);
-                cmd.env(&key, &value);
-            }
+        for (key, value) in self.effective_env() {
+            debug!(
+                "Inheriting env var: {}={}",
+                key,
+                environment::display_value(&key, &value)
+            );
+            cmd.env(&key, &value);
         }
 
+        cmd.envs(self.proxy_env());
+
+        // Limits go first, joining the cgroup is no longer possible once Landlock applies
+        if !self.resource_limits.is_unlimited() {
+            super::limits::apply_on_exec(
+                &mut cmd,
+                self.resource_limits,
+                self.cgroup_procs.as_deref(),
+            );
+        }
+
+        #[cfg(target_os = "linux")]
+        let backend = self.landlock_sandbox(command).map(|sandbox| {
+            super::landlock::restrict_on_exec(&mut cmd, sandbox);
+            "landlock"
+        });
+        // The child-side gaol activation below is disabled, so nothing else restricts it
+        #[cfg(not(target_os = "linux"))]
+        let backend = None;
+
         // Serialize the sandbox rules for the child process
         let rules_json = if let Some(ref serialized) = self.serialized_profile {
             let json = serde_json::to_string(serialized).ok();
@@ -238,28 +365,116 @@ impl SandboxExecutor {
             warn!("🚨 Failed to serialize sandbox rules - running without sandbox!");
         }
 
+        if backend.is_none() {
+            warn!("🚨 No sandbox backend restricts {}", command);
+        }
+
         cmd.stdin(Stdio::null()) // Don't pipe stdin - we have no input to send
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        cmd
+        (cmd, backend)
+    }
+
+    /// Install the resource limits on a `std` command, before any Landlock restriction
+    fn apply_std_limits(&self, command: &mut std::process::Command) {
+        if !self.resource_limits.is_unlimited() {
+            super::limits::apply_std_on_exec(
+                command,
+                self.resource_limits,
+                self.cgroup_procs.as_deref(),
+            );
+        }
+    }
+
+    /// Build the Landlock sandbox for the child process
+    ///
+    /// Returns None when the kernel has no Landlock support or the ruleset cannot be built.
+    /// Only `execute_sandboxed_spawn` has a gaol fallback; `prepare_sandboxed_command`
+    /// then reports that no backend restricts the command.
+    #[cfg(target_os = "linux")]
+    fn landlock_sandbox(&self, command: &str) -> Option<super::landlock::LandlockSandbox> {
+        let abi = super::landlock::active_abi_version()?;
+
+        let extracted;
+        let serialized = match self.serialized_profile {
+            Some(ref serialized) => serialized,
+            None => {
+                extracted = self.extract_sandbox_rules().ok()?;
+                &extracted
+            }
+        };
+
+        let proxy_port = self
+            .egress_proxy
+            .as_deref()
+            .and_then(|url| url.rsplit(':').next())
+            .and_then(|port| port.parse().ok());
+
+        match super::landlock::build_ruleset(serialized, &self.project_path, command, proxy_port)
+        {
+            Ok(sandbox) => {
+                info!("Enforcing sandbox with Landlock ABI v{}", abi);
+                Some(sandbox)
+            }
+            Err(e) => {
+                warn!("Failed to build Landlock ruleset: {}", e);
+                None
+            }
+        }
     }
 
-    /// Extract sandbox rules from the profile
-    /// This is a workaround since gaol doesn't expose the operations
+    /// Extract sandbox rules from the gaol profile
+    ///
+    /// gaol has no write operations, so the project directory is made writable as the
+    /// agents need it. Network access is only granted by the profile's own rules.
     fn extract_sandbox_rules(&self) -> Result<SerializedProfile> {
-        // We need to track the rules when building the profile
-        // For now, return a default set based on what we know
-        // This should be improved by tracking rules during profile creation
-        let operations = vec![
-            SerializedOperation::FileReadAll {
-                path: self.project_path.clone(),
-                is_subpath: true,
-            },
-            SerializedOperation::NetworkOutbound {
-                pattern: "all".to_string(),
-            },
-        ];
+        use gaol::profile::{AddressPattern, Operation, PathPattern};
+
+        let split = |pattern: &PathPattern| match pattern {
+            PathPattern::Literal(path) => (path.clone(), false),
+            PathPattern::Subpath(path) => (path.clone(), true),
+        };
+
+        let mut operations = Vec::new();
+        for op in self.profile.allowed_operations() {
+            operations.push(match op {
+                Operation::FileReadAll(pattern) => {
+                    let (path, is_subpath) = split(pattern);
+                    SerializedOperation::FileReadAll { path, is_subpath }
+                }
+                Operation::FileReadMetadata(pattern) => {
+                    let (path, is_subpath) = split(pattern);
+                    SerializedOperation::FileReadMetadata { path, is_subpath }
+                }
+                Operation::NetworkOutbound(AddressPattern::All) => {
+                    SerializedOperation::NetworkOutbound {
+                        pattern: "all".to_string(),
+                    }
+                }
+                Operation::NetworkOutbound(AddressPattern::Tcp(port)) => {
+                    SerializedOperation::NetworkTcp { port: *port }
+                }
+                Operation::NetworkOutbound(AddressPattern::LocalSocket(path)) => {
+                    SerializedOperation::NetworkLocalSocket { path: path.clone() }
+                }
+                Operation::SystemInfoRead => SerializedOperation::SystemInfoRead,
+                _ => continue,
+            });
+        }
+
+        operations.push(SerializedOperation::FileReadAll {
+            path: self.project_path.clone(),
+            is_subpath: true,
+        });
+        operations.push(SerializedOperation::FileWrite {
+            path: self.project_path.clone(),
+            is_subpath: true,
+        });
+        operations.push(SerializedOperation::FileCreate {
+            path: self.project_path.clone(),
+            is_subpath: true,
+        });
 
         Ok(SerializedProfile { operations })
     }
@@ -324,6 +539,10 @@ impl SandboxExecutor {
         Self {
             project_path,
             serialized_profile: None,
+            egress_proxy: None,
+            resource_limits: ResourceLimits::default(),
+            cgroup_procs: None,
+            env_policy: EnvPolicy::default(),
         }
     }
 
@@ -336,6 +555,10 @@ impl SandboxExecutor {
         Self {
             project_path,
             serialized_profile: Some(serialized_profile),
+            egress_proxy: None,
+            resource_limits: ResourceLimits::default(),
+            cgroup_procs: None,
+            env_policy: EnvPolicy::default(),
         }
     }
 
@@ -351,18 +574,35 @@ impl SandboxExecutor {
             command, args
         );
 
-        std::process::Command::new(command)
+        self.prepare_sandboxed_std_command(command, args, cwd)?
+            .spawn()
+            .context("Failed to spawn process")
+    }
+
+    /// Build the command `execute_sandboxed_spawn` runs (Windows - no sandboxing)
+    pub fn prepare_sandboxed_std_command(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &Path,
+    ) -> Result<std::process::Command> {
+        let mut std_command = std::process::Command::new(command);
+        std_command
             .args(args)
             .current_dir(cwd)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .context("Failed to spawn process")
+            .stderr(Stdio::piped());
+        Ok(std_command)
     }
 
-    /// Prepare a sandboxed tokio Command (Windows - no sandboxing)
-    pub fn prepare_sandboxed_command(&self, command: &str, args: &[&str], cwd: &Path) -> Command {
+    /// Prepare a sandboxed tokio Command (Windows - no sandboxing, so never a backend)
+    pub fn prepare_sandboxed_command(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &Path,
+    ) -> (Command, Option<&'static str>) {
         info!(
             "Preparing command without sandbox on Windows: {} {:?}",
             command, args
@@ -371,11 +611,12 @@ impl SandboxExecutor {
         let mut cmd = Command::new(command);
         cmd.args(args)
             .current_dir(cwd)
+            .envs(self.proxy_env())
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        cmd
+        (cmd, None)
     }
 
     /// Extract sandbox rules (no-op on Windows)
@@ -395,7 +636,7 @@ pub fn should_activate_sandbox() -> bool {
     env::var("GAOL_SANDBOX_ACTIVE").unwrap_or_default() == "1"
 }
 
-/// Helper to create a sandboxed tokio Command
+/// Helper to create a sandboxed tokio Command, along with the backend restricting it
 #[cfg(unix)]
 pub fn create_sandboxed_command(
     command: &str,
@@ -403,24 +644,41 @@ pub fn create_sandboxed_command(
     cwd: &Path,
     profile: gaol::profile::Profile,
     project_path: PathBuf,
-) -> Command {
-    let executor = SandboxExecutor::new(profile, project_path);
+    env_policy: EnvPolicy,
+) -> (Command, Option<&'static str>) {
+    let executor = SandboxExecutor::new(profile, project_path).with_env_policy(env_policy);
     executor.prepare_sandboxed_command(command, args, cwd)
 }
 
 // Serialization helpers for passing profile between processes
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct SerializedProfile {
     pub operations: Vec<SerializedOperation>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+impl SerializedProfile {
+    /// Hosts the egress proxy should let through, empty when there is no allow-list
+    pub fn allowed_domains(&self) -> Vec<String> {
+        self.operations
+            .iter()
+            .filter_map(|op| match op {
+                SerializedOperation::NetworkDomain { domain } => Some(domain.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum SerializedOperation {
     FileReadAll { path: PathBuf, is_subpath: bool },
     FileReadMetadata { path: PathBuf, is_subpath: bool },
+    FileWrite { path: PathBuf, is_subpath: bool },
+    FileCreate { path: PathBuf, is_subpath: bool },
     NetworkOutbound { pattern: String },
     NetworkTcp { port: u16 },
     NetworkLocalSocket { path: PathBuf },
+    NetworkDomain { domain: String },
     SystemInfoRead,
 }
 
@@ -449,6 +707,14 @@ fn deserialize_profile(
                 };
                 operations.push(gaol::profile::Operation::FileReadMetadata(pattern));
             }
+            SerializedOperation::FileWrite { .. } | SerializedOperation::FileCreate { .. } => {
+                // gaol has no write operations; these are only enforced with Landlock
+                debug!("Skipping write rule {:?} for gaol profile", op);
+            }
+            SerializedOperation::NetworkDomain { .. } => {
+                // Domain allow-lists are enforced by the egress proxy
+                debug!("Skipping domain rule {:?} for gaol profile", op);
+            }
             SerializedOperation::NetworkOutbound { pattern } => {
                 let addr_pattern = match pattern.as_str() {
                     "all" => gaol::profile::AddressPattern::All,
@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use landlock::{
+    Access, AccessFs, AccessNet, BitFlags, NetPort, PathBeneath, PathFd, Ruleset, RulesetAttr,
+    RulesetCreated, RulesetCreatedAttr, RulesetStatus, ABI,
+};
+use log::{debug, warn};
+use std::env;
+use std::ffi::CStr;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::executor::{SerializedOperation, SerializedProfile};
+use super::profile::{install_prefixes, resolve_executable, CLAUDE_STATE_PATHS};
+
+/// Highest Landlock ABI whose filesystem rights we request
+///
+/// Older kernels silently drop the rights they do not know about (best-effort mode).
+const TARGET_ABI: ABI = ABI::V5;
+
+/// System locations every dynamically linked program needs in order to start
+const RUNTIME_READ_PATHS: &[&str] = &[
+    "/bin",
+    "/sbin",
+    "/usr/bin",
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/libexec",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc/ld.so.cache",
+    "/etc/localtime",
+    "/etc/hosts",
+    "/etc/resolv.conf",
+    "/etc/ssl",
+    "/proc",
+];
+
+/// First Landlock ABI able to restrict TCP connections
+const NETWORK_ABI: u32 = 4;
+
+/// Device files that stay writable so ordinary tools keep working
+const WRITABLE_DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/full", "/dev/tty"];
+
+/// A Landlock ruleset plus the restrictions Landlock cannot express itself
+pub struct LandlockSandbox {
+    ruleset: RulesetCreated,
+    network_namespace: Option<NetworkNamespace>,
+}
+
+/// A user and network namespace of the child's own, leaving it only a loopback device
+///
+/// This is how gaol denies network access; Landlock can only filter TCP by port. The
+/// id maps are prepared before fork since nothing may allocate between fork and exec.
+struct NetworkNamespace {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+impl NetworkNamespace {
+    fn new() -> Self {
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+        }
+    }
+
+    /// Move the calling process into the namespaces, keeping its user and group ids
+    fn enter(&self) -> io::Result<()> {
+        if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        write_proc_file(c"/proc/self/setgroups", b"deny")?;
+        write_proc_file(c"/proc/self/uid_map", &self.uid_map)?;
+        write_proc_file(c"/proc/self/gid_map", &self.gid_map)
+    }
+}
+
+/// Write a `/proc` file without allocating
+fn write_proc_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+    let result = if written == data.len() as isize {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    };
+    unsafe { libc::close(fd) };
+    result
+}
+
+/// Query the Landlock ABI version supported by the running kernel
+///
+/// Returns `None` when the kernel lacks Landlock or it is disabled at boot.
+pub fn kernel_abi_version() -> Option<u32> {
+    // LANDLOCK_CREATE_RULESET_VERSION asks for the ABI instead of creating a ruleset
+    const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+
+    let version = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<libc::c_void>(),
+            0usize,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+
+    if version > 0 {
+        Some(version as u32)
+    } else {
+        None
+    }
+}
+
+/// The ABI level rulesets are enforced with, or `None` if Landlock is unavailable
+pub fn active_abi_version() -> Option<u32> {
+    kernel_abi_version().map(|version| version.min(TARGET_ABI as u32))
+}
+
+/// Build a Landlock ruleset from serialized sandbox rules
+///
+/// `FileReadAll` rules grant read and execute access, below the path for subpath rules
+/// and on the file itself for literal ones. Landlock never restricts `stat`, so
+/// `FileReadMetadata` rules only grant directory listing. `FileWrite` rules allow
+/// modifying existing files, and `FileCreate` rules additionally allow creating,
+/// removing and renaming entries. Landlock rules always cover a whole hierarchy, so
+/// create rules must name a directory; literal create rules on files are skipped.
+///
+/// The temp directory, a few device files and Claude's state under the home directory
+/// are always writable. The standard runtime locations and the directories and install
+/// prefix of `executable` are readable, otherwise the child could not even be exec'd.
+/// Paths that do not exist are skipped, except `~/.claude` which is created first.
+///
+/// A profile without any network rule gets a network namespace of its own, as under
+/// gaol. Host rules are enforced by the egress proxy: with `proxy_port` set, outgoing TCP
+/// connections are denied except to that port and to `network_tcp` ports, so the child
+/// cannot bypass the proxy. Without a proxy, profiles that only allow some TCP ports are
+/// limited to them. Landlock only filters TCP connections by port, so the proxy port is
+/// reachable on any host, and UDP (including DNS) is never restricted. Kernels before
+/// ABI 4 cannot restrict connections at all.
+pub fn build_ruleset(
+    profile: &SerializedProfile,
+    project_path: &Path,
+    executable: &str,
+    proxy_port: Option<u16>,
+) -> Result<LandlockSandbox> {
+    let all = AccessFs::from_all(TARGET_ABI);
+    let read = AccessFs::from_read(TARGET_ABI);
+    let write = AccessFs::from_write(TARGET_ABI);
+    // Writing to existing files, without creating or removing directory entries
+    let modify = write & AccessFs::from_file(TARGET_ABI);
+
+    let mut tcp_ports = Vec::new();
+    let mut allows_network = false;
+    let mut allows_all_network = false;
+    for op in &profile.operations {
+        match op {
+            SerializedOperation::NetworkOutbound { .. } => {
+                allows_network = true;
+                allows_all_network = true;
+            }
+            SerializedOperation::NetworkTcp { port } => {
+                allows_network = true;
+                tcp_ports.push(*port);
+            }
+            SerializedOperation::NetworkLocalSocket { .. }
+            | SerializedOperation::NetworkDomain { .. } => allows_network = true,
+            _ => {}
+        }
+    }
+    let network_namespace = (!allows_network).then(NetworkNamespace::new);
+    let restrict_tcp = allows_network && (proxy_port.is_some() || !allows_all_network);
+    tcp_ports.extend(proxy_port);
+
+    let mut ruleset = Ruleset::default()
+        .handle_access(all)
+        .context("Failed to configure Landlock ruleset")?;
+    if restrict_tcp {
+        if kernel_abi_version().is_none_or(|version| version < NETWORK_ABI) {
+            warn!("Kernel cannot restrict TCP connections, direct network access stays open");
+        }
+        ruleset = ruleset
+            .handle_access(AccessNet::ConnectTcp)
+            .context("Failed to configure Landlock network restrictions")?;
+    }
+    let mut ruleset = ruleset
+        .create()
+        .context("Failed to create Landlock ruleset")?;
+
+    if restrict_tcp {
+        for port in tcp_ports {
+            ruleset = ruleset
+                .add_rule(NetPort::new(port, AccessNet::ConnectTcp))
+                .with_context(|| format!("Failed to allow TCP connections to port {}", port))?;
+        }
+    }
+
+    for op in &profile.operations {
+        let (path, access) = match op {
+            SerializedOperation::FileReadAll { path, is_subpath } => {
+                (path.clone(), literal_access(path, *is_subpath, read))
+            }
+            SerializedOperation::FileReadMetadata { path, is_subpath } => {
+                if !*is_subpath {
+                    // Metadata of a single file needs no rule
+                    continue;
+                }
+                (path.clone(), AccessFs::ReadDir.into())
+            }
+            SerializedOperation::FileWrite { path, is_subpath } => {
+                (path.clone(), literal_access(path, *is_subpath, modify))
+            }
+            SerializedOperation::FileCreate { path, is_subpath } => {
+                if !*is_subpath && !path.is_dir() {
+                    // Granting this on the parent would open up the parent's whole subtree
+                    warn!(
+                        "Skipping Landlock create rule for {}: literal create rules must name a directory",
+                        path.display()
+                    );
+                    continue;
+                }
+                (path.clone(), write)
+            }
+            _ => continue,
+        };
+        ruleset = add_path_rule(ruleset, &path, access)?;
+    }
+
+    for path in RUNTIME_READ_PATHS {
+        let path = Path::new(path);
+        ruleset = add_path_rule(ruleset, path, literal_access(path, false, read))?;
+    }
+    for dir in executable_dirs(executable)
+        .into_iter()
+        .chain(install_prefixes(executable))
+    {
+        ruleset = add_path_rule(ruleset, &dir, read)?;
+    }
+    if let Some(home) = dirs::home_dir() {
+        // Claude can't create it once sandboxed, and without it the rule would be skipped
+        if let Err(e) = std::fs::create_dir_all(home.join(".claude")) {
+            debug!("Failed to create ~/.claude: {}", e);
+        }
+        for state in CLAUDE_STATE_PATHS {
+            let path = home.join(state);
+            ruleset = add_path_rule(ruleset, &path, literal_access(&path, false, read | write))?;
+        }
+    }
+
+    ruleset = add_path_rule(ruleset, project_path, read)?;
+    ruleset = add_path_rule(ruleset, &env::temp_dir(), read | write)?;
+    for device in WRITABLE_DEVICES {
+        let path = Path::new(device);
+        ruleset = add_path_rule(ruleset, path, literal_access(path, false, read | write))?;
+    }
+
+    Ok(LandlockSandbox {
+        ruleset,
+        network_namespace,
+    })
+}
+
+/// Enforce a sandbox on the calling process
+///
+/// Meant to run between `fork` and `exec`, so it avoids allocating on error. Landlock
+/// also sets `no_new_privs`, so setuid binaries cannot escalate privileges.
+pub fn restrict_self(sandbox: LandlockSandbox) -> io::Result<()> {
+    if let Some(namespace) = &sandbox.network_namespace {
+        namespace.enter()?;
+    }
+    match sandbox.ruleset.restrict_self() {
+        Ok(status) if status.ruleset != RulesetStatus::NotEnforced => Ok(()),
+        _ => Err(io::Error::from_raw_os_error(libc::EPERM)),
+    }
+}
+
+/// Arrange for a sandbox to be enforced in the child right before it execs
+pub fn restrict_on_exec(cmd: &mut tokio::process::Command, sandbox: LandlockSandbox) {
+    let mut sandbox = Some(sandbox);
+    unsafe {
+        cmd.pre_exec(move || match sandbox.take() {
+            Some(sandbox) => restrict_self(sandbox),
+            None => Ok(()),
+        });
+    }
+}
+
+/// Same as [`restrict_on_exec`] for a `std` command
+pub fn restrict_std_on_exec(cmd: &mut std::process::Command, sandbox: LandlockSandbox) {
+    use std::os::unix::process::CommandExt;
+
+    let mut sandbox = Some(sandbox);
+    unsafe {
+        cmd.pre_exec(move || match sandbox.take() {
+            Some(sandbox) => restrict_self(sandbox),
+            None => Ok(()),
+        });
+    }
+}
+
+/// Directories of an executable, both as found on `PATH` and after resolving symlinks
+fn executable_dirs(executable: &str) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for path in resolve_executable(executable) {
+        if let Some(parent) = path.parent() {
+            if !dirs.iter().any(|dir| dir == parent) {
+                dirs.push(parent.to_path_buf());
+            }
+        }
+    }
+    dirs
+}
+
+/// Narrow `access` to the rights that apply to a regular file
+///
+/// Directory rights on a file rule make the kernel reject the whole rule.
+fn literal_access(path: &Path, is_subpath: bool, access: BitFlags<AccessFs>) -> BitFlags<AccessFs> {
+    if is_subpath || path.is_dir() {
+        access
+    } else {
+        access & AccessFs::from_file(TARGET_ABI)
+    }
+}
+
+fn add_path_rule(
+    ruleset: RulesetCreated,
+    path: &Path,
+    access: BitFlags<AccessFs>,
+) -> Result<RulesetCreated> {
+    let fd = match PathFd::new(path) {
+        Ok(fd) => fd,
+        Err(e) => {
+            debug!("Skipping Landlock rule for {}: {}", path.display(), e);
+            return Ok(ruleset);
+        }
+    };
+
+    ruleset
+        .add_rule(PathBeneath::new(fd, access))
+        .with_context(|| format!("Failed to add Landlock rule for {}", path.display()))
+}
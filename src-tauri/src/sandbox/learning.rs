@@ -0,0 +1,627 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Component, Path, PathBuf};
+
+use super::profile::SandboxRule;
+
+/// Literal rules sharing a directory are collapsed into one subpath rule at this count
+const COLLAPSE_LITERALS_AT: usize = 3;
+
+/// Port of DNS servers, whose connections never need a rule of their own
+const DNS_PORT: u16 = 53;
+
+/// Locations that never need a rule, since the sandbox backends always provide them
+const IMPLICIT_PREFIXES: &[&str] = &["/proc", "/sys", "/dev", "/tmp"];
+
+/// Everything a learning run was seen touching
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObservedAccess {
+    pub read_paths: BTreeSet<PathBuf>,
+    pub written_paths: BTreeSet<PathBuf>,
+    pub created_paths: BTreeSet<PathBuf>,
+    /// `host:port` of outbound TCP/UDP connections
+    pub network_endpoints: BTreeSet<String>,
+    pub local_sockets: BTreeSet<PathBuf>,
+    /// Addresses from the DNS answers the program received, mapped to the host name it
+    /// looked up
+    #[serde(default)]
+    pub resolved_addresses: BTreeMap<String, String>,
+}
+
+/// Changes between a profile's current rules and a proposal
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleDiff {
+    pub added: Vec<SandboxRule>,
+    pub removed: Vec<SandboxRule>,
+    pub unchanged: Vec<SandboxRule>,
+}
+
+/// Where the trace of a learning run is written
+pub fn trace_path(app_data_dir: &Path, run_id: i64) -> PathBuf {
+    app_data_dir
+        .join("learning")
+        .join(format!("run-{}.strace", run_id))
+}
+
+/// Whether `strace` can be used for learning runs
+pub fn is_tracing_available() -> bool {
+    cfg!(target_os = "linux")
+        && std::process::Command::new("strace")
+            .arg("-V")
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+}
+
+/// Arguments that make `strace` follow a program and all its children, logging file,
+/// network and exec syscalls to `trace_path`. The traced program and its arguments go
+/// after these.
+pub fn strace_args(trace_path: &Path) -> Vec<String> {
+    vec![
+        "-f".to_string(),
+        "-qq".to_string(),
+        "-s".to_string(),
+        "4096".to_string(),
+        "-e".to_string(),
+        "trace=%file,%network,execve".to_string(),
+        "-o".to_string(),
+        trace_path.to_string_lossy().into_owned(),
+        "--".to_string(),
+    ]
+}
+
+/// Collect the accesses recorded in an strace log
+///
+/// Failed calls are ignored since the program coped without them, except for
+/// non-blocking connects which report `EINPROGRESS`. Relative paths are resolved against
+/// `cwd`, the directory the traced program started in.
+pub fn parse_trace(trace: &str, cwd: &Path) -> ObservedAccess {
+    let mut observed = ObservedAccess::default();
+
+    for line in trace.lines() {
+        let call = match parse_call(line) {
+            Some(call) => call,
+            None => continue,
+        };
+
+        if call.name == "connect" {
+            if call.succeeded || call.result.contains("EINPROGRESS") {
+                record_connect(&call.args, &mut observed);
+            }
+            continue;
+        }
+        if !call.succeeded {
+            continue;
+        }
+        if matches!(call.name.as_str(), "recvfrom" | "recvmsg" | "recvmmsg") {
+            record_dns_answers(&call.args, &mut observed);
+            continue;
+        }
+
+        let strings = quoted_strings(&call.args);
+        // Relative paths of *at calls only resolve against the cwd with AT_FDCWD
+        let is_at_call = call.name.ends_with("at") || call.name.ends_with("at2");
+        let relative_ok = !is_at_call || call.args.starts_with("AT_FDCWD");
+        let resolve = |index: usize| -> Option<PathBuf> {
+            let path = Path::new(strings.get(index)?);
+            if path.is_absolute() {
+                Some(normalize_path(path))
+            } else if relative_ok {
+                Some(normalize_path(&cwd.join(path)))
+            } else {
+                None
+            }
+        };
+
+        match call.name.as_str() {
+            "open" | "openat" | "openat2" => {
+                if let Some(path) = resolve(0) {
+                    if call.args.contains("O_CREAT") {
+                        observed.created_paths.insert(path);
+                    } else if call.args.contains("O_WRONLY") || call.args.contains("O_RDWR") {
+                        observed.written_paths.insert(path);
+                    } else {
+                        observed.read_paths.insert(path);
+                    }
+                }
+            }
+            "execve" | "execveat" => observed.read_paths.extend(resolve(0)),
+            "truncate" => observed.written_paths.extend(resolve(0)),
+            "creat" | "mkdir" | "mkdirat" | "rmdir" | "unlink" | "unlinkat" | "mknod"
+            | "mknodat" => observed.created_paths.extend(resolve(0)),
+            // The new name is the last path argument
+            "symlink" | "symlinkat" | "link" | "linkat" => observed
+                .created_paths
+                .extend(resolve(strings.len().saturating_sub(1))),
+            "rename" | "renameat" | "renameat2" => {
+                observed.created_paths.extend(resolve(0));
+                observed.created_paths.extend(resolve(1));
+            }
+            _ => {}
+        }
+    }
+
+    observed
+}
+
+/// Turn observed accesses into a minimal set of rules
+///
+/// Paths are generalised to `{{PROJECT_PATH}}` and `{{HOME}}` templates. Project reads
+/// become one rule for the whole project, while writes are grouped by the project's
+/// top-level directories. Other paths are grouped by their first two components, and
+/// files sharing a directory are merged into a rule for that directory. Create rules
+/// always name a directory, since Landlock cannot attach them to files.
+///
+/// Connections to addresses the program looked up become domain rules for the host
+/// name, and connections to other addresses become rules for their TCP port. DNS
+/// lookups themselves need no rule.
+pub fn propose_rules(
+    observed: &ObservedAccess,
+    project_path: &Path,
+    home_dir: &Path,
+) -> Vec<SandboxRule> {
+    let temp_dir = env::temp_dir();
+    let generalize = |path: &Path, operation_type: &str| -> Option<(String, String)> {
+        if IMPLICIT_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+            || path.starts_with(&temp_dir)
+        {
+            return None;
+        }
+
+        let is_create = operation_type == "file_create";
+        let (template, base, depth) = if path.starts_with(project_path) {
+            if operation_type == "file_read_all" {
+                return Some(("subpath".to_string(), "{{PROJECT_PATH}}".to_string()));
+            }
+            ("{{PROJECT_PATH}}", project_path, 1)
+        } else if path.starts_with(home_dir) {
+            ("{{HOME}}", home_dir, 1)
+        } else {
+            ("", Path::new("/"), 2)
+        };
+
+        let relative: Vec<_> = path
+            .strip_prefix(base)
+            .ok()?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let join = |parts: &[String]| {
+            let mut value = template.to_string();
+            for part in parts {
+                value.push('/');
+                value.push_str(part);
+            }
+            if value.is_empty() {
+                "/".to_string()
+            } else {
+                value
+            }
+        };
+
+        if relative.is_empty() {
+            Some(("subpath".to_string(), join(&relative)))
+        } else if relative.len() > depth {
+            Some(("subpath".to_string(), join(&relative[..depth])))
+        } else if is_create {
+            // A file directly below the base can only be covered through its directory
+            Some(("subpath".to_string(), join(&relative[..relative.len() - 1])))
+        } else {
+            Some(("literal".to_string(), join(&relative)))
+        }
+    };
+
+    let mut rules = Vec::new();
+    for (operation_type, paths) in [
+        ("file_read_all", &observed.read_paths),
+        ("file_write", &observed.written_paths),
+        ("file_create", &observed.created_paths),
+    ] {
+        let patterns: BTreeSet<(String, String)> = paths
+            .iter()
+            .filter_map(|path| generalize(path, operation_type))
+            .collect();
+        for (pattern_type, pattern_value) in minimize(patterns) {
+            rules.push(make_rule(
+                operation_type,
+                &pattern_type,
+                &pattern_value,
+                None,
+            ));
+        }
+    }
+
+    let mut domains = BTreeSet::new();
+    let mut ports = BTreeSet::new();
+    for endpoint in &observed.network_endpoints {
+        let Some((address, port)) = split_endpoint(endpoint) else {
+            continue;
+        };
+        if port == DNS_PORT {
+            continue;
+        }
+        match resolved_name(observed, address) {
+            Some(host) => {
+                domains.insert(host.to_string());
+            }
+            None => {
+                ports.insert(port);
+            }
+        }
+    }
+    for domain in domains {
+        rules.push(make_rule("network_outbound", "domain", &domain, None));
+    }
+    for port in ports {
+        rules.push(make_rule(
+            "network_outbound",
+            "tcp",
+            &port.to_string(),
+            None,
+        ));
+    }
+    for socket in &observed.local_sockets {
+        rules.push(make_rule(
+            "network_outbound",
+            "local_socket",
+            &socket.to_string_lossy(),
+            Some(r#"["macos"]"#),
+        ));
+    }
+
+    rules
+}
+
+/// Compare rules by operation and pattern, ignoring IDs and timestamps
+pub fn diff_rules(current: &[SandboxRule], proposed: &[SandboxRule]) -> RuleDiff {
+    let key = |rule: &SandboxRule| {
+        (
+            rule.operation_type.clone(),
+            rule.pattern_type.clone(),
+            rule.pattern_value.clone(),
+        )
+    };
+    let current_keys: BTreeSet<_> = current.iter().map(key).collect();
+    let proposed_keys: BTreeSet<_> = proposed.iter().map(key).collect();
+
+    let mut diff = RuleDiff::default();
+    for rule in proposed {
+        if current_keys.contains(&key(rule)) {
+            diff.unchanged.push(rule.clone());
+        } else {
+            diff.added.push(rule.clone());
+        }
+    }
+    diff.removed = current
+        .iter()
+        .filter(|rule| !proposed_keys.contains(&key(rule)))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// Merge literals that share a directory and drop rules covered by a subpath rule
+fn minimize(patterns: BTreeSet<(String, String)>) -> BTreeSet<(String, String)> {
+    let mut by_parent: BTreeMap<String, usize> = BTreeMap::new();
+    for (pattern_type, value) in &patterns {
+        if pattern_type == "literal" {
+            if let Some(parent) = Path::new(value).parent() {
+                *by_parent
+                    .entry(parent.to_string_lossy().into_owned())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    let merged: BTreeSet<(String, String)> = patterns
+        .into_iter()
+        .map(|(pattern_type, value)| {
+            let parent = Path::new(&value)
+                .parent()
+                .map(|p| p.to_string_lossy().into_owned());
+            match parent {
+                // Never widen a rule to a whole template root or the filesystem root
+                Some(parent)
+                    if pattern_type == "literal"
+                        && !matches!(parent.as_str(), "/" | "{{HOME}}" | "{{PROJECT_PATH}}")
+                        && by_parent.get(&parent).copied().unwrap_or(0) >= COLLAPSE_LITERALS_AT =>
+                {
+                    ("subpath".to_string(), parent)
+                }
+                _ => (pattern_type, value),
+            }
+        })
+        .collect();
+
+    let subpaths: Vec<String> = merged
+        .iter()
+        .filter(|(pattern_type, _)| pattern_type == "subpath")
+        .map(|(_, value)| value.clone())
+        .collect();
+    merged
+        .into_iter()
+        .filter(|(_, value)| {
+            !subpaths
+                .iter()
+                .any(|subpath| subpath != value && Path::new(value).starts_with(subpath))
+        })
+        .collect()
+}
+
+fn make_rule(
+    operation_type: &str,
+    pattern_type: &str,
+    pattern_value: &str,
+    platform_support: Option<&str>,
+) -> SandboxRule {
+    SandboxRule {
+        id: None,
+        profile_id: 0,
+        operation_type: operation_type.to_string(),
+        pattern_type: pattern_type.to_string(),
+        pattern_value: pattern_value.to_string(),
+        enabled: true,
+        platform_support: platform_support.map(|p| p.to_string()),
+        created_at: String::new(),
+    }
+}
+
+/// One syscall from an strace log
+struct TracedCall {
+    name: String,
+    args: String,
+    result: String,
+    succeeded: bool,
+}
+
+/// Parse `[pid] name(args) = result`, treating unfinished calls as successful
+fn parse_call(line: &str) -> Option<TracedCall> {
+    let line = line
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start();
+    let open = line.find('(')?;
+    let name = &line[..open];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    let rest = &line[open + 1..];
+    if let Some(args) = rest.strip_suffix(" <unfinished ...>") {
+        return Some(TracedCall {
+            name: name.to_string(),
+            args: args.to_string(),
+            result: String::new(),
+            succeeded: true,
+        });
+    }
+
+    let (args, result) = rest.rsplit_once(") = ")?;
+    Some(TracedCall {
+        name: name.to_string(),
+        args: args.to_string(),
+        succeeded: !result.trim_start().starts_with('-'),
+        result: result.to_string(),
+    })
+}
+
+/// Record the endpoint of a `connect` call
+fn record_connect(args: &str, observed: &mut ObservedAccess) {
+    if args.contains("AF_UNIX") {
+        // Abstract sockets (`sun_path=@"..."`) have no filesystem path
+        if !args.contains("sun_path=@") {
+            if let Some(path) = quoted_strings(args).into_iter().next() {
+                observed.local_sockets.insert(PathBuf::from(path));
+            }
+        }
+        return;
+    }
+
+    let port = args
+        .split("port=htons(")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .and_then(|port| port.parse::<u16>().ok());
+    let address = quoted_strings(args).into_iter().next();
+
+    if let (Some(port), Some(address)) = (port, address) {
+        let endpoint = if args.contains("AF_INET6") {
+            format!("[{}]:{}", address, port)
+        } else {
+            format!("{}:{}", address, port)
+        };
+        observed.network_endpoints.insert(endpoint);
+    }
+}
+
+/// Record the addresses in a DNS answer received by a `recv*` call
+///
+/// Answers are attributed to the name in their question, so addresses reached through
+/// CNAME records map to the name the program asked for.
+fn record_dns_answers(args: &str, observed: &mut ObservedAccess) {
+    // recvmsg prints the sender's address before the buffer
+    for buffer in quoted_bytes(args) {
+        if let Some((name, addresses)) = parse_dns_answer(&buffer) {
+            for address in addresses {
+                observed.resolved_addresses.insert(address, name.clone());
+            }
+            return;
+        }
+    }
+}
+
+/// The question name and A/AAAA addresses of a DNS response
+fn parse_dns_answer(message: &[u8]) -> Option<(String, Vec<String>)> {
+    let read_u16 = |pos: usize| -> Option<u16> {
+        Some(u16::from_be_bytes([
+            *message.get(pos)?,
+            *message.get(pos + 1)?,
+        ]))
+    };
+    let flags = read_u16(2)?;
+    // A response to a standard query, with exactly one question
+    if flags & 0x8000 == 0 || flags & 0x7800 != 0 || read_u16(4)? != 1 {
+        return None;
+    }
+    let answers = read_u16(6)?;
+
+    let (name, mut pos) = read_dns_name(message, 12)?;
+    pos += 4;
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        let (_, next) = read_dns_name(message, pos)?;
+        let record_type = read_u16(next)?;
+        let length = read_u16(next + 8)? as usize;
+        let data = message.get(next + 10..next + 10 + length)?;
+        match (record_type, data.len()) {
+            (1, 4) => addresses.push(Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string()),
+            (28, 16) => {
+                let octets: [u8; 16] = data.try_into().ok()?;
+                addresses.push(Ipv6Addr::from(octets).to_string());
+            }
+            _ => {}
+        }
+        pos = next + 10 + length;
+    }
+
+    (!name.is_empty()).then_some((name.to_ascii_lowercase(), addresses))
+}
+
+/// Read a possibly compressed DNS name at `pos`, returning it and the position after it
+fn read_dns_name(message: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Bounds the pointers followed, so malformed messages can't loop
+    for _ in 0..message.len() {
+        let length = *message.get(pos)? as usize;
+        if length == 0 {
+            return Some((labels.join("."), end.unwrap_or(pos + 1)));
+        }
+        if length & 0xc0 == 0xc0 {
+            let target = (length & 0x3f) << 8 | *message.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = target;
+            continue;
+        }
+        let label = message.get(pos + 1..pos + 1 + length)?;
+        if !label
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'))
+        {
+            return None;
+        }
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + length;
+    }
+    None
+}
+
+/// Split `host:port` or `[v6]:port` into the address and port
+fn split_endpoint(endpoint: &str) -> Option<(&str, u16)> {
+    let (address, port) = endpoint.rsplit_once(':')?;
+    let address = address
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+        .unwrap_or(address);
+    Some((address, port.parse().ok()?))
+}
+
+/// The host name an address was looked up as, also for IPv4-mapped IPv6 addresses
+fn resolved_name<'a>(observed: &'a ObservedAccess, address: &str) -> Option<&'a str> {
+    observed
+        .resolved_addresses
+        .get(address)
+        .or_else(|| {
+            address
+                .strip_prefix("::ffff:")
+                .and_then(|v4| observed.resolved_addresses.get(v4))
+        })
+        .map(String::as_str)
+}
+
+/// The double-quoted strings in strace arguments, with escapes resolved
+fn quoted_strings(args: &str) -> Vec<String> {
+    quoted_bytes(args)
+        .iter()
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        .collect()
+}
+
+/// The raw bytes of the double-quoted strings in strace arguments
+///
+/// strace prints bytes that aren't printable ASCII as C escapes, in octal unless it was
+/// asked for hex.
+fn quoted_bytes(args: &str) -> Vec<Vec<u8>> {
+    let mut strings = Vec::new();
+    let mut bytes = args.bytes().peekable();
+
+    while let Some(b) = bytes.next() {
+        if b != b'"' {
+            continue;
+        }
+        let mut value = Vec::new();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'"' => break,
+                b'\\' => match bytes.next() {
+                    Some(b'n') => value.push(b'\n'),
+                    Some(b't') => value.push(b'\t'),
+                    Some(b'r') => value.push(b'\r'),
+                    Some(b'v') => value.push(0x0b),
+                    Some(b'f') => value.push(0x0c),
+                    Some(b'x') => {
+                        let mut byte = 0u8;
+                        for _ in 0..2 {
+                            match bytes.peek().and_then(|&d| (d as char).to_digit(16)) {
+                                Some(digit) => {
+                                    byte = byte << 4 | digit as u8;
+                                    bytes.next();
+                                }
+                                None => break,
+                            }
+                        }
+                        value.push(byte);
+                    }
+                    Some(first @ b'0'..=b'7') => {
+                        let mut byte = first - b'0';
+                        for _ in 0..2 {
+                            match bytes.peek() {
+                                Some(&digit @ b'0'..=b'7') => {
+                                    byte = byte.wrapping_shl(3) | (digit - b'0');
+                                    bytes.next();
+                                }
+                                _ => break,
+                            }
+                        }
+                        value.push(byte);
+                    }
+                    Some(other) => value.push(other),
+                    None => {}
+                },
+                other => value.push(other),
+            }
+        }
+        strings.push(value);
+    }
+
+    strings
+}
+
+/// Lexically normalize a path, resolving `.` and `..` without touching the filesystem
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
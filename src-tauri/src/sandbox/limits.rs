@@ -0,0 +1,435 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Mount point of the unified cgroup v2 hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Controllers a run's cgroup needs for its memory and process limits
+const CGROUP_CONTROLLERS: &[&str] = &["memory", "pids"];
+
+/// Resource limits of a sandbox profile, each unlimited when unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// CPU time per process, in seconds
+    #[serde(default)]
+    pub cpu_time_secs: Option<u64>,
+    /// Memory for the whole run, in megabytes. Only enforced through a cgroup
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Processes for the whole run. Only enforced through a cgroup
+    #[serde(default)]
+    pub max_processes: Option<u64>,
+    /// Open file descriptors per process
+    #[serde(default)]
+    pub max_open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Read the limit columns starting at `first_column`, in declaration order
+    pub fn from_row(row: &rusqlite::Row, first_column: usize) -> rusqlite::Result<Self> {
+        let get = |offset: usize| -> rusqlite::Result<Option<u64>> {
+            Ok(row
+                .get::<_, Option<i64>>(first_column + offset)?
+                .and_then(|value| u64::try_from(value).ok()))
+        };
+        Ok(Self {
+            cpu_time_secs: get(0)?,
+            memory_mb: get(1)?,
+            max_processes: get(2)?,
+            max_open_files: get(3)?,
+        })
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether any limit is one only a cgroup can enforce
+    pub fn needs_cgroup(&self) -> bool {
+        self.memory_mb.is_some() || self.max_processes.is_some()
+    }
+
+    /// Reject limits of zero, which would stop the agent from starting at all
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("CPU time", self.cpu_time_secs),
+            ("memory", self.memory_mb),
+            ("process", self.max_processes),
+            ("open file", self.max_open_files),
+        ] {
+            if value == Some(0) {
+                anyhow::bail!("The {} limit must be greater than zero", name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A cgroup v2 sub-tree holding one run and everything it spawns
+///
+/// Memory and process limits set here cover the whole tree rather than each process.
+/// Dropping it kills whatever is still inside and removes the cgroup.
+pub struct Cgroup {
+    path: PathBuf,
+    /// Process keeping a systemd scope alive, for cgroups systemd created for us
+    placeholder: Option<std::process::Child>,
+}
+
+impl Cgroup {
+    /// Create a cgroup below the one this process runs in
+    ///
+    /// Fails when cgroup v2 is not mounted, or the current cgroup is not delegated to us
+    /// with the memory and pids controllers, in which case memory and process limits
+    /// cannot be enforced.
+    pub fn create(name: &str, limits: &ResourceLimits) -> Result<Self> {
+        let parent = delegated_cgroup()?;
+        enable_controllers(&parent)?;
+        Self::create_in(&parent, name, limits)
+    }
+
+    /// Have the systemd user manager create the cgroup as a transient scope
+    ///
+    /// Desktop sessions put the app in a cgroup holding processes, which can't have
+    /// children with controllers. `systemd-run --user --scope` instead starts a
+    /// placeholder in a scope of its own that carries the limits and is delegated to us,
+    /// so the run's processes can join it. The placeholder counts towards `TasksMax`.
+    pub fn create_scope(name: &str, limits: &ResourceLimits) -> Result<Self> {
+        if !systemd_scope_available() {
+            anyhow::bail!("No systemd user manager is running");
+        }
+
+        let unit = format!("{}.scope", name);
+        let mut command = std::process::Command::new("systemd-run");
+        command
+            .args(["--user", "--scope", "--quiet", "--collect"])
+            .arg(format!("--unit={}", unit))
+            .args(["-p", "Delegate=yes"]);
+        if let Some(memory_mb) = limits.memory_mb {
+            command
+                .arg("-p")
+                .arg(format!("MemoryMax={}M", memory_mb))
+                .args(["-p", "MemorySwapMax=0"]);
+        }
+        if let Some(max_processes) = limits.max_processes {
+            command
+                .arg("-p")
+                .arg(format!("TasksMax={}", max_processes + 1));
+        }
+        let mut placeholder = command
+            .args(["--", "sleep", "infinity"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("Failed to run systemd-run")?;
+
+        // systemd-run execs the placeholder once the scope exists
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        let path = loop {
+            if let Some(status) = placeholder.try_wait()? {
+                anyhow::bail!("systemd-run failed to create {}: {}", unit, status);
+            }
+            match process_cgroup(&placeholder.id().to_string()) {
+                Some(path) if path.ends_with(&unit) => break Some(path),
+                _ if std::time::Instant::now() > deadline => break None,
+                _ => std::thread::sleep(std::time::Duration::from_millis(20)),
+            }
+        };
+        let cgroup = path.filter(|path| is_writable(&path.join("cgroup.procs")));
+        let Some(path) = cgroup else {
+            let _ = placeholder.kill();
+            let _ = placeholder.wait();
+            anyhow::bail!("systemd did not delegate {} to us", unit);
+        };
+
+        info!("Created systemd scope {} for {:?}", path.display(), limits);
+        Ok(Self {
+            path,
+            placeholder: Some(placeholder),
+        })
+    }
+
+    /// Create a cgroup called `name` inside `parent` and write its limits
+    pub fn create_in(parent: &Path, name: &str, limits: &ResourceLimits) -> Result<Self> {
+        let path = parent.join(name);
+        fs::create_dir(&path)
+            .with_context(|| format!("Failed to create cgroup {}", path.display()))?;
+        let cgroup = Self {
+            path,
+            placeholder: None,
+        };
+
+        if let Some(memory_mb) = limits.memory_mb {
+            cgroup.write("memory.max", &(memory_mb * 1024 * 1024).to_string())?;
+            // Fail fast instead of swapping the machine to a crawl
+            cgroup.write("memory.swap.max", "0").ok();
+        }
+        if let Some(max_processes) = limits.max_processes {
+            cgroup.write("pids.max", &max_processes.to_string())?;
+        }
+
+        info!("Created cgroup {} for {:?}", cgroup.path.display(), limits);
+        Ok(cgroup)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The `cgroup.procs` file a child writes itself into
+    pub fn procs_path(&self) -> PathBuf {
+        self.path.join("cgroup.procs")
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        let path = self.path.join(file);
+        fs::write(&path, value)
+            .with_context(|| format!("Failed to write {} to {}", value, path.display()))
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // cgroup.kill needs Linux 5.14, older kernels leave stragglers to exit by themselves
+        self.write("cgroup.kill", "1").ok();
+        if let Some(mut placeholder) = self.placeholder.take() {
+            // systemd removes the scope once nothing is left in it
+            let _ = placeholder.kill();
+            let _ = placeholder.wait();
+            return;
+        }
+        if fs::remove_dir(&self.path).is_ok() {
+            return;
+        }
+
+        // Killed processes take a moment to leave, which is not worth blocking the
+        // async runtime that usually drops us
+        let path = std::mem::take(&mut self.path);
+        std::thread::spawn(move || {
+            for _ in 0..50 {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                if fs::remove_dir(&path).is_ok() {
+                    return;
+                }
+            }
+            warn!(
+                "Failed to remove cgroup {}, processes may still be running in it",
+                path.display()
+            );
+        });
+    }
+}
+
+/// Whether run cgroups can be created, the same check [`Cgroup::create`] makes
+pub fn cgroup_available() -> bool {
+    delegated_cgroup().is_ok()
+}
+
+/// Whether a systemd user manager can create scopes for [`Cgroup::create_scope`]
+pub fn systemd_scope_available() -> bool {
+    let manager_running = env::var_os("XDG_RUNTIME_DIR")
+        .is_some_and(|dir| Path::new(&dir).join("systemd/private").exists());
+    manager_running
+        && env::var_os("PATH").is_some_and(|paths| {
+            env::split_paths(&paths).any(|dir| dir.join("systemd-run").is_file())
+        })
+}
+
+/// Apply `limits` to the calling process
+///
+/// Meant to run between `fork` and `exec`. Joins `cgroup_procs` first, so the limits
+/// cover the child from its very first instruction. Memory and process limits are left
+/// to the cgroup: `RLIMIT_NPROC` counts every process of the user, and `RLIMIT_DATA`
+/// caps address space that runtimes like V8 reserve without using, so neither is a
+/// workable substitute.
+#[cfg(unix)]
+pub fn apply_to_self(
+    limits: &ResourceLimits,
+    cgroup_procs: Option<&std::ffi::CStr>,
+) -> std::io::Result<()> {
+    if let Some(procs) = cgroup_procs {
+        join_cgroup(procs)?;
+    }
+
+    if let Some(secs) = limits.cpu_time_secs {
+        set_rlimit(libc::RLIMIT_CPU as _, secs)?;
+    }
+    if let Some(files) = limits.max_open_files {
+        set_rlimit(libc::RLIMIT_NOFILE as _, files)?;
+    }
+    Ok(())
+}
+
+/// Arrange for `limits` to be applied in the child right before it execs
+///
+/// `cgroup_procs` is the [`Cgroup::procs_path`] of the run's cgroup, if it has one. Must
+/// be called before any Landlock restriction is registered, since Landlock would
+/// deny writing to the cgroup.
+#[cfg(unix)]
+pub fn apply_on_exec(
+    cmd: &mut tokio::process::Command,
+    limits: ResourceLimits,
+    cgroup_procs: Option<&Path>,
+) {
+    let procs = cgroup_procs.and_then(path_cstring);
+    unsafe {
+        cmd.pre_exec(move || apply_to_self(&limits, procs.as_deref()));
+    }
+}
+
+/// Same as [`apply_on_exec`] for a `std` command
+#[cfg(unix)]
+pub fn apply_std_on_exec(
+    cmd: &mut std::process::Command,
+    limits: ResourceLimits,
+    cgroup_procs: Option<&Path>,
+) {
+    use std::os::unix::process::CommandExt;
+
+    let procs = cgroup_procs.and_then(path_cstring);
+    unsafe {
+        cmd.pre_exec(move || apply_to_self(&limits, procs.as_deref()));
+    }
+}
+
+#[cfg(unix)]
+fn path_cstring(path: &Path) -> Option<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+/// Move the calling process into a cgroup, without allocating
+#[cfg(unix)]
+fn join_cgroup(procs: &std::ffi::CStr) -> std::io::Result<()> {
+    unsafe {
+        let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Writing 0 moves the writing process itself
+        let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+        let result = if written == 1 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        };
+        libc::close(fd);
+        result
+    }
+}
+
+#[cfg(unix)]
+fn set_rlimit(resource: i32, value: u64) -> std::io::Result<()> {
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    unsafe {
+        if libc::getrlimit(resource as _, &mut current) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    // Never try to raise a hard limit, which needs privileges
+    let value = (value as libc::rlim_t).min(current.rlim_max);
+    let limit = libc::rlimit {
+        rlim_cur: value,
+        rlim_max: value,
+    };
+    unsafe {
+        if libc::setrlimit(resource as _, &limit) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// The cgroup v2 directory of the current process
+fn own_cgroup() -> Option<PathBuf> {
+    process_cgroup("self")
+}
+
+/// The cgroup v2 directory of a process, `pid` being a number or `self`
+fn process_cgroup(pid: &str) -> Option<PathBuf> {
+    let root = Path::new(CGROUP_ROOT);
+    if !root.join("cgroup.controllers").exists() {
+        return None;
+    }
+
+    let membership = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    let relative = membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?
+        .trim_start_matches('/');
+    Some(root.join(relative))
+}
+
+/// The current cgroup, if run cgroups can be created below it
+///
+/// It must be writable, and have the memory and pids controllers enabled for its
+/// children. Only the root cgroup can have them enabled by us, since cgroup v2 refuses
+/// to enable controllers for the children of a cgroup that holds processes itself.
+fn delegated_cgroup() -> Result<PathBuf> {
+    let path = own_cgroup().context("cgroup v2 is not available")?;
+    if !is_writable(&path) {
+        anyhow::bail!("cgroup {} is not delegated to us", path.display());
+    }
+
+    let enabled = fs::read_to_string(path.join("cgroup.subtree_control")).unwrap_or_default();
+    let available = fs::read_to_string(path.join("cgroup.controllers")).unwrap_or_default();
+    for controller in CGROUP_CONTROLLERS {
+        if enabled.split_whitespace().any(|c| c == *controller) {
+            continue;
+        }
+        if path != Path::new(CGROUP_ROOT)
+            || !available.split_whitespace().any(|c| c == *controller)
+        {
+            anyhow::bail!(
+                "The {} controller is not enabled for children of {}",
+                controller,
+                path.display()
+            );
+        }
+    }
+    Ok(path)
+}
+
+/// Make sure child cgroups of `parent` get the controllers we need
+fn enable_controllers(parent: &Path) -> Result<()> {
+    let subtree_control = parent.join("cgroup.subtree_control");
+    let enabled = fs::read_to_string(&subtree_control).unwrap_or_default();
+    let missing: Vec<String> = CGROUP_CONTROLLERS
+        .iter()
+        .filter(|controller| !enabled.split_whitespace().any(|c| c == **controller))
+        .map(|controller| format!("+{}", controller))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    debug!("Enabling {:?} in {}", missing, subtree_control.display());
+    // Fails with EBUSY while `parent` itself has processes, unless it is the root
+    fs::write(&subtree_control, missing.join(" ")).with_context(|| {
+        format!(
+            "Failed to enable cgroup controllers in {}",
+            parent.display()
+        )
+    })
+}
+
+fn is_writable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        path_cstring(path)
+            .is_some_and(|path| unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
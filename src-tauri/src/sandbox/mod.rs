@@ -1,11 +1,33 @@
+#[cfg(unix)]
+#[allow(unused)]
+pub mod capture;
 #[allow(unused)]
 pub mod defaults;
 #[allow(unused)]
+pub mod environment;
+#[allow(unused)]
 pub mod executor;
+#[cfg(target_os = "linux")]
+#[allow(unused)]
+pub mod landlock;
+#[allow(unused)]
+pub mod learning;
+#[allow(unused)]
+pub mod limits;
 #[allow(unused)]
 pub mod platform;
 #[allow(unused)]
+pub mod policy;
+#[allow(unused)]
+pub mod probe;
+#[allow(unused)]
 pub mod profile;
+#[allow(unused)]
+pub mod proxy;
+#[allow(unused)]
+pub mod variables;
+#[allow(unused)]
+pub mod violations;
 
 // These are used in agents.rs and claude.rs via direct module paths
 #[allow(unused)]
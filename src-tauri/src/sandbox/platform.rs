@@ -12,6 +12,46 @@ pub struct PlatformCapabilities {
     pub operations: Vec<OperationSupport>,
     /// Platform-specific notes or warnings
     pub notes: Vec<String>,
+    /// Backend that enforces the sandbox: "landlock", "gaol", "seatbelt", "capsicum" or "none"
+    #[serde(default)]
+    pub backend: String,
+    /// Landlock ABI level in use, if the Landlock backend is active
+    #[serde(default)]
+    pub landlock_abi: Option<u32>,
+    /// How profile resource limits are enforced
+    #[serde(default)]
+    pub resource_limits: ResourceLimitSupport,
+}
+
+/// Represents support for the resource limits of sandbox profiles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceLimitSupport {
+    /// Enforcement mechanism: "cgroup_v2", "rlimit" or "none"
+    pub mechanism: String,
+    pub cpu_time: bool,
+    pub memory: bool,
+    pub max_processes: bool,
+    pub max_open_files: bool,
+}
+
+impl ResourceLimitSupport {
+    /// Per-process rlimits, which cannot bound the memory or processes of a whole run
+    fn rlimit() -> Self {
+        Self {
+            mechanism: "rlimit".to_string(),
+            cpu_time: true,
+            memory: false,
+            max_processes: false,
+            max_open_files: true,
+        }
+    }
+
+    fn none() -> Self {
+        Self {
+            mechanism: "none".to_string(),
+            ..Default::default()
+        }
+    }
 }
 
 /// Represents support for a specific operation
@@ -19,7 +59,7 @@ pub struct PlatformCapabilities {
 pub struct OperationSupport {
     /// The operation type
     pub operation: String,
-    /// Support level: "never", "can_be_allowed", "cannot_be_precisely", "always"
+    /// Support level: "never", "can_be_allowed", "cannot_be_precisely", "not_enforced", "always"
     pub support_level: String,
     /// Human-readable description
     pub description: String,
@@ -38,6 +78,104 @@ pub fn get_platform_capabilities() -> PlatformCapabilities {
 }
 
 fn get_linux_capabilities() -> PlatformCapabilities {
+    #[cfg(target_os = "linux")]
+    let landlock_abi = super::landlock::active_abi_version();
+    #[cfg(not(target_os = "linux"))]
+    let landlock_abi: Option<u32> = None;
+
+    let file_read_description = match landlock_abi {
+        Some(abi) => format!("Can allow file reading with Landlock rulesets (ABI v{})", abi),
+        None => "Can allow file reading via bind mounts in chroot jail".to_string(),
+    };
+    let write_support = |action: &str| match landlock_abi {
+        Some(_) => OperationSupport {
+            operation: format!("file_{}", action),
+            support_level: "can_be_allowed".to_string(),
+            description: format!("Can allow file {} per path with Landlock rulesets", action),
+        },
+        None => OperationSupport {
+            operation: format!("file_{}", action),
+            support_level: "never".to_string(),
+            description: format!("File {} rules require Landlock, which this kernel lacks", action),
+        },
+    };
+
+    let network_enforced = landlock_abi.is_some_and(|abi| abi >= 4);
+    let domain_support = if network_enforced {
+        OperationSupport {
+            operation: "network_outbound_domain".to_string(),
+            support_level: "can_be_allowed".to_string(),
+            description: "Can allow specific hosts through the egress proxy, direct TCP connections are denied".to_string(),
+        }
+    } else {
+        OperationSupport {
+            operation: "network_outbound_domain".to_string(),
+            support_level: "not_enforced".to_string(),
+            description: "Traffic is sent through the egress proxy, but direct connections are not denied without Landlock ABI v4".to_string(),
+        }
+    };
+    let tcp_support = match landlock_abi {
+        Some(abi) if abi >= 4 => OperationSupport {
+            operation: "network_outbound_tcp".to_string(),
+            support_level: "can_be_allowed".to_string(),
+            description: "Can allow TCP connections to specific ports on any host with Landlock".to_string(),
+        },
+        Some(_) => OperationSupport {
+            operation: "network_outbound_tcp".to_string(),
+            support_level: "not_enforced".to_string(),
+            description: "TCP port rules need Landlock ABI v4, connections to other ports are not denied".to_string(),
+        },
+        None => OperationSupport {
+            operation: "network_outbound_tcp".to_string(),
+            support_level: "cannot_be_precisely".to_string(),
+            description: "Cannot filter by specific ports with seccomp".to_string(),
+        },
+    };
+
+    let mut notes = match landlock_abi {
+        Some(abi) => vec![
+            format!("Linux sandboxing uses Landlock (ABI v{}) for filesystem access", abi),
+            "Subpath and literal read rules are enforced by the kernel without a chroot".to_string(),
+            "Writes are limited to file_write/file_create rules plus the temp directory".to_string(),
+            "Profiles without network rules run in a network namespace of their own".to_string(),
+            "Privilege escalation through setuid binaries is blocked; child processes can be started and inherit the sandbox".to_string(),
+        ],
+        None => vec![
+            "Linux sandboxing uses namespaces (user, PID, IPC, mount, UTS, network) and seccomp-bpf".to_string(),
+            "File access is controlled via bind mounts in a chroot jail".to_string(),
+            "Landlock is not available on this kernel, so gaol only applies to programs that activate it themselves; agent runs are not restricted".to_string(),
+            "Process creation and privilege escalation are blocked by seccomp-bpf where gaol applies".to_string(),
+        ],
+    };
+    if network_enforced {
+        notes.push("Network access can be limited to allowed hosts through the egress proxy".to_string());
+        notes.push("Landlock filters TCP by port only: the proxy port and allowed ports are reachable on any host".to_string());
+        notes.push("UDP traffic, including DNS, is not restricted by network rules".to_string());
+    } else {
+        notes.push("Network filtering is all-or-nothing: port and host rules are not enforced without Landlock ABI v4".to_string());
+    }
+
+    let resource_limits = if super::limits::cgroup_available() {
+        notes.push("Memory and process limits apply to the whole run through a cgroup v2 sub-tree".to_string());
+        ResourceLimitSupport {
+            mechanism: "cgroup_v2".to_string(),
+            memory: true,
+            max_processes: true,
+            ..ResourceLimitSupport::rlimit()
+        }
+    } else if super::limits::systemd_scope_available() {
+        notes.push("Memory and process limits apply to the whole run through a transient systemd scope".to_string());
+        ResourceLimitSupport {
+            mechanism: "systemd_scope".to_string(),
+            memory: true,
+            max_processes: true,
+            ..ResourceLimitSupport::rlimit()
+        }
+    } else {
+        notes.push("Memory and process limits are not enforced, they need a delegated cgroup v2 sub-tree or a systemd user manager".to_string());
+        ResourceLimitSupport::rlimit()
+    };
+
     PlatformCapabilities {
         os: "linux".to_string(),
         sandboxing_supported: true,
@@ -45,40 +183,37 @@ fn get_linux_capabilities() -> PlatformCapabilities {
             OperationSupport {
                 operation: "file_read_all".to_string(),
                 support_level: "can_be_allowed".to_string(),
-                description: "Can allow file reading via bind mounts in chroot jail".to_string(),
+                description: file_read_description,
             },
             OperationSupport {
                 operation: "file_read_metadata".to_string(),
                 support_level: "cannot_be_precisely".to_string(),
                 description: "Cannot be precisely controlled, allowed if file read is allowed".to_string(),
             },
+            write_support("write"),
+            write_support("create"),
             OperationSupport {
                 operation: "network_outbound_all".to_string(),
                 support_level: "can_be_allowed".to_string(),
                 description: "Can allow all network access by not creating network namespace".to_string(),
             },
-            OperationSupport {
-                operation: "network_outbound_tcp".to_string(),
-                support_level: "cannot_be_precisely".to_string(),
-                description: "Cannot filter by specific ports with seccomp".to_string(),
-            },
+            tcp_support,
             OperationSupport {
                 operation: "network_outbound_local".to_string(),
                 support_level: "cannot_be_precisely".to_string(),
                 description: "Cannot filter by specific socket paths with seccomp".to_string(),
             },
+            domain_support,
             OperationSupport {
                 operation: "system_info_read".to_string(),
                 support_level: "never".to_string(),
                 description: "Not supported on Linux".to_string(),
             },
         ],
-        notes: vec![
-            "Linux sandboxing uses namespaces (user, PID, IPC, mount, UTS, network) and seccomp-bpf".to_string(),
-            "File access is controlled via bind mounts in a chroot jail".to_string(),
-            "Network filtering is all-or-nothing (cannot filter by port/address)".to_string(),
-            "Process creation and privilege escalation are always blocked".to_string(),
-        ],
+        notes,
+        backend: if landlock_abi.is_some() { "landlock" } else { "gaol" }.to_string(),
+        landlock_abi,
+        resource_limits,
     }
 }
 
@@ -97,6 +232,16 @@ fn get_macos_capabilities() -> PlatformCapabilities {
                 support_level: "can_be_allowed".to_string(),
                 description: "Can allow metadata reading with Seatbelt profiles".to_string(),
             },
+            OperationSupport {
+                operation: "file_write".to_string(),
+                support_level: "never".to_string(),
+                description: "Seatbelt profiles generated by gaol cannot grant writes".to_string(),
+            },
+            OperationSupport {
+                operation: "file_create".to_string(),
+                support_level: "never".to_string(),
+                description: "Seatbelt profiles generated by gaol cannot grant writes".to_string(),
+            },
             OperationSupport {
                 operation: "network_outbound_all".to_string(),
                 support_level: "can_be_allowed".to_string(),
@@ -112,6 +257,11 @@ fn get_macos_capabilities() -> PlatformCapabilities {
                 support_level: "can_be_allowed".to_string(),
                 description: "Can allow specific local socket paths".to_string(),
             },
+            OperationSupport {
+                operation: "network_outbound_domain".to_string(),
+                support_level: "never".to_string(),
+                description: "Host allow-lists need Landlock to stop the proxy being bypassed".to_string(),
+            },
             OperationSupport {
                 operation: "system_info_read".to_string(),
                 support_level: "can_be_allowed".to_string(),
@@ -123,7 +273,11 @@ fn get_macos_capabilities() -> PlatformCapabilities {
             "More fine-grained control compared to Linux".to_string(),
             "Can filter network access by port and socket path".to_string(),
             "Supports platform-specific operations like Mach port lookups".to_string(),
+            "CPU time and open file limits use rlimits, which apply per process; memory and process limits are not enforced".to_string(),
         ],
+        backend: "seatbelt".to_string(),
+        landlock_abi: None,
+        resource_limits: ResourceLimitSupport::rlimit(),
     }
 }
 
@@ -158,6 +312,9 @@ fn get_freebsd_capabilities() -> PlatformCapabilities {
             "Uses Capsicum for capability-based security".to_string(),
             "Most operations are not supported".to_string(),
         ],
+        backend: "capsicum".to_string(),
+        landlock_abi: None,
+        resource_limits: ResourceLimitSupport::rlimit(),
     }
 }
 
@@ -170,6 +327,9 @@ fn get_unsupported_capabilities(os: &str) -> PlatformCapabilities {
             format!("Sandboxing is not supported on {} platform", os),
             "Claude Code will run without sandbox restrictions".to_string(),
         ],
+        backend: "none".to_string(),
+        landlock_abi: None,
+        resource_limits: ResourceLimitSupport::none(),
     }
 }
 
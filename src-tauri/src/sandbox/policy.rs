@@ -0,0 +1,76 @@
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// `app_settings` key holding the global sandbox policy
+pub const SANDBOX_POLICY_SETTING: &str = "sandbox_policy";
+
+/// What to do when an agent can't run inside its sandbox
+///
+/// Agents may override the global policy, which defaults to `preferred`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxPolicy {
+    /// Fail the run instead of running it unsandboxed
+    Required,
+    /// Run unsandboxed on platforms without sandbox support, fail when setting it up fails
+    #[default]
+    Preferred,
+    /// Never sandbox
+    Off,
+}
+
+impl SandboxPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SandboxPolicy::Required => "required",
+            SandboxPolicy::Preferred => "preferred",
+            SandboxPolicy::Off => "off",
+        }
+    }
+
+    /// Parse a stored policy, ignoring unknown values
+    pub fn from_stored(value: Option<String>) -> Option<Self> {
+        let value = value?;
+        match value.parse() {
+            Ok(policy) => Some(policy),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        }
+    }
+}
+
+impl FromStr for SandboxPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "required" => Ok(SandboxPolicy::Required),
+            "preferred" => Ok(SandboxPolicy::Preferred),
+            "off" => Ok(SandboxPolicy::Off),
+            other => Err(format!("Unknown sandbox policy: {}", other)),
+        }
+    }
+}
+
+/// The global sandbox policy
+pub fn load_global_policy(conn: &Connection) -> SandboxPolicy {
+    let stored = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![SANDBOX_POLICY_SETTING],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .ok()
+        .flatten();
+    SandboxPolicy::from_stored(stored).unwrap_or_default()
+}
+
+/// The policy a run of an agent follows, given the agent's own override
+pub fn effective_policy(conn: &Connection, agent_policy: Option<SandboxPolicy>) -> SandboxPolicy {
+    agent_policy.unwrap_or_else(|| load_global_policy(conn))
+}
@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::executor::{SerializedOperation, SerializedProfile};
+
+/// File name of the probe binary, built from `src/bin/sandbox_probe.rs`
+const PROBE_BINARY: &str = "sandbox_probe";
+
+/// How long the probe may take before it is killed, e.g. when a sandbox makes it hang
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Program the probe tries to spawn
+#[cfg(unix)]
+const SPAWN_TARGET: &str = "/bin/sh";
+#[cfg(not(unix))]
+const SPAWN_TARGET: &str = "cmd.exe";
+
+/// Result of one probe operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeCheck {
+    /// "read_inside", "read_outside", "write", "create", "connect_local", "system_info"
+    /// or "spawn"
+    pub operation: String,
+    pub target: String,
+    /// Whether the profile's rules allow the operation, None when no rule type covers it
+    pub expected: Option<bool>,
+    /// "allowed", "denied", "failed" or "not_run"
+    pub observed: String,
+    /// Error reported by the probe
+    pub detail: Option<String>,
+    /// Whether enforcement agrees with the rules, None when that can't be told
+    pub matches: Option<bool>,
+}
+
+/// Files and endpoints a probe run works on
+///
+/// Everything lives under `root`, which the caller removes afterwards.
+pub struct ProbeTargets {
+    pub root: PathBuf,
+    /// Project directory the profile is built for
+    pub project: PathBuf,
+    pub read_inside: PathBuf,
+    pub read_outside: PathBuf,
+    pub write: PathBuf,
+    pub create: PathBuf,
+    pub connect: SocketAddr,
+    pub spawn: PathBuf,
+}
+
+impl ProbeTargets {
+    /// Create the probe files under `root`, with `connect` pointing at a local listener
+    pub fn prepare(root: &Path, connect: SocketAddr) -> Result<Self> {
+        let project = root.join("project");
+        let outside = root.join("outside");
+        for dir in [&project, &outside] {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let targets = Self {
+            root: root.to_path_buf(),
+            read_inside: project.join("readable.txt"),
+            read_outside: outside.join("secret.txt"),
+            write: project.join("writable.txt"),
+            create: project.join("created.txt"),
+            project,
+            connect,
+            spawn: PathBuf::from(SPAWN_TARGET),
+        };
+        for file in [&targets.read_inside, &targets.read_outside, &targets.write] {
+            fs::write(file, "sandbox probe\n")
+                .with_context(|| format!("Failed to create {}", file.display()))?;
+        }
+        Ok(targets)
+    }
+
+    /// The operations to attempt, each with its probe argument
+    pub fn plan(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "read_inside",
+                format!("read:{}", self.read_inside.display()),
+            ),
+            (
+                "read_outside",
+                format!("read:{}", self.read_outside.display()),
+            ),
+            ("write", format!("write:{}", self.write.display())),
+            ("create", format!("create:{}", self.create.display())),
+            ("connect_local", format!("connect:{}", self.connect)),
+            ("system_info", "system_info".to_string()),
+            ("spawn", format!("spawn:{}", self.spawn.display())),
+        ]
+    }
+}
+
+/// What the rules of `profile` claim about an operation of the plan
+pub fn expected_outcome(
+    profile: &SerializedProfile,
+    operation: &str,
+    targets: &ProbeTargets,
+) -> Option<bool> {
+    let ops = &profile.operations;
+    let covers = |rule_path: &Path, is_subpath: bool, path: &Path| {
+        if is_subpath {
+            path.starts_with(rule_path)
+        } else {
+            path == rule_path
+        }
+    };
+
+    match operation {
+        "read_inside" | "read_outside" => {
+            let path = if operation == "read_inside" {
+                &targets.read_inside
+            } else {
+                &targets.read_outside
+            };
+            Some(ops.iter().any(|op| {
+                matches!(op, SerializedOperation::FileReadAll { path: p, is_subpath } if covers(p, *is_subpath, path))
+            }))
+        }
+        "write" => Some(ops.iter().any(|op| {
+            matches!(op, SerializedOperation::FileWrite { path: p, is_subpath } if covers(p, *is_subpath, &targets.write))
+        })),
+        "create" => Some(ops.iter().any(|op| {
+            matches!(op, SerializedOperation::FileCreate { path: p, is_subpath } if covers(p, *is_subpath, &targets.create))
+        })),
+        "connect_local" => Some(ops.iter().any(|op| match op {
+            SerializedOperation::NetworkOutbound { pattern } => pattern == "all",
+            SerializedOperation::NetworkTcp { port } => *port == targets.connect.port(),
+            _ => false,
+        })),
+        "system_info" => Some(
+            ops.iter()
+                .any(|op| matches!(op, SerializedOperation::SystemInfoRead)),
+        ),
+        // No rule type covers spawning, only the platform decides
+        _ => None,
+    }
+}
+
+/// Checks for every operation of the plan, before the probe has run
+pub fn planned_checks(profile: &SerializedProfile, targets: &ProbeTargets) -> Vec<ProbeCheck> {
+    targets
+        .plan()
+        .into_iter()
+        .map(|(operation, argument)| ProbeCheck {
+            operation: operation.to_string(),
+            target: argument
+                .split_once(':')
+                .map(|(_, target)| target.to_string())
+                .unwrap_or_default(),
+            expected: expected_outcome(profile, operation, targets),
+            observed: "not_run".to_string(),
+            detail: None,
+            matches: None,
+        })
+        .collect()
+}
+
+/// Fill in the observed outcomes from the probe's output
+pub fn apply_output(checks: &mut [ProbeCheck], stdout: &str) {
+    for line in stdout.lines() {
+        let mut parts = line.splitn(3, ' ');
+        let (Some(index), Some(observed)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Some(check) = index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| checks.get_mut(index))
+        else {
+            continue;
+        };
+
+        check.observed = observed.to_string();
+        check.detail = parts
+            .next()
+            .map(str::to_string)
+            .filter(|detail| !detail.is_empty());
+        check.matches = match (check.expected, observed) {
+            (Some(expected), "allowed") => Some(expected),
+            (Some(expected), "denied") => Some(!expected),
+            _ => None,
+        };
+    }
+}
+
+/// Locate the probe binary next to the running executable
+///
+/// Bundles ship it as a sidecar (`bundle.externalBin`), which Tauri installs next to the
+/// application. Test harnesses run from a `deps` directory below the binaries, so its
+/// parent is searched too.
+pub fn probe_binary() -> Option<PathBuf> {
+    let exe = env::current_exe().ok()?;
+    let name = format!("{}{}", PROBE_BINARY, env::consts::EXE_SUFFIX);
+    exe.parent()?
+        .ancestors()
+        .take(2)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
@@ -1,11 +1,20 @@
+use crate::sandbox::environment::{EnvPolicy, ENVIRONMENT_OPERATION};
 use crate::sandbox::executor::{SerializedOperation, SerializedProfile};
+use crate::sandbox::limits::ResourceLimits;
+use crate::sandbox::variables::PathVariables;
 use anyhow::{Context, Result};
 #[cfg(unix)]
 use gaol::profile::{AddressPattern, Operation, OperationSupport, PathPattern, Profile};
 use log::{debug, info, warn};
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Where Claude keeps its settings, credentials and transcripts, relative to the home
+/// directory
+pub const CLAUDE_STATE_PATHS: &[&str] = &[".claude", ".claude.json"];
 
 /// Represents a sandbox profile from the database
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +26,11 @@ pub struct SandboxProfile {
     pub is_default: bool,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    /// Profile whose rules this one inherits
+    #[serde(default)]
+    pub parent_id: Option<i64>,
 }
 
 /// Represents a sandbox rule from the database
@@ -39,25 +53,35 @@ pub struct ProfileBuildResult {
     #[cfg(not(unix))]
     pub profile: (), // Placeholder for Windows
     pub serialized: SerializedProfile,
+    /// Environment variables the child gets
+    pub env_policy: EnvPolicy,
 }
 
 /// Builder for creating gaol profiles from database configuration
 pub struct ProfileBuilder {
     project_path: PathBuf,
-    home_dir: PathBuf,
+    variables: PathVariables,
+    runtime_rules: Vec<SandboxRule>,
 }
 
 impl ProfileBuilder {
     /// Create a new profile builder
     pub fn new(project_path: PathBuf) -> Result<Self> {
-        let home_dir = dirs::home_dir().context("Could not determine home directory")?;
+        let variables = PathVariables::current(Some(project_path.clone()))?;
 
         Ok(Self {
             project_path,
-            home_dir,
+            variables,
+            runtime_rules: Vec::new(),
         })
     }
 
+    /// Rules the executable needs to work at all, kept whatever the agent's permissions
+    pub fn with_runtime_rules(mut self, rules: Vec<SandboxRule>) -> Self {
+        self.runtime_rules = rules;
+        self
+    }
+
     /// Build a gaol Profile from database rules filtered by agent permissions
     pub fn build_agent_profile(
         &self,
@@ -76,6 +100,7 @@ impl ProfileBuilder {
                 #[cfg(not(unix))]
                 profile: (),
                 serialized: SerializedProfile { operations: vec![] },
+                env_policy: EnvPolicy::from_rules(&rules),
             });
         }
 
@@ -89,6 +114,7 @@ impl ProfileBuilder {
             // Filter rules based on agent permissions
             let include_rule = match rule.operation_type.as_str() {
                 "file_read_all" | "file_read_metadata" => enable_file_read,
+                "file_write" | "file_create" => enable_file_write,
                 "network_outbound" => enable_network,
                 "system_info_read" => true, // Always allow system info reading
                 _ => true,                  // Include unknown rule types by default
@@ -122,6 +148,30 @@ impl ProfileBuilder {
             }
         }
 
+        // Without explicit write rules, writing is allowed anywhere in the project
+        if enable_file_write {
+            let has_write_rules = filtered_rules
+                .iter()
+                .any(|rule| matches!(rule.operation_type.as_str(), "file_write" | "file_create"));
+
+            if !has_write_rules {
+                for operation_type in ["file_write", "file_create"] {
+                    filtered_rules.push(SandboxRule {
+                        id: None,
+                        profile_id: 0,
+                        operation_type: operation_type.to_string(),
+                        pattern_type: "subpath".to_string(),
+                        pattern_value: "{{PROJECT_PATH}}".to_string(),
+                        enabled: true,
+                        platform_support: None,
+                        created_at: String::new(),
+                    });
+                }
+            }
+        }
+
+        filtered_rules.extend(self.runtime_rules.iter().cloned());
+
         self.build_profile_with_serialization(filtered_rules)
     }
 
@@ -144,6 +194,12 @@ impl ProfileBuilder {
         &self,
         rules: Vec<SandboxRule>,
     ) -> Result<ProfileBuildResult> {
+        let (env_rules, rules): (Vec<_>, Vec<_>) = rules
+            .into_iter()
+            .partition(|rule| rule.operation_type == ENVIRONMENT_OPERATION);
+        let env_policy = EnvPolicy::from_rules(&env_rules);
+        let rules = self.apply_domain_allow_list(rules);
+
         #[cfg(unix)]
         {
             let mut operations = Vec::new();
@@ -164,7 +220,16 @@ impl ProfileBuilder {
                 }
 
                 match self.build_operation_with_serialization(&rule) {
-                    Ok(Some((op, serialized))) => {
+                    Ok(Some((None, serialized))) => {
+                        // gaol cannot express writes or domain allow-lists, these are
+                        // enforced by the Landlock backend and the egress proxy
+                        debug!(
+                            "Rule {} has no gaol equivalent, keeping it for Landlock",
+                            rule.operation_type
+                        );
+                        serialized_operations.push(serialized);
+                    }
+                    Ok(Some((Some(op), serialized))) => {
                         // Check if operation is supported on current platform
                         if matches!(
                             op.support(),
@@ -219,6 +284,7 @@ impl ProfileBuilder {
                 serialized: SerializedProfile {
                     operations: serialized_operations,
                 },
+                env_policy,
             })
         }
 
@@ -242,6 +308,7 @@ impl ProfileBuilder {
                 serialized: SerializedProfile {
                     operations: serialized_operations,
                 },
+                env_policy,
             })
         }
     }
@@ -250,24 +317,25 @@ impl ProfileBuilder {
     #[cfg(unix)]
     fn build_operation(&self, rule: &SandboxRule) -> Result<Option<Operation>> {
         match self.build_operation_with_serialization(rule) {
-            Ok(Some((op, _))) => Ok(Some(op)),
+            Ok(Some((op, _))) => Ok(op),
             Ok(None) => Ok(None),
             Err(e) => Err(e),
         }
     }
 
     /// Build a gaol Operation and its serialized form from a database rule
+    /// Write and domain rules have no gaol Operation, so only their serialized form is returned
     #[cfg(unix)]
     fn build_operation_with_serialization(
         &self,
         rule: &SandboxRule,
-    ) -> Result<Option<(Operation, SerializedOperation)>> {
+    ) -> Result<Option<(Option<Operation>, SerializedOperation)>> {
         match rule.operation_type.as_str() {
             "file_read_all" => {
                 let (pattern, path, is_subpath) =
                     self.build_path_pattern_with_info(&rule.pattern_type, &rule.pattern_value)?;
                 Ok(Some((
-                    Operation::FileReadAll(pattern),
+                    Some(Operation::FileReadAll(pattern)),
                     SerializedOperation::FileReadAll { path, is_subpath },
                 )))
             }
@@ -275,19 +343,44 @@ impl ProfileBuilder {
                 let (pattern, path, is_subpath) =
                     self.build_path_pattern_with_info(&rule.pattern_type, &rule.pattern_value)?;
                 Ok(Some((
-                    Operation::FileReadMetadata(pattern),
+                    Some(Operation::FileReadMetadata(pattern)),
                     SerializedOperation::FileReadMetadata { path, is_subpath },
                 )))
             }
+            "file_write" => {
+                let (_, path, is_subpath) =
+                    self.build_path_pattern_with_info(&rule.pattern_type, &rule.pattern_value)?;
+                Ok(Some((
+                    None,
+                    SerializedOperation::FileWrite { path, is_subpath },
+                )))
+            }
+            "file_create" => {
+                let (_, path, is_subpath) =
+                    self.build_path_pattern_with_info(&rule.pattern_type, &rule.pattern_value)?;
+                Ok(Some((
+                    None,
+                    SerializedOperation::FileCreate { path, is_subpath },
+                )))
+            }
+            "network_outbound" if rule.pattern_type == "domain" => Ok(Some((
+                None,
+                SerializedOperation::NetworkDomain {
+                    domain: rule.pattern_value.trim().to_ascii_lowercase(),
+                },
+            ))),
             "network_outbound" => {
                 let (pattern, serialized) = self.build_address_pattern_with_serialization(
                     &rule.pattern_type,
                     &rule.pattern_value,
                 )?;
-                Ok(Some((Operation::NetworkOutbound(pattern), serialized)))
+                Ok(Some((
+                    Some(Operation::NetworkOutbound(pattern)),
+                    serialized,
+                )))
             }
             "system_info_read" => Ok(Some((
-                Operation::SystemInfoRead,
+                Some(Operation::SystemInfoRead),
                 SerializedOperation::SystemInfoRead,
             ))),
             _ => Ok(None),
@@ -308,12 +401,7 @@ impl ProfileBuilder {
         pattern_type: &str,
         pattern_value: &str,
     ) -> Result<(PathPattern, PathBuf, bool)> {
-        // Replace template variables
-        let expanded_value = pattern_value
-            .replace("{{PROJECT_PATH}}", &self.project_path.to_string_lossy())
-            .replace("{{HOME}}", &self.home_dir.to_string_lossy());
-
-        let path = PathBuf::from(expanded_value);
+        let path = PathBuf::from(self.variables.expand(pattern_value)?);
 
         match pattern_type {
             "literal" => Ok((PathPattern::Literal(path.clone()), path, false)),
@@ -361,7 +449,7 @@ impl ProfileBuilder {
                 ))
             }
             "local_socket" => {
-                let path = PathBuf::from(pattern_value);
+                let path = PathBuf::from(self.variables.expand(pattern_value)?);
                 Ok((
                     AddressPattern::LocalSocket(path.clone()),
                     SerializedOperation::NetworkLocalSocket { path },
@@ -374,6 +462,39 @@ impl ProfileBuilder {
         }
     }
 
+    /// Drop other outbound network rules when a domain allow-list applies
+    ///
+    /// Domain rules make the agent go through the egress proxy, so direct access such as
+    /// `network_outbound all` would defeat them. Local socket rules are kept. Only
+    /// Landlock can stop the child from bypassing the proxy, so elsewhere domain rules
+    /// are dropped instead.
+    fn apply_domain_allow_list(&self, rules: Vec<SandboxRule>) -> Vec<SandboxRule> {
+        let is_domain_rule = |rule: &SandboxRule| {
+            rule.operation_type == "network_outbound" && rule.pattern_type == "domain"
+        };
+        if !cfg!(target_os = "linux") {
+            return rules
+                .into_iter()
+                .filter(|rule| !is_domain_rule(rule))
+                .collect();
+        }
+        let has_allow_list = rules.iter().any(|rule| {
+            rule.enabled && is_domain_rule(rule) && self.is_rule_supported_on_platform(rule)
+        });
+        if !has_allow_list {
+            return rules;
+        }
+
+        rules
+            .into_iter()
+            .filter(|rule| {
+                rule.operation_type != "network_outbound"
+                    || rule.pattern_type == "local_socket"
+                    || is_domain_rule(rule)
+            })
+            .collect()
+    }
+
     /// Check if a rule is supported on the current platform
     fn is_rule_supported_on_platform(&self, rule: &SandboxRule) -> bool {
         if let Some(platforms_json) = &rule.platform_support {
@@ -392,7 +513,7 @@ impl ProfileBuilder {
         &self,
         rule: &SandboxRule,
     ) -> Result<Option<SerializedOperation>> {
-        let pattern_value = self.expand_pattern_value(&rule.pattern_value);
+        let pattern_value = self.expand_pattern_value(&rule.pattern_value)?;
 
         match rule.operation_type.as_str() {
             "file_read_all" => {
@@ -408,6 +529,21 @@ impl ProfileBuilder {
                     is_subpath,
                 }))
             }
+            "file_write" => {
+                let (path, is_subpath) =
+                    self.parse_path_pattern(&rule.pattern_type, &pattern_value)?;
+                Ok(Some(SerializedOperation::FileWrite { path, is_subpath }))
+            }
+            "file_create" => {
+                let (path, is_subpath) =
+                    self.parse_path_pattern(&rule.pattern_type, &pattern_value)?;
+                Ok(Some(SerializedOperation::FileCreate { path, is_subpath }))
+            }
+            "network_outbound" if rule.pattern_type == "domain" => {
+                Ok(Some(SerializedOperation::NetworkDomain {
+                    domain: pattern_value.trim().to_ascii_lowercase(),
+                }))
+            }
             "network_outbound" => Ok(Some(SerializedOperation::NetworkOutbound {
                 pattern: pattern_value,
             })),
@@ -426,10 +562,8 @@ impl ProfileBuilder {
 
     /// Helper method to expand pattern values (Windows version)
     #[cfg(not(unix))]
-    fn expand_pattern_value(&self, pattern_value: &str) -> String {
-        pattern_value
-            .replace("{{PROJECT_PATH}}", &self.project_path.to_string_lossy())
-            .replace("{{HOME}}", &self.home_dir.to_string_lossy())
+    fn expand_pattern_value(&self, pattern_value: &str) -> Result<String> {
+        self.variables.expand(pattern_value)
     }
 
     /// Helper method to parse path patterns (Windows version)
@@ -452,10 +586,97 @@ impl ProfileBuilder {
     }
 }
 
+/// Rules every sandboxed Claude run needs
+///
+/// Claude reads and writes its own state under the home directory and loads its code
+/// from where it is installed, so a run can't even start without these.
+pub fn claude_runtime_rules(claude_path: &str) -> Vec<SandboxRule> {
+    let rule = |operation_type: &str, pattern_type: &str, pattern_value: String| SandboxRule {
+        id: None,
+        profile_id: 0,
+        operation_type: operation_type.to_string(),
+        pattern_type: pattern_type.to_string(),
+        pattern_value,
+        enabled: true,
+        platform_support: None,
+        created_at: String::new(),
+    };
+
+    let mut rules = Vec::new();
+    for state in CLAUDE_STATE_PATHS {
+        let pattern_value = format!("{{{{HOME}}}}/{}", state);
+        // Only directories take create rules
+        let (pattern_type, operations) = if state.ends_with(".json") {
+            ("literal", &["file_read_all", "file_write"][..])
+        } else {
+            (
+                "subpath",
+                &["file_read_all", "file_write", "file_create"][..],
+            )
+        };
+        for operation_type in operations {
+            rules.push(rule(operation_type, pattern_type, pattern_value.clone()));
+        }
+    }
+    for prefix in install_prefixes(claude_path) {
+        rules.push(rule(
+            "file_read_all",
+            "subpath",
+            prefix.to_string_lossy().into_owned(),
+        ));
+    }
+    rules
+}
+
+/// An executable as found on `PATH` and, when that is a symlink, its target
+///
+/// Node based CLIs are usually symlinks from a `bin` directory into their package.
+pub fn resolve_executable(executable: &str) -> Vec<PathBuf> {
+    let found = if executable.contains('/') {
+        Some(PathBuf::from(executable))
+    } else {
+        env::var_os("PATH").and_then(|paths| {
+            env::split_paths(&paths)
+                .map(|dir| dir.join(executable))
+                .find(|candidate| candidate.is_file())
+        })
+    };
+
+    let mut paths = Vec::new();
+    if let Some(path) = found {
+        if let Ok(target) = path.canonicalize() {
+            if target != path {
+                paths.push(target);
+            }
+        }
+        paths.insert(0, path);
+    }
+    paths
+}
+
+/// Install prefixes of an executable, the parents of the directories holding it
+///
+/// Prefixes that would cover the whole filesystem or the home directory are left out.
+pub fn install_prefixes(executable: &str) -> Vec<PathBuf> {
+    let home = dirs::home_dir();
+    let mut prefixes: Vec<PathBuf> = Vec::new();
+    for path in resolve_executable(executable) {
+        let Some(prefix) = path.parent().and_then(Path::parent) else {
+            continue;
+        };
+        let too_broad = prefix.parent().is_none()
+            || home.as_deref().is_some_and(|home| home.starts_with(prefix));
+        if !too_broad && !prefixes.iter().any(|known| known == prefix) {
+            prefixes.push(prefix.to_path_buf());
+        }
+    }
+    prefixes
+}
+
 /// Load a sandbox profile by ID
 pub fn load_profile(conn: &Connection, profile_id: i64) -> Result<SandboxProfile> {
     conn.query_row(
-        "SELECT id, name, description, is_active, is_default, created_at, updated_at 
+        "SELECT id, name, description, is_active, is_default, created_at, updated_at, cpu_time_limit_secs, memory_limit_mb, max_processes, max_open_files, parent_id 
          FROM sandbox_profiles WHERE id = ?1",
         params![profile_id],
         |row| {
@@ -467,6 +688,8 @@ pub fn load_profile(conn: &Connection, profile_id: i64) -> Result<SandboxProfile
                 is_default: row.get(4)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
+                resource_limits: ResourceLimits::from_row(row, 7)?,
+                parent_id: row.get(11)?,
             })
         },
     )
@@ -476,7 +699,7 @@ pub fn load_profile(conn: &Connection, profile_id: i64) -> Result<SandboxProfile
 /// Load the default sandbox profile
 pub fn load_default_profile(conn: &Connection) -> Result<SandboxProfile> {
     conn.query_row(
-        "SELECT id, name, description, is_active, is_default, created_at, updated_at 
+        "SELECT id, name, description, is_active, is_default, created_at, updated_at, cpu_time_limit_secs, memory_limit_mb, max_processes, max_open_files, parent_id 
          FROM sandbox_profiles WHERE is_default = 1",
         [],
         |row| {
@@ -488,6 +711,8 @@ pub fn load_default_profile(conn: &Connection) -> Result<SandboxProfile> {
                 is_default: row.get(4)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
+                resource_limits: ResourceLimits::from_row(row, 7)?,
+                parent_id: row.get(11)?,
             })
         },
     )
@@ -519,6 +744,103 @@ pub fn load_profile_rules(conn: &Connection, profile_id: i64) -> Result<Vec<Sand
     Ok(rules)
 }
 
+/// Load the inheritance chain of a profile, starting with the root and ending with it
+pub fn load_profile_chain(conn: &Connection, profile_id: i64) -> Result<Vec<SandboxProfile>> {
+    let mut chain = vec![load_profile(conn, profile_id)?];
+    while let Some(parent_id) = chain.last().and_then(|profile| profile.parent_id) {
+        if let Some(start) = chain.iter().position(|p| p.id == Some(parent_id)) {
+            let cycle: Vec<&str> = chain[start..]
+                .iter()
+                .chain(std::iter::once(&chain[start]))
+                .map(|p| p.name.as_str())
+                .collect();
+            anyhow::bail!("Sandbox profile inheritance cycle: {}", cycle.join(" -> "));
+        }
+        chain.push(
+            load_profile(conn, parent_id)
+                .with_context(|| format!("Failed to load parent profile {}", parent_id))?,
+        );
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+/// Check that making `parent_id` the parent of `profile_id` doesn't create a cycle
+pub fn check_parent(conn: &Connection, profile_id: i64, parent_id: i64) -> Result<()> {
+    if load_profile_chain(conn, parent_id)?
+        .iter()
+        .any(|p| p.id == Some(profile_id))
+    {
+        anyhow::bail!(
+            "Profile {} cannot inherit from profile {}, which already inherits from it",
+            profile_id,
+            parent_id
+        );
+    }
+    Ok(())
+}
+
+/// Load the rules a profile ends up with after inheritance
+///
+/// Rules are merged from the root of the chain down. A rule with the same operation and
+/// pattern as an inherited one replaces it, so a disabled rule in a child masks the
+/// parent's. Each rule keeps the `profile_id` of the profile that defines it.
+pub fn load_effective_rules(conn: &Connection, profile_id: i64) -> Result<Vec<SandboxRule>> {
+    let mut rules: Vec<SandboxRule> = Vec::new();
+    let mut positions: HashMap<(String, String, String), usize> = HashMap::new();
+
+    for profile in load_profile_chain(conn, profile_id)? {
+        let Some(id) = profile.id else { continue };
+        for rule in load_all_profile_rules(conn, id)? {
+            let key = (
+                rule.operation_type.clone(),
+                rule.pattern_type.clone(),
+                rule.pattern_value.clone(),
+            );
+            match positions.get(&key) {
+                Some(&index) => rules[index] = rule,
+                None => {
+                    positions.insert(key, rules.len());
+                    rules.push(rule);
+                }
+            }
+        }
+    }
+
+    rules.retain(|rule| rule.enabled);
+    debug!(
+        "Resolved {} effective rules for profile {}",
+        rules.len(),
+        profile_id
+    );
+    Ok(rules)
+}
+
+/// Load the rules defined on a profile itself, including disabled ones
+fn load_all_profile_rules(conn: &Connection, profile_id: i64) -> Result<Vec<SandboxRule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, operation_type, pattern_type, pattern_value, enabled, platform_support, created_at 
+         FROM sandbox_rules WHERE profile_id = ?1 ORDER BY id",
+    )?;
+
+    let rules = stmt
+        .query_map(params![profile_id], |row| {
+            Ok(SandboxRule {
+                id: Some(row.get(0)?),
+                profile_id: row.get(1)?,
+                operation_type: row.get(2)?,
+                pattern_type: row.get(3)?,
+                pattern_value: row.get(4)?,
+                enabled: row.get(5)?,
+                platform_support: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rules)
+}
+
 /// Get or create the gaol Profile for execution
 #[cfg(unix)]
 pub fn get_gaol_profile(
@@ -535,8 +857,8 @@ pub fn get_gaol_profile(
 
     info!("Using sandbox profile: {}", profile.name);
 
-    // Load the rules
-    let rules = load_profile_rules(conn, profile.id.unwrap())?;
+    // Load the rules, including those inherited from parent profiles
+    let rules = load_effective_rules(conn, profile.id.unwrap())?;
     info!("Loaded {} sandbox rules", rules.len());
 
     // Build the gaol profile
@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use super::violations::{DetectedViolation, ViolationMonitor};
+
+/// Largest request head the proxy accepts before giving up on a client
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// Called for each blocked request, with the violation if this is the first time its
+/// destination was recorded
+pub type BlockedCallback = Arc<dyn Fn(Option<DetectedViolation>) + Send + Sync>;
+
+/// Local HTTP proxy that only lets sandboxed agents reach allow-listed hosts
+///
+/// Handles `CONNECT` tunnels for HTTPS and absolute-form requests for plain HTTP, so
+/// anything honouring `HTTPS_PROXY`/`HTTP_PROXY` works through it. Requests to other
+/// hosts get a `403` and are recorded as `network_outbound` violations. The proxy stops
+/// when dropped.
+pub struct EgressProxy {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl EgressProxy {
+    /// Start a proxy on an ephemeral loopback port
+    ///
+    /// `allowed_domains` holds host names, with `*.example.com` matching every subdomain
+    /// of `example.com`. Blocked requests are recorded through `monitor`, so they count
+    /// towards the same kill threshold as the run's other violations.
+    pub async fn start(
+        allowed_domains: Vec<String>,
+        monitor: Arc<Mutex<ViolationMonitor>>,
+        on_blocked: Option<BlockedCallback>,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .context("Failed to bind egress proxy")?;
+        let addr = listener.local_addr()?;
+        info!(
+            "Egress proxy listening on {} for {:?}",
+            addr, allowed_domains
+        );
+
+        let shared = Arc::new(Shared {
+            allowed_domains,
+            monitor,
+            on_blocked,
+        });
+        let task = tokio::spawn(async move {
+            loop {
+                let (client, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        warn!("Egress proxy failed to accept a connection: {}", e);
+                        continue;
+                    }
+                };
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_client(client, &shared).await {
+                        debug!("Egress proxy connection from {} ended: {}", peer, e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { addr, task })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Value for the `HTTPS_PROXY`/`HTTP_PROXY` variables of the child
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Shared {
+    allowed_domains: Vec<String>,
+    monitor: Arc<Mutex<ViolationMonitor>>,
+    on_blocked: Option<BlockedCallback>,
+}
+
+/// Whether `host` matches an allow-list entry
+///
+/// Matching ignores case and a trailing dot. `*.example.com` matches subdomains of
+/// `example.com` but not `example.com` itself.
+pub fn is_host_allowed(host: &str, allowed_domains: &[String]) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    allowed_domains.iter().any(|domain| {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        match domain.strip_prefix("*.") {
+            Some(parent) => host
+                .strip_suffix(parent)
+                .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+            None => host == domain,
+        }
+    })
+}
+
+/// Host and port a proxy request is for, from its request line
+///
+/// `CONNECT host:port` is used for tunnels, and `GET http://host[:port]/path` for plain
+/// HTTP requests.
+pub fn request_target(request_line: &str) -> Option<(String, u16)> {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+
+    let (authority, default_port) = if method.eq_ignore_ascii_case("CONNECT") {
+        (target, 443)
+    } else {
+        let rest = target.strip_prefix("http://")?;
+        (rest.split('/').next()?, 80)
+    };
+    // Drop userinfo, which some clients still send
+    let authority = authority.rsplit('@').next()?;
+
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        // Bracketed IPv6 literal
+        let (host, after) = rest.split_once(']')?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        (host, port)
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (authority, default_port),
+        }
+    };
+
+    if host.is_empty() {
+        None
+    } else {
+        Some((host.to_string(), port))
+    }
+}
+
+async fn handle_client(mut client: TcpStream, shared: &Shared) -> Result<()> {
+    let mut buffer = Vec::new();
+    let head_len = loop {
+        let mut chunk = [0u8; 4096];
+        let read = client.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buffer.len() > MAX_HEAD_BYTES {
+            anyhow::bail!("request head too large");
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_len]).into_owned();
+    let request_line = head.lines().next().unwrap_or_default();
+    let (host, port) = match request_target(request_line) {
+        Some(target) => target,
+        None => {
+            client
+                .write_all(
+                    b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if !is_host_allowed(&host, &shared.allowed_domains) {
+        block(shared, &host, port);
+        client
+            .write_all(
+                format!(
+                    "HTTP/1.1 403 Forbidden\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nBlocked by sandbox: {} is not an allowed host\n",
+                    host
+                )
+                .as_bytes(),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            client
+                .write_all(
+                    b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+            return Err(e).with_context(|| format!("Failed to connect to {}:{}", host, port));
+        }
+    };
+
+    if request_line
+        .split_whitespace()
+        .next()
+        .is_some_and(|method| method.eq_ignore_ascii_case("CONNECT"))
+    {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+        // Anything the client sent after the CONNECT head already belongs to the tunnel
+        upstream.write_all(&buffer[head_len..]).await?;
+    } else {
+        // Origin servers must accept absolute-form request targets, so forward as is
+        upstream.write_all(&buffer).await?;
+    }
+
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+fn block(shared: &Shared, host: &str, port: u16) {
+    debug!("Egress proxy blocked {}:{}", host, port);
+    let violation = DetectedViolation {
+        operation_type: "network_outbound".to_string(),
+        pattern_value: Some(format!("{}:{}", host, port)),
+        process_name: None,
+    };
+
+    let recorded = match shared.monitor.lock() {
+        Ok(mut monitor) => monitor.record_once(violation),
+        Err(_) => None,
+    };
+    if let Some(on_blocked) = &shared.on_blocked {
+        on_blocked(recorded);
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use super::profile::SandboxRule;
+
+/// Variables rule patterns may contain, each written as `{{NAME}}`
+pub const PATH_VARIABLES: &[&str] = &["PROJECT_PATH", "HOME", "TMPDIR", "CARGO_HOME"];
+
+/// Values of the variables in sandbox rule patterns
+///
+/// Patterns naming these locations instead of absolute paths keep working when a profile
+/// is shared with someone whose home directory or checkout lives elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathVariables {
+    /// Project the rules are built for, unknown when exporting
+    pub project_path: Option<PathBuf>,
+    pub home: PathBuf,
+    pub tmp_dir: PathBuf,
+    pub cargo_home: PathBuf,
+}
+
+impl PathVariables {
+    /// Values on this machine
+    ///
+    /// `TMPDIR` follows the environment variable of the same name and `CARGO_HOME`
+    /// falls back to `~/.cargo`, the way the tools using them resolve them.
+    pub fn current(project_path: Option<PathBuf>) -> Result<Self> {
+        let home = dirs::home_dir().context("Could not determine home directory")?;
+        let cargo_home = env::var_os("CARGO_HOME")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".cargo"));
+
+        Ok(Self {
+            project_path,
+            home,
+            tmp_dir: env::temp_dir(),
+            cargo_home,
+        })
+    }
+
+    fn value(&self, name: &str) -> Option<&Path> {
+        match name {
+            "PROJECT_PATH" => self.project_path.as_deref(),
+            "HOME" => Some(&self.home),
+            "TMPDIR" => Some(&self.tmp_dir),
+            "CARGO_HOME" => Some(&self.cargo_home),
+            _ => None,
+        }
+    }
+
+    /// Replace every `{{NAME}}` in `pattern` with its value
+    ///
+    /// Unknown variables are an error rather than being left in a path that can never
+    /// match.
+    pub fn expand(&self, pattern: &str) -> Result<String> {
+        let mut expanded = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+        while let Some((before, name, after)) = next_variable(rest, pattern)? {
+            let value = self.value(name).with_context(|| {
+                format!("No value for {{{{{}}}}} in pattern: {}", name, pattern)
+            })?;
+            expanded.push_str(before);
+            expanded.push_str(&value.to_string_lossy());
+            rest = after;
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Rewrite an absolute path under one of the variables' locations to use the variable
+    ///
+    /// The most specific location wins, so a project inside the home directory becomes
+    /// `{{PROJECT_PATH}}` rather than `{{HOME}}/...`. Anything else is returned unchanged.
+    pub fn collapse(&self, value: &str) -> String {
+        let path = Path::new(value);
+        if !path.is_absolute() {
+            return value.to_string();
+        }
+
+        PATH_VARIABLES
+            .iter()
+            .filter_map(|name| {
+                let base = self.value(name)?;
+                // A location of `/` would swallow every path
+                base.parent()?;
+                let rest = path.strip_prefix(base).ok()?;
+                Some((name, base.components().count(), rest))
+            })
+            .max_by_key(|(_, depth, _)| *depth)
+            .map(|(name, _, rest)| {
+                if rest.as_os_str().is_empty() {
+                    format!("{{{{{}}}}}", name)
+                } else {
+                    format!("{{{{{}}}}}/{}", name, rest.to_string_lossy())
+                }
+            })
+            .unwrap_or_else(|| value.to_string())
+    }
+
+    /// `rules` with the absolute paths of path rules rewritten into variables
+    pub fn collapse_rules(&self, rules: Vec<SandboxRule>) -> Vec<SandboxRule> {
+        rules
+            .into_iter()
+            .map(|mut rule| {
+                if is_path_rule(&rule) {
+                    rule.pattern_value = self.collapse(&rule.pattern_value);
+                }
+                rule
+            })
+            .collect()
+    }
+}
+
+/// Check that `pattern` only uses known variables
+pub fn validate_pattern(pattern: &str) -> Result<()> {
+    let mut rest = pattern;
+    while let Some((_, name, after)) = next_variable(rest, pattern)? {
+        if !PATH_VARIABLES.contains(&name) {
+            bail!(
+                "Unknown variable {{{{{}}}}} in pattern, expected one of {}",
+                name,
+                PATH_VARIABLES.join(", ")
+            );
+        }
+        rest = after;
+    }
+    Ok(())
+}
+
+/// Whether the rule's pattern is a filesystem path
+pub fn is_path_rule(rule: &SandboxRule) -> bool {
+    rule.operation_type.starts_with("file_")
+        || (rule.operation_type == "network_outbound" && rule.pattern_type == "local_socket")
+}
+
+/// Split `rest` around its first `{{NAME}}`, as text before, name and text after
+fn next_variable<'a>(rest: &'a str, pattern: &str) -> Result<Option<(&'a str, &'a str, &'a str)>> {
+    let Some(start) = rest.find("{{") else {
+        return Ok(None);
+    };
+    let Some(len) = rest[start + 2..].find("}}") else {
+        bail!("Unterminated variable in pattern: {}", pattern);
+    };
+    let end = start + 2 + len;
+    Ok(Some((
+        &rest[..start],
+        &rest[start + 2..end],
+        &rest[end + 2..],
+    )))
+}
@@ -0,0 +1,700 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::executor::{SerializedOperation, SerializedProfile};
+use super::learning::normalize_path;
+
+/// `app_settings` key holding the violations per minute that stop a run
+pub const KILL_THRESHOLD_SETTING: &str = "sandbox_violation_kill_threshold";
+
+/// Window the kill threshold is measured over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A denied operation recognised in the output of a sandboxed process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectedViolation {
+    /// Sandbox operation type that was denied, e.g. `file_read_all`
+    pub operation_type: String,
+    /// Path or address the operation targeted, if it could be recovered
+    pub pattern_value: Option<String>,
+    /// Program that reported the denial
+    pub process_name: Option<String>,
+}
+
+/// Profile, agent and run that detected violations are attributed to
+#[derive(Debug, Clone, Default)]
+pub struct ViolationContext {
+    pub profile_id: Option<i64>,
+    pub agent_id: Option<i64>,
+    pub agent_run_id: Option<i64>,
+    pub pid: Option<i32>,
+}
+
+/// Counts violations over a sliding one-minute window
+#[derive(Debug, Clone)]
+pub struct ViolationRate {
+    threshold: usize,
+    times: VecDeque<Instant>,
+    tripped: bool,
+}
+
+impl ViolationRate {
+    /// Trip once more than `threshold` violations happen within a minute
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            times: VecDeque::new(),
+            tripped: false,
+        }
+    }
+
+    /// Count a violation at `now`
+    ///
+    /// Returns true the first time the threshold is exceeded, and false afterwards.
+    pub fn record(&mut self, now: Instant) -> bool {
+        while let Some(&oldest) = self.times.front() {
+            if now.duration_since(oldest) < RATE_WINDOW {
+                break;
+            }
+            self.times.pop_front();
+        }
+        self.times.push_back(now);
+
+        if self.tripped || self.times.len() <= self.threshold {
+            return false;
+        }
+        self.tripped = true;
+        true
+    }
+}
+
+/// Watches the stderr of a sandboxed run's tool commands and records the denials they report
+///
+/// Each distinct operation and target is recorded once per monitor, since tools tend to
+/// retry or print the same error many times. Repeats still count towards the kill
+/// threshold, as long as the sandbox is what denied them.
+pub struct ViolationMonitor {
+    db_path: PathBuf,
+    context: ViolationContext,
+    conn: Option<Connection>,
+    seen: HashSet<(String, Option<String>)>,
+    rate: Option<(ViolationRate, KillRules)>,
+    kill_requested: bool,
+    first_seen: Vec<DetectedViolation>,
+}
+
+impl ViolationMonitor {
+    pub fn new(db_path: PathBuf, context: ViolationContext) -> Self {
+        Self {
+            db_path,
+            context,
+            conn: None,
+            seen: HashSet::new(),
+            rate: None,
+            kill_requested: false,
+            first_seen: Vec::new(),
+        }
+    }
+
+    /// Request a kill once more than `threshold` violations the sandbox enforced happen
+    /// within a minute
+    ///
+    /// `rules` and `working_dir` are what the run was sandboxed with. Denials of anything
+    /// they allow come from file permissions or the remote end and are not counted.
+    pub fn with_kill_threshold(
+        mut self,
+        threshold: Option<usize>,
+        rules: SerializedProfile,
+        working_dir: PathBuf,
+    ) -> Self {
+        self.rate = threshold.map(|threshold| {
+            (
+                ViolationRate::new(threshold),
+                KillRules { rules, working_dir },
+            )
+        });
+        self
+    }
+
+    /// Attribute violations recorded from now on to `pid`
+    ///
+    /// The monitor of a run is created before its process, so the egress proxy can
+    /// record through it from the start.
+    pub fn set_pid(&mut self, pid: i32) {
+        self.context.pid = Some(pid);
+    }
+
+    /// Whether the kill threshold was exceeded since the last call
+    pub fn take_kill_request(&mut self) -> bool {
+        std::mem::take(&mut self.kill_requested)
+    }
+
+    /// Violations never seen before for this agent, recorded since the last call
+    pub fn take_first_seen(&mut self) -> Vec<DetectedViolation> {
+        std::mem::take(&mut self.first_seen)
+    }
+
+    /// Inspect a line a tool command wrote to stderr
+    pub fn inspect_stderr_line(&mut self, line: &str) -> Option<DetectedViolation> {
+        let violation = detect_violation(line)?;
+        self.record_once(violation)
+    }
+
+    /// Record a violation detected by other means, such as the egress proxy
+    ///
+    /// Returns `None` if the same operation and target were already recorded.
+    pub fn record_once(&mut self, violation: DetectedViolation) -> Option<DetectedViolation> {
+        if let Some((rate, kill_rules)) = &mut self.rate {
+            if is_enforced(&kill_rules.rules, &kill_rules.working_dir, &violation)
+                && rate.record(Instant::now())
+            {
+                self.kill_requested = true;
+            }
+        }
+
+        let key = (
+            violation.operation_type.clone(),
+            violation.pattern_value.clone(),
+        );
+        if !self.seen.insert(key) {
+            return None;
+        }
+
+        if let Err(e) = self.record(&violation) {
+            warn!("Failed to record sandbox violation: {}", e);
+        }
+        Some(violation)
+    }
+
+    fn record(&mut self, violation: &DetectedViolation) -> Result<()> {
+        if self.conn.is_none() {
+            let conn = Connection::open(&self.db_path)
+                .with_context(|| format!("Failed to open {}", self.db_path.display()))?;
+            self.conn = Some(conn);
+        }
+
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        if is_first_seen(conn, &self.context, violation)? {
+            self.first_seen.push(violation.clone());
+        }
+        insert_violation(conn, &self.context, violation)
+    }
+}
+
+/// Sandbox rules a monitor checks denials against before counting them
+struct KillRules {
+    rules: SerializedProfile,
+    working_dir: PathBuf,
+}
+
+/// Whether the sandbox is what denied a violation
+///
+/// That is the case when the rules don't allow the operation on its target. Violations
+/// without a target can't be told apart from ordinary permission errors, so they are
+/// not considered enforced. Relative paths are taken relative to `working_dir`.
+pub fn is_enforced(
+    rules: &SerializedProfile,
+    working_dir: &Path,
+    violation: &DetectedViolation,
+) -> bool {
+    let Some(target) = violation.pattern_value.as_deref() else {
+        return false;
+    };
+    let ops = &rules.operations;
+
+    if violation.operation_type == "network_outbound" {
+        let (host, port) = match target.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().ok()),
+            None => (target, None),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let allowed = ops.iter().any(|op| match op {
+            SerializedOperation::NetworkOutbound { pattern } => pattern == "all",
+            SerializedOperation::NetworkTcp { port: allowed } => port == Some(*allowed),
+            SerializedOperation::NetworkDomain { domain } => {
+                host.eq_ignore_ascii_case(domain)
+                    || host
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+            }
+            _ => false,
+        });
+        return !allowed;
+    }
+
+    let path = normalize_path(&working_dir.join(target));
+    let covers = |rule_path: &Path, is_subpath: bool| {
+        if is_subpath {
+            path.starts_with(rule_path)
+        } else {
+            path == rule_path
+        }
+    };
+    let allowed = ops
+        .iter()
+        .any(|op| match (violation.operation_type.as_str(), op) {
+            ("file_read_all", SerializedOperation::FileReadAll { path, is_subpath })
+            | ("file_write", SerializedOperation::FileWrite { path, is_subpath })
+            | ("file_create", SerializedOperation::FileCreate { path, is_subpath }) => {
+                covers(path, *is_subpath)
+            }
+            _ => false,
+        });
+    !allowed
+}
+
+/// Whether the agent has never had this operation denied on this target before
+pub fn is_first_seen(
+    conn: &Connection,
+    context: &ViolationContext,
+    violation: &DetectedViolation,
+) -> Result<bool> {
+    let seen: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sandbox_violations
+             WHERE agent_id IS ?1 AND operation_type = ?2 AND pattern_value IS ?3)",
+            params![
+                context.agent_id,
+                violation.operation_type,
+                violation.pattern_value
+            ],
+            |row| row.get(0),
+        )
+        .context("Failed to look up earlier sandbox violations")?;
+    Ok(!seen)
+}
+
+/// The kill threshold for runs of an agent, None when they are never stopped for violations
+///
+/// The agent's own threshold wins over the one of its sandbox profile, which wins over
+/// the global one. A threshold of 0 at any level disables stopping.
+pub fn resolve_kill_threshold(
+    conn: &Connection,
+    agent_id: i64,
+    profile_id: Option<i64>,
+) -> Option<usize> {
+    let own = |table: &str, id: i64| {
+        conn.query_row(
+            &format!(
+                "SELECT violation_kill_threshold FROM {} WHERE id = ?1",
+                table
+            ),
+            params![id],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+        .flatten()
+    };
+
+    let threshold =
+        own("agents", agent_id).or_else(|| profile_id.and_then(|id| own("sandbox_profiles", id)));
+    match threshold {
+        Some(threshold) => usize::try_from(threshold)
+            .ok()
+            .filter(|threshold| *threshold > 0),
+        None => load_kill_threshold(conn),
+    }
+}
+
+/// The global kill threshold, None when runs are never stopped for violations
+pub fn load_kill_threshold(conn: &Connection) -> Option<usize> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![KILL_THRESHOLD_SETTING],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .and_then(|value| value.parse::<usize>().ok())
+    .filter(|threshold| *threshold > 0)
+}
+
+/// Insert a detected violation into the `sandbox_violations` table
+pub fn insert_violation(
+    conn: &Connection,
+    context: &ViolationContext,
+    violation: &DetectedViolation,
+) -> Result<()> {
+    debug!(
+        "Recording sandbox violation: {} {:?}",
+        violation.operation_type, violation.pattern_value
+    );
+
+    conn.execute(
+        "INSERT INTO sandbox_violations (profile_id, agent_id, agent_run_id, operation_type, pattern_value, process_name, pid)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            context.profile_id,
+            context.agent_id,
+            context.agent_run_id,
+            violation.operation_type,
+            violation.pattern_value,
+            violation.process_name,
+            context.pid
+        ],
+    )
+    .context("Failed to insert sandbox violation")?;
+
+    Ok(())
+}
+
+/// Recognise an EPERM/EACCES denial in a line of process output
+///
+/// Understands the usual shapes these errors take:
+/// - coreutils and shells: `cat: /etc/shadow: Permission denied`,
+///   `touch: cannot touch 'x': Permission denied`, `sh: 1: cannot create x: Permission denied`
+/// - Node.js: `EACCES: permission denied, open '/etc/shadow'`, `connect EPERM 10.0.0.1:443`
+/// - Python: `PermissionError: [Errno 13] Permission denied: '/etc/shadow'`
+pub fn detect_violation(line: &str) -> Option<DetectedViolation> {
+    let line = line.trim();
+    if let Some(violation) = detect_errno_code(line) {
+        return Some(violation);
+    }
+
+    // ASCII only, so byte offsets into `lower` stay valid for `line`
+    let lower = line.to_ascii_lowercase();
+    let marker = ["permission denied", "operation not permitted"]
+        .iter()
+        .find_map(|marker| lower.find(marker).map(|pos| (pos, marker.len())))?;
+    let (before, after) = (&line[..marker.0], &line[marker.0 + marker.1..]);
+
+    // Python puts the path after the message
+    if let Some(trailing) = after.strip_prefix(':').map(str::trim) {
+        let process_name = before
+            .split(':')
+            .next()
+            .filter(|name| name.ends_with("Error"))
+            .map(|_| "python".to_string());
+        return Some(DetectedViolation {
+            operation_type: classify_operation(before).to_string(),
+            pattern_value: Some(unquote(trailing).to_string()),
+            process_name,
+        });
+    }
+
+    // `program: [line: ]context target: Permission denied`
+    let mut parts: Vec<&str> = before
+        .trim_end()
+        .trim_end_matches(':')
+        .split(": ")
+        .collect();
+    if parts.len() < 2 {
+        return None;
+    }
+    let program = parts.remove(0);
+    // Shells prefix the script line number
+    let is_line_number = |part: &str| {
+        let number = part.strip_prefix("line ").unwrap_or(part);
+        !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+    };
+    if parts.len() > 1 && is_line_number(parts[0]) {
+        parts.remove(0);
+    }
+    let detail = parts.join(": ");
+    let target = extract_target(&detail);
+    // Classify on the wording only, so paths like `/usr/bin/link` don't skew it
+    let action = match &target {
+        Some(target) => detail.replace(target.as_str(), ""),
+        None => detail.clone(),
+    };
+
+    Some(DetectedViolation {
+        operation_type: classify_operation(&action).to_string(),
+        pattern_value: target,
+        process_name: program_name(program),
+    })
+}
+
+/// Node.js style errors carrying the errno code and the failing syscall
+fn detect_errno_code(line: &str) -> Option<DetectedViolation> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let code_pos = words
+        .iter()
+        .position(|word| matches!(word.trim_end_matches(':'), "EACCES" | "EPERM"))?;
+
+    // `EACCES: permission denied, open '/etc/shadow'`
+    if let Some((_, call)) = line.rsplit_once(", ") {
+        if let Some((syscall, target)) = call.split_once(' ') {
+            if syscall.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Some(DetectedViolation {
+                    operation_type: classify_operation(syscall).to_string(),
+                    pattern_value: Some(unquote(target).to_string()),
+                    process_name: Some("node".to_string()),
+                });
+            }
+        }
+    }
+
+    // `connect EACCES 10.0.0.1:443`
+    let syscall = code_pos.checked_sub(1).map(|pos| words[pos])?;
+    let target = words.get(code_pos + 1)?;
+    Some(DetectedViolation {
+        operation_type: classify_operation(syscall).to_string(),
+        pattern_value: Some(unquote(target).to_string()),
+        process_name: Some("node".to_string()),
+    })
+}
+
+/// Map the wording around a denial to a sandbox operation type
+fn classify_operation(context: &str) -> &'static str {
+    let context = context.to_ascii_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| context.contains(word));
+
+    if has(&["connect", "socket", "bind", "getaddrinfo"]) {
+        "network_outbound"
+    } else if has(&[
+        "create", "touch", "mkdir", "remove", "unlink", "rmdir", "rename", "move", "symlink",
+        "link",
+    ]) {
+        "file_create"
+    } else if has(&["write", "truncate", "append", "copyfile", "chmod"]) {
+        "file_write"
+    } else {
+        "file_read_all"
+    }
+}
+
+/// The path or address named in an error detail such as `cannot touch 'x'`
+fn extract_target(detail: &str) -> Option<String> {
+    for (open, close) in [('\'', '\''), ('‘', '’'), ('"', '"'), ('`', '\'')] {
+        if let Some(start) = detail.find(open) {
+            let rest = &detail[start + open.len_utf8()..];
+            if let Some(end) = rest.find(close) {
+                return Some(rest[..end].to_string());
+            }
+        }
+    }
+
+    detail
+        .split_whitespace()
+        .last()
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+}
+
+/// Basename of a program as printed in front of an error, ignoring non-program prefixes
+fn program_name(program: &str) -> Option<String> {
+    let name = program.rsplit('/').next().unwrap_or(program).trim();
+    if name.is_empty() || name.contains(char::is_whitespace) || name.ends_with("Error") {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches(|c| matches!(c, '\'' | '"' | '‘' | '’'))
+}
+
+/// Width of the time buckets violation trends are counted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBucket {
+    Minute,
+    #[default]
+    Hour,
+    Day,
+}
+
+impl TimeBucket {
+    /// `strftime` format truncating `denied_at` to the start of its bucket
+    fn format(self) -> &'static str {
+        match self {
+            TimeBucket::Minute => "%Y-%m-%d %H:%M:00",
+            TimeBucket::Hour => "%Y-%m-%d %H:00:00",
+            TimeBucket::Day => "%Y-%m-%d",
+        }
+    }
+}
+
+/// Which violations analytics are computed over
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViolationFilter {
+    pub agent_id: Option<i64>,
+    pub profile_id: Option<i64>,
+    /// Only violations from the last this many hours
+    pub since_hours: Option<i64>,
+}
+
+/// Number of violations of one agent and profile within a time bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViolationBucket {
+    /// Start of the bucket, in the format of `denied_at`
+    pub bucket: String,
+    pub agent_id: Option<i64>,
+    pub profile_id: Option<i64>,
+    pub count: i64,
+}
+
+/// A denied path or host with how often it was denied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeniedTarget {
+    pub target: String,
+    pub count: i64,
+    pub last_denied_at: String,
+}
+
+/// An operation and target an agent was first denied within the filtered period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstSeenViolation {
+    pub agent_id: Option<i64>,
+    pub operation_type: String,
+    pub pattern_value: Option<String>,
+    pub first_denied_at: String,
+    pub count: i64,
+}
+
+/// Trends, top targets and new kinds of violations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViolationAnalytics {
+    pub buckets: Vec<ViolationBucket>,
+    pub top_paths: Vec<DeniedTarget>,
+    pub top_hosts: Vec<DeniedTarget>,
+    pub first_seen: Vec<FirstSeenViolation>,
+}
+
+/// Conditions shared by the analytics queries, binding `?1` to `?3`
+const FILTER_CONDITIONS: &str = "(?1 IS NULL OR agent_id = ?1)
+     AND (?2 IS NULL OR profile_id = ?2)
+     AND (?3 IS NULL OR denied_at > datetime('now', '-' || ?3 || ' hours'))";
+
+/// Compute trends, the `limit` most denied paths and hosts, and first-seen violations
+pub fn violation_analytics(
+    conn: &Connection,
+    bucket: TimeBucket,
+    filter: &ViolationFilter,
+    limit: usize,
+) -> Result<ViolationAnalytics> {
+    Ok(ViolationAnalytics {
+        buckets: violation_trends(conn, bucket, filter)?,
+        top_paths: top_denied_targets(conn, filter, "file_%", limit)?,
+        top_hosts: top_denied_targets(conn, filter, "network_%", limit)?,
+        first_seen: first_seen_violations(conn, filter, limit)?,
+    })
+}
+
+/// Violation counts per time bucket, agent and profile, oldest first
+pub fn violation_trends(
+    conn: &Connection,
+    bucket: TimeBucket,
+    filter: &ViolationFilter,
+) -> Result<Vec<ViolationBucket>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT strftime(?4, denied_at) AS bucket, agent_id, profile_id, COUNT(*)
+         FROM sandbox_violations
+         WHERE {}
+         GROUP BY bucket, agent_id, profile_id
+         ORDER BY bucket, agent_id, profile_id",
+        FILTER_CONDITIONS
+    ))?;
+    let buckets = stmt
+        .query_map(
+            params![
+                filter.agent_id,
+                filter.profile_id,
+                filter.since_hours,
+                bucket.format()
+            ],
+            |row| {
+                Ok(ViolationBucket {
+                    bucket: row.get(0)?,
+                    agent_id: row.get(1)?,
+                    profile_id: row.get(2)?,
+                    count: row.get(3)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to load violation trends")?;
+    Ok(buckets)
+}
+
+/// The most often denied targets of operations matching `operation_like`
+fn top_denied_targets(
+    conn: &Connection,
+    filter: &ViolationFilter,
+    operation_like: &str,
+    limit: usize,
+) -> Result<Vec<DeniedTarget>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT pattern_value, COUNT(*) AS count, MAX(denied_at)
+         FROM sandbox_violations
+         WHERE {} AND operation_type LIKE ?4 AND pattern_value IS NOT NULL
+         GROUP BY pattern_value
+         ORDER BY count DESC, pattern_value
+         LIMIT ?5",
+        FILTER_CONDITIONS
+    ))?;
+    let targets = stmt
+        .query_map(
+            params![
+                filter.agent_id,
+                filter.profile_id,
+                filter.since_hours,
+                operation_like,
+                limit as i64
+            ],
+            |row| {
+                Ok(DeniedTarget {
+                    target: row.get(0)?,
+                    count: row.get(1)?,
+                    last_denied_at: row.get(2)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to load denied targets")?;
+    Ok(targets)
+}
+
+/// Operations and targets each agent was first denied within the filtered period,
+/// newest first
+///
+/// Earlier violations count even when they are older than `since_hours`, so only
+/// genuinely new kinds show up.
+fn first_seen_violations(
+    conn: &Connection,
+    filter: &ViolationFilter,
+    limit: usize,
+) -> Result<Vec<FirstSeenViolation>> {
+    let mut stmt = conn.prepare(
+        "SELECT agent_id, operation_type, pattern_value, MIN(denied_at) AS first_denied_at, COUNT(*)
+         FROM sandbox_violations
+         WHERE (?1 IS NULL OR agent_id = ?1) AND (?2 IS NULL OR profile_id = ?2)
+         GROUP BY agent_id, operation_type, pattern_value
+         HAVING ?3 IS NULL OR first_denied_at > datetime('now', '-' || ?3 || ' hours')
+         ORDER BY first_denied_at DESC
+         LIMIT ?4",
+    )?;
+    let first_seen = stmt
+        .query_map(
+            params![
+                filter.agent_id,
+                filter.profile_id,
+                filter.since_hours,
+                limit as i64
+            ],
+            |row| {
+                Ok(FirstSeenViolation {
+                    agent_id: row.get(0)?,
+                    operation_type: row.get(1)?,
+                    pattern_value: row.get(2)?,
+                    first_denied_at: row.get(3)?,
+                    count: row.get(4)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to load first-seen violations")?;
+    Ok(first_seen)
+}
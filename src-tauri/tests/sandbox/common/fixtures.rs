@@ -1,6 +1,7 @@
 //! Test fixtures and data for sandbox testing
 use anyhow::Result;
 use once_cell::sync::Lazy;
+use organized_agents_lib::sandbox::profile::SandboxRule;
 use rusqlite::{params, Connection};
 use std::path::PathBuf;
 // Removed std::sync::Mutex - using parking_lot::Mutex instead
@@ -13,6 +14,27 @@ use parking_lot::Mutex;
 pub static TEST_DB: Lazy<Mutex<TestDatabase>> =
     Lazy::new(|| Mutex::new(TestDatabase::new().expect("Failed to create test database")));
 
+/// Helper to create a sandbox rule
+pub fn make_rule(
+    operation_type: &str,
+    pattern_type: &str,
+    pattern_value: &str,
+    platforms: Option<&[&str]>,
+) -> SandboxRule {
+    SandboxRule {
+        id: None,
+        profile_id: 0,
+        operation_type: operation_type.to_string(),
+        pattern_type: pattern_type.to_string(),
+        pattern_value: pattern_value.to_string(),
+        enabled: true,
+        platform_support: platforms.map(|p| {
+            serde_json::to_string(&p.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap()
+        }),
+        created_at: String::new(),
+    }
+}
+
 /// Test database manager
 pub struct TestDatabase {
     pub conn: Connection,
@@ -43,7 +65,12 @@ impl TestDatabase {
                 is_active BOOLEAN NOT NULL DEFAULT 0,
                 is_default BOOLEAN NOT NULL DEFAULT 0,
                 created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                cpu_time_limit_secs INTEGER,
+                memory_limit_mb INTEGER,
+                max_processes INTEGER,
+                max_open_files INTEGER,
+                parent_id INTEGER REFERENCES sandbox_profiles(id)
             )",
             [],
         )?;
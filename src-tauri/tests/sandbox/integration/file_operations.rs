@@ -1,8 +1,8 @@
 //! Integration tests for file operations in sandbox
 use crate::sandbox::common::*;
 use crate::skip_if_unsupported;
-use claudia_lib::sandbox::executor::SandboxExecutor;
-use claudia_lib::sandbox::profile::ProfileBuilder;
+use organized_agents_lib::sandbox::executor::SandboxExecutor;
+use organized_agents_lib::sandbox::profile::ProfileBuilder;
 use gaol::profile::{Operation, PathPattern, Profile};
 use serial_test::serial;
 use tempfile::TempDir;
@@ -263,7 +263,7 @@ fn test_template_variable_expansion() {
         .expect("Failed to create test profile");
 
     // Load and build the profile
-    let db_rules = claudia_lib::sandbox::profile::load_profile_rules(&test_db.conn, profile_id)
+    let db_rules = organized_agents_lib::sandbox::profile::load_profile_rules(&test_db.conn, profile_id)
         .expect("Failed to load profile rules");
 
     let builder = ProfileBuilder::new(test_fs.project_path.clone())
@@ -299,3 +299,85 @@ fn test_template_variable_expansion() {
         }
     }
 }
+
+/// Claude's state under the home directory stays writable under the Agent-specific
+/// profile, even for agents without file write permission
+#[test]
+#[serial]
+#[cfg(target_os = "linux")]
+fn test_agent_profile_allows_claude_state() {
+    use organized_agents_lib::sandbox::profile::claude_runtime_rules;
+    use std::os::unix::fs::PermissionsExt;
+
+    if organized_agents_lib::sandbox::landlock::active_abi_version().is_none() {
+        eprintln!("Skipping test: Landlock not available");
+        return;
+    }
+
+    let test_fs = TestFileSystem::new().expect("Failed to create test filesystem");
+    let bin_dir = TempDir::new().expect("Failed to create temp dir");
+
+    // The temp directory is always writable, so it moves next to the home directory
+    let root = TempDir::new().expect("Failed to create temp dir");
+    let home = root.path().join("home");
+    let tmp_dir = root.path().join("tmp");
+    std::fs::create_dir_all(&home).expect("Failed to create home");
+    std::fs::create_dir_all(&tmp_dir).expect("Failed to create temp dir");
+    let original_env: Vec<_> = ["HOME", "TMPDIR"]
+        .into_iter()
+        .map(|key| (key, std::env::var_os(key)))
+        .collect();
+    std::env::set_var("HOME", &home);
+    std::env::set_var("TMPDIR", &tmp_dir);
+
+    let stand_in = bin_dir.path().join("claude");
+    std::fs::write(
+        &stand_in,
+        "#!/bin/sh\n\
+         mkdir -p \"$HOME/.claude/projects\" || exit 1\n\
+         echo '{}' > \"$HOME/.claude/projects/session.jsonl\" || exit 1\n\
+         echo '{}' > \"$HOME/.claude.json\" || exit 1\n\
+         ! echo '{}' 2>/dev/null > \"$HOME/outside.txt\"\n",
+    )
+    .expect("Failed to write stand-in");
+    std::fs::set_permissions(&stand_in, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to make stand-in executable");
+    std::fs::write(home.join(".claude.json"), "").expect("Failed to seed .claude.json");
+
+    let stand_in = stand_in.to_string_lossy().into_owned();
+    let build_result = ProfileBuilder::new(test_fs.project_path.clone())
+        .expect("Failed to create profile builder")
+        .with_runtime_rules(claude_runtime_rules(&stand_in))
+        .build_agent_profile(vec![], true, true, false, false)
+        .expect("Failed to build agent profile");
+    let executor = SandboxExecutor::new_with_serialization(
+        build_result.profile,
+        test_fs.project_path.clone(),
+        build_result.serialized,
+    );
+    let output = executor
+        .prepare_sandboxed_std_command(&stand_in, &[], &test_fs.project_path)
+        .expect("Failed to prepare command")
+        .output();
+
+    for (key, value) in original_env {
+        match value {
+            Some(value) => std::env::set_var(key, value),
+            None => std::env::remove_var(key),
+        }
+    }
+
+    let output = output.expect("Failed to run stand-in");
+    assert!(
+        output.status.success(),
+        "Stand-in failed: {:?} {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(home.join(".claude/projects/session.jsonl").exists());
+    assert_eq!(
+        std::fs::read_to_string(home.join(".claude.json")).unwrap(),
+        "{}\n"
+    );
+    assert!(!home.join("outside.txt").exists());
+}
@@ -1,7 +1,7 @@
 //! Integration tests for network operations in sandbox
 use crate::sandbox::common::*;
 use crate::skip_if_unsupported;
-use claudia_lib::sandbox::executor::SandboxExecutor;
+use organized_agents_lib::sandbox::executor::SandboxExecutor;
 use gaol::profile::{AddressPattern, Operation, Profile};
 use serial_test::serial;
 use std::net::TcpListener;
@@ -131,6 +131,77 @@ fn test_forbidden_network() {
     }
 }
 
+/// Run a binary connecting to a local listener under Landlock, reporting whether it connected
+#[cfg(target_os = "linux")]
+fn landlock_connect(profile: Profile, name: &str, port: u16) -> Option<bool> {
+    if organized_agents_lib::sandbox::landlock::active_abi_version().is_none() {
+        eprintln!("Skipping test: Landlock not available");
+        return None;
+    }
+
+    let test_fs = TestFileSystem::new().expect("Failed to create test filesystem");
+    let test_code = test_code::network_connect(&format!("127.0.0.1:{}", port));
+    let binary_dir = TempDir::new().expect("Failed to create temp dir");
+    let binary_path =
+        create_test_binary(name, &test_code, binary_dir.path()).expect("Failed to create test binary");
+
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).expect("Failed to bind listener");
+    std::thread::spawn(move || {
+        let _ = listener.accept();
+    });
+
+    let executor = SandboxExecutor::new(profile, test_fs.project_path.clone());
+    let mut child = executor
+        .execute_sandboxed_spawn(&binary_path.to_string_lossy(), &[], &test_fs.project_path)
+        .expect("Failed to spawn under Landlock");
+    Some(child.wait().expect("Failed to wait for child").success())
+}
+
+/// Without network rules the child gets its own network namespace, even for localhost
+#[test]
+#[serial]
+#[cfg(target_os = "linux")]
+fn test_landlock_isolates_network_without_network_rules() {
+    let profile = Profile::new(Vec::new()).expect("Failed to create profile");
+    if let Some(connected) = landlock_connect(profile, "test_landlock_no_network", get_available_port())
+    {
+        assert!(!connected, "Network should be isolated without network rules");
+    }
+
+    let profile = Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)])
+        .expect("Failed to create profile");
+    if let Some(connected) = landlock_connect(profile, "test_landlock_network", get_available_port()) {
+        assert!(connected, "Network should be reachable when allowed");
+    }
+}
+
+/// TCP port rules are enforced by Landlock from ABI v4 on
+#[test]
+#[serial]
+#[cfg(target_os = "linux")]
+fn test_landlock_network_tcp_port_specific() {
+    if organized_agents_lib::sandbox::landlock::active_abi_version().is_none_or(|abi| abi < 4) {
+        eprintln!("Skipping test: Landlock cannot filter TCP ports on this kernel");
+        return;
+    }
+
+    let allowed_port = get_available_port();
+    let forbidden_port = get_available_port();
+    let profile = Profile::new(vec![Operation::NetworkOutbound(AddressPattern::Tcp(allowed_port))])
+        .expect("Failed to create profile");
+
+    assert_eq!(
+        landlock_connect(profile.clone(), "test_landlock_allowed_port", allowed_port),
+        Some(true),
+        "Connection to allowed port should succeed"
+    );
+    assert_eq!(
+        landlock_connect(profile, "test_landlock_forbidden_port", forbidden_port),
+        Some(false),
+        "Connection to forbidden port should fail"
+    );
+}
+
 /// Test TCP port-specific network rules (macOS only)
 #[test]
 #[serial]
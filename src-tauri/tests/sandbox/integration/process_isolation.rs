@@ -1,7 +1,7 @@
 //! Integration tests for process isolation in sandbox
 use crate::sandbox::common::*;
 use crate::skip_if_unsupported;
-use claudia_lib::sandbox::executor::SandboxExecutor;
+use organized_agents_lib::sandbox::executor::SandboxExecutor;
 use gaol::profile::{AddressPattern, Operation, PathPattern, Profile};
 use serial_test::serial;
 use tempfile::TempDir;
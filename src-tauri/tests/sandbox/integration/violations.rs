@@ -1,7 +1,7 @@
 //! Integration tests for sandbox violation detection and logging
 use crate::sandbox::common::*;
 use crate::skip_if_unsupported;
-use claudia_lib::sandbox::executor::SandboxExecutor;
+use organized_agents_lib::sandbox::executor::SandboxExecutor;
 use gaol::profile::{Operation, PathPattern, Profile};
 use serial_test::serial;
 use std::sync::{Arc, Mutex};
@@ -295,3 +295,38 @@ fn main() {{
         }
     }
 }
+
+/// Tool commands copy their stderr into the capture pipe, once even through nested shells
+#[tokio::test]
+#[cfg(unix)]
+async fn test_tool_stderr_is_captured() {
+    use organized_agents_lib::sandbox::capture::ToolStderrCapture;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    if std::process::Command::new("bash").arg("--version").output().is_err() {
+        eprintln!("Skipping test: bash not available");
+        return;
+    }
+
+    let capture = ToolStderrCapture::new().expect("Failed to set up capture");
+    let mut cmd = tokio::process::Command::new("bash");
+    cmd.arg("-c")
+        .arg("bash -c 'cat /nonexistent/secret'; echo done")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    capture.attach(&mut cmd);
+    let child = cmd.spawn().expect("Failed to spawn bash");
+    let (receiver, _script) = capture.into_reader().expect("Failed to read capture");
+
+    let output = child.wait_with_output().await.expect("Failed to wait for bash");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("/nonexistent/secret"), "stderr was {:?}", stderr);
+
+    let mut captured = Vec::new();
+    let mut lines = BufReader::new(receiver).lines();
+    while let Some(line) = lines.next_line().await.expect("Failed to read capture") {
+        captured.push(line);
+    }
+    assert_eq!(captured, vec![stderr.trim_end().to_string()]);
+}
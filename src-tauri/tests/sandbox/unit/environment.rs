@@ -0,0 +1,102 @@
+//! Unit tests for sandbox environment policies
+use crate::sandbox::common::make_rule;
+use organized_agents_lib::sandbox::environment::{
+    display_value, is_secret, matches_pattern, EnvPolicy, REDACTED,
+};
+use pretty_assertions::assert_eq;
+use test_case::test_case;
+
+fn parent_env() -> Vec<(String, String)> {
+    [
+        ("PATH", "/usr/bin"),
+        ("HOME", "/home/dev"),
+        ("LC_CTYPE", "C.UTF-8"),
+        ("NPM_CONFIG_REGISTRY", "https://registry.npmjs.org"),
+        ("NPM_TOKEN", "npm_secret"),
+        ("GITHUB_TOKEN", "ghp_secret"),
+        ("AWS_SECRET_ACCESS_KEY", "aws_secret"),
+        ("ANTHROPIC_API_KEY", "sk-ant-secret"),
+        ("EDITOR", "vim"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect()
+}
+
+fn names(policy: &EnvPolicy) -> Vec<String> {
+    policy.effective_env(parent_env()).into_keys().collect()
+}
+
+#[test_case("PATH", "PATH", true ; "exact")]
+#[test_case("PATH", "PATHEXT", false ; "exact is not a prefix")]
+#[test_case("LC_*", "LC_CTYPE", true ; "trailing wildcard")]
+#[test_case("*_PROXY", "HTTPS_PROXY", true ; "leading wildcard")]
+#[test_case("NPM_*_REGISTRY", "NPM_CONFIG_REGISTRY", true ; "inner wildcard")]
+#[test_case("A*A", "A", false ; "overlapping ends")]
+#[test_case("*", "ANYTHING", true ; "only wildcard")]
+fn test_matches_pattern(pattern: &str, name: &str, matches: bool) {
+    assert_eq!(matches_pattern(pattern, name), matches);
+}
+
+#[test_case("GITHUB_TOKEN", true)]
+#[test_case("aws_secret_access_key", true)]
+#[test_case("DB_PASSWORD", true)]
+#[test_case("KEYBOARD", false)]
+#[test_case("PATH", false)]
+fn test_is_secret(name: &str, secret: bool) {
+    assert_eq!(is_secret(name), secret);
+}
+
+#[test]
+fn test_default_policy_passes_safe_variables() {
+    assert_eq!(
+        names(&EnvPolicy::default()),
+        vec!["ANTHROPIC_API_KEY", "HOME", "LC_CTYPE", "PATH"]
+    );
+}
+
+#[test]
+fn test_rules_allow_deny_and_set() {
+    let policy = EnvPolicy::from_rules(&[
+        make_rule("environment", "allow", "PATH", None),
+        make_rule("environment", "allow", "NPM_*", None),
+        make_rule("environment", "allow", "GITHUB_TOKEN", None),
+        make_rule("environment", "deny", "*_REGISTRY", None),
+        make_rule("environment", "set", "CI=true", None),
+        make_rule("environment", "set", "PATH=/opt/bin:/usr/bin", None),
+        make_rule("environment", "set", "missing-separator", None),
+    ]);
+
+    let env = policy.effective_env(parent_env());
+
+    // Allow rules add to the defaults. NPM_TOKEN is secret, so the wildcard doesn't pass
+    // it through
+    assert_eq!(
+        env.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("ANTHROPIC_API_KEY".to_string(), "sk-ant-secret".to_string()),
+            ("CI".to_string(), "true".to_string()),
+            ("GITHUB_TOKEN".to_string(), "ghp_secret".to_string()),
+            ("HOME".to_string(), "/home/dev".to_string()),
+            ("LC_CTYPE".to_string(), "C.UTF-8".to_string()),
+            ("PATH".to_string(), "/opt/bin:/usr/bin".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_deny_rules_remove_default_variables() {
+    let policy = EnvPolicy::from_rules(&[
+        make_rule("environment", "allow", "EDITOR", None),
+        make_rule("environment", "deny", "ANTHROPIC_API_KEY", None),
+        make_rule("environment", "deny", "LC_*", None),
+    ]);
+
+    assert_eq!(names(&policy), vec!["EDITOR", "HOME", "PATH"]);
+}
+
+#[test]
+fn test_secret_values_are_redacted() {
+    assert_eq!(display_value("GITHUB_TOKEN", "ghp_secret"), REDACTED);
+    assert_eq!(display_value("EDITOR", "vim"), "vim");
+}
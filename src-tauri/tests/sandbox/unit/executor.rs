@@ -1,5 +1,5 @@
 //! Unit tests for SandboxExecutor
-use claudia_lib::sandbox::executor::{should_activate_sandbox, SandboxExecutor};
+use organized_agents_lib::sandbox::executor::{should_activate_sandbox, SandboxExecutor};
 use gaol::profile::{AddressPattern, Operation, PathPattern, Profile};
 use std::env;
 use std::path::PathBuf;
@@ -56,10 +56,13 @@ fn test_prepare_sandboxed_command() {
     let profile = create_test_profile(project_path.clone());
     let executor = SandboxExecutor::new(profile, project_path.clone());
 
-    let _cmd = executor.prepare_sandboxed_command("echo", &["hello"], &project_path);
+    let (_cmd, backend) = executor.prepare_sandboxed_command("echo", &["hello"], &project_path);
 
-    // The command should have sandbox environment variables set
-    // Note: We can't easily test Command internals, but we can verify it doesn't panic
+    // Only Landlock restricts prepared commands
+    let landlock = cfg!(target_os = "linux")
+        && organized_agents_lib::sandbox::platform::get_platform_capabilities().backend
+            == "landlock";
+    assert_eq!(backend.is_some(), landlock);
 }
 
 #[test]
@@ -144,3 +147,26 @@ fn test_spawn_sandboxed_process() {
         }
     }
 }
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_prepared_std_command_runs_under_tokio() {
+    let capabilities = organized_agents_lib::sandbox::platform::get_platform_capabilities();
+    if !capabilities.sandboxing_supported {
+        eprintln!("Skipping test: no sandbox backend on {}", capabilities.os);
+        return;
+    }
+
+    let project_path = env::current_dir().unwrap_or_else(|_| PathBuf::from("/tmp"));
+    let profile = create_test_profile(project_path.clone());
+    let executor = SandboxExecutor::new(profile, project_path.clone());
+
+    let command = executor
+        .prepare_sandboxed_std_command("/bin/echo", &["sandbox test"], &project_path)
+        .expect("Failed to prepare command");
+    let output = tokio::process::Command::from(command)
+        .output()
+        .await
+        .expect("A supported sandbox backend should spawn the command");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "sandbox test\n");
+}
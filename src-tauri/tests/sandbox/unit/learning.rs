@@ -0,0 +1,156 @@
+//! Unit tests for learning runs and profile proposals
+use crate::sandbox::common::make_rule;
+use organized_agents_lib::sandbox::learning::{diff_rules, parse_trace, propose_rules, ObservedAccess};
+use organized_agents_lib::sandbox::profile::SandboxRule;
+use pretty_assertions::assert_eq;
+use std::path::{Path, PathBuf};
+
+const TRACE: &str = include_str!("../../fixtures/sandbox_learning/agent_run.strace");
+const PROJECT: &str = "/home/dev/project";
+const HOME: &str = "/home/dev";
+
+fn keys(rules: &[SandboxRule]) -> Vec<(&str, &str, &str)> {
+    rules
+        .iter()
+        .map(|r| {
+            (
+                r.operation_type.as_str(),
+                r.pattern_type.as_str(),
+                r.pattern_value.as_str(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_trace_classifies_accesses() {
+    let observed = parse_trace(TRACE, Path::new(PROJECT));
+
+    // Relative paths resolve against the working directory
+    assert!(observed
+        .read_paths
+        .contains(&PathBuf::from("/home/dev/project/Cargo.toml")));
+    assert!(observed
+        .read_paths
+        .contains(&PathBuf::from("/usr/bin/cargo")));
+    // Failed opens and paths relative to other directories are ignored
+    assert!(!observed
+        .read_paths
+        .contains(&PathBuf::from("/home/dev/.ssh/id_ed25519")));
+    assert!(!observed
+        .read_paths
+        .iter()
+        .any(|p| p.ends_with("fingerprint")));
+
+    assert_eq!(
+        observed.written_paths.iter().collect::<Vec<_>>(),
+        vec![Path::new("/home/dev/project/src/lib.rs")]
+    );
+    for created in [
+        "/home/dev/project/target/debug/build",
+        "/home/dev/project/target/debug/app.tmp",
+        "/home/dev/project/target/debug/app",
+        "/home/dev/project/src/old.rs",
+    ] {
+        assert!(observed.created_paths.contains(&PathBuf::from(created)));
+    }
+
+    // Non-blocking connects count, failed ones and abstract sockets don't
+    assert_eq!(
+        observed.network_endpoints.iter().collect::<Vec<_>>(),
+        vec!["10.0.0.5:5432", "127.0.0.53:53", "160.79.104.10:443"]
+    );
+    // Addresses are mapped back to the names the program looked up
+    assert_eq!(
+        observed.resolved_addresses.get("160.79.104.10").map(String::as_str),
+        Some("api.anthropic.com")
+    );
+    assert_eq!(
+        observed.local_sockets.iter().collect::<Vec<_>>(),
+        vec![Path::new("/run/user/1000/bus")]
+    );
+}
+
+#[test]
+fn test_propose_rules_generalizes_paths() {
+    let observed = parse_trace(TRACE, Path::new(PROJECT));
+    let rules = propose_rules(&observed, Path::new(PROJECT), Path::new(HOME));
+
+    assert_eq!(
+        keys(&rules),
+        vec![
+            ("file_read_all", "literal", "{{HOME}}/.claude.json"),
+            ("file_read_all", "subpath", "/etc"),
+            ("file_read_all", "subpath", "/lib/x86_64-linux-gnu"),
+            ("file_read_all", "subpath", "/usr/bin"),
+            ("file_read_all", "subpath", "/usr/lib"),
+            ("file_read_all", "subpath", "{{HOME}}/.cargo"),
+            ("file_read_all", "subpath", "{{HOME}}/.claude"),
+            ("file_read_all", "subpath", "{{PROJECT_PATH}}"),
+            ("file_write", "subpath", "{{PROJECT_PATH}}/src"),
+            ("file_create", "subpath", "{{HOME}}/.claude"),
+            ("file_create", "subpath", "{{PROJECT_PATH}}/src"),
+            ("file_create", "subpath", "{{PROJECT_PATH}}/target"),
+            ("network_outbound", "domain", "api.anthropic.com"),
+            ("network_outbound", "tcp", "5432"),
+            ("network_outbound", "local_socket", "/run/user/1000/bus"),
+        ]
+    );
+}
+
+#[test]
+fn test_propose_rules_never_widens_to_template_roots() {
+    let mut observed = ObservedAccess::default();
+    for file in ["README.md", "Cargo.toml", "Cargo.lock", "build.rs"] {
+        observed.written_paths.insert(Path::new(PROJECT).join(file));
+    }
+    for file in [".gitconfig", ".npmrc", ".bashrc"] {
+        observed.read_paths.insert(Path::new(HOME).join(file));
+    }
+    // A file created directly in the project needs the project itself
+    observed
+        .created_paths
+        .insert(Path::new(PROJECT).join("notes.txt"));
+
+    let rules = propose_rules(&observed, Path::new(PROJECT), Path::new(HOME));
+
+    assert_eq!(
+        keys(&rules),
+        vec![
+            ("file_read_all", "literal", "{{HOME}}/.bashrc"),
+            ("file_read_all", "literal", "{{HOME}}/.gitconfig"),
+            ("file_read_all", "literal", "{{HOME}}/.npmrc"),
+            ("file_write", "literal", "{{PROJECT_PATH}}/Cargo.lock"),
+            ("file_write", "literal", "{{PROJECT_PATH}}/Cargo.toml"),
+            ("file_write", "literal", "{{PROJECT_PATH}}/README.md"),
+            ("file_write", "literal", "{{PROJECT_PATH}}/build.rs"),
+            ("file_create", "subpath", "{{PROJECT_PATH}}"),
+        ]
+    );
+}
+
+#[test]
+fn test_diff_rules() {
+    let current = vec![
+        make_rule("file_read_all", "subpath", "{{PROJECT_PATH}}", None),
+        make_rule("file_read_all", "subpath", "/opt", None),
+        make_rule("network_outbound", "all", "", None),
+    ];
+    let proposed = vec![
+        make_rule("file_read_all", "subpath", "{{PROJECT_PATH}}", None),
+        make_rule("file_write", "subpath", "{{PROJECT_PATH}}/src", None),
+        make_rule("network_outbound", "all", "", None),
+    ];
+
+    let diff = diff_rules(&current, &proposed);
+
+    assert_eq!(
+        keys(&diff.added),
+        vec![("file_write", "subpath", "{{PROJECT_PATH}}/src")]
+    );
+    assert_eq!(
+        keys(&diff.removed),
+        vec![("file_read_all", "subpath", "/opt")]
+    );
+    assert_eq!(diff.unchanged.len(), 2);
+}
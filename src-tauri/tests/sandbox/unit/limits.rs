@@ -0,0 +1,114 @@
+//! Unit tests for sandbox resource limits
+use organized_agents_lib::sandbox::limits::{Cgroup, ResourceLimits};
+use std::fs;
+use tempfile::tempdir;
+use test_case::test_case;
+
+#[test_case(ResourceLimits::default(), true ; "unlimited")]
+#[test_case(ResourceLimits { memory_mb: Some(512), ..Default::default() }, true ; "memory")]
+#[test_case(ResourceLimits { cpu_time_secs: Some(0), ..Default::default() }, false ; "zero cpu time")]
+#[test_case(ResourceLimits { max_open_files: Some(0), ..Default::default() }, false ; "zero open files")]
+fn test_validate(limits: ResourceLimits, valid: bool) {
+    assert_eq!(limits.validate().is_ok(), valid);
+}
+
+#[test_case(ResourceLimits::default(), false ; "unlimited")]
+#[test_case(ResourceLimits { cpu_time_secs: Some(30), max_open_files: Some(64), ..Default::default() }, false ; "rlimits only")]
+#[test_case(ResourceLimits { memory_mb: Some(512), ..Default::default() }, true ; "memory")]
+#[test_case(ResourceLimits { max_processes: Some(32), ..Default::default() }, true ; "processes")]
+fn test_needs_cgroup(limits: ResourceLimits, needs_cgroup: bool) {
+    assert_eq!(limits.needs_cgroup(), needs_cgroup);
+}
+
+#[test]
+fn test_systemd_scope_requires_a_user_manager() {
+    use organized_agents_lib::sandbox::limits::systemd_scope_available;
+
+    if systemd_scope_available() {
+        eprintln!("Skipping test: a systemd user manager is running");
+        return;
+    }
+    let limits = ResourceLimits {
+        memory_mb: Some(256),
+        ..Default::default()
+    };
+    assert!(Cgroup::create_scope("organized-agents-run-test", &limits).is_err());
+}
+
+#[test]
+fn test_limits_deserialize_with_missing_fields() {
+    let limits: ResourceLimits = serde_json::from_str(r#"{"max_processes": 64}"#).unwrap();
+    assert_eq!(
+        limits,
+        ResourceLimits {
+            max_processes: Some(64),
+            ..Default::default()
+        }
+    );
+    assert!(!limits.is_unlimited());
+}
+
+#[test]
+fn test_cgroup_writes_limits() {
+    // A plain directory stands in for the cgroup hierarchy
+    let parent = tempdir().unwrap();
+    let limits = ResourceLimits {
+        memory_mb: Some(256),
+        max_processes: Some(32),
+        ..Default::default()
+    };
+
+    let cgroup = Cgroup::create_in(parent.path(), "organized-agents-run-1", &limits).unwrap();
+    let path = cgroup.path().to_path_buf();
+    assert_eq!(
+        fs::read_to_string(path.join("memory.max")).unwrap(),
+        (256 * 1024 * 1024).to_string()
+    );
+    assert_eq!(
+        fs::read_to_string(path.join("memory.swap.max")).unwrap(),
+        "0"
+    );
+    assert_eq!(fs::read_to_string(path.join("pids.max")).unwrap(), "32");
+    assert_eq!(cgroup.procs_path(), path.join("cgroup.procs"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rlimits_apply_to_child() {
+    let limits = ResourceLimits {
+        cpu_time_secs: Some(30),
+        max_open_files: Some(64),
+        ..Default::default()
+    };
+
+    let mut command = std::process::Command::new("sh");
+    command.args(["-c", "ulimit -t; ulimit -n"]);
+    organized_agents_lib::sandbox::limits::apply_std_on_exec(&mut command, limits, None);
+    let output = command.output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "30\n64\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_memory_and_process_limits_need_a_cgroup() {
+    let limits = ResourceLimits {
+        memory_mb: Some(64),
+        max_processes: Some(1),
+        ..Default::default()
+    };
+    let ulimits = |limits: Option<ResourceLimits>| {
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", "ulimit -a"]);
+        if let Some(limits) = limits {
+            organized_agents_lib::sandbox::limits::apply_std_on_exec(&mut command, limits, None);
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // Per-user and address space rlimits would break the agent, so nothing changes
+    assert_eq!(ulimits(Some(limits)), ulimits(None));
+}
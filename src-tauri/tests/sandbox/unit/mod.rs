@@ -1,7 +1,25 @@
 //! Unit tests for sandbox components
 #[cfg(test)]
+mod environment;
+#[cfg(test)]
 mod executor;
 #[cfg(test)]
+mod learning;
+#[cfg(test)]
+mod limits;
+#[cfg(test)]
 mod platform;
 #[cfg(test)]
+mod policy;
+#[cfg(test)]
+mod probe;
+#[cfg(test)]
 mod profile_builder;
+#[cfg(test)]
+mod profile_inheritance;
+#[cfg(test)]
+mod proxy;
+#[cfg(test)]
+mod variables;
+#[cfg(test)]
+mod violations;
@@ -1,5 +1,5 @@
 //! Unit tests for platform capabilities
-use claudia_lib::sandbox::platform::{get_platform_capabilities, is_sandboxing_available};
+use organized_agents_lib::sandbox::platform::{get_platform_capabilities, is_sandboxing_available};
 use pretty_assertions::assert_eq;
 use std::env;
 
@@ -65,7 +65,12 @@ fn test_linux_capabilities() {
         .iter()
         .find(|op| op.operation == "network_outbound_tcp")
         .expect("network_outbound_tcp should be present");
-    assert_eq!(network_tcp.support_level, "cannot_be_precisely");
+    let expected_tcp = match caps.landlock_abi {
+        Some(abi) if abi >= 4 => "can_be_allowed",
+        Some(_) => "not_enforced",
+        None => "cannot_be_precisely",
+    };
+    assert_eq!(network_tcp.support_level, expected_tcp);
 
     let system_info = caps
         .operations
@@ -75,6 +80,30 @@ fn test_linux_capabilities() {
     assert_eq!(system_info.support_level, "never");
 }
 
+#[test]
+#[cfg(target_os = "linux")]
+fn test_linux_backend_reports_landlock_abi() {
+    let caps = get_platform_capabilities();
+
+    match caps.landlock_abi {
+        Some(abi) => {
+            assert_eq!(caps.backend, "landlock");
+            assert!(abi >= 1, "Landlock ABI levels start at 1");
+            assert!(
+                caps.notes.iter().any(|note| note.contains("Landlock")),
+                "Notes should mention the Landlock backend"
+            );
+            if abi >= 4 {
+                assert!(
+                    caps.notes.iter().any(|note| note.contains("UDP")),
+                    "Notes should say UDP is not restricted"
+                );
+            }
+        }
+        None => assert_eq!(caps.backend, "gaol"),
+    }
+}
+
 #[test]
 #[cfg(target_os = "macos")]
 fn test_macos_capabilities() {
@@ -179,3 +208,12 @@ fn test_support_level_values() {
         );
     }
 }
+
+#[test]
+fn test_memory_and_process_limits_need_cgroups() {
+    let support = get_platform_capabilities().resource_limits;
+
+    let cgroup = support.mechanism == "cgroup_v2";
+    assert_eq!(support.memory, cgroup);
+    assert_eq!(support.max_processes, cgroup);
+}
@@ -0,0 +1,59 @@
+//! Unit tests for sandbox policies
+use organized_agents_lib::sandbox::policy::{
+    effective_policy, load_global_policy, SandboxPolicy, SANDBOX_POLICY_SETTING,
+};
+use rusqlite::{params, Connection};
+use test_case::test_case;
+
+fn settings_db(policy: Option<&str>) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute(
+        "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+        [],
+    )
+    .unwrap();
+    if let Some(policy) = policy {
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![SANDBOX_POLICY_SETTING, policy],
+        )
+        .unwrap();
+    }
+    conn
+}
+
+#[test_case(None, SandboxPolicy::Preferred ; "defaults to preferred")]
+#[test_case(Some("required"), SandboxPolicy::Required ; "required")]
+#[test_case(Some("off"), SandboxPolicy::Off ; "off")]
+#[test_case(Some("strict"), SandboxPolicy::Preferred ; "unknown value")]
+fn test_global_policy(stored: Option<&str>, expected: SandboxPolicy) {
+    assert_eq!(load_global_policy(&settings_db(stored)), expected);
+}
+
+#[test]
+fn test_agent_policy_overrides_global() {
+    let conn = settings_db(Some("required"));
+
+    assert_eq!(effective_policy(&conn, None), SandboxPolicy::Required);
+    assert_eq!(
+        effective_policy(&conn, Some(SandboxPolicy::Off)),
+        SandboxPolicy::Off
+    );
+}
+
+#[test]
+fn test_policy_round_trips_through_serde_and_storage() {
+    for policy in [
+        SandboxPolicy::Required,
+        SandboxPolicy::Preferred,
+        SandboxPolicy::Off,
+    ] {
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(json, format!("\"{}\"", policy.as_str()));
+        assert_eq!(
+            SandboxPolicy::from_stored(Some(policy.as_str().to_string())),
+            Some(policy)
+        );
+    }
+    assert_eq!(SandboxPolicy::from_stored(None), None);
+}
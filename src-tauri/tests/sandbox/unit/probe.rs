@@ -0,0 +1,108 @@
+//! Unit tests for the sandbox probe report
+use organized_agents_lib::sandbox::executor::{SerializedOperation, SerializedProfile};
+use organized_agents_lib::sandbox::probe::{apply_output, expected_outcome, planned_checks, ProbeTargets};
+use pretty_assertions::assert_eq;
+use std::net::SocketAddr;
+use tempfile::TempDir;
+
+fn targets(dir: &TempDir) -> ProbeTargets {
+    let connect: SocketAddr = "127.0.0.1:4567".parse().unwrap();
+    ProbeTargets::prepare(dir.path(), connect).unwrap()
+}
+
+#[test]
+fn test_prepare_creates_probe_files() {
+    let dir = TempDir::new().unwrap();
+    let targets = targets(&dir);
+
+    assert!(targets.read_inside.is_file());
+    assert!(targets.read_outside.is_file());
+    assert!(targets.write.is_file());
+    assert!(!targets.create.exists());
+    assert!(targets.read_inside.starts_with(&targets.project));
+    assert!(!targets.read_outside.starts_with(&targets.project));
+
+    let operations: Vec<&str> = targets.plan().into_iter().map(|(op, _)| op).collect();
+    assert_eq!(
+        operations,
+        vec![
+            "read_inside",
+            "read_outside",
+            "write",
+            "create",
+            "connect_local",
+            "system_info",
+            "spawn"
+        ]
+    );
+}
+
+#[test]
+fn test_expected_outcome_follows_rules() {
+    let dir = TempDir::new().unwrap();
+    let targets = targets(&dir);
+    let profile = SerializedProfile {
+        operations: vec![
+            SerializedOperation::FileReadAll {
+                path: targets.project.clone(),
+                is_subpath: true,
+            },
+            SerializedOperation::FileWrite {
+                path: targets.write.clone(),
+                is_subpath: false,
+            },
+            SerializedOperation::NetworkTcp { port: 4567 },
+        ],
+    };
+
+    let expected = |op| expected_outcome(&profile, op, &targets);
+    assert_eq!(expected("read_inside"), Some(true));
+    assert_eq!(expected("read_outside"), Some(false));
+    assert_eq!(expected("write"), Some(true));
+    assert_eq!(expected("create"), Some(false));
+    assert_eq!(expected("connect_local"), Some(true));
+    assert_eq!(expected("system_info"), Some(false));
+    assert_eq!(expected("spawn"), None);
+}
+
+#[test]
+fn test_apply_output_compares_with_rules() {
+    let dir = TempDir::new().unwrap();
+    let targets = targets(&dir);
+    let profile = SerializedProfile {
+        operations: vec![SerializedOperation::FileReadAll {
+            path: targets.project.clone(),
+            is_subpath: true,
+        }],
+    };
+    let mut checks = planned_checks(&profile, &targets);
+    assert!(checks.iter().all(|check| check.observed == "not_run"));
+
+    apply_output(
+        &mut checks,
+        "0 allowed\n1 allowed\n2 denied Permission denied (os error 13)\n4 failed Connection refused\n6 allowed\nnot a result\n",
+    );
+
+    let outcomes: Vec<(&str, &str, Option<bool>)> = checks
+        .iter()
+        .map(|c| (c.operation.as_str(), c.observed.as_str(), c.matches))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            ("read_inside", "allowed", Some(true)),
+            // Reading outside the project was not stopped
+            ("read_outside", "allowed", Some(false)),
+            ("write", "denied", Some(true)),
+            ("create", "not_run", None),
+            ("connect_local", "failed", None),
+            ("system_info", "not_run", None),
+            ("spawn", "allowed", None),
+        ]
+    );
+    assert_eq!(
+        checks[2].detail.as_deref(),
+        Some("Permission denied (os error 13)")
+    );
+    assert_eq!(checks[0].detail, None);
+}
@@ -1,29 +1,10 @@
 //! Unit tests for ProfileBuilder
-use claudia_lib::sandbox::profile::{ProfileBuilder, SandboxRule};
+use crate::sandbox::common::make_rule;
+use organized_agents_lib::sandbox::executor::SerializedOperation;
+use organized_agents_lib::sandbox::profile::ProfileBuilder;
 use std::path::PathBuf;
 use test_case::test_case;
 
-/// Helper to create a sandbox rule
-fn make_rule(
-    operation_type: &str,
-    pattern_type: &str,
-    pattern_value: &str,
-    platforms: Option<&[&str]>,
-) -> SandboxRule {
-    SandboxRule {
-        id: None,
-        profile_id: 0,
-        operation_type: operation_type.to_string(),
-        pattern_type: pattern_type.to_string(),
-        pattern_value: pattern_value.to_string(),
-        enabled: true,
-        platform_support: platforms.map(|p| {
-            serde_json::to_string(&p.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap()
-        }),
-        created_at: String::new(),
-    }
-}
-
 #[test]
 fn test_profile_builder_creation() {
     let project_path = PathBuf::from("/test/project");
@@ -195,6 +176,92 @@ fn test_platform_filtering() {
     // Rules for other platforms should be filtered out
 }
 
+#[test]
+fn test_write_rules_are_serialized() {
+    let project_path = PathBuf::from("/test/project");
+    let builder = ProfileBuilder::new(project_path.clone()).unwrap();
+
+    let rules = vec![
+        make_rule("file_write", "subpath", "{{PROJECT_PATH}}/src", None),
+        make_rule("file_create", "subpath", "{{PROJECT_PATH}}/tests", None),
+        make_rule("file_write", "literal", "{{PROJECT_PATH}}/Cargo.lock", None),
+    ];
+
+    let result = builder
+        .build_profile_with_serialization(rules)
+        .expect("Write rules should build without a gaol equivalent");
+
+    let writes: Vec<_> = result
+        .serialized
+        .operations
+        .iter()
+        .filter_map(|op| match op {
+            SerializedOperation::FileWrite { path, is_subpath } => {
+                Some(("write", path.clone(), *is_subpath))
+            }
+            SerializedOperation::FileCreate { path, is_subpath } => {
+                Some(("create", path.clone(), *is_subpath))
+            }
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        writes,
+        vec![
+            ("write", project_path.join("src"), true),
+            ("create", project_path.join("tests"), true),
+            ("write", project_path.join("Cargo.lock"), false),
+        ]
+    );
+}
+
+#[test]
+fn test_agent_write_permission_filters_write_rules() {
+    let project_path = PathBuf::from("/test/project");
+    let builder = ProfileBuilder::new(project_path.clone()).unwrap();
+    let is_write = |op: &SerializedOperation| {
+        matches!(
+            op,
+            SerializedOperation::FileWrite { .. } | SerializedOperation::FileCreate { .. }
+        )
+    };
+
+    let rules = vec![make_rule(
+        "file_write",
+        "subpath",
+        "{{PROJECT_PATH}}/src",
+        None,
+    )];
+
+    // Write rules are dropped when the agent may not write
+    let denied = builder
+        .build_agent_profile(rules.clone(), true, true, false, false)
+        .unwrap();
+    assert!(!denied.serialized.operations.iter().any(is_write));
+
+    // Explicit write rules are kept as the only writable locations
+    let limited = builder
+        .build_agent_profile(rules, true, true, true, false)
+        .unwrap();
+    let writes: Vec<_> = limited
+        .serialized
+        .operations
+        .iter()
+        .filter(|op| is_write(op))
+        .collect();
+    assert_eq!(writes.len(), 1);
+
+    // Without write rules the whole project is writable
+    let open = builder
+        .build_agent_profile(vec![], true, true, true, false)
+        .unwrap();
+    assert!(open.serialized.operations.iter().any(|op| matches!(
+        op,
+        SerializedOperation::FileCreate { path, is_subpath: true } if path == &project_path
+    )));
+}
+
 #[test]
 fn test_invalid_operation_type() {
     let project_path = PathBuf::from("/test/project");
@@ -335,3 +402,45 @@ fn test_rule_order_preservation() {
     let _profile = builder.build_profile(rules);
     // Order should be preserved in the resulting profile
 }
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_domain_rules_replace_unrestricted_network() {
+    let builder = ProfileBuilder::new(PathBuf::from("/test/project")).unwrap();
+
+    let rules = vec![
+        make_rule("network_outbound", "all", "", None),
+        make_rule("network_outbound", "domain", "API.Anthropic.com", None),
+        make_rule("network_outbound", "domain", "*.npmjs.org", None),
+    ];
+
+    let result = builder
+        .build_profile_with_serialization(rules)
+        .expect("Domain rules should build without a gaol equivalent");
+
+    assert!(!result
+        .serialized
+        .operations
+        .iter()
+        .any(|op| matches!(op, SerializedOperation::NetworkOutbound { .. })));
+    assert_eq!(
+        result.serialized.allowed_domains(),
+        vec!["api.anthropic.com".to_string(), "*.npmjs.org".to_string()]
+    );
+}
+
+#[test]
+#[serial_test::serial]
+fn test_install_prefixes_skip_root_and_home() {
+    use organized_agents_lib::sandbox::profile::install_prefixes;
+
+    assert_eq!(
+        install_prefixes("/opt/claude/bin/claude"),
+        vec![PathBuf::from("/opt/claude")]
+    );
+    assert!(install_prefixes("/bin/claude").is_empty());
+    if let Some(home) = dirs::home_dir() {
+        let in_home_bin = home.join("bin/claude");
+        assert!(install_prefixes(&in_home_bin.to_string_lossy()).is_empty());
+    }
+}
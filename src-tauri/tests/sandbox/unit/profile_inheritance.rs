@@ -0,0 +1,98 @@
+//! Unit tests for sandbox profile inheritance
+use crate::sandbox::common::*;
+use organized_agents_lib::sandbox::profile::{check_parent, load_effective_rules, load_profile_chain};
+use rusqlite::params;
+
+fn set_parent(db: &TestDatabase, profile_id: i64, parent_id: i64) {
+    db.conn
+        .execute(
+            "UPDATE sandbox_profiles SET parent_id = ?1 WHERE id = ?2",
+            params![parent_id, profile_id],
+        )
+        .unwrap();
+}
+
+fn rule_values(db: &TestDatabase, profile_id: i64) -> Vec<(i64, String)> {
+    load_effective_rules(&db.conn, profile_id)
+        .unwrap()
+        .into_iter()
+        .map(|rule| (rule.profile_id, rule.pattern_value))
+        .collect()
+}
+
+#[test]
+fn test_effective_rules_merge_the_chain() {
+    let db = TestDatabase::new().unwrap();
+    let base = db
+        .create_test_profile(
+            "base",
+            vec![
+                TestRule::file_read("/usr/lib", true),
+                TestRule::file_read("/etc", true),
+            ],
+        )
+        .unwrap();
+    let node = db
+        .create_test_profile("node", vec![TestRule::file_read("/opt/node", true)])
+        .unwrap();
+    let agent = db
+        .create_test_profile(
+            "agent",
+            vec![
+                // Masks the inherited rule
+                TestRule {
+                    enabled: false,
+                    ..TestRule::file_read("/etc", true)
+                },
+                TestRule::file_read("/srv/data", false),
+            ],
+        )
+        .unwrap();
+    set_parent(&db, node, base);
+    set_parent(&db, agent, node);
+
+    let chain: Vec<String> = load_profile_chain(&db.conn, agent)
+        .unwrap()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    assert_eq!(chain, vec!["base", "node", "agent"]);
+
+    assert_eq!(
+        rule_values(&db, agent),
+        vec![
+            (base, "/usr/lib".to_string()),
+            (node, "/opt/node".to_string()),
+            (agent, "/srv/data".to_string()),
+        ]
+    );
+
+    // Changes to the base show up in every descendant
+    db.conn
+        .execute(
+            "UPDATE sandbox_rules SET pattern_value = '/usr/lib64' WHERE profile_id = ?1 AND pattern_value = '/usr/lib'",
+            params![base],
+        )
+        .unwrap();
+    assert_eq!(rule_values(&db, agent)[0], (base, "/usr/lib64".to_string()));
+    assert_eq!(rule_values(&db, node)[0], (base, "/usr/lib64".to_string()));
+}
+
+#[test]
+fn test_cycles_are_detected() {
+    let db = TestDatabase::new().unwrap();
+    let a = db.create_test_profile("a", vec![]).unwrap();
+    let b = db.create_test_profile("b", vec![]).unwrap();
+    let c = db.create_test_profile("c", vec![]).unwrap();
+    set_parent(&db, b, a);
+    set_parent(&db, c, b);
+
+    assert!(check_parent(&db.conn, a, c).is_err());
+    assert!(check_parent(&db.conn, a, a).is_err());
+    assert!(check_parent(&db.conn, c, a).is_ok());
+
+    // A cycle already in the database fails to resolve instead of looping
+    set_parent(&db, a, c);
+    let error = load_effective_rules(&db.conn, c).unwrap_err().to_string();
+    assert!(error.contains("cycle"), "{}", error);
+}
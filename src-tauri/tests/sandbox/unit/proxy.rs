@@ -0,0 +1,171 @@
+//! Unit tests for the egress proxy
+use organized_agents_lib::sandbox::executor::SerializedProfile;
+use organized_agents_lib::sandbox::proxy::{is_host_allowed, request_target, EgressProxy};
+use organized_agents_lib::sandbox::violations::{ViolationContext, ViolationMonitor};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+use test_case::test_case;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn allow_list() -> Vec<String> {
+    vec!["api.anthropic.com".to_string(), "*.npmjs.org".to_string()]
+}
+
+#[test_case("api.anthropic.com", true ; "exact host")]
+#[test_case("API.Anthropic.COM.", true ; "case and trailing dot")]
+#[test_case("registry.npmjs.org", true ; "subdomain wildcard")]
+#[test_case("npmjs.org", false ; "wildcard needs a subdomain")]
+#[test_case("evilnpmjs.org", false ; "wildcard needs a label boundary")]
+#[test_case("anthropic.com", false ; "parent of exact host")]
+#[test_case("api.anthropic.com.evil.io", false ; "allowed host as prefix")]
+fn test_is_host_allowed(host: &str, allowed: bool) {
+    assert_eq!(is_host_allowed(host, &allow_list()), allowed);
+}
+
+#[test_case("CONNECT api.anthropic.com:443 HTTP/1.1", Some(("api.anthropic.com", 443)) ; "connect")]
+#[test_case("CONNECT [2607:6bc0::10]:8443 HTTP/1.1", Some(("2607:6bc0::10", 8443)) ; "connect ipv6")]
+#[test_case("GET http://example.com/index.html HTTP/1.1", Some(("example.com", 80)) ; "absolute form")]
+#[test_case("GET http://user@example.com:8080/ HTTP/1.1", Some(("example.com", 8080)) ; "userinfo and port")]
+#[test_case("GET /index.html HTTP/1.1", None ; "origin form")]
+#[test_case("CONNECT :443 HTTP/1.1", None ; "missing host")]
+fn test_request_target(line: &str, expected: Option<(&str, u16)>) {
+    assert_eq!(
+        request_target(line),
+        expected.map(|(host, port)| (host.to_string(), port))
+    );
+}
+
+fn violations_db(dir: &std::path::Path) -> std::path::PathBuf {
+    let db_path = dir.join("agents.db");
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute(
+        "CREATE TABLE sandbox_violations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            profile_id INTEGER,
+            agent_id INTEGER,
+            agent_run_id INTEGER,
+            operation_type TEXT NOT NULL,
+            pattern_value TEXT,
+            process_name TEXT,
+            pid INTEGER,
+            denied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .unwrap();
+    db_path
+}
+
+async fn send(proxy: &EgressProxy, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", proxy.port()))
+        .await
+        .unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut response = vec![0u8; 1024];
+    let read = stream.read(&mut response).await.unwrap();
+    String::from_utf8_lossy(&response[..read]).into_owned()
+}
+
+#[tokio::test]
+async fn test_blocked_hosts_are_recorded() {
+    let dir = tempdir().unwrap();
+    let db_path = violations_db(dir.path());
+    let blocked = Arc::new(Mutex::new(Vec::new()));
+    let blocked_clone = blocked.clone();
+
+    let monitor = Arc::new(Mutex::new(
+        ViolationMonitor::new(
+            db_path.clone(),
+            ViolationContext {
+                agent_id: Some(7),
+                agent_run_id: Some(42),
+                ..Default::default()
+            },
+        )
+        .with_kill_threshold(
+            Some(1),
+            SerializedProfile { operations: vec![] },
+            dir.path().to_path_buf(),
+        ),
+    ));
+
+    let proxy = EgressProxy::start(
+        allow_list(),
+        monitor.clone(),
+        Some(Arc::new(move |violation| {
+            blocked_clone.lock().unwrap().push(violation)
+        })),
+    )
+    .await
+    .unwrap();
+
+    for _ in 0..2 {
+        let response = send(&proxy, b"CONNECT evil.example:443 HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+    }
+
+    // Repeated attempts are only recorded once
+    let conn = Connection::open(&db_path).unwrap();
+    let rows: Vec<(String, String, i64)> = conn
+        .prepare("SELECT operation_type, pattern_value, agent_run_id FROM sandbox_violations")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        vec![(
+            "network_outbound".to_string(),
+            "evil.example:443".to_string(),
+            42
+        )]
+    );
+    // Every attempt reaches the callback and counts towards the run's kill threshold
+    let blocked = blocked.lock().unwrap();
+    assert_eq!(blocked.len(), 2);
+    assert!(blocked[0].is_some() && blocked[1].is_none());
+    assert!(monitor.lock().unwrap().take_kill_request());
+}
+
+#[tokio::test]
+async fn test_allowed_hosts_are_tunnelled() {
+    let dir = tempdir().unwrap();
+    let upstream = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let upstream_port = upstream.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut conn, _) = upstream.accept().await.unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        conn.write_all(&buf).await.unwrap();
+    });
+
+    let proxy = EgressProxy::start(
+        vec!["127.0.0.1".to_string()],
+        Arc::new(Mutex::new(ViolationMonitor::new(
+            violations_db(dir.path()),
+            ViolationContext::default(),
+        ))),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", proxy.port()))
+        .await
+        .unwrap();
+    stream
+        .write_all(format!("CONNECT 127.0.0.1:{} HTTP/1.1\r\n\r\n", upstream_port).as_bytes())
+        .await
+        .unwrap();
+    let mut head = vec![0u8; 39];
+    stream.read_exact(&mut head).await.unwrap();
+    assert_eq!(head, b"HTTP/1.1 200 Connection Established\r\n\r\n");
+
+    stream.write_all(b"ping").await.unwrap();
+    let mut echoed = [0u8; 4];
+    stream.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"ping");
+}
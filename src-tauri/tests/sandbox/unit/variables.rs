@@ -0,0 +1,87 @@
+//! Unit tests for variables in sandbox rule patterns
+use crate::sandbox::common::make_rule;
+use organized_agents_lib::sandbox::variables::{validate_pattern, PathVariables};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+use test_case::test_case;
+
+fn variables() -> PathVariables {
+    PathVariables {
+        project_path: Some(PathBuf::from("/home/dev/src/app")),
+        home: PathBuf::from("/home/dev"),
+        tmp_dir: PathBuf::from("/tmp"),
+        cargo_home: PathBuf::from("/home/dev/.cargo"),
+    }
+}
+
+#[test_case("{{PROJECT_PATH}}/src", "/home/dev/src/app/src" ; "project")]
+#[test_case("{{HOME}}/.gitconfig", "/home/dev/.gitconfig" ; "home")]
+#[test_case("{{TMPDIR}}", "/tmp" ; "tmpdir")]
+#[test_case("{{CARGO_HOME}}/registry", "/home/dev/.cargo/registry" ; "cargo home")]
+#[test_case("/usr/lib", "/usr/lib" ; "no variables")]
+fn test_expand(pattern: &str, expanded: &str) {
+    assert_eq!(variables().expand(pattern).unwrap(), expanded);
+}
+
+#[test]
+fn test_expand_rejects_unknown_and_missing_values() {
+    assert!(variables().expand("{{NOPE}}/x").is_err());
+    assert!(variables().expand("{{HOME/x").is_err());
+
+    let exporting = PathVariables {
+        project_path: None,
+        ..variables()
+    };
+    assert!(exporting.expand("{{PROJECT_PATH}}").is_err());
+}
+
+#[test_case("/home/dev/src/app/src", "{{PROJECT_PATH}}/src" ; "most specific wins")]
+#[test_case("/home/dev/src/app", "{{PROJECT_PATH}}" ; "exact location")]
+#[test_case("/home/dev/.cargo/registry", "{{CARGO_HOME}}/registry" ; "cargo inside home")]
+#[test_case("/home/dev/.ssh", "{{HOME}}/.ssh" ; "home")]
+#[test_case("/home/dev2/notes", "/home/dev2/notes" ; "component boundary")]
+#[test_case("/tmp/build", "{{TMPDIR}}/build" ; "tmpdir")]
+#[test_case("relative/path", "relative/path" ; "relative")]
+fn test_collapse(path: &str, collapsed: &str) {
+    assert_eq!(variables().collapse(path), collapsed);
+}
+
+#[test]
+fn test_collapse_round_trips() {
+    let variables = variables();
+    for path in [
+        "/home/dev/src/app/target",
+        "/home/dev/.cargo/bin",
+        "/etc/hosts",
+    ] {
+        assert_eq!(variables.expand(&variables.collapse(path)).unwrap(), path);
+    }
+}
+
+#[test]
+fn test_collapse_rules_only_touches_paths() {
+    let rules = variables().collapse_rules(vec![
+        make_rule("file_read_all", "subpath", "/home/dev/.cargo", None),
+        make_rule("network_outbound", "local_socket", "/tmp/agent.sock", None),
+        make_rule("environment", "set", "CACHE=/home/dev/.cache", None),
+        make_rule("network_outbound", "domain", "crates.io", None),
+    ]);
+
+    let values: Vec<&str> = rules.iter().map(|r| r.pattern_value.as_str()).collect();
+    assert_eq!(
+        values,
+        vec![
+            "{{CARGO_HOME}}",
+            "{{TMPDIR}}/agent.sock",
+            "CACHE=/home/dev/.cache",
+            "crates.io"
+        ]
+    );
+}
+
+#[test]
+fn test_validate_pattern() {
+    assert!(validate_pattern("{{HOME}}/.npmrc").is_ok());
+    assert!(validate_pattern("/usr/lib").is_ok());
+    assert!(validate_pattern("{{PROJECT_ROOT}}/src").is_err());
+}
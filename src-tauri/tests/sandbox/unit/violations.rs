@@ -0,0 +1,301 @@
+//! Unit tests for automatic sandbox violation detection
+use organized_agents_lib::sandbox::executor::{SerializedOperation, SerializedProfile};
+use organized_agents_lib::sandbox::violations::{
+    detect_violation, insert_violation, is_enforced, is_first_seen, resolve_kill_threshold,
+    violation_analytics, DeniedTarget, DetectedViolation, TimeBucket, ViolationBucket,
+    ViolationContext, ViolationFilter, ViolationRate, KILL_THRESHOLD_SETTING,
+};
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use test_case::test_case;
+
+fn violation(operation_type: &str, path: &str, process: Option<&str>) -> DetectedViolation {
+    DetectedViolation {
+        operation_type: operation_type.to_string(),
+        pattern_value: Some(path.to_string()),
+        process_name: process.map(|p| p.to_string()),
+    }
+}
+
+#[test_case("cat: /etc/shadow: Permission denied", "file_read_all", "/etc/shadow", Some("cat") ; "coreutils read")]
+#[test_case("ls: cannot open directory '/root': Permission denied", "file_read_all", "/root", Some("ls") ; "quoted directory")]
+#[test_case("touch: cannot touch 'src/new.rs': Permission denied", "file_create", "src/new.rs", Some("touch") ; "touch")]
+#[test_case("rm: cannot remove 'Cargo.lock': Operation not permitted", "file_create", "Cargo.lock", Some("rm") ; "remove with eperm")]
+#[test_case("/bin/sh: 1: cannot create out.txt: Permission denied", "file_create", "out.txt", Some("sh") ; "dash redirect")]
+#[test_case("bash: line 1: /usr/bin/linker: Permission denied", "file_read_all", "/usr/bin/linker", Some("bash") ; "bash line number and path wording")]
+#[test_case("Error: EACCES: permission denied, open '/etc/shadow'", "file_read_all", "/etc/shadow", Some("node") ; "node open")]
+#[test_case("Error: EPERM: operation not permitted, mkdir '/opt/cache'", "file_create", "/opt/cache", Some("node") ; "node mkdir")]
+#[test_case("Error: connect EACCES 10.0.0.1:443", "network_outbound", "10.0.0.1:443", Some("node") ; "node connect")]
+#[test_case("PermissionError: [Errno 13] Permission denied: '/etc/shadow'", "file_read_all", "/etc/shadow", Some("python") ; "python")]
+#[test_case("cat: /srv/İstanbul.txt: Permission denied", "file_read_all", "/srv/İstanbul.txt", Some("cat") ; "non-ascii path")]
+fn test_detects_denials(line: &str, operation_type: &str, path: &str, process: Option<&str>) {
+    assert_eq!(
+        detect_violation(line),
+        Some(violation(operation_type, path, process))
+    );
+}
+
+#[test_case("Compiling sandbox v0.1.0" ; "ordinary output")]
+#[test_case("git@github.com: Permission denied (publickey)." ; "ssh authentication")]
+#[test_case("Permission denied" ; "no target")]
+fn test_ignores_unrelated_lines(line: &str) {
+    assert_eq!(detect_violation(line), None);
+}
+
+fn violations_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute(
+        "CREATE TABLE sandbox_violations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            profile_id INTEGER,
+            agent_id INTEGER,
+            agent_run_id INTEGER,
+            operation_type TEXT NOT NULL,
+            pattern_value TEXT,
+            process_name TEXT,
+            pid INTEGER,
+            denied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .unwrap();
+    conn
+}
+
+/// Insert a violation `minutes_ago` minutes before now
+fn insert_at(conn: &Connection, agent_id: i64, operation_type: &str, path: &str, minutes_ago: i64) {
+    conn.execute(
+        "INSERT INTO sandbox_violations (profile_id, agent_id, operation_type, pattern_value, denied_at)
+         VALUES (7, ?1, ?2, ?3, datetime('now', '-' || ?4 || ' minutes'))",
+        params![agent_id, operation_type, path, minutes_ago],
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_insert_violation_fills_run_columns() {
+    let conn = violations_db();
+
+    let context = ViolationContext {
+        profile_id: None,
+        agent_id: Some(3),
+        agent_run_id: Some(42),
+        pid: Some(1234),
+    };
+    insert_violation(
+        &conn,
+        &context,
+        &violation("file_write", "/etc/hosts", Some("tee")),
+    )
+    .unwrap();
+
+    let row: (i64, String, String, i32) = conn
+        .query_row(
+            "SELECT agent_run_id, operation_type, pattern_value, pid FROM sandbox_violations",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        row,
+        (42, "file_write".to_string(), "/etc/hosts".to_string(), 1234)
+    );
+}
+
+#[test]
+fn test_rate_trips_once_above_threshold() {
+    let start = Instant::now();
+    let mut rate = ViolationRate::new(3);
+
+    // Spread over more than a minute, so never more than 3 in the window
+    for i in 0..6 {
+        assert!(!rate.record(start + Duration::from_secs(25 * i)));
+    }
+
+    let burst = start + Duration::from_secs(300);
+    let tripped: Vec<bool> = (0..6)
+        .map(|i| rate.record(burst + Duration::from_millis(i)))
+        .collect();
+    assert_eq!(tripped, vec![false, false, false, true, false, false]);
+}
+
+#[test]
+fn test_first_seen_is_per_agent_and_target() {
+    let conn = violations_db();
+    let context = ViolationContext {
+        agent_id: Some(1),
+        ..Default::default()
+    };
+    let shadow = violation("file_read_all", "/etc/shadow", Some("cat"));
+
+    assert!(is_first_seen(&conn, &context, &shadow).unwrap());
+    insert_violation(&conn, &context, &shadow).unwrap();
+    assert!(!is_first_seen(&conn, &context, &shadow).unwrap());
+
+    let other_agent = ViolationContext {
+        agent_id: Some(2),
+        ..Default::default()
+    };
+    assert!(is_first_seen(&conn, &other_agent, &shadow).unwrap());
+    let passwd = violation("file_read_all", "/etc/passwd", Some("cat"));
+    assert!(is_first_seen(&conn, &context, &passwd).unwrap());
+}
+
+#[test]
+fn test_analytics_trends_and_top_targets() {
+    let conn = violations_db();
+    insert_at(&conn, 1, "file_read_all", "/etc/shadow", 10);
+    insert_at(&conn, 1, "file_read_all", "/etc/shadow", 10);
+    insert_at(&conn, 1, "file_write", "/etc/hosts", 10);
+    insert_at(&conn, 2, "network_outbound", "10.0.0.1:443", 10);
+    // Outside the period
+    insert_at(&conn, 1, "file_write", "/etc/hosts", 60 * 48);
+
+    let filter = ViolationFilter {
+        since_hours: Some(24),
+        ..Default::default()
+    };
+    let analytics = violation_analytics(&conn, TimeBucket::Day, &filter, 10).unwrap();
+
+    let day: String = conn
+        .query_row("SELECT date('now', '-10 minutes')", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(
+        analytics.buckets,
+        vec![
+            ViolationBucket {
+                bucket: day.clone(),
+                agent_id: Some(1),
+                profile_id: Some(7),
+                count: 3,
+            },
+            ViolationBucket {
+                bucket: day,
+                agent_id: Some(2),
+                profile_id: Some(7),
+                count: 1,
+            },
+        ]
+    );
+
+    let targets = |targets: &[DeniedTarget]| -> Vec<(String, i64)> {
+        targets
+            .iter()
+            .map(|t| (t.target.clone(), t.count))
+            .collect()
+    };
+    assert_eq!(
+        targets(&analytics.top_paths),
+        vec![
+            ("/etc/shadow".to_string(), 2),
+            ("/etc/hosts".to_string(), 1)
+        ]
+    );
+    assert_eq!(
+        targets(&analytics.top_hosts),
+        vec![("10.0.0.1:443".to_string(), 1)]
+    );
+
+    // /etc/hosts was already denied to agent 1 before the period
+    let first_seen: Vec<(Option<i64>, Option<String>)> = analytics
+        .first_seen
+        .into_iter()
+        .map(|v| (v.agent_id, v.pattern_value))
+        .collect();
+    assert_eq!(first_seen.len(), 2);
+    assert!(first_seen.contains(&(Some(1), Some("/etc/shadow".to_string()))));
+    assert!(first_seen.contains(&(Some(2), Some("10.0.0.1:443".to_string()))));
+}
+
+#[test]
+fn test_analytics_filters_by_agent() {
+    let conn = violations_db();
+    insert_at(&conn, 1, "file_read_all", "/etc/shadow", 5);
+    insert_at(&conn, 2, "file_read_all", "/root", 5);
+
+    let filter = ViolationFilter {
+        agent_id: Some(2),
+        ..Default::default()
+    };
+    let analytics = violation_analytics(&conn, TimeBucket::Hour, &filter, 10).unwrap();
+    assert_eq!(analytics.buckets.len(), 1);
+    assert_eq!(analytics.buckets[0].agent_id, Some(2));
+    assert_eq!(analytics.top_paths[0].target, "/root");
+}
+
+#[test_case("file_read_all", "/home/user/project/src/main.rs", false ; "read inside the project")]
+#[test_case("file_read_all", "src/main.rs", false ; "relative read inside the project")]
+#[test_case("file_read_all", "/etc/shadow", true ; "read outside the rules")]
+#[test_case("file_write", "/home/user/project/out.txt", false ; "write inside the project")]
+#[test_case("file_write", "/home/user/project/../secret", true ; "write outside the project")]
+#[test_case("file_create", "/usr/lib/x", true ; "create with only read access")]
+#[test_case("network_outbound", "10.0.0.1:443", false ; "allowed port")]
+#[test_case("network_outbound", "10.0.0.1:22", true ; "other port")]
+#[test_case("network_outbound", "api.github.com", false ; "allowed domain")]
+#[test_case("network_outbound", "evil.example", true ; "other domain")]
+fn test_enforced_denials(operation_type: &str, target: &str, enforced: bool) {
+    let rules = SerializedProfile {
+        operations: vec![
+            SerializedOperation::FileReadAll {
+                path: PathBuf::from("/home/user/project"),
+                is_subpath: true,
+            },
+            SerializedOperation::FileReadAll {
+                path: PathBuf::from("/usr/lib"),
+                is_subpath: true,
+            },
+            SerializedOperation::FileWrite {
+                path: PathBuf::from("/home/user/project"),
+                is_subpath: true,
+            },
+            SerializedOperation::NetworkTcp { port: 443 },
+            SerializedOperation::NetworkDomain {
+                domain: "github.com".to_string(),
+            },
+        ],
+    };
+    let denial = violation(operation_type, target, None);
+    assert_eq!(
+        is_enforced(&rules, Path::new("/home/user/project"), &denial),
+        enforced
+    );
+}
+
+#[test]
+fn test_denials_without_target_are_not_enforced() {
+    let rules = SerializedProfile { operations: vec![] };
+    let denial = DetectedViolation {
+        operation_type: "file_read_all".to_string(),
+        pattern_value: None,
+        process_name: None,
+    };
+    assert!(!is_enforced(&rules, Path::new("/tmp"), &denial));
+}
+
+#[test]
+fn test_kill_threshold_prefers_agent_then_profile_then_global() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+         CREATE TABLE agents (id INTEGER PRIMARY KEY, violation_kill_threshold INTEGER);
+         CREATE TABLE sandbox_profiles (id INTEGER PRIMARY KEY, violation_kill_threshold INTEGER);
+         INSERT INTO agents (id, violation_kill_threshold) VALUES (1, NULL), (2, 5), (3, 0);
+         INSERT INTO sandbox_profiles (id, violation_kill_threshold) VALUES (10, NULL), (11, 20);",
+    )
+    .unwrap();
+
+    assert_eq!(resolve_kill_threshold(&conn, 1, Some(10)), None);
+
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, '50')",
+        params![KILL_THRESHOLD_SETTING],
+    )
+    .unwrap();
+    assert_eq!(resolve_kill_threshold(&conn, 1, Some(10)), Some(50));
+    assert_eq!(resolve_kill_threshold(&conn, 1, None), Some(50));
+    assert_eq!(resolve_kill_threshold(&conn, 1, Some(11)), Some(20));
+    assert_eq!(resolve_kill_threshold(&conn, 2, Some(11)), Some(5));
+    // 0 disables stopping even when the levels below set a threshold
+    assert_eq!(resolve_kill_threshold(&conn, 3, Some(11)), None);
+}
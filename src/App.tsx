@@ -317,7 +317,7 @@ function App() {
       case "agents":
         return (
           <div className="flex-1 overflow-hidden">
-            <CCAgents onBack={() => setView("welcome")} />
+            <CCAgents onBack={() => setView("welcome")} projectPath={selectedProject?.path} />
           </div>
         );
 
@@ -19,7 +19,8 @@ import {
   Layers,
   Zap,
   Star,
-  TrendingDown
+  TrendingDown,
+  ShieldAlert
 } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardFooter, CardHeader } from "@/components/ui/card";
@@ -63,6 +64,10 @@ interface CCAgentsProps {
    * Optional className for styling
    */
   className?: string;
+  /**
+   * Project whose own agents are listed alongside the global ones
+   */
+  projectPath?: string;
 }
 
 // Available icons for agents - now using all icons from IconPicker
@@ -106,7 +111,7 @@ const COST_SAVINGS_DATA = {
  * @example
  * <CCAgents onBack={() => setView('home')} />
  */
-export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className }) => {
+export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className, projectPath }) => {
   const [agents, setAgents] = useState<Agent[]>([]);
   const [runs, setRuns] = useState<AgentRunWithMetrics[]>([]);
   const [loading, setLoading] = useState(true);
@@ -131,13 +136,13 @@ export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className }) => {
   useEffect(() => {
     loadAgents();
     loadRuns();
-  }, []);
+  }, [projectPath]);
 
   const loadAgents = async () => {
     try {
       setLoading(true);
       setError(null);
-      const agentsList = await api.listAgents();
+      const agentsList = await api.listAgents(projectPath);
       setAgents(agentsList);
     } catch (err) {
       console.error("Failed to load agents:", err);
@@ -200,6 +205,38 @@ export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className }) => {
     setAgentToDelete(null);
   };
 
+  /**
+   * Lets a project agent loosen its sandbox as its definition file asks, once the user confirms
+   * @param agent - The agent with the pending sandbox settings
+   */
+  const handleApproveSandbox = async (agent: Agent) => {
+    const settings = agent.pending_sandbox;
+    if (!agent.id || !settings) return;
+
+    const changes = [
+      agent.sandbox_enabled && !settings.sandbox_enabled && "run without a sandbox",
+      !agent.enable_file_read && settings.enable_file_read && "read files",
+      !agent.enable_file_write && settings.enable_file_write && "write files",
+      !agent.enable_network && settings.enable_network && "use the network",
+    ].filter(Boolean);
+    if (!window.confirm(
+      `${agent.source_file ?? "Its definition file"} lets "${agent.name}" ${changes.join(", ")}. Allow this?`
+    )) {
+      return;
+    }
+
+    try {
+      await api.approveAgentSandbox(agent.id, settings);
+      setToast({ message: `Sandbox settings of "${agent.name}" approved`, type: "success" });
+      await loadAgents();
+    } catch (err) {
+      console.error("Failed to approve sandbox settings:", err);
+      const errorMessage = err instanceof Error ? err.message : String(err);
+      setToast({ message: errorMessage, type: "error" });
+      await loadAgents();
+    }
+  };
+
   const handleEditAgent = (agent: Agent) => {
     setSelectedAgent(agent);
     setView("edit");
@@ -612,6 +649,11 @@ export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className }) => {
                                         <categoryConfig.icon className="h-3 w-3 mr-1" />
                                         {categoryConfig.name}
                                       </Badge>
+                                      {agent.project_path && (
+                                        <Badge variant="outline" className="text-xs" title={agent.source_file ?? undefined}>
+                                          Project
+                                        </Badge>
+                                      )}
                                     </div>
                                   </CardHeader>
                                   <CardContent className="pt-0 pb-4 flex flex-col items-center text-center">
@@ -636,6 +678,18 @@ export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className }) => {
                                       <Play className="h-3 w-3" />
                                       Execute
                                     </Button>
+                                    {agent.pending_sandbox && (
+                                      <Button
+                                        size="sm"
+                                        variant="ghost"
+                                        onClick={() => handleApproveSandbox(agent)}
+                                        className="flex items-center gap-1 text-yellow-600 hover:text-yellow-600"
+                                        title="The definition file asks for a looser sandbox"
+                                      >
+                                        <ShieldAlert className="h-3 w-3" />
+                                        Review sandbox
+                                      </Button>
+                                    )}
                                     <Button
                                       size="sm"
                                       variant="ghost"
@@ -534,6 +534,17 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
       setError(null);
       
       const newSessionId = `${Date.now()}-${Math.random().toString(36).substr(2, 9)}`;
+      const uncheckpointed = await api.getUncheckpointedChanges(
+        effectiveSession.id,
+        effectiveSession.project_id,
+        projectPath
+      );
+      if (
+        uncheckpointed.length > 0 &&
+        !confirm(`Uncommitted changes to ${uncheckpointed.join(", ")} are not in any checkpoint and will be overwritten.\n\nFork anyway?`)
+      ) {
+        return;
+      }
       await api.forkFromCheckpoint(
         forkCheckpointId,
         effectiveSession.id,
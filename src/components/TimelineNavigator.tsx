@@ -144,7 +144,14 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
         "Auto-save before restore"
       );
       
-      // Then restore
+      // Then restore, asking before overwriting changes no checkpoint holds
+      const uncheckpointed = await api.getUncheckpointedChanges(sessionId, projectId, projectPath);
+      if (
+        uncheckpointed.length > 0 &&
+        !confirm(`Uncommitted changes to ${uncheckpointed.join(", ")} are not in any checkpoint and will be overwritten.\n\nRestore anyway?`)
+      ) {
+        return;
+      }
       await api.restoreCheckpoint(checkpoint.id, sessionId, projectId, projectPath);
       
       await loadTimeline();
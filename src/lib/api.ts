@@ -111,6 +111,19 @@ export interface SandboxProfile {
   is_default: boolean;
   created_at: string;
   updated_at: string;
+  resource_limits?: ResourceLimits;
+  /** Profile whose rules this one inherits */
+  parent_id?: number | null;
+}
+
+/** Resource limits of a sandbox profile, each unlimited when null */
+export interface ResourceLimits {
+  /** CPU time per process, in seconds */
+  cpu_time_secs?: number | null;
+  /** Memory for the whole run, or per process without a cgroup, in megabytes */
+  memory_mb?: number | null;
+  max_processes?: number | null;
+  max_open_files?: number | null;
 }
 
 export interface SandboxRule {
@@ -129,6 +142,45 @@ export interface PlatformCapabilities {
   sandboxing_supported: boolean;
   operations: OperationSupport[];
   notes: string[];
+  /** Enforcing backend: "landlock", "gaol", "seatbelt", "capsicum" or "none" */
+  backend: string;
+  /** Landlock ABI level in use on Linux, if any */
+  landlock_abi?: number | null;
+  resource_limits?: ResourceLimitSupport;
+}
+
+export interface ResourceLimitSupport {
+  /** Enforcement mechanism: "cgroup_v2", "systemd_scope", "rlimit" or "none" */
+  mechanism: string;
+  cpu_time: boolean;
+  memory: boolean;
+  max_processes: boolean;
+  max_open_files: boolean;
+}
+
+export interface ProbeCheck {
+  /** "read_inside", "read_outside", "write", "create", "connect_local", "system_info" or "spawn" */
+  operation: string;
+  target: string;
+  /** Whether the rules allow the operation, null when no rule type covers it */
+  expected: boolean | null;
+  /** "allowed", "denied", "failed" or "not_run" */
+  observed: string;
+  detail: string | null;
+  /** Whether enforcement agrees with the rules, null when that can't be told */
+  matches: boolean | null;
+}
+
+export interface SandboxTestReport {
+  profile_name: string;
+  platform: string;
+  rule_count: number;
+  summary: string;
+  /** Environment of sandboxed processes, with secrets redacted */
+  environment: string;
+  checks: ProbeCheck[];
+  /** Number of operations whose outcome contradicts the rules */
+  mismatches: number;
 }
 
 export interface OperationSupport {
@@ -150,6 +202,16 @@ export interface SandboxViolation {
   denied_at: string;
 }
 
+/**
+ * Denial detected in a sandboxed agent's output, emitted as `sandbox-violation:{runId}`
+ * after it has been recorded in the violations table
+ */
+export interface DetectedSandboxViolation {
+  operation_type: string;
+  pattern_value?: string | null;
+  process_name?: string | null;
+}
+
 export interface SandboxViolationStats {
   total: number;
   recent_24h: number;
@@ -159,6 +221,41 @@ export interface SandboxViolationStats {
   }>;
 }
 
+export type ViolationTimeBucket = "minute" | "hour" | "day";
+
+export interface ViolationBucket {
+  /** Start of the bucket, in the format of `denied_at` */
+  bucket: string;
+  agent_id: number | null;
+  profile_id: number | null;
+  count: number;
+}
+
+export interface DeniedTarget {
+  target: string;
+  count: number;
+  last_denied_at: string;
+}
+
+/**
+ * Operation and target an agent was first denied within the period. Live runs also
+ * emit these as `sandbox-violation-new:{runId}`
+ */
+export interface FirstSeenViolation {
+  agent_id: number | null;
+  operation_type: string;
+  pattern_value: string | null;
+  first_denied_at: string;
+  count: number;
+}
+
+export interface SandboxViolationAnalytics {
+  buckets: ViolationBucket[];
+  top_paths: DeniedTarget[];
+  top_hosts: DeniedTarget[];
+  first_seen: FirstSeenViolation[];
+}
+
 // Import/Export types
 export interface SandboxProfileExport {
   version: number;
@@ -179,6 +276,24 @@ export interface ImportResult {
   new_name?: string;
 }
 
+export interface SandboxRuleDiff {
+  added: SandboxRule[];
+  removed: SandboxRule[];
+  unchanged: SandboxRule[];
+}
+
+/**
+ * Profile proposed from a learning run, diffed against the agent's current profile
+ */
+export interface SandboxLearningProposal {
+  run_id: number;
+  observed_paths: number;
+  observed_endpoints: number;
+  diff: SandboxRuleDiff;
+  /** Can be passed to importSandboxProfiles as is */
+  export: SandboxProfileExport;
+}
+
 // Agent API types
 export interface Agent {
   id?: number;
@@ -193,6 +308,78 @@ export interface Agent {
   enable_network: boolean;
   created_at: string;
   updated_at: string;
+  /** Overrides the global sandbox policy when set */
+  sandbox_policy?: SandboxPolicy | null;
+  /** Project the agent belongs to, null for global agents */
+  project_path?: string | null;
+  /** Definition file of a project agent, relative to its project */
+  source_file?: string | null;
+  /** Looser sandbox settings the definition file asks for, applied once the user approves */
+  pending_sandbox?: SandboxSettings | null;
+}
+
+/** The sandbox settings of an agent */
+export interface SandboxSettings {
+  sandbox_enabled: boolean;
+  enable_file_read: boolean;
+  enable_file_write: boolean;
+  enable_network: boolean;
+}
+
+/** A name defined by more than one agent a project can see */
+export interface AgentConflict {
+  name: string;
+  /** The global agent with the name, if one has it */
+  global_agent_id?: number | null;
+  /** Definition files in the project with the name */
+  files: string[];
+}
+
+/** What syncing a project's `.claude/agents` changed, by definition file */
+export interface ProjectAgentSync {
+  added: string[];
+  updated: string[];
+  removed: string[];
+  /** Files left as they were last synced, until they can be read again */
+  invalid: { file: string; error: string }[];
+  conflicts: AgentConflict[];
+}
+
+/**
+ * What happens when an agent can't run sandboxed: `required` fails the run,
+ * `preferred` runs it unsandboxed where the platform has no sandbox support and
+ * `off` never sandboxes
+ */
+export type SandboxPolicy = "required" | "preferred" | "off";
+
+/** What a violation kill threshold override applies to */
+export type ThresholdScope = "agent" | "profile";
+
+/** An agent as it was at one point, never changed once recorded */
+export interface AgentVersion {
+  id: number;
+  agent_id: number;
+  version: number;
+  name: string;
+  icon: string;
+  system_prompt: string;
+  default_task?: string | null;
+  model: string;
+  sandbox_enabled: boolean;
+  enable_file_read: boolean;
+  enable_file_write: boolean;
+  enable_network: boolean;
+  sandbox_policy?: SandboxPolicy | null;
+  created_at: string;
+}
+
+export interface AgentVersionDiff {
+  from_version: number;
+  to_version: number;
+  /** Settings other than the system prompt that differ */
+  changes: { field: string; from: unknown; to: unknown }[];
+  /** The system prompt line by line, empty when it is the same in both */
+  system_prompt: { change: "unchanged" | "added" | "removed"; text: string }[];
 }
 
 export interface AgentExport {
@@ -228,11 +415,69 @@ export interface AgentRun {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'queued', 'pending', 'running', 'completed', 'failed', 'cancelled'
   pid?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  /** Why the run failed, if it did */
+  failure_reason?: string | null;
+  /** Whether the agent actually ran inside a sandbox */
+  ran_sandboxed?: boolean;
+  /** Whether the run's memory and process limits were enforced, unknown without any */
+  limits_enforced?: boolean | null;
+  /** Queue priority, higher runs first */
+  priority?: number;
+  /** Version of the agent the run used, unknown for runs from before versions */
+  agent_version_id?: number | null;
+}
+
+export interface RunQueueLimits {
+  max_concurrent: number;
+  max_per_project: number;
+}
+
+export interface QueuedRun {
+  run_id: number;
+  agent_id: number;
+  agent_name: string;
+  project_path: string;
+  task: string;
+  priority: number;
+  queued_at: string;
+}
+
+export interface RunQueue {
+  limits: RunQueueLimits;
+  /** Runs holding a slot, starting or running */
+  active: number;
+  /** Queued runs in the order they get slots */
+  queued: QueuedRun[];
+}
+
+/** Payload of the `agent-runs-reconciled` event emitted at startup */
+export interface ReconciledRuns {
+  /** Still running, and followed through their session JSONL again */
+  adopted: number[];
+  completed: number[];
+  failed: number[];
+}
+
+export type OutputStream = "stdout" | "stderr";
+
+/** Part of a run's output, by byte offset or by line */
+export type OutputRange =
+  | { unit: "bytes"; offset: number; limit: number }
+  | { unit: "lines"; start: number; count: number };
+
+export interface OutputPage {
+  content: string;
+  offset: number;
+  /** Offset to request the next page from */
+  next_offset: number;
+  first_line?: number;
+  total_bytes: number;
+  eof: boolean;
 }
 
 export interface AgentRunMetrics {
@@ -324,6 +569,22 @@ export interface Checkpoint {
   description?: string;
   parentCheckpointId?: string;
   metadata: CheckpointMetadata;
+  tags: string[];
+  pinned: boolean;
+  gitCommit?: string;
+}
+
+/**
+ * Filters for searching checkpoints of a session or project
+ */
+export interface CheckpointSearchQuery {
+  tags?: string[];
+  description?: string;
+  userPrompt?: string;
+  pinnedOnly?: boolean;
+  from?: string;
+  to?: string;
+  filePath?: string;
 }
 
 /**
@@ -369,6 +630,51 @@ export interface SessionTimeline {
   autoCheckpointEnabled: boolean;
   checkpointStrategy: CheckpointStrategy;
   totalCheckpoints: number;
+  gitMirrorEnabled: boolean;
+  fileWatcherEnabled: boolean;
+}
+
+/**
+ * A problem found while verifying checkpoint storage
+ */
+export interface StorageIssue {
+  kind:
+    | "unreadable_timeline"
+    | "missing_checkpoint"
+    | "untracked_checkpoint"
+    | "corrupt_messages"
+    | "invalid_ref"
+    | "dangling_ref"
+    | "corrupt_blob"
+    | "orphaned_blob"
+    | "timeline_mismatch";
+  sessionId: string;
+  checkpointId?: string;
+  detail: string;
+  repaired: boolean;
+}
+
+/**
+ * Result of verifying checkpoint storage
+ */
+export interface StorageVerificationReport {
+  projectId: string;
+  sessionsChecked: number;
+  checkpointsChecked: number;
+  refsChecked: number;
+  blobsChecked: number;
+  issues: StorageIssue[];
+}
+
+/**
+ * Result of importing a checkpoint archive
+ */
+export interface ArchiveImportResult {
+  projectId: string;
+  sessionId: string;
+  checkpoints: Checkpoint[];
+  verifiedBlobs: number;
+  warnings: string[];
 }
 
 /**
@@ -794,18 +1100,63 @@ export const api = {
   // Agent API methods
   
   /**
-   * Lists all CC agents
+   * Lists the global CC agents, and those of a project synced from its definition files
+   * @param projectPath - Project whose agents to include
    * @returns Promise resolving to an array of agents
    */
-  async listAgents(): Promise<Agent[]> {
+  async listAgents(projectPath?: string): Promise<Agent[]> {
     try {
-      return await invoke<Agent[]>('list_agents');
+      return await invoke<Agent[]>('list_agents', { projectPath });
     } catch (error) {
       console.error("Failed to list agents:", error);
       throw error;
     }
   },
 
+  /**
+   * Syncs the agents of a project with its `.claude/agents` directory
+   * @param projectPath - The project path
+   * @returns Promise resolving to what changed and any name conflicts
+   */
+  async syncProjectAgents(projectPath: string): Promise<ProjectAgentSync> {
+    try {
+      return await invoke<ProjectAgentSync>('sync_project_agents', { projectPath });
+    } catch (error) {
+      console.error("Failed to sync project agents:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gives a project agent the looser sandbox settings its definition file asks for
+   * @param id - The agent ID
+   * @param settings - The settings the user approved, as `pending_sandbox` showed them
+   * @returns Promise resolving to the updated agent
+   */
+  async approveAgentSandbox(id: number, settings: SandboxSettings): Promise<Agent> {
+    try {
+      return await invoke<Agent>('approve_agent_sandbox', { id, settings });
+    } catch (error) {
+      console.error("Failed to approve agent sandbox settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Shares an agent with a project by writing its definition file to `.claude/agents`
+   * @param id - The agent ID
+   * @param projectPath - The project path
+   * @returns Promise resolving to the project agent
+   */
+  async addAgentToProject(id: number, projectPath: string): Promise<Agent> {
+    try {
+      return await invoke<Agent>('add_agent_to_project', { id, projectPath });
+    } catch (error) {
+      console.error("Failed to add agent to project:", error);
+      throw error;
+    }
+  },
+
   /**
    * Creates a new agent
    * @param name - The agent name
@@ -893,6 +1244,21 @@ export const api = {
     }
   },
 
+  /**
+   * Sets an agent's own sandbox policy
+   * @param id - The agent ID
+   * @param policy - The policy, or null to follow the global policy
+   * @returns Promise resolving to the updated agent
+   */
+  async setAgentSandboxPolicy(id: number, policy: SandboxPolicy | null): Promise<Agent> {
+    try {
+      return await invoke<Agent>('set_agent_sandbox_policy', { id, policy });
+    } catch (error) {
+      console.error("Failed to set agent sandbox policy:", error);
+      throw error;
+    }
+  },
+
   /**
    * Deletes an agent
    * @param id - The agent ID to delete
@@ -921,6 +1287,64 @@ export const api = {
     }
   },
 
+  /**
+   * Lists the versions of an agent, newest first
+   * @param agentId - The agent ID
+   * @returns Promise resolving to the versions
+   */
+  async listAgentVersions(agentId: number): Promise<AgentVersion[]> {
+    try {
+      return await invoke<AgentVersion[]>('list_agent_versions', { agentId });
+    } catch (error) {
+      console.error("Failed to list agent versions:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets a single version of an agent, such as the one a run used
+   * @param versionId - The version ID
+   * @returns Promise resolving to the version
+   */
+  async getAgentVersion(versionId: number): Promise<AgentVersion> {
+    try {
+      return await invoke<AgentVersion>('get_agent_version', { versionId });
+    } catch (error) {
+      console.error("Failed to get agent version:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Compares two versions of the same agent
+   * @param fromVersionId - The older version ID
+   * @param toVersionId - The newer version ID
+   * @returns Promise resolving to what changed
+   */
+  async diffAgentVersions(fromVersionId: number, toVersionId: number): Promise<AgentVersionDiff> {
+    try {
+      return await invoke<AgentVersionDiff>('diff_agent_versions', { fromVersionId, toVersionId });
+    } catch (error) {
+      console.error("Failed to diff agent versions:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Rolls an agent back to an earlier version, recorded as a new version
+   * @param agentId - The agent ID
+   * @param versionId - The version to roll back to
+   * @returns Promise resolving to the rolled back agent
+   */
+  async rollbackAgent(agentId: number, versionId: number): Promise<Agent> {
+    try {
+      return await invoke<Agent>('rollback_agent', { agentId, versionId });
+    } catch (error) {
+      console.error("Failed to roll back agent:", error);
+      throw error;
+    }
+  },
+
   /**
    * Exports a single agent to JSON format
    * @param id - The agent ID to export
@@ -969,11 +1393,13 @@ export const api = {
    * @param projectPath - The project path to run the agent in
    * @param task - The task description
    * @param model - Optional model override
-   * @returns Promise resolving to the run ID when execution starts
+   * @param learningMode - Run without sandbox while tracing accesses, see proposeSandboxProfile
+   * @param priority - Queue priority, higher runs first
+   * @returns Promise resolving to the run ID once the run starts or is queued
    */
-  async executeAgent(agentId: number, projectPath: string, task: string, model?: string): Promise<number> {
+  async executeAgent(agentId: number, projectPath: string, task: string, model?: string, learningMode?: boolean, priority?: number): Promise<number> {
     try {
-      return await invoke<number>('execute_agent', { agentId, projectPath, task, model });
+      return await invoke<number>('execute_agent', { agentId, projectPath, task, model, learningMode, priority });
     } catch (error) {
       console.error("Failed to execute agent:", error);
       // Return a sentinel value to indicate error
@@ -1038,13 +1464,99 @@ export const api = {
   },
 
   /**
-   * Kills a running agent session
-   * @param runId - The run ID to kill
-   * @returns Promise resolving to whether the session was successfully killed
+   * Gets the queued runs and the concurrency limits
+   * @returns Promise resolving to the run queue
    */
-  async killAgentSession(runId: number): Promise<boolean> {
+  async getRunQueue(): Promise<RunQueue> {
     try {
-      return await invoke<boolean>('kill_agent_session', { runId });
+      return await invoke<RunQueue>('get_run_queue');
+    } catch (error) {
+      console.error("Failed to get run queue:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets how many runs may run at once
+   * @param maxConcurrent - Maximum runs overall
+   * @param maxPerProject - Maximum runs in one project
+   * @returns Promise resolving to the stored limits
+   */
+  async setRunQueueLimits(maxConcurrent: number, maxPerProject: number): Promise<RunQueueLimits> {
+    try {
+      return await invoke<RunQueueLimits>('set_run_queue_limits', { maxConcurrent, maxPerProject });
+    } catch (error) {
+      console.error("Failed to set run queue limits:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Changes the priority of a queued run
+   * @param runId - The queued run
+   * @param priority - The new priority, higher runs first
+   * @returns Promise resolving to whether the run was still queued
+   */
+  async setQueuedRunPriority(runId: number, priority: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('set_queued_run_priority', { runId, priority });
+    } catch (error) {
+      console.error("Failed to set queued run priority:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Moves a queued run in front of another queued run
+   * @param runId - The queued run to move
+   * @param beforeRunId - The run to move in front of, or undefined for the back of the queue
+   * @returns Promise resolving to whether the runs were still queued
+   */
+  async moveQueuedRun(runId: number, beforeRunId?: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('move_queued_run', { runId, beforeRunId });
+    } catch (error) {
+      console.error("Failed to move queued run:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets how long a cancelled run gets to exit before it is killed
+   * @returns Promise resolving to the grace period in seconds
+   */
+  async getAgentCancelGracePeriod(): Promise<number> {
+    try {
+      return await invoke<number>('get_agent_cancel_grace_period');
+    } catch (error) {
+      console.error("Failed to get cancel grace period:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets how long a cancelled run gets to exit before it is killed
+   * @param graceSecs - The grace period in seconds
+   * @returns Promise resolving when the grace period is stored
+   */
+  async setAgentCancelGracePeriod(graceSecs: number): Promise<void> {
+    try {
+      return await invoke('set_agent_cancel_grace_period', { graceSecs });
+    } catch (error) {
+      console.error("Failed to set cancel grace period:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Cancels a running agent session, or takes a queued run off the queue
+   * @param runId - The run ID to cancel
+   * @param graceSecs - Seconds the run gets to exit after SIGTERM, defaults to the configured grace period
+   * @returns Promise resolving to whether the session was successfully cancelled
+   */
+  async killAgentSession(runId: number, graceSecs?: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('kill_agent_session', { runId, graceSecs });
     } catch (error) {
       console.error("Failed to kill agent session:", error);
       throw new Error(`Failed to kill agent session: ${error instanceof Error ? error.message : 'Unknown error'}`);
@@ -1092,6 +1604,49 @@ export const api = {
     }
   },
 
+  /**
+   * Get a page of the output a run wrote to disk
+   * @param runId - The run ID to get output for
+   * @param range - The byte offset or lines to read
+   * @param stream - Which output to read, defaults to stdout
+   * @returns Promise resolving to the page
+   */
+  async getSessionOutputPage(runId: number, range: OutputRange, stream?: OutputStream): Promise<OutputPage> {
+    try {
+      return await invoke<OutputPage>('get_session_output', { runId, range, stream });
+    } catch (error) {
+      console.error("Failed to get session output page:", error);
+      throw new Error(`Failed to get session output page: ${error instanceof Error ? error.message : 'Unknown error'}`);
+    }
+  },
+
+  /**
+   * Gets how many bytes of live output a run keeps in memory
+   * @returns Promise resolving to the cap in bytes
+   */
+  async getAgentLiveOutputCap(): Promise<number> {
+    try {
+      return await invoke<number>('get_agent_live_output_cap');
+    } catch (error) {
+      console.error("Failed to get live output cap:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets how many bytes of live output a run keeps in memory
+   * @param capBytes - The cap in bytes, applied to runs started afterwards
+   * @returns Promise resolving when the cap is stored
+   */
+  async setAgentLiveOutputCap(capBytes: number): Promise<void> {
+    try {
+      return await invoke('set_agent_live_output_cap', { capBytes });
+    } catch (error) {
+      console.error("Failed to set live output cap:", error);
+      throw error;
+    }
+  },
+
   /**
    * Get live output directly from process stdout buffer
    * @param runId - The run ID to get live output for
@@ -1230,6 +1785,50 @@ export const api = {
     }
   },
 
+  /**
+   * Sets the resource limits of a sandbox profile
+   * @param id - The profile ID
+   * @param limits - The new limits, null fields are unlimited
+   * @returns Promise resolving to the updated profile
+   */
+  async setSandboxProfileLimits(id: number, limits: ResourceLimits): Promise<SandboxProfile> {
+    try {
+      return await invoke<SandboxProfile>('set_sandbox_profile_limits', { id, limits });
+    } catch (error) {
+      console.error("Failed to set sandbox profile limits:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Makes a sandbox profile inherit the rules of another profile
+   * @param id - The profile ID
+   * @param parentId - The profile to inherit from, or null to stop inheriting
+   * @returns Promise resolving to the updated profile
+   */
+  async setSandboxProfileParent(id: number, parentId: number | null): Promise<SandboxProfile> {
+    try {
+      return await invoke<SandboxProfile>('set_sandbox_profile_parent', { id, parent_id: parentId });
+    } catch (error) {
+      console.error("Failed to set sandbox profile parent:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the rules a sandbox profile ends up with, including inherited ones
+   * @param profileId - The profile ID
+   * @returns Promise resolving to the effective rules, each with the ID of the profile defining it
+   */
+  async getEffectiveSandboxRules(profileId: number): Promise<SandboxRule[]> {
+    try {
+      return await invoke<SandboxRule[]>('get_effective_sandbox_rules', { profile_id: profileId });
+    } catch (error) {
+      console.error("Failed to get effective sandbox rules:", error);
+      throw error;
+    }
+  },
+
   /**
    * Deletes a sandbox profile
    * @param id - The profile ID to delete
@@ -1368,11 +1967,11 @@ export const api = {
   /**
    * Tests a sandbox profile
    * @param profileId - The profile ID to test
-   * @returns Promise resolving to test result message
+   * @returns Promise resolving to the per-operation probe report
    */
-  async testSandboxProfile(profileId: number): Promise<string> {
+  async testSandboxProfile(profileId: number): Promise<SandboxTestReport> {
     try {
-      return await invoke<string>('test_sandbox_profile', { profile_id: profileId });
+      return await invoke<SandboxTestReport>('test_sandbox_profile', { profile_id: profileId });
     } catch (error) {
       console.error("Failed to test sandbox profile:", error);
       throw error;
@@ -1458,16 +2057,133 @@ export const api = {
     }
   },
 
+  /**
+   * Gets violation trends, top denied paths and hosts, and first-seen violations
+   * @param options - Bucket width (default hour), filters, period in hours (default 24) and top-N limit (default 10)
+   * @returns Promise resolving to the analytics
+   */
+  async getSandboxViolationAnalytics(options: {
+    bucket?: ViolationTimeBucket;
+    agentId?: number;
+    profileId?: number;
+    sinceHours?: number;
+    limit?: number;
+  } = {}): Promise<SandboxViolationAnalytics> {
+    try {
+      return await invoke<SandboxViolationAnalytics>('get_sandbox_violation_analytics', {
+        bucket: options.bucket,
+        agent_id: options.agentId,
+        profile_id: options.profileId,
+        since_hours: options.sinceHours,
+        limit: options.limit
+      });
+    } catch (error) {
+      console.error("Failed to get sandbox violation analytics:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the global sandbox policy
+   * @returns Promise resolving to the policy agents follow unless they set their own
+   */
+  async getSandboxPolicy(): Promise<SandboxPolicy> {
+    try {
+      return await invoke<SandboxPolicy>('get_sandbox_policy');
+    } catch (error) {
+      console.error("Failed to get sandbox policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets the global sandbox policy
+   * @param policy - The new policy
+   */
+  async setSandboxPolicy(policy: SandboxPolicy): Promise<void> {
+    try {
+      return await invoke('set_sandbox_policy', { policy });
+    } catch (error) {
+      console.error("Failed to set sandbox policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the violations per minute that stop a sandboxed run
+   * @returns Promise resolving to the threshold, or null when disabled
+   */
+  async getSandboxViolationKillThreshold(): Promise<number | null> {
+    try {
+      return await invoke<number | null>('get_sandbox_violation_kill_threshold');
+    } catch (error) {
+      console.error("Failed to get sandbox violation threshold:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets the violations per minute that stop a sandboxed run. Stopped runs emit
+   * `sandbox-violation-threshold:{runId}` and are marked failed
+   * @param threshold - The threshold, or null to disable
+   */
+  async setSandboxViolationKillThreshold(threshold: number | null): Promise<void> {
+    try {
+      return await invoke('set_sandbox_violation_kill_threshold', { threshold });
+    } catch (error) {
+      console.error("Failed to set sandbox violation threshold:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the violations per minute that stop runs of an agent, or of agents using a
+   * sandbox profile
+   * @param scope - Whether `id` is an agent or a sandbox profile
+   * @param id - The agent or profile ID
+   * @returns Promise resolving to the threshold, 0 when disabled, or null to follow the
+   * profile's threshold for agents and the global one for profiles
+   */
+  async getViolationKillThresholdOverride(scope: ThresholdScope, id: number): Promise<number | null> {
+    try {
+      return await invoke<number | null>('get_violation_kill_threshold_override', { scope, id });
+    } catch (error) {
+      console.error("Failed to get violation threshold override:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets the violations per minute that stop runs of an agent, or of agents using a
+   * sandbox profile. An agent's threshold wins over its profile's, which wins over the
+   * global one
+   * @param scope - Whether `id` is an agent or a sandbox profile
+   * @param id - The agent or profile ID
+   * @param threshold - The threshold, 0 to disable, or null to follow the next level
+   */
+  async setViolationKillThresholdOverride(scope: ThresholdScope, id: number, threshold: number | null): Promise<void> {
+    try {
+      return await invoke('set_violation_kill_threshold_override', { scope, id, threshold });
+    } catch (error) {
+      console.error("Failed to set violation threshold override:", error);
+      throw error;
+    }
+  },
+
   // Import/Export methods
 
   /**
    * Exports a single sandbox profile with its rules
    * @param profileId - The profile ID to export
+   * @param projectPath - Optional project whose paths are rewritten to {{PROJECT_PATH}}
    * @returns Promise resolving to export data
    */
-  async exportSandboxProfile(profileId: number): Promise<SandboxProfileExport> {
+  async exportSandboxProfile(profileId: number, projectPath?: string): Promise<SandboxProfileExport> {
     try {
-      return await invoke<SandboxProfileExport>('export_sandbox_profile', { profile_id: profileId });
+      return await invoke<SandboxProfileExport>('export_sandbox_profile', {
+        profile_id: profileId,
+        project_path: projectPath
+      });
     } catch (error) {
       console.error("Failed to export sandbox profile:", error);
       throw error;
@@ -1476,11 +2192,14 @@ export const api = {
 
   /**
    * Exports all sandbox profiles
+   * @param projectPath - Optional project whose paths are rewritten to {{PROJECT_PATH}}
    * @returns Promise resolving to export data
    */
-  async exportAllSandboxProfiles(): Promise<SandboxProfileExport> {
+  async exportAllSandboxProfiles(projectPath?: string): Promise<SandboxProfileExport> {
     try {
-      return await invoke<SandboxProfileExport>('export_all_sandbox_profiles');
+      return await invoke<SandboxProfileExport>('export_all_sandbox_profiles', {
+        project_path: projectPath
+      });
     } catch (error) {
       console.error("Failed to export all sandbox profiles:", error);
       throw error;
@@ -1501,6 +2220,20 @@ export const api = {
     }
   },
 
+  /**
+   * Proposes a minimal sandbox profile from what a learning run touched
+   * @param runId - ID of a run started with learning mode
+   * @returns Promise resolving to the proposal and its diff against the current profile
+   */
+  async proposeSandboxProfile(runId: number): Promise<SandboxLearningProposal> {
+    try {
+      return await invoke<SandboxLearningProposal>('propose_sandbox_profile', { runId });
+    } catch (error) {
+      console.error("Failed to propose sandbox profile:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets overall usage statistics
    * @returns Promise resolving to usage statistics
@@ -1588,6 +2321,9 @@ export const api = {
 
   /**
    * Restores a session to a specific checkpoint
+   *
+   * Uncommitted changes that are not in any checkpoint are overwritten and listed in the
+   * warnings; check getUncheckpointedChanges first and confirm with the user.
    */
   async restoreCheckpoint(
     checkpointId: string,
@@ -1618,6 +2354,78 @@ export const api = {
     });
   },
 
+  /**
+   * Updates the tags, pinned flag and description of a checkpoint
+   */
+  async updateCheckpointAnnotations(
+    checkpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    tags: string[],
+    pinned: boolean,
+    description?: string
+  ): Promise<Checkpoint> {
+    return invoke("update_checkpoint_annotations", {
+      checkpointId,
+      sessionId,
+      projectId,
+      projectPath,
+      tags,
+      pinned,
+      description
+    });
+  },
+
+  /**
+   * Searches checkpoints of a session, or of every session in the project when no session is given
+   */
+  async searchCheckpoints(
+    projectId: string,
+    query: CheckpointSearchQuery,
+    sessionId?: string
+  ): Promise<Checkpoint[]> {
+    return invoke("search_checkpoints", {
+      projectId,
+      sessionId,
+      query
+    });
+  },
+
+  /**
+   * Creates a git branch in the project repo from a mirrored checkpoint
+   */
+  async promoteCheckpointToBranch(
+    checkpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    branchName: string
+  ): Promise<string> {
+    return invoke("promote_checkpoint_to_branch", {
+      checkpointId,
+      sessionId,
+      projectId,
+      projectPath,
+      branchName
+    });
+  },
+
+  /**
+   * Lists uncommitted changes that are not captured by any checkpoint (checked before restoring)
+   */
+  async getUncheckpointedChanges(
+    sessionId: string,
+    projectId: string,
+    projectPath: string
+  ): Promise<string[]> {
+    return invoke("get_uncheckpointed_changes", {
+      sessionId,
+      projectId,
+      projectPath
+    });
+  },
+
   /**
    * Forks a new timeline branch from a checkpoint
    */
@@ -1639,6 +2447,70 @@ export const api = {
     });
   },
 
+  /**
+   * Exports a checkpoint (and optionally its ancestors) to a compressed archive file
+   */
+  async exportCheckpointArchive(
+    projectId: string,
+    sessionId: string,
+    checkpointId: string,
+    includeAncestors: boolean,
+    filePath: string
+  ): Promise<void> {
+    return invoke("export_checkpoint_archive", {
+      projectId,
+      sessionId,
+      checkpointId,
+      includeAncestors,
+      filePath
+    });
+  },
+
+  /**
+   * Imports a checkpoint archive into a new session of a project
+   */
+  async importCheckpointArchive(
+    filePath: string,
+    projectId: string,
+    sessionId?: string
+  ): Promise<ArchiveImportResult> {
+    return invoke("import_checkpoint_archive", {
+      filePath,
+      projectId,
+      sessionId
+    });
+  },
+
+  /**
+   * Verifies checkpoint storage of a project (or a single session), optionally repairing it
+   */
+  async verifyCheckpointStorage(
+    projectId: string,
+    repair: boolean,
+    sessionId?: string
+  ): Promise<StorageVerificationReport> {
+    return invoke("verify_checkpoint_storage", {
+      projectId,
+      sessionId,
+      repair
+    });
+  },
+
+  /**
+   * Lists the checkpoint timelines of every session in a project, including past sessions
+   */
+  async listProjectTimelines(projectId: string): Promise<SessionTimeline[]> {
+    return invoke("list_project_timelines", { projectId });
+  },
+
+  /**
+   * Lists checkpoints across all sessions of a project, oldest first.
+   * Use restoreCheckpoint / forkFromCheckpoint with the checkpoint's sessionId to act on them.
+   */
+  async listProjectCheckpoints(projectId: string): Promise<Checkpoint[]> {
+    return invoke("list_project_checkpoints", { projectId });
+  },
+
   /**
    * Gets the timeline for a session
    */
@@ -1662,14 +2534,18 @@ export const api = {
     projectId: string,
     projectPath: string,
     autoCheckpointEnabled: boolean,
-    checkpointStrategy: CheckpointStrategy
+    checkpointStrategy: CheckpointStrategy,
+    gitMirrorEnabled?: boolean,
+    fileWatcherEnabled?: boolean
   ): Promise<void> {
     return invoke("update_checkpoint_settings", {
       sessionId,
       projectId,
       projectPath,
       autoCheckpointEnabled,
-      checkpointStrategy
+      checkpointStrategy,
+      gitMirrorEnabled,
+      fileWatcherEnabled
     });
   },
 
@@ -1773,6 +2649,8 @@ export const api = {
     checkpoint_strategy: CheckpointStrategy;
     total_checkpoints: number;
     current_checkpoint_id?: string;
+    git_mirror_enabled: boolean;
+    file_watcher_enabled: boolean;
   }> {
     try {
       return await invoke("get_checkpoint_settings", {